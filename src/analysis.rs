@@ -0,0 +1,799 @@
+use crate::{
+    constants::*,
+    error::ChompError,
+    frame::{audio_frames, audio_region, Frame, FrameIterator},
+    header::{Header, Layer, MPEG_Version, Mode},
+    id3::{find_id3v2, id3v2_size},
+    lame::{LameTag, VbrMethod},
+    vbr::VbrInfo,
+    vbri::VbriHeader,
+    xing::XingHeader,
+};
+use std::{ops::Range, time::Duration};
+
+// Offset and new bitrate (kbit/s) of every frame whose bitrate differs from the previous one.
+// The first audio frame is always listed, as the initial bitrate.
+pub fn bitrate_changes(data: &[u8]) -> Result<Vec<(usize, usize)>, ChompError> {
+    let frames = audio_frames(data);
+    if frames.is_empty() {
+        return Err(ChompError::NoFrames);
+    }
+
+    let mut changes: Vec<(usize, usize)> = Vec::new();
+    for frame in &frames {
+        let bitrate = frame.header.bitrate();
+
+        if changes.last().is_none_or(|(_, last)| *last != bitrate) {
+            changes.push((frame.offset, bitrate));
+        }
+    }
+
+    Ok(changes)
+}
+
+// Lowest and highest bitrate (kbit/s) of the audio frames
+pub fn bitrate_range(data: &[u8]) -> Result<(usize, usize), ChompError> {
+    let bitrates = audio_frames(data)
+        .iter()
+        .map(|frame| frame.header.bitrate())
+        .collect::<Vec<_>>();
+
+    match (bitrates.iter().min(), bitrates.iter().max()) {
+        (Some(min), Some(max)) => Ok((*min, *max)),
+        _ => Err(ChompError::NoFrames),
+    }
+}
+
+// A stream keeps one sample rate, a change usually means corruption or concatenated files
+pub fn check_sample_rate_consistency(data: &[u8]) -> Result<(), ChompError> {
+    let frames = audio_frames(data);
+    let expected = frames
+        .first()
+        .ok_or(ChompError::NoFrames)?
+        .header
+        .sample_rate();
+
+    match frames
+        .iter()
+        .find(|frame| frame.header.sample_rate() != expected)
+    {
+        Some(frame) => Err(ChompError::SampleRateChanged {
+            offset: frame.offset,
+            expected,
+            found: frame.header.sample_rate(),
+        }),
+        None => Ok(()),
+    }
+}
+
+// Byte range of each logical stream of a buffer made of concatenated files. A new stream starts
+// at an ID3v2 tag found after some frames, or at the first frame whose version or sample rate
+// differs from the previous one. A tag only followed by garbage stays in the previous range.
+pub fn split_streams(data: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut position = 0;
+    // Version and sample rate of the current stream, None before its first frame
+    let mut format = None;
+    // Start of the tag that opens the next stream
+    let mut boundary = None;
+
+    while position < data.len() {
+        if let Some(size) = id3v2_size(&data[position..]) {
+            if format.is_some() && boundary.is_none() {
+                boundary = Some(position);
+            }
+            position += size;
+            continue;
+        }
+
+        // Frames are searched up to the next tag, so a tag is never mistaken for audio
+        let end = find_id3v2(data, position).unwrap_or(data.len());
+        for frame in FrameIterator::new(&data[position..end]).flatten() {
+            let offset = position + frame.offset;
+            let frame_format = (frame.header.version(), frame.header.sample_rate());
+
+            if let Some(tag) = boundary.take() {
+                ranges.push(start..tag);
+                start = tag;
+            } else if format.is_some_and(|format| format != frame_format) {
+                ranges.push(start..offset);
+                start = offset;
+            }
+            format = Some(frame_format);
+        }
+        position = end;
+    }
+
+    if start < data.len() {
+        ranges.push(start..data.len());
+    }
+
+    ranges
+}
+
+// Where the audio starts: offset of the first frame confirmed by the one following it, once
+// leading ID3v2 and APE tags and any garbage are skipped. The Xing/Info frame, when there is
+// one, is that first frame.
+pub fn first_frame_offset(data: &[u8]) -> Result<usize, ChompError> {
+    FrameIterator::new(data)
+        .flatten()
+        .next()
+        .map(|frame| frame.offset)
+        .ok_or(ChompError::NoFrames)
+}
+
+// Offset of frame `n`, frames counted from the one `first_frame_offset` finds. For a CBR stream
+// it's computed from the length of the first frame and the padding its bitrate calls for, then
+// checked against the headers found there. Other streams, or CBR ones padded another way, are
+// iterated. Errors with InvalidRange when the stream has no frame `n`.
+pub fn frame_offset(data: &[u8], n: usize) -> Result<usize, ChompError> {
+    let first = FrameIterator::new(data)
+        .flatten()
+        .next()
+        .ok_or(ChompError::NoFrames)?;
+
+    if let Some(offset) = cbr_frame_offset(data, &first, n) {
+        return Ok(offset);
+    }
+
+    FrameIterator::new(data)
+        .flatten()
+        .nth(n)
+        .map(|frame| frame.offset)
+        .ok_or(ChompError::InvalidRange)
+}
+
+fn cbr_frame_offset(data: &[u8], first: &Frame, n: usize) -> Option<usize> {
+    // Xing (rather than Info) and VBRI headers mark VBR streams. Encoders leave the Info frame
+    // out of the padding pattern, the audio frames start it over.
+    let (origin, n) = match XingHeader::from_frame(first) {
+        Some(xing) if xing.cbr => match n.checked_sub(1) {
+            Some(n) => (first.offset + first.data.len(), n),
+            None => return Some(first.offset),
+        },
+        Some(_) => return None,
+        None if VbriHeader::from_frame(first).is_some() => return None,
+        None => (first.offset, n),
+    };
+
+    let header = &first.header;
+    let audio = audio_region(data);
+    let slot = header.slot_size();
+    let length = header.frame_length() - header.has_padding() as usize * slot;
+
+    let same = |offset: usize| {
+        let found = Header::from_bytes(data.get(offset..audio.end)?).ok()?;
+        (found.version() == header.version()
+            && found.layer() == header.layer()
+            && found.sample_rate() == header.sample_rate()
+            && found.bitrate() == header.bitrate())
+        .then(|| offset + found.frame_length())
+    };
+
+    // The padding can fall on either side of a frame boundary. An offset past usize::MAX is
+    // past the end of the data too, iterating then tells there's no such frame.
+    header
+        .padded_frames(n)
+        .filter_map(|padded| {
+            n.checked_mul(length)?
+                .checked_add(padded * slot)?
+                .checked_add(origin)
+        })
+        .find(|offset| same(*offset).is_some_and(|next| next == audio.end || same(next).is_some()))
+}
+
+// Channel configuration of the whole stream, as announced by the mode of its frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelLayout {
+    Mono,
+    Stereo,
+    JointStereo,
+    // Two independent channels, e.g. two languages
+    DualMono,
+}
+
+impl ChannelLayout {
+    pub fn channels(&self) -> usize {
+        match self {
+            ChannelLayout::Mono => 1,
+            _ => 2,
+        }
+    }
+}
+
+impl From<Mode> for ChannelLayout {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Stereo => ChannelLayout::Stereo,
+            Mode::JointStereo => ChannelLayout::JointStereo,
+            Mode::DualChannel => ChannelLayout::DualMono,
+            Mode::SingleChannel => ChannelLayout::Mono,
+        }
+    }
+}
+
+// Channel layout of the stream, read from the mode of its first frame
+pub fn channel_layout(data: &[u8]) -> Result<ChannelLayout, ChompError> {
+    FrameIterator::new(data)
+        .flatten()
+        .next()
+        .map(|frame| ChannelLayout::from(frame.header.mode()))
+        .ok_or(ChompError::NoFrames)
+}
+
+// Number of PCM samples (per channel) the stream decodes to. The frame count of the Xing/Info or
+// VBRI header is trusted when present. With `trim`, the encoder delay and padding stored in a
+// LAME tag are removed so the result matches the original audio.
+pub fn total_samples(data: &[u8], trim: bool) -> Result<u64, ChompError> {
+    let first = FrameIterator::new(data)
+        .flatten()
+        .next()
+        .ok_or(ChompError::NoFrames)?;
+    let samples_per_frame = first.header.samples_per_frame() as u64;
+
+    let frames = match VbrInfo::from_frame(&first).and_then(|vbr| vbr.frames) {
+        Some(frames) => frames as u64,
+        None => audio_frames(data).len() as u64,
+    };
+    let total = frames * samples_per_frame;
+
+    Ok(match LameTag::from_frame(&first).filter(|_| trim) {
+        Some(lame) => total.saturating_sub((lame.delay() + lame.padding()) as u64),
+        None => total,
+    })
+}
+
+// How `duration` got its answer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DurationMethod {
+    // Frame count of the Xing/Info or VBRI header
+    VbrHeader,
+    // Size of the audio over the bitrate its first frames share
+    ConstantBitrate,
+    // Every frame read
+    Scan,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DurationInfo {
+    pub duration: Duration,
+    pub method: DurationMethod,
+    // False for an estimate, which a bitrate change after the first frames or garbage between
+    // frames can throw off
+    pub exact: bool,
+}
+
+// Duration of the stream, read from the Xing/Info or VBRI frame count when there is one.
+// Otherwise, if the first frames share their bitrate, it's estimated from the bytes between the
+// first audio frame and the tags ending the data (APE, appended ID3v2, ID3v1). Anything else is
+// scanned.
+pub fn duration(data: &[u8]) -> Result<DurationInfo, ChompError> {
+    let mut frames = FrameIterator::new(data).flatten().peekable();
+    let first = frames.peek().cloned().ok_or(ChompError::NoFrames)?;
+
+    if let Some(vbr) = VbrInfo::from_frame(&first) {
+        if let Some(count) = vbr.frames {
+            return Ok(DurationInfo {
+                duration: first.header.duration() * count,
+                method: DurationMethod::VbrHeader,
+                exact: true,
+            });
+        }
+        frames.next();
+    }
+
+    let probed = frames
+        .by_ref()
+        .take(CBR_PROBE_FRAMES + 1)
+        .collect::<Vec<_>>();
+    let mut duration = probed.iter().map(|frame| frame.header.duration()).sum();
+
+    if let [audio, ..] = &probed[..] {
+        let bitrate = audio.header.bitrate();
+        let constant = probed.iter().all(|frame| frame.header.bitrate() == bitrate);
+
+        if constant && probed.len() > CBR_PROBE_FRAMES {
+            let bytes = (audio_region(data).end - audio.offset) as u64;
+            return Ok(DurationInfo {
+                duration: Duration::from_nanos(bytes * 8_000_000 / bitrate as u64),
+                method: DurationMethod::ConstantBitrate,
+                exact: false,
+            });
+        }
+    }
+
+    duration += frames.map(|frame| frame.header.duration()).sum();
+    Ok(DurationInfo {
+        duration,
+        method: DurationMethod::Scan,
+        exact: true,
+    })
+}
+
+// Average bitrate of the audio frames, in kbit/s. With a frame count in the Xing/Info or VBRI
+// header the size comes from its byte count, or from the bytes between the first audio frame and the tags
+// ending the data, without scanning the frames.
+pub fn average_bitrate(data: &[u8]) -> Result<usize, ChompError> {
+    let first = FrameIterator::new(data)
+        .flatten()
+        .next()
+        .ok_or(ChompError::NoFrames)?;
+
+    let (bytes, duration) = match VbrInfo::from_frame(&first) {
+        Some(VbrInfo {
+            frames: Some(frames),
+            bytes,
+            ..
+        }) => {
+            let bytes = match bytes {
+                Some(bytes) => bytes as usize,
+                None => audio_region(data).end - first.offset,
+            };
+            // Both counts leave out the Xing/Info or VBRI frame, as the average bitrate of `analyze`
+            let bytes = bytes.saturating_sub(first.data.len());
+            (bytes, first.header.duration() * frames)
+        }
+        _ => {
+            let frames = audio_frames(data);
+            let bytes = frames.iter().map(|frame| frame.data.len()).sum();
+            (
+                bytes,
+                frames.iter().map(|frame| frame.header.duration()).sum(),
+            )
+        }
+    };
+
+    if duration.is_zero() {
+        return Err(ChompError::NoFrames);
+    }
+    Ok((bytes as f64 * 8.0 / duration.as_secs_f64() / 1000.0).round() as usize)
+}
+
+// Number of whole frames with the configuration of `header` that fit in `target`, the inverse of
+// `Header::duration`
+pub fn frames_for_duration(header: &Header, target: Duration) -> usize {
+    let samples = target.as_nanos() * header.sample_rate() as u128 / 1_000_000_000;
+    (samples / header.samples_per_frame() as u128) as usize
+}
+
+// Bytes spent on padding over the audio frames, one slot (4 bytes for Layer I, 1 otherwise) per
+// frame setting its padding bit
+pub fn padding_byte_count(data: &[u8]) -> Result<usize, ChompError> {
+    let frames = audio_frames(data);
+    if frames.is_empty() {
+        return Err(ChompError::NoFrames);
+    }
+
+    Ok(frames
+        .iter()
+        .filter(|frame| frame.header.has_padding())
+        .map(|frame| frame.header.slot_size())
+        .sum())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Cbr,
+    Vbr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    pub version: MPEG_Version,
+    pub layer: Layer,
+    pub mode: Mode,
+    // In Hz
+    pub sample_rate: usize,
+    pub encoding: Encoding,
+    // Bitrate of the first audio frame, in kbit/s
+    pub nominal_bitrate: usize,
+    // Over the whole stream, in kbit/s
+    pub average_bitrate: usize,
+    pub frames: usize,
+    pub duration: Duration,
+    // LAME tag of the Xing/Info frame, if any
+    pub lame: Option<LameTag>,
+}
+
+// Encoder settings stored in the Xing/Info frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QualityInfo {
+    // 0 (worst) to 100 (best). LAME writes 100 - 10 * V - q, its VBR quality (-V) and algorithm
+    // quality (-q) settings.
+    pub xing_quality: Option<u32>,
+    pub vbr_method: Option<VbrMethod>,
+    // In Hz
+    pub lowpass: Option<usize>,
+}
+
+impl QualityInfo {
+    // LAME's -V setting, from 0 (best) to 9
+    pub fn vbr_quality(&self) -> Option<u32> {
+        Some(100u32.checked_sub(self.xing_quality?)? / 10)
+    }
+
+    // LAME's -q setting, from 0 (best) to 9
+    pub fn algorithm_quality(&self) -> Option<u32> {
+        Some(100u32.checked_sub(self.xing_quality?)? % 10)
+    }
+}
+
+// What the Xing/Info frame and its LAME tag tell about the encoding, None without Xing/Info
+// frame or when it holds neither a quality nor a LAME tag
+pub fn estimate_quality(data: &[u8]) -> Option<QualityInfo> {
+    let first = FrameIterator::new(data).flatten().next()?;
+    let xing = XingHeader::from_frame(&first)?;
+    let lame = LameTag::from_frame(&first);
+
+    if xing.quality.is_none() && lame.is_none() {
+        return None;
+    }
+
+    Some(QualityInfo {
+        xing_quality: xing.quality,
+        vbr_method: lame.as_ref().map(LameTag::vbr_method),
+        lowpass: lame.as_ref().and_then(LameTag::lowpass),
+    })
+}
+
+// Everything about the stream in a single pass over its frames
+pub fn analyze(data: &[u8]) -> Result<Analysis, ChompError> {
+    let frames = audio_frames(data);
+    let first = frames.first().ok_or(ChompError::NoFrames)?;
+
+    let mut encoding = Encoding::Cbr;
+    let mut bytes = 0;
+    let mut duration = Duration::ZERO;
+    for frame in &frames {
+        if frame.header.bitrate() != first.header.bitrate() {
+            encoding = Encoding::Vbr;
+        }
+        bytes += frame.data.len();
+        duration += frame.header.duration();
+    }
+
+    Ok(Analysis {
+        version: first.header.version(),
+        layer: first.header.layer(),
+        mode: first.header.mode(),
+        sample_rate: first.header.sample_rate(),
+        encoding,
+        nominal_bitrate: first.header.bitrate(),
+        average_bitrate: (bytes as f64 * 8.0 / duration.as_secs_f64() / 1000.0).round() as usize,
+        frames: frames.len(),
+        duration,
+        lame: FrameIterator::new(data)
+            .flatten()
+            .next()
+            .and_then(|frame| LameTag::from_frame(&frame)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn sample_rate_change() {
+        // 128 kbit/s at 48000 Hz, 384 bytes
+        let data = frames(&[HEADER_128, HEADER_128, [0xFF, 0xFB, 0x94, 0x40], HEADER_128]);
+        match check_sample_rate_consistency(&data) {
+            Err(ChompError::SampleRateChanged {
+                offset,
+                expected,
+                found,
+            }) => assert_eq!((offset, expected, found), (2 * 417, 44100, 48000)),
+            result => panic!("{result:?}"),
+        }
+
+        assert!(check_sample_rate_consistency(&frames(&[HEADER_128, HEADER_192])).is_ok());
+    }
+
+    #[test]
+    fn alternating_bitrates() {
+        let data = frames(&[HEADER_128, HEADER_192, HEADER_192, HEADER_128, HEADER_192]);
+        assert_eq!(
+            bitrate_changes(&data).unwrap(),
+            [
+                (0, 128),
+                (417, 192),
+                (417 + 2 * 626, 128),
+                (2 * 417 + 2 * 626, 192)
+            ]
+        );
+
+        let data = frames(&[HEADER_128; 3]);
+        assert_eq!(bitrate_changes(&data).unwrap(), [(0, 128)]);
+        assert!(matches!(bitrate_changes(&[]), Err(ChompError::NoFrames)));
+    }
+
+    #[test]
+    fn total_samples_without_info_frame() {
+        let data = frames(&[HEADER_128; 5]);
+        assert_eq!(total_samples(&data, true).unwrap(), 5 * 1152);
+        assert_eq!(total_samples(&data, false).unwrap(), 5 * 1152);
+        assert!(matches!(
+            total_samples(&[], true),
+            Err(ChompError::NoFrames)
+        ));
+    }
+
+    #[test]
+    fn total_samples_of_lame_files() {
+        let vbr = include_bytes!("../fixtures/vbr.mp3");
+        assert_eq!(total_samples(vbr, true).unwrap(), 22050);
+        assert_eq!(total_samples(vbr, false).unwrap(), 21 * 1152);
+
+        let mpeg2 = include_bytes!("../fixtures/mpeg2.mp3");
+        assert_eq!(total_samples(mpeg2, true).unwrap(), 11025);
+    }
+
+    #[test]
+    fn optional_trim() {
+        let data = include_bytes!("../fixtures/cbr.mp3");
+        assert_eq!(total_samples(data, true).unwrap(), 11025);
+        assert_eq!(
+            total_samples(data, false).unwrap(),
+            audio_frames(data).len() as u64 * 1152
+        );
+    }
+
+    #[test]
+    fn analyze_cbr() {
+        let analysis = analyze(include_bytes!("../fixtures/cbr.mp3")).unwrap();
+
+        assert_eq!(analysis.version, MPEG_Version::One);
+        assert_eq!(analysis.layer, Layer::Three);
+        assert_eq!(analysis.mode, Mode::JointStereo);
+        assert_eq!(analysis.sample_rate, 44100);
+        assert_eq!(analysis.encoding, Encoding::Cbr);
+        assert_eq!(analysis.nominal_bitrate, 128);
+        assert_eq!(analysis.average_bitrate, 128);
+        // The Info frame is left out
+        assert_eq!(analysis.frames, 11);
+        assert_eq!(
+            analysis.duration,
+            Header::from_bytes(&HEADER_128).unwrap().duration() * 11
+        );
+        assert!(analysis.lame.is_some());
+    }
+
+    #[test]
+    fn analyze_vbr() {
+        let analysis = analyze(include_bytes!("../fixtures/vbr.mp3")).unwrap();
+        assert_eq!(analysis.encoding, Encoding::Vbr);
+        assert_eq!(analysis.frames, 21);
+        assert!(matches!(analyze(&[0; 1000]), Err(ChompError::NoFrames)));
+    }
+
+    // ID3v2.3 tag made of `size` bytes of padding
+    fn id3v2(size: u8) -> Vec<u8> {
+        let mut tag = b"ID3\x03\x00\x00\x00\x00\x00".to_vec();
+        tag.push(size);
+        tag.resize(10 + size as usize, 0);
+        tag
+    }
+
+    #[test]
+    fn concatenated_tagged_files() {
+        let cbr = include_bytes!("../fixtures/cbr.mp3");
+        let vbr = include_bytes!("../fixtures/vbr.mp3");
+        let first = [id3v2(20), cbr.to_vec()].concat();
+        let data = [first.clone(), id3v2(30), vbr.to_vec()].concat();
+
+        assert_eq!(
+            split_streams(&data),
+            [0..first.len(), first.len()..data.len()]
+        );
+        // Same format on both sides, only the tag tells them apart
+        assert_eq!(
+            split_streams(&[cbr, &cbr[..]].concat()),
+            vec![0..2 * cbr.len()]
+        );
+    }
+
+    #[test]
+    fn format_change() {
+        let cbr = include_bytes!("../fixtures/cbr.mp3");
+        let mpeg2 = include_bytes!("../fixtures/mpeg2.mp3");
+        let data = [&cbr[..], mpeg2].concat();
+        assert_eq!(split_streams(&data), [0..cbr.len(), cbr.len()..data.len()]);
+
+        // A tag followed by garbage belongs to the stream before it
+        let data = [cbr.to_vec(), id3v2(10), vec![0; 100]].concat();
+        assert_eq!(split_streams(&data), vec![0..data.len()]);
+    }
+
+    #[test]
+    fn frames_in_a_duration() {
+        let header = Header::from_bytes(&HEADER_128).unwrap();
+        // 44100 / 1152 = 38.28
+        assert_eq!(frames_for_duration(&header, Duration::from_secs(1)), 38);
+        assert_eq!(frames_for_duration(&header, header.duration() * 10), 10);
+        assert_eq!(
+            frames_for_duration(&header, header.duration() * 10 - Duration::from_nanos(1)),
+            9
+        );
+        assert_eq!(frames_for_duration(&header, Duration::ZERO), 0);
+
+        // MPEG-2 Layer III, 576 samples at 22050 Hz
+        let header = Header::from_bytes(&[0xFF, 0xF3, 0x80, 0xC0]).unwrap();
+        assert_eq!(frames_for_duration(&header, Duration::from_secs(1)), 38);
+    }
+
+    #[test]
+    fn padding_bytes() {
+        let padded = [0xFF, 0xFB, 0x92, 0x40];
+        let data = frames(&[HEADER_128, padded, padded, HEADER_192, padded]);
+        assert_eq!(padding_byte_count(&data).unwrap(), 3);
+        assert_eq!(padding_byte_count(&frames(&[HEADER_128; 4])).unwrap(), 0);
+
+        // Layer I pads with 4 byte slots
+        let data = frames(&[[0xFF, 0xFF, 0x92, 0x40], [0xFF, 0xFF, 0x90, 0x40]]);
+        assert_eq!(padding_byte_count(&data).unwrap(), 4);
+
+        // 128 kbit/s at 44100 Hz is 417.96 bytes per frame: 10 of the 11 audio frames are padded
+        let cbr = include_bytes!("../fixtures/cbr.mp3");
+        assert_eq!(padding_byte_count(cbr).unwrap(), 10);
+        assert!(matches!(padding_byte_count(&[]), Err(ChompError::NoFrames)));
+    }
+
+    #[test]
+    fn bitrate_range_of_a_vbr_stream() {
+        // 160, 96, 256 then 128 kbit/s
+        let data = frames(&[
+            [0xFF, 0xFB, 0xA0, 0x40],
+            [0xFF, 0xFB, 0x70, 0x40],
+            [0xFF, 0xFB, 0xD0, 0x40],
+            HEADER_128,
+        ]);
+        assert_eq!(bitrate_range(&data).unwrap(), (96, 256));
+        assert_eq!(bitrate_range(&frames(&[HEADER_192])).unwrap(), (192, 192));
+        assert!(matches!(bitrate_range(&[]), Err(ChompError::NoFrames)));
+
+        // The 128 kbit/s Xing frame isn't counted, the audio frames span 32 to 256 kbit/s
+        let vbr = include_bytes!("../fixtures/vbr.mp3");
+        assert_eq!(bitrate_range(vbr).unwrap(), (32, 256));
+    }
+
+    #[test]
+    fn first_frame_after_a_tag() {
+        let tag = include_bytes!("../fixtures/lame.id3");
+        let cbr = include_bytes!("../fixtures/cbr.mp3");
+        assert_eq!(first_frame_offset(cbr).unwrap(), 0);
+
+        let data = [&tag[..], cbr].concat();
+        assert_eq!(first_frame_offset(&data).unwrap(), tag.len());
+
+        // A header not followed by another one is garbage
+        let mut garbage = HEADER_128.to_vec();
+        garbage.resize(50, 0x55);
+        let data = [&tag[..], &garbage, cbr].concat();
+        assert_eq!(first_frame_offset(&data).unwrap(), tag.len() + 50);
+
+        assert!(matches!(first_frame_offset(tag), Err(ChompError::NoFrames)));
+    }
+
+    #[test]
+    fn quality_of_lame_files() {
+        let vbr = estimate_quality(include_bytes!("../fixtures/vbr.mp3")).unwrap();
+        // -V 4 with the default VBR algorithm
+        assert_eq!(
+            vbr,
+            QualityInfo {
+                xing_quality: Some(60),
+                vbr_method: Some(VbrMethod::V2),
+                lowpass: Some(17500),
+            }
+        );
+        assert_eq!(vbr.vbr_quality(), Some(4));
+
+        let cbr = estimate_quality(include_bytes!("../fixtures/cbr.mp3")).unwrap();
+        assert_eq!(cbr.vbr_method, Some(VbrMethod::Cbr));
+        assert!(estimate_quality(&frames(&[HEADER_128; 3])).is_none());
+    }
+
+    #[test]
+    fn channel_layouts() {
+        assert_eq!(
+            channel_layout(include_bytes!("../fixtures/mpeg2.mp3")).unwrap(),
+            ChannelLayout::Mono
+        );
+        assert_eq!(
+            channel_layout(include_bytes!("../fixtures/cbr.mp3")).unwrap(),
+            ChannelLayout::JointStereo
+        );
+
+        let layout = |mode: u8| channel_layout(&frames(&[[0xFF, 0xFB, 0x90, mode]; 2])).unwrap();
+        assert_eq!(layout(0x00), ChannelLayout::Stereo);
+        assert_eq!(layout(0x80), ChannelLayout::DualMono);
+        assert_eq!(layout(0xC0), ChannelLayout::Mono);
+        assert!(matches!(channel_layout(&[]), Err(ChompError::NoFrames)));
+    }
+
+    #[test]
+    fn frame_offsets_match_iteration() {
+        let tag = include_bytes!("../fixtures/lame.id3");
+        let cbr = include_bytes!("../fixtures/cbr.mp3");
+        for data in [
+            frames(&[HEADER_128; 12]),
+            cbr.to_vec(),
+            [&tag[..], cbr].concat(),
+            include_bytes!("../fixtures/crc.mp3").to_vec(),
+            include_bytes!("../fixtures/mpeg2.mp3").to_vec(),
+            include_bytes!("../fixtures/vbr.mp3").to_vec(),
+            include_bytes!("../fixtures/vbri.mp3").to_vec(),
+        ] {
+            let offsets: Vec<_> = FrameIterator::new(&data)
+                .flatten()
+                .map(|frame| frame.offset)
+                .collect();
+            for (n, offset) in offsets.iter().enumerate() {
+                assert_eq!(frame_offset(&data, n).unwrap(), *offset);
+            }
+            for n in [offsets.len(), usize::MAX / 417 + 1, usize::MAX] {
+                assert!(matches!(
+                    frame_offset(&data, n),
+                    Err(ChompError::InvalidRange)
+                ));
+            }
+        }
+
+        // 417.96 bytes a frame at 128 kbit/s: 9 of the 10 audio frames before the 11th are padded
+        let first = FrameIterator::new(cbr).flatten().next().unwrap();
+        assert_eq!(
+            frame_offset(cbr, 11).unwrap(),
+            first.data.len() + 10 * 417 + 9
+        );
+        assert!(matches!(frame_offset(tag, 0), Err(ChompError::NoFrames)));
+    }
+
+    #[test]
+    fn duration_methods() {
+        let scanned = |data: &[u8]| -> Duration {
+            FrameIterator::new(data)
+                .flatten()
+                .map(|frame| frame.header.duration())
+                .sum()
+        };
+
+        // The 11 audio frames counted in the Info frame
+        let cbr = include_bytes!("../fixtures/cbr.mp3");
+        let info = duration(cbr).unwrap();
+        assert_eq!(info.method, DurationMethod::VbrHeader);
+        assert!(info.exact);
+        let header = Header::from_bytes(cbr).unwrap();
+        assert_eq!(info.duration, header.duration() * 11);
+
+        // No Info frame, 128 kbit/s from the start: estimated from the size, trailing tags left
+        // out of it
+        let crc = include_bytes!("../fixtures/crc.mp3");
+        let estimate = duration(crc).unwrap();
+        assert_eq!(estimate.method, DurationMethod::ConstantBitrate);
+        assert!(!estimate.exact);
+        assert!(estimate.duration.abs_diff(scanned(crc)) < header.duration());
+
+        let trailers = [
+            &crc[..],
+            include_bytes!("../fixtures/mp3gain.ape"),
+            include_bytes!("../fixtures/lame.id3v1"),
+        ]
+        .concat();
+        assert_eq!(duration(&trailers).unwrap(), estimate);
+
+        // Bitrate changes, or too few frames to call the stream CBR, are scanned
+        let data = frames(&[HEADER_128, HEADER_192, HEADER_128, HEADER_192].repeat(3));
+        let scan = duration(&data).unwrap();
+        assert_eq!(scan.method, DurationMethod::Scan);
+        assert!(scan.exact);
+        assert_eq!(scan.duration, scanned(&data));
+
+        let data = frames(&[HEADER_128; CBR_PROBE_FRAMES]);
+        assert_eq!(duration(&data).unwrap().method, DurationMethod::Scan);
+        assert_eq!(duration(&data).unwrap().duration, header.duration() * 8);
+
+        assert!(matches!(duration(&[]), Err(ChompError::NoFrames)));
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{constants::*, id3v1::strip_id3v1};
+use std::ops::Range;
+
+// Byte range of an APEv2 (or APEv1) tag at the end of the data, header included when the tag
+// has one. The tag can be followed by an ID3v1 tag.
+pub fn find_ape_tag(data: &[u8]) -> Option<Range<usize>> {
+    let end = strip_id3v1(data).len();
+
+    let footer = data.get(end.checked_sub(APE_FOOTER_SIZE)?..end)?;
+    if &footer[..8] != b"APETAGEX" {
+        return None;
+    }
+
+    let field = |offset: usize| u32::from_le_bytes(footer[offset..offset + 4].try_into().unwrap());
+    // Items and footer, the header isn't counted
+    let size = field(12) as usize;
+    if size < APE_FOOTER_SIZE {
+        return None;
+    }
+    let flags = field(20);
+    let header = if flags & APE_HAS_HEADER_FLAG != 0 {
+        APE_FOOTER_SIZE
+    } else {
+        0
+    };
+
+    let start = end.checked_sub(size.checked_add(header)?)?;
+    Some(start..end)
+}
+
+// Byte range of an APEv2 tag starting at `from`, as written at the start of a file. Only APEv2
+// tags have a header, and so can be found from their start.
+pub fn find_leading_ape_tag(data: &[u8], from: usize) -> Option<Range<usize>> {
+    let header = data.get(from..from.checked_add(APE_FOOTER_SIZE)?)?;
+    if &header[..8] != b"APETAGEX" {
+        return None;
+    }
+
+    let field = |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+    if field(20) & APE_IS_HEADER_FLAG == 0 || (field(12) as usize) < APE_FOOTER_SIZE {
+        return None;
+    }
+
+    // Items and footer, the header isn't counted
+    let end = from + APE_FOOTER_SIZE + field(12) as usize;
+    Some(from..end.min(data.len()))
+}
+
+// APE tag item: its value is UTF-8 text, binary data or a UTF-8 link, as told by its flags
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApeItem {
+    pub key: String,
+    pub value: Vec<u8>,
+    pub binary: bool,
+}
+
+// APEv2 (or APEv1) tag ending the data, see `find_ape_tag`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApeTag {
+    items: Vec<ApeItem>,
+}
+
+impl ApeTag {
+    // Items are read up to the footer. Each is a 32 bits value size and 32 bits flags (little
+    // endian), a null terminated ASCII key then the value. An item running past the footer ends
+    // the parsing.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let range = find_ape_tag(data)?;
+        let tag = &data[range];
+        let footer = tag.len() - APE_FOOTER_SIZE;
+        let flags = u32::from_le_bytes(tag[footer + 20..footer + 24].try_into().unwrap());
+        let header = if flags & APE_HAS_HEADER_FLAG != 0 {
+            APE_FOOTER_SIZE
+        } else {
+            0
+        };
+        let mut items_bytes = &tag[header..footer];
+
+        let mut items = Vec::new();
+        while items_bytes.len() > 8 {
+            let field = |offset: usize| {
+                u32::from_le_bytes(items_bytes[offset..offset + 4].try_into().unwrap())
+            };
+            let size = field(0) as usize;
+            let flags = field(4);
+
+            let rest = &items_bytes[8..];
+            let Some(key_end) = rest.iter().position(|byte| *byte == 0) else {
+                break;
+            };
+            let Some(value) = rest.get(key_end + 1..key_end + 1 + size) else {
+                break;
+            };
+
+            items.push(ApeItem {
+                key: String::from_utf8_lossy(&rest[..key_end]).into_owned(),
+                value: value.to_vec(),
+                binary: flags >> 1 & 0b11 == 1,
+            });
+            items_bytes = &rest[key_end + 1 + size..];
+        }
+
+        Some(Self { items })
+    }
+
+    pub fn items(&self) -> &[ApeItem] {
+        &self.items
+    }
+
+    // Value of the text item with this key, keys being case insensitive
+    pub fn text(&self, key: &str) -> Option<String> {
+        self.items
+            .iter()
+            .find(|item| !item.binary && item.key.eq_ignore_ascii_case(key))
+            .map(|item| String::from_utf8_lossy(&item.value).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{audio_frames, audio_region, FrameIterator};
+
+    // APEv2 footer (or header) announcing `size` bytes of items and footer
+    fn footer(size: u32, flags: u32) -> Vec<u8> {
+        let mut bytes = b"APETAGEX".to_vec();
+        bytes.extend(2000u32.to_le_bytes());
+        bytes.extend(size.to_le_bytes());
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(flags.to_le_bytes());
+        bytes.extend([0; 8]);
+        bytes
+    }
+
+    #[test]
+    fn size_below_footer_is_rejected() {
+        for size in [0, 8, 31] {
+            let mut data = vec![0; 64];
+            data.extend(footer(size, APE_HAS_HEADER_FLAG));
+            assert_eq!(find_ape_tag(&data), None);
+            assert_eq!(ApeTag::parse(&data), None);
+
+            let mut data = vec![0; 64];
+            data.extend(footer(size, 0));
+            assert_eq!(find_ape_tag(&data), None);
+            assert_eq!(ApeTag::parse(&data), None);
+
+            let data = footer(size, APE_HAS_HEADER_FLAG | APE_IS_HEADER_FLAG);
+            assert_eq!(find_leading_ape_tag(&data, 0), None);
+        }
+    }
+
+    #[test]
+    fn empty_tag() {
+        let mut data = vec![0; 64];
+        data.extend(footer(APE_FOOTER_SIZE as u32, 0));
+        assert_eq!(find_ape_tag(&data), Some(64..96));
+        assert_eq!(ApeTag::parse(&data).unwrap().items(), &[]);
+    }
+
+    #[test]
+    fn tag_after_the_audio() {
+        let cbr = include_bytes!("../fixtures/cbr.mp3");
+        let mut item = 5u32.to_le_bytes().to_vec();
+        item.extend(0u32.to_le_bytes());
+        item.extend(b"Title\0Hello");
+        let size = (item.len() + APE_FOOTER_SIZE) as u32;
+
+        let mut data = cbr.to_vec();
+        data.extend(footer(size, APE_HAS_HEADER_FLAG | APE_IS_HEADER_FLAG));
+        data.extend(&item);
+        data.extend(footer(size, APE_HAS_HEADER_FLAG));
+        let tag = cbr.len()..data.len();
+        assert_eq!(find_ape_tag(&data), Some(tag.clone()));
+
+        let ape = ApeTag::parse(&data).unwrap();
+        assert_eq!(ape.items().len(), 1);
+        assert_eq!(ape.items()[0].key, "Title");
+
+        // Followed by an ID3v1 tag
+        let mut id3v1 = b"TAG".to_vec();
+        id3v1.resize(128, 0);
+        data.extend(id3v1);
+        assert_eq!(find_ape_tag(&data), Some(tag));
+        assert_eq!(audio_region(&data), 0..cbr.len());
+        assert_eq!(audio_frames(&data).len(), 11);
+
+        // Larger than the data
+        let data = footer(1000, 0);
+        assert_eq!(find_ape_tag(&data), None);
+    }
+
+    #[test]
+    fn mp3gain_tag_before_id3v1() {
+        let cbr = include_bytes!("../fixtures/cbr.mp3");
+        let ape = include_bytes!("../fixtures/mp3gain.ape");
+        let id3v1 = include_bytes!("../fixtures/lame.id3v1");
+        let data = [&cbr[..], ape, id3v1].concat();
+
+        assert_eq!(find_ape_tag(&data), Some(cbr.len()..cbr.len() + ape.len()));
+        let tag = ApeTag::parse(&data).unwrap();
+        assert_eq!(tag.items().len(), 4);
+        assert_eq!(tag.text("replaygain_track_gain").unwrap(), "-6.155000 dB");
+        assert_eq!(tag.text("MP3GAIN_MINMAX").unwrap(), "103,197");
+
+        // Neither tag is scanned for frames
+        assert_eq!(audio_region(&data), 0..cbr.len());
+        let frames: Vec<_> = FrameIterator::new(&data).collect();
+        assert_eq!(frames.len(), 12);
+        assert!(frames.iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn tag_before_the_audio() {
+        let cbr = include_bytes!("../fixtures/cbr.mp3");
+        let ape = include_bytes!("../fixtures/mp3gain.ape");
+        let data = [&ape[..], cbr].concat();
+
+        assert_eq!(find_leading_ape_tag(&data, 0), Some(0..ape.len()));
+        assert_eq!(find_leading_ape_tag(&data, 1), None);
+        // The footer alone doesn't start a tag
+        assert_eq!(
+            find_leading_ape_tag(&data, ape.len() - APE_FOOTER_SIZE),
+            None
+        );
+
+        assert_eq!(audio_region(&data), ape.len()..data.len());
+        let first = FrameIterator::new(&data).next().unwrap().unwrap();
+        assert_eq!(first.offset, ape.len());
+    }
+}
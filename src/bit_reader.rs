@@ -0,0 +1,130 @@
+use crate::error::ChompError;
+use bitvec::prelude::*;
+
+// Sequential, most significant bit first, reader over a byte buffer
+pub struct BitReader<'a> {
+    bits: &'a BitSlice<u8, Msb0>,
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self::from_bits(bytes.view_bits::<Msb0>())
+    }
+
+    pub fn from_bits(bits: &'a BitSlice<u8, Msb0>) -> Self {
+        Self { bits, position: 0 }
+    }
+
+    // Reads up to 32 bits as an unsigned integer
+    pub fn read_bits(&mut self, count: usize) -> Result<u32, ChompError> {
+        debug_assert!(count <= 32);
+
+        if count == 0 {
+            return Ok(0);
+        }
+
+        Ok(self.read_slice(count)?.load_be::<u32>())
+    }
+
+    // Borrows the next `count` bits
+    pub fn read_slice(&mut self, count: usize) -> Result<&'a BitSlice<u8, Msb0>, ChompError> {
+        let bits = self
+            .bits
+            .get(self.position..self.position + count)
+            .ok_or(ChompError::TooShort)?;
+        self.position += count;
+
+        Ok(bits)
+    }
+
+    // Reads `count` whole bytes, the position doesn't need to be byte aligned
+    pub fn read_bytes(&mut self, count: usize) -> Result<Vec<u8>, ChompError> {
+        let bits = self.read_slice(count * 8)?;
+        Ok(bits.chunks(8).map(|byte| byte.load_be::<u8>()).collect())
+    }
+
+    // Moves `count` bits forward, failing without moving if fewer remain
+    pub fn skip(&mut self, count: usize) -> Result<(), ChompError> {
+        if count > self.remaining() {
+            return Err(ChompError::TooShort);
+        }
+
+        self.position += count;
+        Ok(())
+    }
+
+    pub fn read_bit(&mut self) -> Result<bool, ChompError> {
+        let bit = *self.bits.get(self.position).ok_or(ChompError::TooShort)?;
+        self.position += 1;
+        Ok(bit)
+    }
+
+    // In bits, from the start of the buffer
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    // Moves to `position` bits from the start of the buffer, reading past its end fails as usual
+    pub fn seek(&mut self, position: usize) {
+        self.position = position;
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bits.len().saturating_sub(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mixed_widths() {
+        // 1010 1100 | 0011 0101 | 1111 0000 | 0110 1001
+        let bytes = [0xAC, 0x35, 0xF0, 0x69];
+        let mut reader = BitReader::new(&bytes);
+
+        assert_eq!(reader.read_bits(3).unwrap(), 0b101);
+        assert!(!reader.read_bit().unwrap());
+        // Across the first byte boundary
+        assert_eq!(reader.read_bits(7).unwrap(), 0b110_0001);
+        assert_eq!(reader.read_bits(0).unwrap(), 0);
+        // Across two boundaries
+        assert_eq!(reader.read_bits(14).unwrap(), 0b10_1011_1110_0000);
+        assert_eq!(reader.position(), 25);
+        assert_eq!(reader.remaining(), 7);
+        assert_eq!(reader.read_bits(7).unwrap(), 0b110_1001);
+
+        reader.seek(0);
+        assert_eq!(reader.read_bits(32).unwrap(), 0xAC35_F069);
+        assert!(matches!(reader.read_bit(), Err(ChompError::TooShort)));
+    }
+
+    #[test]
+    fn unaligned_bytes_and_slices() {
+        let bytes = [0xAC, 0x35, 0xF0, 0x69];
+        let mut reader = BitReader::new(&bytes);
+
+        reader.skip(4).unwrap();
+        assert_eq!(reader.read_bytes(2).unwrap(), [0xC3, 0x5F]);
+        assert_eq!(
+            reader.read_slice(6).unwrap(),
+            bits![u8, Msb0; 0, 0, 0, 0, 0, 1]
+        );
+        assert_eq!(reader.position(), 26);
+
+        // Failures leave the position alone
+        assert!(matches!(reader.skip(7), Err(ChompError::TooShort)));
+        assert!(matches!(reader.read_bytes(1), Err(ChompError::TooShort)));
+        assert!(matches!(reader.read_bits(7), Err(ChompError::TooShort)));
+        assert_eq!(reader.position(), 26);
+        reader.skip(6).unwrap();
+        assert_eq!(reader.remaining(), 0);
+
+        // Over part of a buffer
+        let mut reader = BitReader::from_bits(&bytes.view_bits::<Msb0>()[12..20]);
+        assert_eq!(reader.read_bytes(1).unwrap(), [0x5F]);
+        assert_eq!(reader.remaining(), 0);
+    }
+}
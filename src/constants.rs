@@ -1,7 +1,7 @@
 pub const HEADER_SIZE: usize = 32;
 
-pub const SYNC_SIZE: usize = 12;
-pub const ID_SIZE: usize = 1;
+pub const SYNC_SIZE: usize = 11;
+pub const ID_SIZE: usize = 2;
 pub const LAYER_SIZE: usize = 2;
 pub const PROTECTION_BIT_SIZE: usize = 1;
 pub const BITRATE_SIZE: usize = 4;
@@ -13,3 +13,997 @@ pub const MODE_EXTENSION_SIZE: usize = 2;
 pub const COPYRIGHT_BIT_SIZE: usize = 1;
 pub const HOME_SIZE: usize = 1;
 pub const EMPHASIS_SIZE: usize = 2;
+
+// Bit position of each field from the start of the header, each following the previous one
+pub const SYNC_OFFSET: usize = 0;
+pub const ID_OFFSET: usize = SYNC_OFFSET + SYNC_SIZE;
+pub const LAYER_OFFSET: usize = ID_OFFSET + ID_SIZE;
+pub const PROTECTION_BIT_OFFSET: usize = LAYER_OFFSET + LAYER_SIZE;
+pub const BITRATE_OFFSET: usize = PROTECTION_BIT_OFFSET + PROTECTION_BIT_SIZE;
+pub const FREQUENCY_OFFSET: usize = BITRATE_OFFSET + BITRATE_SIZE;
+pub const PADDING_BIT_OFFSET: usize = FREQUENCY_OFFSET + FREQUENCY_SIZE;
+pub const PRIVATE_BIT_OFFSET: usize = PADDING_BIT_OFFSET + PADDING_BIT_SIZE;
+pub const MODE_OFFSET: usize = PRIVATE_BIT_OFFSET + PRIVATE_BIT_SIZE;
+pub const MODE_EXTENSION_OFFSET: usize = MODE_OFFSET + MODE_SIZE;
+pub const COPYRIGHT_BIT_OFFSET: usize = MODE_EXTENSION_OFFSET + MODE_EXTENSION_SIZE;
+pub const HOME_OFFSET: usize = COPYRIGHT_BIT_OFFSET + COPYRIGHT_BIT_SIZE;
+pub const EMPHASIS_OFFSET: usize = HOME_OFFSET + HOME_SIZE;
+
+// (offset, size) of the 13 fields, in header order
+pub const FIELD_OFFSETS: [(usize, usize); 13] = [
+    (SYNC_OFFSET, SYNC_SIZE),
+    (ID_OFFSET, ID_SIZE),
+    (LAYER_OFFSET, LAYER_SIZE),
+    (PROTECTION_BIT_OFFSET, PROTECTION_BIT_SIZE),
+    (BITRATE_OFFSET, BITRATE_SIZE),
+    (FREQUENCY_OFFSET, FREQUENCY_SIZE),
+    (PADDING_BIT_OFFSET, PADDING_BIT_SIZE),
+    (PRIVATE_BIT_OFFSET, PRIVATE_BIT_SIZE),
+    (MODE_OFFSET, MODE_SIZE),
+    (MODE_EXTENSION_OFFSET, MODE_EXTENSION_SIZE),
+    (COPYRIGHT_BIT_OFFSET, COPYRIGHT_BIT_SIZE),
+    (HOME_OFFSET, HOME_SIZE),
+    (EMPHASIS_OFFSET, EMPHASIS_SIZE),
+];
+
+// The fields cover the header exactly
+const _: () = assert!(EMPHASIS_OFFSET + EMPHASIS_SIZE == HEADER_SIZE);
+
+// MPEG-1 Layer III, no CRC, 128 kbit/s, 44100 Hz, stereo, no padding and every flag cleared
+pub const DEFAULT_HEADER: [u8; 4] = [0xFF, 0xFB, 0x90, 0x00];
+
+// LAME extension, stored right after the Xing/Info data
+pub const LAME_TAG_SIZE: usize = 36;
+pub const LAME_ENCODER_SIZE: usize = 9;
+pub const LAME_VBR_METHOD_OFFSET: usize = 9;
+pub const LAME_LOWPASS_OFFSET: usize = 10;
+pub const LAME_PEAK_OFFSET: usize = 11;
+pub const LAME_RADIO_GAIN_OFFSET: usize = 15;
+pub const LAME_AUDIOPHILE_GAIN_OFFSET: usize = 17;
+pub const LAME_FLAGS_OFFSET: usize = 19;
+pub const LAME_BITRATE_OFFSET: usize = 20;
+pub const LAME_DELAY_PADDING_OFFSET: usize = 21;
+pub const LAME_MISC_OFFSET: usize = 24;
+pub const LAME_MP3_GAIN_OFFSET: usize = 25;
+pub const LAME_PRESET_OFFSET: usize = 26;
+pub const LAME_MUSIC_LENGTH_OFFSET: usize = 28;
+pub const LAME_MUSIC_CRC_OFFSET: usize = 32;
+// Covers every byte of the frame before it
+pub const LAME_TAG_CRC_OFFSET: usize = 34;
+
+// Samples of latency added by the standard decoding process (hybrid filterbank + synthesis)
+pub const DECODER_DELAY: usize = 529;
+
+// In bytes
+pub const CRC_SIZE: usize = 2;
+
+// Xing/Info header flags
+pub const XING_FRAMES_FLAG: u32 = 0x0001;
+pub const XING_BYTES_FLAG: u32 = 0x0002;
+pub const XING_TOC_FLAG: u32 = 0x0004;
+pub const XING_QUALITY_FLAG: u32 = 0x0008;
+pub const XING_TOC_SIZE: usize = 100;
+
+// Frames sharing their bitrate at the start of a stream without Xing/Info or VBRI header for
+// `duration` to take it for CBR
+pub const CBR_PROBE_FRAMES: usize = 8;
+
+// The VBRI header starts 32 bytes after the 4 byte frame header
+pub const VBRI_OFFSET: usize = 36;
+// Fixed part of the VBRI header, the toc follows
+pub const VBRI_HEADER_SIZE: usize = 26;
+
+// ID3v2 header and footer are both 10 bytes
+pub const ID3V2_HEADER_SIZE: usize = 10;
+pub const ID3V2_FRAME_HEADER_SIZE: usize = 10;
+pub const ID3V22_FRAME_HEADER_SIZE: usize = 6;
+pub const ID3V2_UNSYNCHRONISATION_FLAG: u8 = 0x80;
+pub const ID3V2_EXTENDED_HEADER_FLAG: u8 = 0x40;
+// Same bit as the extended header in 2.3 and 2.4
+pub const ID3V22_COMPRESSION_FLAG: u8 = 0x40;
+pub const ID3V2_FOOTER_FLAG: u8 = 0x10;
+// CTOC frame flag of the root table of contents
+pub const ID3V2_CTOC_TOP_LEVEL_FLAG: u8 = 0x02;
+
+// How far `probe` looks for frames, past any leading ID3v2 tag
+pub const PROBE_LIMIT: usize = 64 * 1024;
+pub const PROBE_FRAMES: usize = 3;
+
+// Layer III scalefactor bit lengths (slen1, slen2) indexed by the MPEG-1 scalefac_compress
+pub const SLEN: [(usize, usize); 16] = [
+    (0, 0),
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (3, 0),
+    (1, 1),
+    (1, 2),
+    (1, 3),
+    (2, 1),
+    (2, 2),
+    (2, 3),
+    (3, 1),
+    (3, 2),
+    (3, 3),
+    (4, 2),
+    (4, 3),
+];
+
+// First long block band of each scfsi group, the last group ending at band 21
+pub const SCFSI_BANDS: [usize; 5] = [0, 6, 11, 16, 21];
+
+// MPEG-2 and 2.5 scalefactor partitions: number of scalefactors read with each of the 4 slen,
+// indexed by [table][block][partition]. Tables 0 to 2 are selected by the scalefac_compress
+// range, 3 to 5 by the same ranges for the intensity coded channel. Blocks are long, short and
+// mixed, short blocks counting one scalefactor per window.
+pub const NR_OF_SFB: [[[usize; 4]; 3]; 6] = [
+    [[6, 5, 5, 5], [9, 9, 9, 9], [6, 9, 9, 9]],
+    [[6, 5, 7, 3], [9, 9, 12, 6], [6, 9, 12, 6]],
+    [[11, 10, 0, 0], [18, 18, 0, 0], [15, 18, 0, 0]],
+    [[7, 7, 7, 0], [12, 12, 12, 0], [6, 15, 12, 0]],
+    [[6, 6, 6, 3], [12, 9, 9, 6], [6, 12, 9, 6]],
+    [[8, 8, 5, 0], [15, 12, 9, 0], [6, 18, 9, 0]],
+];
+
+// Sample rates of each version, indexed by the frequency field. MPEG-2 halves the MPEG-1 ones and
+// MPEG-2.5 quarters them.
+pub const MPEG1_SAMPLE_RATES: [usize; 3] = [44100, 48000, 32000];
+pub const MPEG2_SAMPLE_RATES: [usize; 3] = [22050, 24000, 16000];
+pub const MPEG25_SAMPLE_RATES: [usize; 3] = [11025, 12000, 8000];
+
+// Layer III sample rates, in the order of the scalefactor band tables
+pub const SFB_SAMPLE_RATES: [usize; 9] =
+    [44100, 48000, 32000, 22050, 24000, 16000, 11025, 12000, 8000];
+
+// First spectral line of each long block scalefactor band, per sample rate, ending with 576
+pub const SFB_LONG: [[usize; 23]; 9] = [
+    // 44.1 kHz
+    [
+        0, 4, 8, 12, 16, 20, 24, 30, 36, 44, 52, 62, 74, 90, 110, 134, 162, 196, 238, 288, 342,
+        418, 576,
+    ],
+    // 48 kHz
+    [
+        0, 4, 8, 12, 16, 20, 24, 30, 36, 42, 50, 60, 72, 88, 106, 128, 156, 190, 230, 276, 330,
+        384, 576,
+    ],
+    // 32 kHz
+    [
+        0, 4, 8, 12, 16, 20, 24, 30, 36, 44, 54, 66, 82, 102, 126, 156, 194, 240, 296, 364, 448,
+        550, 576,
+    ],
+    // 22.05 kHz
+    [
+        0, 6, 12, 18, 24, 30, 36, 44, 54, 66, 80, 96, 116, 140, 168, 200, 238, 284, 336, 396, 464,
+        522, 576,
+    ],
+    // 24 kHz
+    [
+        0, 6, 12, 18, 24, 30, 36, 44, 54, 66, 80, 96, 114, 136, 162, 194, 232, 278, 332, 394, 464,
+        540, 576,
+    ],
+    // 16 kHz
+    [
+        0, 6, 12, 18, 24, 30, 36, 44, 54, 66, 80, 96, 116, 140, 168, 200, 238, 284, 336, 396, 464,
+        522, 576,
+    ],
+    // 11.025 kHz
+    [
+        0, 6, 12, 18, 24, 30, 36, 44, 54, 66, 80, 96, 116, 140, 168, 200, 238, 284, 336, 396, 464,
+        522, 576,
+    ],
+    // 12 kHz
+    [
+        0, 6, 12, 18, 24, 30, 36, 44, 54, 66, 80, 96, 116, 140, 168, 200, 238, 284, 336, 396, 464,
+        522, 576,
+    ],
+    // 8 kHz
+    [
+        0, 12, 24, 36, 48, 60, 72, 88, 108, 132, 160, 192, 232, 280, 336, 400, 476, 566, 568, 570,
+        572, 574, 576,
+    ],
+];
+
+// First spectral line of each short block scalefactor band (within one window), per sample
+// rate, ending with 192
+pub const SFB_SHORT: [[usize; 14]; 9] = [
+    // 44.1 kHz
+    [0, 4, 8, 12, 16, 22, 30, 40, 52, 66, 84, 106, 136, 192],
+    // 48 kHz
+    [0, 4, 8, 12, 16, 22, 28, 38, 50, 64, 80, 100, 126, 192],
+    // 32 kHz
+    [0, 4, 8, 12, 16, 22, 30, 42, 58, 78, 104, 138, 180, 192],
+    // 22.05 kHz
+    [0, 4, 8, 12, 18, 24, 32, 42, 56, 74, 100, 132, 174, 192],
+    // 24 kHz
+    [0, 4, 8, 12, 18, 26, 36, 48, 62, 80, 104, 136, 180, 192],
+    // 16 kHz
+    [0, 4, 8, 12, 18, 26, 36, 48, 62, 80, 104, 134, 174, 192],
+    // 11.025 kHz
+    [0, 4, 8, 12, 18, 26, 36, 48, 62, 80, 104, 134, 174, 192],
+    // 12 kHz
+    [0, 4, 8, 12, 18, 26, 36, 48, 62, 80, 104, 134, 174, 192],
+    // 8 kHz
+    [0, 8, 16, 24, 36, 52, 72, 96, 124, 160, 162, 164, 166, 192],
+];
+
+// "RIFF", the file size and "WAVE", then chunks each starting with an id and a size
+pub const RIFF_HEADER_SIZE: usize = 12;
+pub const RIFF_CHUNK_HEADER_SIZE: usize = 8;
+// Format tags of the fmt chunk
+pub const WAVE_FORMAT_MPEG: u16 = 0x0050;
+pub const WAVE_FORMAT_MPEG_LAYER3: u16 = 0x0055;
+
+// Default buffer size of a decoder reading its frames, see `Decoder::new`
+pub const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+// Bytes such a decoder keeps buffered ahead when looking for a frame, enough for the largest frame
+// (5760 bytes, a 640 kbit/s free format MPEG-2.5 Layer III frame) and the one confirming it
+pub const STREAM_LOOKAHEAD: usize = 16 * 1024;
+
+// APE tag footer (and header), and the ID3v1 tag that may follow it
+pub const APE_FOOTER_SIZE: usize = 32;
+pub const APE_HAS_HEADER_FLAG: u32 = 0x8000_0000;
+pub const APE_IS_HEADER_FLAG: u32 = 0x2000_0000;
+pub const ID3V1_SIZE: usize = 128;
+
+// ID3v1 genre list: 0 to 79 from the original specification, 80 to 191 added by Winamp
+pub const GENRES: [&str; 192] = [
+    "Blues",
+    "Classic Rock",
+    "Country",
+    "Dance",
+    "Disco",
+    "Funk",
+    "Grunge",
+    "Hip-Hop",
+    "Jazz",
+    "Metal",
+    "New Age",
+    "Oldies",
+    "Other",
+    "Pop",
+    "R&B",
+    "Rap",
+    "Reggae",
+    "Rock",
+    "Techno",
+    "Industrial",
+    "Alternative",
+    "Ska",
+    "Death Metal",
+    "Pranks",
+    "Soundtrack",
+    "Euro-Techno",
+    "Ambient",
+    "Trip-Hop",
+    "Vocal",
+    "Jazz+Funk",
+    "Fusion",
+    "Trance",
+    "Classical",
+    "Instrumental",
+    "Acid",
+    "House",
+    "Game",
+    "Sound Clip",
+    "Gospel",
+    "Noise",
+    "Alternative Rock",
+    "Bass",
+    "Soul",
+    "Punk",
+    "Space",
+    "Meditative",
+    "Instrumental Pop",
+    "Instrumental Rock",
+    "Ethnic",
+    "Gothic",
+    "Darkwave",
+    "Techno-Industrial",
+    "Electronic",
+    "Pop-Folk",
+    "Eurodance",
+    "Dream",
+    "Southern Rock",
+    "Comedy",
+    "Cult",
+    "Gangsta",
+    "Top 40",
+    "Christian Rap",
+    "Pop/Funk",
+    "Jungle",
+    "Native American",
+    "Cabaret",
+    "New Wave",
+    "Psychedelic",
+    "Rave",
+    "Showtunes",
+    "Trailer",
+    "Lo-Fi",
+    "Tribal",
+    "Acid Punk",
+    "Acid Jazz",
+    "Polka",
+    "Retro",
+    "Musical",
+    "Rock & Roll",
+    "Hard Rock",
+    "Folk",
+    "Folk-Rock",
+    "National Folk",
+    "Swing",
+    "Fast Fusion",
+    "Bebop",
+    "Latin",
+    "Revival",
+    "Celtic",
+    "Bluegrass",
+    "Avantgarde",
+    "Gothic Rock",
+    "Progressive Rock",
+    "Psychedelic Rock",
+    "Symphonic Rock",
+    "Slow Rock",
+    "Big Band",
+    "Chorus",
+    "Easy Listening",
+    "Acoustic",
+    "Humour",
+    "Speech",
+    "Chanson",
+    "Opera",
+    "Chamber Music",
+    "Sonata",
+    "Symphony",
+    "Booty Bass",
+    "Primus",
+    "Porn Groove",
+    "Satire",
+    "Slow Jam",
+    "Club",
+    "Tango",
+    "Samba",
+    "Folklore",
+    "Ballad",
+    "Power Ballad",
+    "Rhythmic Soul",
+    "Freestyle",
+    "Duet",
+    "Punk Rock",
+    "Drum Solo",
+    "A Cappella",
+    "Euro-House",
+    "Dance Hall",
+    "Goa",
+    "Drum & Bass",
+    "Club-House",
+    "Hardcore",
+    "Terror",
+    "Indie",
+    "BritPop",
+    "Afro-Punk",
+    "Polsk Punk",
+    "Beat",
+    "Christian Gangsta Rap",
+    "Heavy Metal",
+    "Black Metal",
+    "Crossover",
+    "Contemporary Christian",
+    "Christian Rock",
+    "Merengue",
+    "Salsa",
+    "Thrash Metal",
+    "Anime",
+    "JPop",
+    "Synthpop",
+    "Abstract",
+    "Art Rock",
+    "Baroque",
+    "Bhangra",
+    "Big Beat",
+    "Breakbeat",
+    "Chillout",
+    "Downtempo",
+    "Dub",
+    "EBM",
+    "Eclectic",
+    "Electro",
+    "Electroclash",
+    "Emo",
+    "Experimental",
+    "Garage",
+    "Global",
+    "IDM",
+    "Illbient",
+    "Industro-Goth",
+    "Jam Band",
+    "Krautrock",
+    "Leftfield",
+    "Lounge",
+    "Math Rock",
+    "New Romantic",
+    "Nu-Breakz",
+    "Post-Punk",
+    "Post-Rock",
+    "Psytrance",
+    "Shoegaze",
+    "Space Rock",
+    "Trop Rock",
+    "World Music",
+    "Neoclassical",
+    "Audiobook",
+    "Audio Theatre",
+    "Neue Deutsche Welle",
+    "Podcast",
+    "Indie Rock",
+    "G-Funk",
+    "Dubstep",
+    "Garage Rock",
+    "Psybient",
+];
+
+// Added to the long block scalefactors when preflag is set
+pub const PRETAB: [usize; 22] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 3, 3, 3, 2, 0,
+];
+
+// Layer III alias reduction butterflies, from the coefficients
+// c = [-0.6, -0.535, -0.33, -0.185, -0.095, -0.041, -0.0142, -0.0037]:
+// cs = 1 / sqrt(1 + c²), ca = c / sqrt(1 + c²)
+pub const ALIAS_CS: [f32; 8] = [
+    0.8574929, 0.881742, 0.94962865, 0.9833146, 0.9955178, 0.9991606, 0.9998992, 0.99999315,
+];
+pub const ALIAS_CA: [f32; 8] = [
+    -0.51449573,
+    -0.47173196,
+    -0.31337744,
+    -0.1819132,
+    -0.09457419,
+    -0.040965583,
+    -0.014198569,
+    -0.0036999746,
+];
+
+// Polyphase synthesis window D (table 3-B.3 of ISO/IEC 11172-3)
+pub const SYNTH_WINDOW: [f32; 512] = [
+    0.0,
+    -0.000015259,
+    -0.000015259,
+    -0.000015259,
+    -0.000015259,
+    -0.000015259,
+    -0.000015259,
+    -0.000030518,
+    -0.000030518,
+    -0.000030518,
+    -0.000030518,
+    -0.000045776,
+    -0.000045776,
+    -0.000061035,
+    -0.000061035,
+    -0.000076294,
+    -0.000076294,
+    -0.000091553,
+    -0.000106812,
+    -0.000106812,
+    -0.00012207,
+    -0.000137329,
+    -0.000152588,
+    -0.000167847,
+    -0.000198364,
+    -0.000213623,
+    -0.000244141,
+    -0.000259399,
+    -0.000289917,
+    -0.000320435,
+    -0.000366211,
+    -0.000396729,
+    -0.000442505,
+    -0.000473022,
+    -0.000534058,
+    -0.000579834,
+    -0.00062561,
+    -0.000686646,
+    -0.000747681,
+    -0.000808716,
+    -0.00088501,
+    -0.000961304,
+    -0.001037598,
+    -0.001113892,
+    -0.001205444,
+    -0.001296997,
+    -0.00138855,
+    -0.001480103,
+    -0.001586914,
+    -0.001693726,
+    -0.001785278,
+    -0.001907349,
+    -0.00201416,
+    -0.002120972,
+    -0.002243042,
+    -0.002349854,
+    -0.002456665,
+    -0.002578735,
+    -0.002685547,
+    -0.002792358,
+    -0.00289917,
+    -0.002990723,
+    -0.003082275,
+    -0.003173828,
+    0.003250122,
+    0.003326416,
+    0.003387451,
+    0.003433228,
+    0.003463745,
+    0.003479004,
+    0.003479004,
+    0.003463745,
+    0.003417969,
+    0.003372192,
+    0.00328064,
+    0.003173828,
+    0.003051758,
+    0.002883911,
+    0.002700806,
+    0.002487183,
+    0.002227783,
+    0.001937866,
+    0.001617432,
+    0.001266479,
+    0.000869751,
+    0.000442505,
+    -0.000030518,
+    -0.000549316,
+    -0.001098633,
+    -0.001693726,
+    -0.002334595,
+    -0.003005981,
+    -0.003723145,
+    -0.004486084,
+    -0.0052948,
+    -0.006118774,
+    -0.007003784,
+    -0.007919312,
+    -0.008865356,
+    -0.009841919,
+    -0.010848999,
+    -0.011886597,
+    -0.012939453,
+    -0.014022827,
+    -0.01512146,
+    -0.016235352,
+    -0.017349243,
+    -0.018463135,
+    -0.019577026,
+    -0.020690918,
+    -0.02178955,
+    -0.022857666,
+    -0.023910522,
+    -0.024932861,
+    -0.025909424,
+    -0.02684021,
+    -0.02772522,
+    -0.028533936,
+    -0.029281616,
+    -0.029937744,
+    -0.030532837,
+    -0.03100586,
+    -0.03138733,
+    -0.031661987,
+    -0.031814575,
+    -0.031845093,
+    -0.03173828,
+    -0.03147888,
+    0.031082153,
+    0.030517578,
+    0.029785156,
+    0.028884888,
+    0.027801514,
+    0.026535034,
+    0.02508545,
+    0.023422241,
+    0.021575928,
+    0.01953125,
+    0.01725769,
+    0.014801025,
+    0.012115479,
+    0.009231567,
+    0.006134033,
+    0.002822876,
+    -0.000686646,
+    -0.004394531,
+    -0.00831604,
+    -0.012420654,
+    -0.016708374,
+    -0.0211792,
+    -0.025817871,
+    -0.03060913,
+    -0.03555298,
+    -0.040634155,
+    -0.045837402,
+    -0.051132202,
+    -0.056533813,
+    -0.06199646,
+    -0.06752014,
+    -0.07305908,
+    -0.07862854,
+    -0.08418274,
+    -0.08970642,
+    -0.09516907,
+    -0.10054016,
+    -0.1058197,
+    -0.110946655,
+    -0.11592102,
+    -0.12069702,
+    -0.1252594,
+    -0.12956238,
+    -0.1335907,
+    -0.13729858,
+    -0.14067078,
+    -0.14367676,
+    -0.1462555,
+    -0.14842224,
+    -0.15011597,
+    -0.15130615,
+    -0.15196228,
+    -0.15206909,
+    -0.15159607,
+    -0.15049744,
+    -0.1487732,
+    -0.1463623,
+    -0.14326477,
+    -0.13945007,
+    -0.1348877,
+    -0.12957764,
+    -0.12347412,
+    -0.11657715,
+    -0.1088562,
+    0.10031128,
+    0.090927124,
+    0.08068848,
+    0.06959534,
+    0.057617188,
+    0.044784546,
+    0.031082153,
+    0.01651001,
+    0.001068115,
+    -0.015228271,
+    -0.03237915,
+    -0.050354004,
+    -0.06916809,
+    -0.088775635,
+    -0.10916138,
+    -0.13031006,
+    -0.15220642,
+    -0.17478943,
+    -0.19805908,
+    -0.22198486,
+    -0.24650574,
+    -0.2715912,
+    -0.2972107,
+    -0.32331848,
+    -0.34986877,
+    -0.37680054,
+    -0.40408325,
+    -0.43165588,
+    -0.45947266,
+    -0.48747253,
+    -0.51560974,
+    -0.54382324,
+    -0.57203674,
+    -0.6002197,
+    -0.6282959,
+    -0.6562195,
+    -0.6839142,
+    -0.71131897,
+    -0.7383728,
+    -0.7650299,
+    -0.791214,
+    -0.816864,
+    -0.84194946,
+    -0.8663635,
+    -0.89009094,
+    -0.9130554,
+    -0.9351959,
+    -0.95648193,
+    -0.9768524,
+    -0.99624634,
+    -1.0146179,
+    -1.0319366,
+    -1.0481567,
+    -1.0632172,
+    -1.0771179,
+    -1.0897827,
+    -1.1012115,
+    -1.1113739,
+    -1.120224,
+    -1.1277466,
+    -1.1339264,
+    -1.1387634,
+    -1.1422119,
+    -1.1442871,
+    1.144989,
+    1.1442871,
+    1.1422119,
+    1.1387634,
+    1.1339264,
+    1.1277466,
+    1.120224,
+    1.1113739,
+    1.1012115,
+    1.0897827,
+    1.0771179,
+    1.0632172,
+    1.0481567,
+    1.0319366,
+    1.0146179,
+    0.99624634,
+    0.9768524,
+    0.95648193,
+    0.9351959,
+    0.9130554,
+    0.89009094,
+    0.8663635,
+    0.84194946,
+    0.816864,
+    0.791214,
+    0.7650299,
+    0.7383728,
+    0.71131897,
+    0.6839142,
+    0.6562195,
+    0.6282959,
+    0.6002197,
+    0.57203674,
+    0.54382324,
+    0.51560974,
+    0.48747253,
+    0.45947266,
+    0.43165588,
+    0.40408325,
+    0.37680054,
+    0.34986877,
+    0.32331848,
+    0.2972107,
+    0.2715912,
+    0.24650574,
+    0.22198486,
+    0.19805908,
+    0.17478943,
+    0.15220642,
+    0.13031006,
+    0.10916138,
+    0.088775635,
+    0.06916809,
+    0.050354004,
+    0.03237915,
+    0.015228271,
+    -0.001068115,
+    -0.01651001,
+    -0.031082153,
+    -0.044784546,
+    -0.057617188,
+    -0.06959534,
+    -0.08068848,
+    -0.090927124,
+    0.10031128,
+    0.1088562,
+    0.11657715,
+    0.12347412,
+    0.12957764,
+    0.1348877,
+    0.13945007,
+    0.14326477,
+    0.1463623,
+    0.1487732,
+    0.15049744,
+    0.15159607,
+    0.15206909,
+    0.15196228,
+    0.15130615,
+    0.15011597,
+    0.14842224,
+    0.1462555,
+    0.14367676,
+    0.14067078,
+    0.13729858,
+    0.1335907,
+    0.12956238,
+    0.1252594,
+    0.12069702,
+    0.11592102,
+    0.110946655,
+    0.1058197,
+    0.10054016,
+    0.09516907,
+    0.08970642,
+    0.08418274,
+    0.07862854,
+    0.07305908,
+    0.06752014,
+    0.06199646,
+    0.056533813,
+    0.051132202,
+    0.045837402,
+    0.040634155,
+    0.03555298,
+    0.03060913,
+    0.025817871,
+    0.0211792,
+    0.016708374,
+    0.012420654,
+    0.00831604,
+    0.004394531,
+    0.000686646,
+    -0.002822876,
+    -0.006134033,
+    -0.009231567,
+    -0.012115479,
+    -0.014801025,
+    -0.01725769,
+    -0.01953125,
+    -0.021575928,
+    -0.023422241,
+    -0.02508545,
+    -0.026535034,
+    -0.027801514,
+    -0.028884888,
+    -0.029785156,
+    -0.030517578,
+    0.031082153,
+    0.03147888,
+    0.03173828,
+    0.031845093,
+    0.031814575,
+    0.031661987,
+    0.03138733,
+    0.03100586,
+    0.030532837,
+    0.029937744,
+    0.029281616,
+    0.028533936,
+    0.02772522,
+    0.02684021,
+    0.025909424,
+    0.024932861,
+    0.023910522,
+    0.022857666,
+    0.02178955,
+    0.020690918,
+    0.019577026,
+    0.018463135,
+    0.017349243,
+    0.016235352,
+    0.01512146,
+    0.014022827,
+    0.012939453,
+    0.011886597,
+    0.010848999,
+    0.009841919,
+    0.008865356,
+    0.007919312,
+    0.007003784,
+    0.006118774,
+    0.0052948,
+    0.004486084,
+    0.003723145,
+    0.003005981,
+    0.002334595,
+    0.001693726,
+    0.001098633,
+    0.000549316,
+    0.000030518,
+    -0.000442505,
+    -0.000869751,
+    -0.001266479,
+    -0.001617432,
+    -0.001937866,
+    -0.002227783,
+    -0.002487183,
+    -0.002700806,
+    -0.002883911,
+    -0.003051758,
+    -0.003173828,
+    -0.00328064,
+    -0.003372192,
+    -0.003417969,
+    -0.003463745,
+    -0.003479004,
+    -0.003479004,
+    -0.003463745,
+    -0.003433228,
+    -0.003387451,
+    -0.003326416,
+    0.003250122,
+    0.003173828,
+    0.003082275,
+    0.002990723,
+    0.00289917,
+    0.002792358,
+    0.002685547,
+    0.002578735,
+    0.002456665,
+    0.002349854,
+    0.002243042,
+    0.002120972,
+    0.00201416,
+    0.001907349,
+    0.001785278,
+    0.001693726,
+    0.001586914,
+    0.001480103,
+    0.00138855,
+    0.001296997,
+    0.001205444,
+    0.001113892,
+    0.001037598,
+    0.000961304,
+    0.00088501,
+    0.000808716,
+    0.000747681,
+    0.000686646,
+    0.00062561,
+    0.000579834,
+    0.000534058,
+    0.000473022,
+    0.000442505,
+    0.000396729,
+    0.000366211,
+    0.000320435,
+    0.000289917,
+    0.000259399,
+    0.000244141,
+    0.000213623,
+    0.000198364,
+    0.000167847,
+    0.000152588,
+    0.000137329,
+    0.00012207,
+    0.000106812,
+    0.000106812,
+    0.000091553,
+    0.000076294,
+    0.000076294,
+    0.000061035,
+    0.000061035,
+    0.000045776,
+    0.000045776,
+    0.000030518,
+    0.000030518,
+    0.000030518,
+    0.000030518,
+    0.000015259,
+    0.000015259,
+    0.000015259,
+    0.000015259,
+    0.000015259,
+    0.000015259,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::HEADER_128;
+    use bitvec::prelude::*;
+
+    #[test]
+    fn field_offsets() {
+        // Back to back from bit 0 up to the end of the header
+        let mut end = 0;
+        for (offset, size) in FIELD_OFFSETS {
+            assert_eq!(offset, end);
+            end += size;
+        }
+        assert_eq!(end, HEADER_SIZE);
+        assert_eq!(EMPHASIS_OFFSET + EMPHASIS_SIZE, HEADER_SIZE);
+
+        // 0xFF 0xFB 0x90 0x40 read field by field
+        let bits = HEADER_128.view_bits::<Msb0>();
+        let fields: Vec<u8> = FIELD_OFFSETS
+            .iter()
+            .skip(1)
+            .map(|(offset, size)| bits[*offset..offset + size].load_be())
+            .collect();
+        assert_eq!(fields, [0b11, 0b01, 1, 9, 0, 0, 0, 0b01, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{bit_reader::BitReader, constants::*, header::*, layer2::layer2_alloc_table};
+
+// CRC-16 used by MPEG audio: polynomial 0x8005, initial value 0xFFFF
+// It covers the last 16 bits of the header then a layer dependent part of the frame.
+const POLYNOMIAL: u16 = 0x8005;
+
+fn crc16(crc: u16, bytes: &[u8], bits: usize) -> u16 {
+    (0..bits).fold(crc, |crc, i| {
+        let bit = (bytes[i / 8] >> (7 - i % 8)) & 1;
+        let top = (crc >> 15) as u8;
+        let crc = crc << 1;
+
+        if top ^ bit == 1 {
+            crc ^ POLYNOMIAL
+        } else {
+            crc
+        }
+    })
+}
+
+// CRC-16 of the LAME tag: polynomial 0x8005 reflected, initial value 0
+pub(crate) fn lame_crc(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u16, |crc, _| {
+            if crc & 1 == 1 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+// Number of bits covered by the CRC after the header and the CRC itself
+fn protected_bits(frame: &[u8], header: &Header) -> Option<usize> {
+    match header.layer() {
+        // The bit allocation, 4 bits per subband and channel (shared above the joint stereo bound)
+        Layer::One => {
+            let bound = match header.mode() {
+                Mode::SingleChannel => return Some(32 * 4),
+                Mode::JointStereo => header.mode_extension().bound(),
+                _ => 32,
+            };
+            Some(4 * (2 * bound + (32 - bound)))
+        }
+        // The bit allocation, then 2 bits of scalefactor selection for each subband and channel
+        // carrying samples, so the allocation has to be read
+        Layer::Two => {
+            let table = layer2_alloc_table(header).table();
+            let bound = header.joint_stereo_bound(table.sblimit());
+            let mut reader = BitReader::new(frame.get(HEADER_SIZE / 8 + CRC_SIZE..)?);
+
+            let mut allocated = 0;
+            for subband in 0..table.sblimit() {
+                let nbal = table.nbal(subband);
+                if subband < bound {
+                    for _ in 0..header.channels() {
+                        allocated += (reader.read_bits(nbal).ok()? != 0) as usize;
+                    }
+                } else if reader.read_bits(nbal).ok()? != 0 {
+                    allocated += header.channels();
+                }
+            }
+            Some(reader.position() + 2 * allocated)
+        }
+        Layer::Three => Some(header.side_info_length() * 8),
+        Layer::Reserved => None,
+    }
+}
+
+// CRC of the frame as it should be stored, None if the protected region can't be determined
+pub(crate) fn compute_crc(frame: &[u8], header: &Header) -> Option<u16> {
+    let start = HEADER_SIZE / 8 + CRC_SIZE;
+    let bits = protected_bits(frame, header)?;
+    let protected = frame.get(start..start + bits.div_ceil(8))?;
+
+    Some(crc16(crc16(0xFFFF, &frame[2..4], 16), protected, bits))
+}
+
+// None when the frame isn't protected or is too short for its protected region
+pub fn verify_crc(frame: &[u8], header: &Header) -> Option<bool> {
+    if !header.is_protected() {
+        return None;
+    }
+
+    let stored = u16::from_be_bytes([*frame.get(4)?, *frame.get(5)?]);
+    Some(compute_crc(frame, header)? == stored)
+}
+
+// Stores the CRC the frame should carry in the 2 bytes following the header. Frames that aren't
+// protected, or are too short for their protected region, are left untouched.
+pub fn recompute_crc(frame: &mut [u8], header: &Header) {
+    if !header.is_protected() {
+        return;
+    }
+
+    if let Some(crc) = compute_crc(frame, header) {
+        if let Some(stored) = frame.get_mut(HEADER_SIZE / 8..HEADER_SIZE / 8 + CRC_SIZE) {
+            stored.copy_from_slice(&crc.to_be_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::FrameIterator;
+
+    #[test]
+    fn layer2_crc() {
+        let data = include_bytes!("../fixtures/layer2.mp2");
+        let frames: Vec<_> = FrameIterator::new(data).flatten().collect();
+        assert_eq!(frames.len(), 12);
+
+        for frame in frames {
+            assert_eq!(verify_crc(frame.data, &frame.header), Some(true));
+
+            let mut bytes = frame.data.to_vec();
+            bytes[6] ^= 0x80;
+            assert_eq!(verify_crc(&bytes, &frame.header), Some(false));
+            recompute_crc(&mut bytes, &frame.header);
+            assert_eq!(verify_crc(&bytes, &frame.header), Some(true));
+        }
+    }
+
+    #[test]
+    fn repair_corrupted_crc() {
+        let data = include_bytes!("../fixtures/crc.mp3");
+        for frame in FrameIterator::new(data).flatten() {
+            let mut bytes = frame.data.to_vec();
+            assert_eq!(verify_crc(&bytes, &frame.header), Some(true));
+
+            bytes[4] ^= 0xFF;
+            assert_eq!(verify_crc(&bytes, &frame.header), Some(false));
+            recompute_crc(&mut bytes, &frame.header);
+            assert_eq!(verify_crc(&bytes, &frame.header), Some(true));
+            assert_eq!(bytes, frame.data);
+
+            // Too short for the side info
+            let mut short = frame.data[..10].to_vec();
+            short[4] ^= 0xFF;
+            recompute_crc(&mut short, &frame.header);
+            assert_eq!(short[4], frame.data[4] ^ 0xFF);
+        }
+
+        // Unprotected frames are left untouched
+        let data = include_bytes!("../fixtures/cbr.mp3");
+        let frame = FrameIterator::new(data).flatten().nth(1).unwrap();
+        let mut bytes = frame.data.to_vec();
+        recompute_crc(&mut bytes, &frame.header);
+        assert_eq!(bytes, frame.data);
+        assert_eq!(verify_crc(&bytes, &frame.header), None);
+    }
+}
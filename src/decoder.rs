@@ -0,0 +1,772 @@
+use crate::{
+    bit_reader::BitReader,
+    error::ChompError,
+    frame::Frame,
+    header::{Header, Layer},
+    huffman::{decode_big_values, decode_count1},
+    hybrid::{antialias, frequency_inversion, imdct},
+    lame::LameTag,
+    layer1::decode_layer1,
+    layer2::decode_layer2,
+    reorder::reorder,
+    replaygain::{ReplayGain, ReplayGainOptions},
+    requantize::requantize,
+    scalefactors::Scalefactors,
+    side_info::{GranuleInfo, SideInfo},
+    stereo::stereo,
+    stream::Input,
+    synthesis::{synthesis, synthesize_slot},
+    vbr::VbrInfo,
+};
+
+// The furthest back main_data_begin can point, in bytes (9 bits for MPEG-1)
+const RESERVOIR_SIZE: usize = 511;
+
+// PCM samples of one frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedFrame {
+    // Interleaved, nominally within [-1.0, 1.0]
+    samples: Vec<f32>,
+    channels: usize,
+    // In Hz
+    sample_rate: usize,
+}
+
+impl DecodedFrame {
+    // Interleaved: left, right, left, right... for stereo
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    pub fn samples_per_channel(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    // Samples as 16 bit integers, interleaved like `samples`
+    pub fn samples_i16(&self) -> Vec<i16> {
+        self.samples
+            .iter()
+            .map(|sample| (sample * 32768.0).round().clamp(-32768.0, 32767.0) as i16)
+            .collect()
+    }
+}
+
+// Outcome of feeding one frame to the decoder
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decoded {
+    Frame(DecodedFrame),
+    // The frame's main data starts `missing` bytes before what the previous frames provided
+    // (first frames of a stream, after a seek or after corrupted frames). Its audio is skipped
+    // but its bytes still feed the bit reservoir, so decoding can go on with the next frames.
+    Skipped { missing: usize },
+    // The Xing/Info or VBRI frame starting the stream, it holds no audio
+    Info,
+}
+
+impl Decoded {
+    pub fn frame(self) -> Option<DecodedFrame> {
+        match self {
+            Decoded::Frame(frame) => Some(frame),
+            Decoded::Skipped { .. } | Decoded::Info => None,
+        }
+    }
+}
+
+// What the Xing/Info frame tells about the samples of the stream, per channel
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct StreamSamples {
+    // Samples of the audio frames, None without frame count
+    decoded: Option<u64>,
+    // Encoder delay and padding, decoder delay accounted for, 0 without LAME tag
+    leading: u64,
+    trailing: u64,
+}
+
+impl StreamSamples {
+    pub(crate) fn from_frame(frame: &Frame) -> Self {
+        let samples_per_frame = frame.header.samples_per_frame();
+        let frames = VbrInfo::from_frame(frame).and_then(|info| info.frames);
+
+        let (leading, trailing) = match (frames, LameTag::from_frame(frame)) {
+            (Some(frames), Some(lame)) => lame.gapless_samples(frames as usize, samples_per_frame),
+            _ => (0, 0),
+        };
+
+        Self {
+            decoded: frames.map(|frames| frames as u64 * samples_per_frame as u64),
+            leading: leading as u64,
+            trailing: trailing as u64,
+        }
+    }
+}
+
+// Input of a decoder fed its frames one by one, see `Decoder::decode_frame`. Decoders reading
+// the frames themselves from a reader are made with `Decoder::new`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoReader;
+
+// Decoding state carried over from one granule to the next, per channel
+#[derive(Debug, Clone)]
+pub struct Decoder<R = NoReader> {
+    // Second half of the previous IMDCT outputs, per subband
+    overlap: [[[f32; 18]; 32]; 2],
+    // Polyphase synthesis V vectors
+    fifo: [[f32; 1024]; 2],
+    // Last main data bytes of the previous frames, the bit reservoir
+    reservoir: Vec<u8>,
+    // Gapless playback, see `with_gapless`
+    gapless: bool,
+    // Read from the Xing/Info frame, once fed
+    pub(crate) stream: Option<StreamSamples>,
+    // Samples per channel of the audio frames fed so far, None once lost by a reset
+    pub(crate) position: Option<u64>,
+    // Factor applied to the output and whether to clamp it, see `with_replay_gain`
+    gain: Option<(f32, bool)>,
+    // The reader and its buffer, see `Decoder::new`
+    pub(crate) input: Input<R>,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::with_input(Input::new(NoReader, 0))
+    }
+}
+
+impl<R> Decoder<R> {
+    pub(crate) fn with_input(input: Input<R>) -> Self {
+        Self {
+            overlap: [[[0.0; 18]; 32]; 2],
+            fifo: [[0.0; 1024]; 2],
+            reservoir: Vec::with_capacity(RESERVOIR_SIZE),
+            gapless: true,
+            stream: None,
+            position: Some(0),
+            gain: None,
+            input,
+        }
+    }
+
+    // With gapless playback (the default), once fed the Xing/Info frame starting the stream, the
+    // decoder drops the encoder delay and its own latency from the start of the output and the
+    // encoder padding from its end, as stored in the LAME tag. Decoding tracks back to back then
+    // plays them without gap, and the output has the exact length of the encoded audio.
+    pub fn with_gapless(mut self, gapless: bool) -> Self {
+        self.gapless = gapless;
+        self
+    }
+
+    // Scales the output by the ReplayGain of the stream (see `ReplayGain::from_stream`), as the
+    // options select. Nothing changes when `gain` holds no value for either mode.
+    pub fn with_replay_gain(mut self, gain: &ReplayGain, options: ReplayGainOptions) -> Self {
+        self.gain = gain
+            .scale(options.mode, options.preamp, options.clipping)
+            .map(|scale| (scale, scale > 1.0));
+        self
+    }
+
+    // Number of samples per channel the whole stream decodes to, gapless trimming included.
+    // Known once the Xing/Info frame has been fed, if it holds a frame count.
+    pub fn total_samples(&self) -> Option<u64> {
+        let stream = self.stream?;
+        let decoded = stream.decoded?;
+
+        match self.gapless {
+            true => Some(decoded.saturating_sub(stream.leading + stream.trailing)),
+            false => Some(decoded),
+        }
+    }
+
+    // Forgets the previous granules, the next one is decoded as if the stream started there.
+    // To be called after a seek. What was read from the Xing/Info frame is kept, but the stream
+    // position is lost, so gapless trimming is off until the next `seek`.
+    pub fn reset(&mut self) {
+        self.overlap = [[[0.0; 18]; 32]; 2];
+        self.fifo = [[0.0; 1024]; 2];
+        self.reservoir.clear();
+        self.position = None;
+    }
+
+    // Decodes the frames of a stream one after the other, a Layer III frame being able to use the
+    // main data left in the previous ones. A Xing/Info or VBRI frame is only recognized as such
+    // when it's the first frame fed.
+    pub fn decode_frame(&mut self, frame: &Frame) -> Result<Decoded, ChompError> {
+        if self.position == Some(0) && VbrInfo::from_frame(frame).is_some() {
+            self.stream = Some(StreamSamples::from_frame(frame));
+            return Ok(Decoded::Info);
+        }
+
+        let start = self.position;
+        self.position = start.map(|start| start + frame.header.samples_per_frame() as u64);
+
+        match self.decode_samples(frame)? {
+            Decoded::Frame(decoded) => {
+                let mut decoded = self.trim(decoded, start);
+                self.apply_gain(&mut decoded);
+                Ok(Decoded::Frame(decoded))
+            }
+            decoded => Ok(decoded),
+        }
+    }
+
+    // Drops the samples of `frame`, starting at `start` in the stream, that gapless playback
+    // leaves out
+    fn trim(&self, mut frame: DecodedFrame, start: Option<u64>) -> DecodedFrame {
+        let (Some(stream), Some(start), true) = (self.stream, start, self.gapless) else {
+            return frame;
+        };
+
+        let length = frame.samples_per_channel() as u64;
+        let end = stream
+            .decoded
+            .map_or(u64::MAX, |decoded| decoded.saturating_sub(stream.trailing));
+        let keep_end = end.clamp(start, start + length) - start;
+        let keep_start = stream.leading.clamp(start, start + keep_end) - start;
+
+        frame.samples.truncate(keep_end as usize * frame.channels);
+        frame.samples.drain(..keep_start as usize * frame.channels);
+        frame
+    }
+
+    fn apply_gain(&self, frame: &mut DecodedFrame) {
+        let Some((scale, clamp)) = self.gain else {
+            return;
+        };
+
+        for sample in &mut frame.samples {
+            *sample *= scale;
+            if clamp {
+                *sample = sample.clamp(-1.0, 1.0);
+            }
+        }
+    }
+
+    fn decode_samples(&mut self, frame: &Frame) -> Result<Decoded, ChompError> {
+        let header = &frame.header;
+
+        match header.layer() {
+            Layer::One => {
+                let subbands = decode_layer1(frame)?;
+                Ok(Decoded::Frame(self.synthesize_subbands(header, &subbands)))
+            }
+            Layer::Two => {
+                let subbands = decode_layer2(frame)?;
+                Ok(Decoded::Frame(self.synthesize_subbands(header, &subbands)))
+            }
+            Layer::Three => self.decode_layer3(frame),
+            Layer::Reserved => Err(ChompError::UnsupportedVersionLayer {
+                version: header.version(),
+                layer: Layer::Reserved,
+            }),
+        }
+    }
+
+    fn decode_layer3(&mut self, frame: &Frame) -> Result<Decoded, ChompError> {
+        let header = &frame.header;
+        let side_info_bytes = frame.side_info().ok_or(ChompError::TooShort)?;
+        let side_info = SideInfo::parse(&mut BitReader::new(side_info_bytes), header)?;
+        let body = &frame.body()[side_info_bytes.len()..];
+
+        let available = self.reservoir.len();
+        let main_data = match available.checked_sub(side_info.main_data_begin) {
+            Some(start) => {
+                let mut main_data = self.reservoir[start..].to_vec();
+                main_data.extend_from_slice(body);
+                Some(main_data)
+            }
+            None => None,
+        };
+
+        self.reservoir.extend_from_slice(body);
+        let excess = self.reservoir.len().saturating_sub(RESERVOIR_SIZE);
+        self.reservoir.drain(..excess);
+
+        let Some(main_data) = main_data else {
+            return Ok(Decoded::Skipped {
+                missing: side_info.main_data_begin - available,
+            });
+        };
+        let samples = self.decode_granules(header, &side_info, &main_data)?;
+
+        Ok(Decoded::Frame(DecodedFrame {
+            samples,
+            channels: side_info.channels(),
+            sample_rate: header.sample_rate(),
+        }))
+    }
+
+    // Layers I and II: subband samples, indexed [slot][channel][subband], straight to PCM
+    fn synthesize_subbands<const SLOTS: usize>(
+        &mut self,
+        header: &Header,
+        subbands: &[[[f32; 32]; 2]; SLOTS],
+    ) -> DecodedFrame {
+        let channels = header.channels();
+        let mut samples = vec![0.0; SLOTS * 32 * channels];
+
+        for (slot, subbands) in subbands.iter().enumerate() {
+            for (channel, subbands) in subbands.iter().enumerate().take(channels) {
+                let mut pcm = [0.0; 32];
+                synthesize_slot(subbands, &mut self.fifo[channel], &mut pcm);
+
+                for (i, sample) in pcm.into_iter().enumerate() {
+                    samples[(slot * 32 + i) * channels + channel] = sample.clamp(-1.0, 1.0);
+                }
+            }
+        }
+
+        DecodedFrame {
+            samples,
+            channels,
+            sample_rate: header.sample_rate(),
+        }
+    }
+
+    fn decode_granules(
+        &mut self,
+        header: &Header,
+        side_info: &SideInfo,
+        main_data: &[u8],
+    ) -> Result<Vec<f32>, ChompError> {
+        let channels = side_info.channels();
+        let mut output = vec![0.0; side_info.granules() * 576 * channels];
+        let mut reader = BitReader::new(main_data);
+        let mut scalefactors = [Scalefactors::default(); 2];
+
+        for granule in 0..side_info.granules() {
+            let mut samples = [[0.0; 576]; 2];
+
+            for channel in 0..channels {
+                let info = &side_info.granules[granule][channel];
+                let end = reader.position() + info.part2_3_length;
+
+                scalefactors[channel] = Scalefactors::read(
+                    &mut reader,
+                    side_info,
+                    granule,
+                    channel,
+                    Some(&scalefactors[channel]),
+                )?;
+
+                let mut values = [0; 576];
+                let big_values = decode_big_values(&mut reader, header, info, end, &mut values)?;
+                decode_count1(&mut reader, info, end, big_values, &mut values)?;
+                // Stuffing bits, or a discarded count1 quadruple overrunning the granule
+                reader.seek(end);
+
+                requantize(
+                    &values,
+                    header,
+                    info,
+                    &scalefactors[channel],
+                    &mut samples[channel],
+                );
+            }
+
+            if channels == 2 {
+                stereo(
+                    header,
+                    &side_info.granules[granule][1],
+                    &scalefactors[1],
+                    &mut samples,
+                );
+            }
+
+            for (channel, samples) in samples.iter_mut().enumerate().take(channels) {
+                let info = &side_info.granules[granule][channel];
+                let pcm = self.decode_channel(channel, header, info, samples);
+
+                for (i, sample) in pcm.into_iter().enumerate() {
+                    output[(granule * 576 + i) * channels + channel] = sample.clamp(-1.0, 1.0);
+                }
+            }
+        }
+
+        Ok(output)
+    }
+
+    // Requantized (and stereo processed) spectrum of one granule to PCM
+    fn decode_channel(
+        &mut self,
+        channel: usize,
+        header: &Header,
+        info: &GranuleInfo,
+        samples: &mut [f32; 576],
+    ) -> [f32; 576] {
+        reorder(header, info, samples);
+        antialias(info, samples);
+
+        let mut time = [0.0; 576];
+        self.imdct(channel, info, samples, &mut time);
+        frequency_inversion(&mut time);
+
+        let mut pcm = [0.0; 576];
+        self.synthesis(channel, &time, &mut pcm);
+        pcm
+    }
+
+    // IMDCT, windowing and overlap-add of one granule of `channel`
+    pub fn imdct(
+        &mut self,
+        channel: usize,
+        info: &GranuleInfo,
+        samples: &[f32; 576],
+        output: &mut [f32; 576],
+    ) {
+        imdct(info, samples, &mut self.overlap[channel], output);
+    }
+
+    // Polyphase synthesis of one granule of `channel` into 576 PCM samples
+    pub fn synthesis(&mut self, channel: usize, samples: &[f32; 576], output: &mut [f32; 576]) {
+        synthesis(samples, &mut self.fifo[channel], output);
+    }
+}
+
+impl Decoder {
+    // Gets ready to decode `frames[target]` after a seek: resets, then decodes and discards the
+    // frames before it, enough of them to fill the bit reservoir of the frame right before the
+    // target, which in turn rebuilds the IMDCT overlap and synthesis state. Errors in those frames
+    // are ignored, they only degrade the first frames decoded. `frames` are those of the whole
+    // stream, with or without the Xing/Info frame (read for gapless playback when present), so
+    // trimming still only drops the delay at the true start of the stream and the padding at
+    // its end. A target past the last frame seeks to the end of the stream.
+    pub fn seek(&mut self, frames: &[Frame], target: usize) {
+        self.reset();
+        let target = target.min(frames.len());
+        let start = priming_start(target, |index| frames[index].main_data_size());
+
+        let info = frames
+            .first()
+            .filter(|frame| VbrInfo::from_frame(frame).is_some());
+        if let Some(frame) = info {
+            self.stream = Some(StreamSamples::from_frame(frame));
+        }
+
+        let skipped = &frames[(info.is_some() as usize).min(start)..start];
+        self.position = Some(
+            skipped
+                .iter()
+                .map(|frame| frame.header.samples_per_frame() as u64)
+                .sum(),
+        );
+
+        for frame in &frames[start..target] {
+            let _ = self.decode_frame(frame);
+        }
+    }
+}
+
+// First of the frames to decode, and discard, before frame `target` so that its bit reservoir,
+// and with it the IMDCT overlap and synthesis state, are rebuilt. `main_data_size` is that of
+// each frame of the stream.
+pub(crate) fn priming_start(target: usize, main_data_size: impl Fn(usize) -> usize) -> usize {
+    let mut start = target.saturating_sub(1);
+    let mut primed = 0;
+    while start > 0 && primed < RESERVOIR_SIZE {
+        start -= 1;
+        primed += main_data_size(start);
+    }
+    start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        frame::FrameIterator,
+        replaygain::{Clipping, GainMode},
+        test_utils::{assert_close, wav},
+    };
+
+    // The whole stream, as 16 bit samples
+    fn decode_pcm(data: &[u8]) -> (usize, usize, Vec<i16>) {
+        let mut decoder = Decoder::default().with_gapless(false);
+        let (mut channels, mut sample_rate, mut samples) = (0, 0, Vec::new());
+        for frame in FrameIterator::new(data).flatten() {
+            if let Decoded::Frame(frame) = decoder.decode_frame(&frame).unwrap() {
+                channels = frame.channels();
+                sample_rate = frame.sample_rate();
+                samples.extend(
+                    frame
+                        .samples()
+                        .iter()
+                        .map(|sample| (sample * 32768.0).round().clamp(-32768.0, 32767.0) as i16),
+                );
+            }
+        }
+        (channels, sample_rate, samples)
+    }
+
+    // The reference WAVs are the output of symphonia, without gapless trimming
+    #[test]
+    fn conformance() {
+        for (mp3, reference) in [
+            (
+                &include_bytes!("../fixtures/cbr.mp3")[..],
+                &include_bytes!("../fixtures/cbr.wav")[..],
+            ),
+            (
+                include_bytes!("../fixtures/mpeg2.mp3"),
+                include_bytes!("../fixtures/mpeg2.wav"),
+            ),
+            (
+                include_bytes!("../fixtures/mpeg25.mp3"),
+                include_bytes!("../fixtures/mpeg25.wav"),
+            ),
+            (
+                include_bytes!("../fixtures/mpeg2_stereo.mp3"),
+                include_bytes!("../fixtures/mpeg2_stereo.wav"),
+            ),
+            (
+                include_bytes!("../fixtures/mixed.mp3"),
+                include_bytes!("../fixtures/mixed.wav"),
+            ),
+            (
+                include_bytes!("../fixtures/layer1.mp1"),
+                include_bytes!("../fixtures/layer1.wav"),
+            ),
+            (
+                include_bytes!("../fixtures/layer2.mp2"),
+                include_bytes!("../fixtures/layer2.wav"),
+            ),
+        ] {
+            let (channels, sample_rate, samples) = decode_pcm(mp3);
+            let (expected_channels, expected_rate, expected) = wav(reference);
+            assert_eq!((channels, sample_rate), (expected_channels, expected_rate));
+            assert_eq!(samples.len(), expected.len());
+
+            // Rounding apart, within 1 of the 16 bit samples
+            let mismatch = samples
+                .iter()
+                .zip(&expected)
+                .position(|(sample, expected)| (*sample as i32 - *expected as i32).abs() > 1);
+            assert_eq!(mismatch, None, "{sample_rate} Hz");
+        }
+    }
+
+    #[test]
+    fn reset_clears_the_overlap() {
+        let info = GranuleInfo::default();
+        let samples = std::array::from_fn(|i| (i % 7) as f32);
+        let mut decoder = Decoder::default();
+
+        let mut first = [0.0; 576];
+        decoder.imdct(1, &info, &samples, &mut first);
+        let mut second = [0.0; 576];
+        decoder.imdct(1, &info, &samples, &mut second);
+        assert_ne!(first, second);
+
+        decoder.reset();
+        let mut output = [0.0; 576];
+        decoder.imdct(1, &info, &samples, &mut output);
+        assert_eq!(output, first);
+    }
+
+    #[test]
+    fn reset_clears_the_fifo() {
+        let samples = std::array::from_fn(|i| (i % 5) as f32 / 10.0);
+        let mut decoder = Decoder::default();
+
+        let mut first = [0.0; 576];
+        decoder.synthesis(0, &samples, &mut first);
+        let mut second = [0.0; 576];
+        decoder.synthesis(0, &samples, &mut second);
+        assert_ne!(first, second);
+
+        decoder.reset();
+        let mut output = [0.0; 576];
+        decoder.synthesis(0, &samples, &mut output);
+        assert_eq!(output, first);
+    }
+
+    #[test]
+    fn starting_mid_stream() {
+        let data = include_bytes!("../fixtures/cbr.mp3");
+        let frames: Vec<_> = FrameIterator::new(data).flatten().collect();
+
+        let mut full = Decoder::default().with_gapless(false);
+        let expected: Vec<_> = frames
+            .iter()
+            .map(|frame| full.decode_frame(frame).unwrap())
+            .collect();
+
+        // Frame 6 starts its main data 179 bytes back, in frames never fed
+        let mut decoder = Decoder::default().with_gapless(false);
+        assert_eq!(
+            decoder.decode_frame(&frames[6]).unwrap(),
+            Decoded::Skipped { missing: 179 }
+        );
+
+        // Its bytes filled the reservoir: frame 7 decodes, only missing the IMDCT overlap and
+        // synthesis state of the skipped frame. They are rebuilt from the next frame on.
+        let Decoded::Frame(frame) = decoder.decode_frame(&frames[7]).unwrap() else {
+            panic!("frame 7 not decoded");
+        };
+        assert_eq!(frame.samples_per_channel(), 1152);
+        assert!(Decoded::Frame(frame) != expected[7]);
+
+        for (frame, expected) in frames[8..].iter().zip(&expected[8..]) {
+            assert_eq!(&decoder.decode_frame(frame).unwrap(), expected);
+        }
+
+        // Seeking primes the reservoir and the decoder state with the frames before the target
+        decoder.seek(&frames, 6);
+        assert_eq!(decoder.decode_frame(&frames[6]).unwrap(), expected[6]);
+    }
+
+    #[test]
+    fn seek_past_the_end() {
+        let data = include_bytes!("../fixtures/cbr.mp3");
+        let frames: Vec<_> = FrameIterator::new(data).flatten().collect();
+
+        let mut full = Decoder::default().with_gapless(false);
+        let decoded: Vec<_> = frames
+            .iter()
+            .map(|frame| full.decode_frame(frame).unwrap())
+            .collect();
+
+        let mut decoder = Decoder::default().with_gapless(false);
+        decoder.seek(&frames, 6);
+        for (frame, expected) in frames[6..].iter().zip(&decoded[6..]) {
+            assert_eq!(&decoder.decode_frame(frame).unwrap(), expected);
+        }
+
+        decoder.seek(&frames, frames.len());
+        decoder.seek(&frames, frames.len() + 1);
+        decoder.seek(&frames, usize::MAX);
+    }
+
+    // The sample count LAME was given back, the encoder delay and padding dropped
+    #[test]
+    fn gapless_playback() {
+        for (data, expected) in [
+            (&include_bytes!("../fixtures/cbr.mp3")[..], 11025),
+            (include_bytes!("../fixtures/vbr.mp3"), 22050),
+            (include_bytes!("../fixtures/mpeg2.mp3"), 11025),
+            (include_bytes!("../fixtures/mpeg25.mp3"), 4000),
+            (include_bytes!("../fixtures/mpeg2_stereo.mp3"), 4800),
+        ] {
+            let frames: Vec<_> = FrameIterator::new(data).flatten().collect();
+            let samples_per_frame = frames[0].header.samples_per_frame();
+
+            for (gapless, total) in [
+                (true, expected),
+                (false, (frames.len() - 1) * samples_per_frame),
+            ] {
+                let mut decoder = Decoder::default().with_gapless(gapless);
+                let mut decoded = 0;
+                for frame in &frames {
+                    if let Decoded::Frame(frame) = decoder.decode_frame(frame).unwrap() {
+                        decoded += frame.samples_per_channel();
+                    }
+                }
+                assert_eq!(decoded, total);
+                assert_eq!(decoder.total_samples(), Some(total as u64));
+            }
+        }
+    }
+
+    #[test]
+    fn gapless_seek() {
+        let data = include_bytes!("../fixtures/cbr.mp3");
+        let frames: Vec<_> = FrameIterator::new(data).flatten().collect();
+
+        // Frame 6 is audio frame 5, mid-stream: none of its samples are dropped
+        let mut decoder = Decoder::default();
+        decoder.seek(&frames, 6);
+        let Decoded::Frame(frame) = decoder.decode_frame(&frames[6]).unwrap() else {
+            panic!("frame 6 not decoded");
+        };
+        assert_eq!(frame.samples_per_channel(), 1152);
+
+        // From the start, the whole stream still has the exact length
+        decoder.seek(&frames, 0);
+        let decoded: usize = frames
+            .iter()
+            .filter_map(|frame| match decoder.decode_frame(frame).unwrap() {
+                Decoded::Frame(frame) => Some(frame.samples_per_channel()),
+                _ => None,
+            })
+            .sum();
+        assert_eq!(decoded, 11025);
+
+        // Once reset, the position is lost and nothing is trimmed
+        decoder.reset();
+        let Decoded::Frame(frame) = decoder.decode_frame(&frames[1]).unwrap() else {
+            panic!("frame 1 not decoded");
+        };
+        assert_eq!(frame.samples_per_channel(), 1152);
+    }
+
+    // Samples of the whole stream, decoded with `decoder`
+    fn decode_all(mut decoder: Decoder, data: &[u8]) -> Vec<f32> {
+        let mut samples = Vec::new();
+        for frame in FrameIterator::new(data).flatten() {
+            if let Decoded::Frame(frame) = decoder.decode_frame(&frame).unwrap() {
+                samples.extend(frame.samples());
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn replay_gain() {
+        let data = include_bytes!("../fixtures/cbr.mp3");
+        let plain = decode_all(Decoder::default(), data);
+        let options = |preamp: f32, clipping: Clipping| ReplayGainOptions {
+            mode: GainMode::Track,
+            preamp,
+            clipping,
+        };
+        let gain = ReplayGain {
+            track_gain: Some(-6.0206),
+            track_peak: Some(0.25),
+            ..ReplayGain::default()
+        };
+
+        // Half as loud
+        let options_half = options(0.0, Clipping::Peak);
+        let half = decode_all(
+            Decoder::default().with_replay_gain(&gain, options_half),
+            data,
+        );
+        let expected: Vec<_> = plain.iter().map(|sample| sample / 2.0).collect();
+        assert_close(&half, &expected);
+
+        // 5 times louder, brought back to 4 times so that the 0.25 peak doesn't clip
+        let options_peak = options(20.0, Clipping::Peak);
+        let peak = decode_all(
+            Decoder::default().with_replay_gain(&gain, options_peak),
+            data,
+        );
+        let expected: Vec<_> = plain.iter().map(|sample| sample * 4.0).collect();
+        assert_close(&peak, &expected);
+
+        // 5 times louder, clamped to full scale, 16 bit samples included
+        let options_hard = options(20.0, Clipping::Hard);
+        let mut decoder = Decoder::default().with_replay_gain(&gain, options_hard);
+        let mut clamped = 0;
+        for frame in FrameIterator::new(data).flatten() {
+            if let Decoded::Frame(frame) = decoder.decode_frame(&frame).unwrap() {
+                assert!(frame.samples().iter().all(|sample| sample.abs() <= 1.0));
+                clamped += frame
+                    .samples_i16()
+                    .iter()
+                    .filter(|sample| matches!(**sample, i16::MIN | i16::MAX))
+                    .count();
+            }
+        }
+        assert!(clamped > 0);
+
+        // Nothing changes without gain
+        let none = Decoder::default().with_replay_gain(&ReplayGain::default(), options_hard);
+        assert_eq!(decode_all(none, data), plain);
+    }
+}
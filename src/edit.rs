@@ -0,0 +1,610 @@
+use crate::{
+    constants::*,
+    crc::recompute_crc,
+    error::ChompError,
+    frame::{
+        audio_frames, surrounding_tags, Frame, FrameIterator, ParseMode, StreamEvent, TagKind,
+    },
+    header::Header,
+    vbr::VbrInfo,
+    xing::XingHeader,
+};
+use std::{
+    io::{Read, Seek, SeekFrom, Write},
+    time::Duration,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CutReport {
+    // Audio frames written, the prepended ones included
+    pub frames: usize,
+    // Frames written before the requested start to carry the bit reservoir (see `cut`)
+    pub prepended_frames: usize,
+    // Duration of the written audio
+    pub duration: Duration,
+    // How much of `duration` comes from the prepended frames
+    pub extra: Duration,
+}
+
+// Copies the frames covering [start, end) into `writer` as a standalone stream, without
+// re-encoding, behind a freshly generated Xing/Info frame.
+//
+// A Layer III frame may take part of its main data from the frames before it (the bit
+// reservoir, see main_data_begin). Instead of moving the start to a frame that doesn't use the
+// reservoir, which can be arbitrarily far away, the frames holding the reservoir of the first
+// requested frame are written before it. This adds audio in front of the requested range,
+// `CutReport::extra` tells how much. main_data_begin reaches up to 511 bytes back (255 for MPEG-2
+// and 2.5), so at low bitrates that's many frames: up to 8 (about 209 ms) at 32 kbit/s 44.1 kHz
+// stereo, and about 20 (0.5 s) at 8 kbit/s MPEG-2. The first prepended frame may itself miss part
+// of its reservoir: Decoder skips it (Decoded::Skipped), other decoders aren't bound to mute it.
+// The requested range always has its whole reservoir.
+pub fn cut<W: Write>(
+    data: &[u8],
+    start: Duration,
+    end: Duration,
+    writer: &mut W,
+) -> Result<CutReport, ChompError> {
+    if start >= end {
+        return Err(ChompError::InvalidRange);
+    }
+
+    let frames = audio_frames(data);
+    if frames.is_empty() {
+        return Err(ChompError::NoFrames);
+    }
+
+    // Frames overlapping the requested range
+    let mut time = Duration::ZERO;
+    let mut first = None;
+    let mut last = 0;
+    for (i, frame) in frames.iter().enumerate() {
+        let frame_end = time + frame.header.duration();
+        if frame_end > start && time < end {
+            first.get_or_insert(i);
+            last = i + 1;
+        }
+        time = frame_end;
+    }
+    let first = first.ok_or(ChompError::InvalidRange)?;
+
+    // Walk back until enough main data is available for the first requested frame
+    let mut lead = first;
+    let mut needed = frames[first].main_data_begin().unwrap_or(0);
+    while needed > 0 && lead > 0 {
+        lead -= 1;
+        needed = needed.saturating_sub(frames[lead].main_data_size());
+    }
+
+    let written = &frames[lead..last];
+    write_with_info_frame(written, writer)?;
+
+    Ok(CutReport {
+        frames: written.len(),
+        prepended_frames: first - lead,
+        duration: written.iter().map(|f| f.header.duration()).sum(),
+        extra: frames[lead..first]
+            .iter()
+            .map(|f| f.header.duration())
+            .sum(),
+    })
+}
+
+fn write_with_info_frame<W: Write>(frames: &[Frame], writer: &mut W) -> Result<(), ChompError> {
+    let first = frames.first().ok_or(ChompError::NoFrames)?;
+    let template = first.header.raw_bytes();
+    let header_bytes = XingHeader::frame_header(template).ok_or(ChompError::TooShort)?;
+    let info_length = Header::from_bytes(&header_bytes)?.frame_length();
+
+    let mut offsets = Vec::with_capacity(frames.len());
+    let mut total = info_length;
+    for frame in frames {
+        offsets.push(total);
+        total += frame.data.len();
+    }
+
+    let xing = XingHeader {
+        cbr: frames
+            .iter()
+            .all(|f| f.header.bitrate() == first.header.bitrate()),
+        frames: Some(frames.len() as u32),
+        bytes: Some(total as u32),
+        toc: Some(XingHeader::build_toc(&offsets, total)),
+        quality: None,
+    };
+
+    writer.write_all(&xing.to_frame(header_bytes).ok_or(ChompError::TooShort)?)?;
+    for frame in frames {
+        frame.write_to(writer)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConcatWarning {
+    // Only reported in lenient mode, strict mode refuses the input instead
+    Incompatible { input: usize },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConcatReport {
+    pub inputs: usize,
+    // Audio frames written, the Info frame excluded
+    pub frames: usize,
+    // Bytes written, the Info frame included
+    pub bytes: usize,
+    pub duration: Duration,
+    pub warnings: Vec<ConcatWarning>,
+}
+
+// Joins streams frame by frame. Only the audio frames of each input are kept, so their tags and
+// Xing/Info frames are dropped, and a single Info frame describing the result is written in front.
+// That frame can only be completed once every input is known, hence the `Seek` bound.
+pub struct Concat<W: Write + Seek> {
+    writer: W,
+    mode: ParseMode,
+    // Writer position of the Info frame
+    start: u64,
+    info_header: Option<[u8; 4]>,
+    reference: Option<Header>,
+    // Frame offsets relative to `start`, for the toc
+    offsets: Vec<usize>,
+    cbr: bool,
+    report: ConcatReport,
+}
+
+impl<W: Write + Seek> Concat<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            mode: ParseMode::Strict,
+            start: 0,
+            info_header: None,
+            reference: None,
+            offsets: Vec::new(),
+            cbr: true,
+            report: ConcatReport::default(),
+        }
+    }
+
+    // In lenient mode incompatible inputs are appended anyway and reported as warnings
+    pub fn with_mode(mut self, mode: ParseMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn append_reader<R: Read>(&mut self, mut reader: R) -> Result<(), ChompError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        self.append(&data)
+    }
+
+    pub fn append(&mut self, data: &[u8]) -> Result<(), ChompError> {
+        let input = self.report.inputs;
+        let frames = audio_frames(data);
+        let first = frames.first().ok_or(ChompError::NoFrames)?;
+
+        // The first input only becomes the reference once accepted
+        let reference = self.reference.as_ref().unwrap_or(&first.header);
+        if !frames
+            .iter()
+            .all(|f| reference.is_compatible_with(&f.header))
+        {
+            match self.mode {
+                ParseMode::Strict => return Err(ChompError::IncompatibleStreams),
+                ParseMode::Lenient => self
+                    .report
+                    .warnings
+                    .push(ConcatWarning::Incompatible { input }),
+            }
+        }
+
+        if self.info_header.is_none() {
+            let template = first.header.raw_bytes();
+            let header_bytes = XingHeader::frame_header(template).ok_or(ChompError::TooShort)?;
+            let placeholder = XingHeader::empty().to_frame(header_bytes).unwrap();
+
+            self.start = self.writer.stream_position()?;
+            self.writer.write_all(&placeholder)?;
+            self.info_header = Some(header_bytes);
+            self.report.bytes = placeholder.len();
+        }
+
+        let reference_bitrate = self
+            .reference
+            .get_or_insert_with(|| first.header.clone())
+            .bitrate();
+        for frame in &frames {
+            frame.write_to(&mut self.writer)?;
+            self.offsets.push(self.report.bytes);
+            self.cbr &= frame.header.bitrate() == reference_bitrate;
+            self.report.bytes += frame.data.len();
+            self.report.frames += 1;
+            self.report.duration += frame.header.duration();
+        }
+
+        self.report.inputs += 1;
+        Ok(())
+    }
+
+    // Completes the Info frame and hands the writer back
+    pub fn finish(mut self) -> Result<(W, ConcatReport), ChompError> {
+        let header_bytes = self.info_header.ok_or(ChompError::NoFrames)?;
+
+        let xing = XingHeader {
+            cbr: self.cbr,
+            frames: Some(self.report.frames as u32),
+            bytes: Some(self.report.bytes as u32),
+            toc: Some(XingHeader::build_toc(&self.offsets, self.report.bytes)),
+            quality: None,
+        };
+
+        let end = self.writer.stream_position()?;
+        self.writer.seek(SeekFrom::Start(self.start))?;
+        self.writer
+            .write_all(&xing.to_frame(header_bytes).unwrap())?;
+        self.writer.seek(SeekFrom::Start(end))?;
+        self.writer.flush()?;
+
+        Ok((self.writer, self.report))
+    }
+}
+
+// Joins `readers` into `writer` in strict mode, see `Concat`
+pub fn concat<R: Read, W: Write + Seek>(
+    readers: impl IntoIterator<Item = R>,
+    writer: W,
+) -> Result<ConcatReport, ChompError> {
+    let mut builder = Concat::new(writer);
+
+    for reader in readers {
+        builder.append_reader(reader)?;
+    }
+
+    builder.finish().map(|(_, report)| report)
+}
+
+// Header flags to force, None leaves the flag as it is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeaderEdits {
+    pub copyright: Option<bool>,
+    pub original: Option<bool>,
+    pub private: Option<bool>,
+}
+
+impl HeaderEdits {
+    fn apply(&self, header: &mut [u8]) {
+        let mut set = |byte: usize, mask: u8, value: Option<bool>| match value {
+            Some(true) => header[byte] |= mask,
+            Some(false) => header[byte] &= !mask,
+            None => (),
+        };
+
+        set(2, 0x01, self.private);
+        set(3, 0x08, self.copyright);
+        set(3, 0x04, self.original);
+    }
+}
+
+// Copies `input` to `output`, applying `edits` to the header of every audio frame. The CRC of
+// protected frames is recomputed as it covers the header. Everything else (tags, frame bodies,
+// the Xing/Info or VBRI frame as a LAME tag CRC covers the header) is copied untouched.
+// Returns the number of rewritten frames.
+pub fn rewrite_headers<R: Read, W: Write>(
+    mut input: R,
+    mut output: W,
+    edits: HeaderEdits,
+) -> Result<usize, ChompError> {
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+
+    let mut copied = 0;
+    let mut rewritten = 0;
+    for (i, frame) in FrameIterator::new(&data).flatten().enumerate() {
+        if i == 0 && VbrInfo::from_frame(&frame).is_some() {
+            continue;
+        }
+
+        output.write_all(&data[copied..frame.offset])?;
+
+        let mut bytes = frame.data.to_vec();
+        edits.apply(&mut bytes);
+        let header = Header::from_bytes(&bytes)?;
+        recompute_crc(&mut bytes, &header);
+
+        output.write_all(&bytes)?;
+        copied = frame.offset + frame.data.len();
+        rewritten += 1;
+    }
+
+    output.write_all(&data[copied..])?;
+    output.flush()?;
+
+    Ok(rewritten)
+}
+
+// Bytes removed by `strip_tags`, by kind
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StripReport {
+    // Audio frames written
+    pub frames: usize,
+    pub audio_bytes: usize,
+    // Leading (stacked ones included), appended and between frames ID3v2 tags
+    pub id3v2_bytes: usize,
+    pub id3v1_bytes: usize,
+    pub ape_bytes: usize,
+    // The Xing/Info or VBRI frame, when dropped
+    pub info_frame_bytes: usize,
+    // Anything else: garbage between frames, a truncated last frame, RIFF chunks
+    pub other_bytes: usize,
+}
+
+// Copies only the audio frames of `input` to `output`, byte for byte, dropping every tag and
+// anything that isn't part of a frame. The Xing/Info or VBRI frame is kept unless
+// `drop_info_frame` is set, its counts stay right as they never include tags. Two files with the
+// same audio but different tags give the same output.
+pub fn strip_tags<R: Read, W: Write>(
+    mut input: R,
+    mut output: W,
+    drop_info_frame: bool,
+) -> Result<StripReport, ChompError> {
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+
+    let mut report = StripReport::default();
+    let (tags, _) = surrounding_tags(&data);
+    for (kind, range) in tags {
+        match kind {
+            TagKind::Id3v2 => report.id3v2_bytes += range.len(),
+            TagKind::Ape => report.ape_bytes += range.len(),
+            TagKind::Id3v1 => report.id3v1_bytes += range.len(),
+        }
+    }
+
+    let mut first = true;
+    // Lenient iteration never yields errors
+    for event in FrameIterator::new(&data).events().flatten() {
+        match event {
+            StreamEvent::Id3v2(tag) => report.id3v2_bytes += tag.len(),
+            StreamEvent::Frame(frame) => {
+                if std::mem::take(&mut first)
+                    && drop_info_frame
+                    && VbrInfo::from_frame(&frame).is_some()
+                {
+                    report.info_frame_bytes += frame.data.len();
+                    continue;
+                }
+
+                report.audio_bytes += frame.write_to(&mut output)?;
+                report.frames += 1;
+            }
+        }
+    }
+
+    report.other_bytes = data.len()
+        - report.audio_bytes
+        - report.id3v2_bytes
+        - report.id3v1_bytes
+        - report.ape_bytes
+        - report.info_frame_bytes;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        crc::verify_crc,
+        header::{Copyright, Home},
+    };
+    use std::io::Cursor;
+
+    const CBR: &[u8] = include_bytes!("../fixtures/cbr.mp3");
+
+    #[test]
+    fn cut_range() {
+        let mut output = Vec::new();
+        let start = Duration::from_millis(50);
+        let end = Duration::from_millis(150);
+        let report = cut(CBR, start, end, &mut output).unwrap();
+
+        let frames: Vec<_> = FrameIterator::new(&output).flatten().collect();
+
+        // Frames 1 to 5 cover the range, each lasting 1152 / 44100 s
+        let frame = frames[1].header.duration();
+        assert_eq!(report.frames - report.prepended_frames, 5);
+        assert_eq!(report.duration, frame * report.frames as u32);
+        assert_eq!(report.extra, frame * report.prepended_frames as u32);
+        assert!(report.prepended_frames <= 1);
+
+        assert_eq!(frames.len(), report.frames + 1);
+        let xing = XingHeader::from_frame(&frames[0]).unwrap();
+        assert!(xing.cbr);
+        assert_eq!(xing.frames, Some(report.frames as u32));
+        assert_eq!(xing.bytes, Some(output.len() as u32));
+
+        let duration: Duration = frames[1..].iter().map(|f| f.header.duration()).sum();
+        assert_eq!(duration, report.duration);
+        assert!(duration - report.extra >= end - start);
+    }
+
+    #[test]
+    fn cut_empty_range() {
+        let time = Duration::from_millis(50);
+        let result = cut(CBR, time, time, &mut Vec::new());
+        assert!(matches!(result, Err(ChompError::InvalidRange)));
+
+        let result = cut(
+            CBR,
+            Duration::from_secs(5),
+            Duration::from_secs(6),
+            &mut Vec::new(),
+        );
+        assert!(matches!(result, Err(ChompError::InvalidRange)));
+    }
+
+    fn scanned_duration(data: &[u8]) -> Duration {
+        audio_frames(data).iter().map(|f| f.header.duration()).sum()
+    }
+
+    #[test]
+    fn concat_tagged_inputs() {
+        let vbr = include_bytes!("../fixtures/vbr.mp3");
+        let mut tagged = b"ID3\x03\x00\x00\x00\x00\x00\x10".to_vec();
+        tagged.resize(26, 0);
+        tagged.extend(CBR);
+        tagged.extend(b"TAG");
+        tagged.resize(tagged.len() + 125, 0);
+
+        let mut output = Cursor::new(Vec::new());
+        let report = concat([&tagged[..], vbr, CBR], &mut output).unwrap();
+        let output = output.into_inner();
+
+        let expected = 2 * scanned_duration(CBR) + scanned_duration(vbr);
+        assert_eq!(report.inputs, 3);
+        assert_eq!(report.frames, 11 + 21 + 11);
+        assert_eq!(report.bytes, output.len());
+        assert_eq!(report.duration, expected);
+        assert!(report.warnings.is_empty());
+
+        // A single Info frame, then the audio frames only
+        let frames: Vec<_> = FrameIterator::new(&output).flatten().collect();
+        assert_eq!(frames.len(), report.frames + 1);
+        assert!(!XingHeader::from_frame(&frames[0]).unwrap().cbr);
+        assert!(frames[1..]
+            .iter()
+            .all(|frame| XingHeader::from_frame(frame).is_none()));
+        assert_eq!(scanned_duration(&output), expected);
+        assert_eq!(
+            crate::analysis::duration(&output).unwrap().duration,
+            expected
+        );
+    }
+
+    #[test]
+    fn concat_incompatible_inputs() {
+        let mpeg2 = include_bytes!("../fixtures/mpeg2.mp3");
+        let result = concat([CBR, mpeg2], Cursor::new(Vec::new()));
+        assert!(matches!(result, Err(ChompError::IncompatibleStreams)));
+
+        let mut builder = Concat::new(Cursor::new(Vec::new())).with_mode(ParseMode::Lenient);
+        builder.append(CBR).unwrap();
+        builder.append(mpeg2).unwrap();
+        builder.append(CBR).unwrap();
+        let (_, report) = builder.finish().unwrap();
+        assert_eq!(report.warnings, [ConcatWarning::Incompatible { input: 1 }]);
+        assert_eq!(report.frames, 11 + audio_frames(mpeg2).len() + 11);
+    }
+
+    #[test]
+    fn append_after_a_rejected_input() {
+        let mpeg2 = include_bytes!("../fixtures/mpeg2.mp3");
+        let mut builder = Concat::new(Cursor::new(Vec::new()));
+        let mixed = [CBR, mpeg2].concat();
+        assert!(matches!(
+            builder.append(&mixed),
+            Err(ChompError::IncompatibleStreams)
+        ));
+
+        // The rejected input left nothing behind, not even its header as the reference
+        builder.append(mpeg2).unwrap();
+        let (output, report) = builder.finish().unwrap();
+        assert_eq!(report.inputs, 1);
+        assert_eq!(report.frames, audio_frames(mpeg2).len());
+
+        let mut fresh = Concat::new(Cursor::new(Vec::new()));
+        fresh.append(mpeg2).unwrap();
+        assert_eq!(output.into_inner(), fresh.finish().unwrap().0.into_inner());
+    }
+
+    #[test]
+    fn rewrite_header_flags() {
+        let edits = HeaderEdits {
+            copyright: Some(true),
+            original: Some(false),
+            private: Some(true),
+        };
+
+        for input in [CBR, include_bytes!("../fixtures/crc.mp3")] {
+            let mut output = Vec::new();
+            let rewritten = rewrite_headers(input, &mut output, edits).unwrap();
+            assert_eq!(output.len(), input.len());
+
+            let before: Vec<_> = FrameIterator::new(input).flatten().collect();
+            let after: Vec<_> = FrameIterator::new(&output).flatten().collect();
+            assert_eq!(after.len(), before.len());
+            assert_eq!(rewritten, audio_frames(input).len());
+
+            for (before, after) in before.iter().zip(&after) {
+                if VbrInfo::from_frame(before).is_some() {
+                    assert_eq!(after.data, before.data);
+                    continue;
+                }
+
+                assert_eq!(after.header.copyright(), Copyright::On);
+                assert_eq!(after.header.home(), Home::Off);
+                assert!(after.header.private_bit());
+                assert_eq!(after.body(), before.body());
+                if before.header.is_protected() {
+                    assert_eq!(verify_crc(before.data, &before.header), Some(true));
+                    assert_eq!(verify_crc(after.data, &after.header), Some(true));
+                    assert_ne!(after.data[4..6], before.data[4..6]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rewrite_headers_recomputes_layer2_crc() {
+        let data = include_bytes!("../fixtures/layer2.mp2");
+        let edits = HeaderEdits {
+            copyright: Some(true),
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        assert_eq!(rewrite_headers(&data[..], &mut output, edits).unwrap(), 12);
+        for frame in FrameIterator::new(&output).flatten() {
+            assert_eq!(frame.header.copyright(), Copyright::On);
+            assert_eq!(verify_crc(frame.data, &frame.header), Some(true));
+        }
+    }
+
+    #[test]
+    fn strip_stacked_and_trailing_tags() {
+        let lame = include_bytes!("../fixtures/lame.id3");
+        let itunes = include_bytes!("../fixtures/itunes.id3");
+        let ape = include_bytes!("../fixtures/mp3gain.ape");
+        let id3v1 = include_bytes!("../fixtures/lame.id3v1");
+        let data = [&lame[..], itunes, CBR, ape, id3v1].concat();
+        let frames = FrameIterator::new(CBR).count();
+
+        let mut output = Vec::new();
+        let report = strip_tags(Cursor::new(&data), &mut output, false).unwrap();
+        assert_eq!(output, CBR);
+        assert_eq!(
+            report,
+            StripReport {
+                frames,
+                audio_bytes: CBR.len(),
+                id3v2_bytes: lame.len() + itunes.len(),
+                id3v1_bytes: 128,
+                ape_bytes: ape.len(),
+                info_frame_bytes: 0,
+                other_bytes: 0,
+            }
+        );
+
+        // Without the Info frame, and with garbage between frames
+        let info = FrameIterator::new(CBR).next().unwrap().unwrap().data.len();
+        let (head, tail) = data.split_at(lame.len() + itunes.len() + info + 417);
+        let data = [head, &[0x55; 50], tail].concat();
+        let mut stripped = Vec::new();
+        let report = strip_tags(Cursor::new(&data), &mut stripped, true).unwrap();
+        assert_eq!(stripped, CBR[info..]);
+        assert_eq!(report.frames, frames - 1);
+        assert_eq!(report.info_frame_bytes, info);
+        assert_eq!(report.other_bytes, 50);
+    }
+}
@@ -0,0 +1,211 @@
+use crate::{
+    error::ChompError,
+    frame::{audio_frames, Frame, FrameIterator},
+    lame::LameTag,
+    vbri::VbriHeader,
+    xing::XingHeader,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoder {
+    Lame,
+    // FFmpeg, encoding through libmp3lame but writing its own name ("Lavc58.54", "Lavf") in the
+    // LAME tag
+    Ffmpeg,
+    // The original Xing encoder
+    Xing,
+    // Fraunhofer's encoders
+    Fhg,
+    Unknown,
+}
+
+// How the padding bit is used across the frames of a CBR stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PaddingPattern {
+    // Frames are padded just enough to keep the exact bitrate, as most encoders do
+    Regular,
+    // No frame is padded although the bitrate needs it
+    Never,
+    // Every frame is padded although the bitrate doesn't need it
+    Always,
+    Irregular,
+}
+
+#[derive(Debug, Clone)]
+pub struct EncoderInfo {
+    pub encoder: Encoder,
+    // Whether `encoder` was named by the stream itself (LAME tag, VBRI header), rather than
+    // guessed from a pattern
+    pub certain: bool,
+
+    // Evidence, from the first frame
+    pub xing: Option<XingHeader>,
+    pub lame: Option<LameTag>,
+    pub vbri: Option<VbriHeader>,
+    // Evidence, from the audio frames
+    pub private_bit_frames: usize,
+    // CBR streams only
+    pub padding: Option<PaddingPattern>,
+}
+
+impl EncoderInfo {
+    // Encoder string of the LAME tag: "LAME3.100", "LAME3.99r", "Lavc58.54"...
+    pub fn version(&self) -> Option<String> {
+        self.lame.as_ref().map(LameTag::encoder)
+    }
+}
+
+// Names the encoder of the stream, from the strongest evidence found:
+// - a LAME tag names its encoder, LAME itself or FFmpeg
+// - a VBRI header is only written by Fraunhofer's encoder
+// - a Xing header without a LAME tag is left by the Xing encoder, and by LAME before 3.90
+// - without any of those, a stream setting the private bit or padding its frames irregularly is
+//   likely from a Fraunhofer encoder, LAME and Xing never do so by default
+pub fn detect_encoder(data: &[u8]) -> Result<EncoderInfo, ChompError> {
+    let first = FrameIterator::new(data)
+        .flatten()
+        .next()
+        .ok_or(ChompError::NoFrames)?;
+    let frames = audio_frames(data);
+
+    let xing = XingHeader::from_frame(&first);
+    let lame = LameTag::from_frame(&first);
+    let vbri = VbriHeader::from_frame(&first);
+    let private_bit_frames = frames
+        .iter()
+        .filter(|frame| frame.header.private_bit())
+        .count();
+    let padding = padding_pattern(&frames);
+
+    let (encoder, certain) = match (&lame, &vbri, &xing) {
+        (Some(lame), _, _) => {
+            let version = lame.encoder();
+            if version.starts_with("LAME") || version.starts_with("L3.") {
+                (Encoder::Lame, true)
+            } else if version.starts_with("Lav") {
+                (Encoder::Ffmpeg, true)
+            } else {
+                (Encoder::Unknown, false)
+            }
+        }
+        (None, Some(_), _) => (Encoder::Fhg, true),
+        (None, None, Some(_)) => (Encoder::Xing, false),
+        (None, None, None)
+            if private_bit_frames > 0
+                || padding.is_some_and(|padding| padding != PaddingPattern::Regular) =>
+        {
+            (Encoder::Fhg, false)
+        }
+        (None, None, None) => (Encoder::Unknown, false),
+    };
+
+    Ok(EncoderInfo {
+        encoder,
+        certain,
+        xing,
+        lame,
+        vbri,
+        private_bit_frames,
+        padding,
+    })
+}
+
+// None for VBR streams, or streams changing sample rate
+fn padding_pattern(frames: &[Frame]) -> Option<PaddingPattern> {
+    let header = &frames.first()?.header;
+    if frames.iter().any(|frame| {
+        frame.header.bitrate() != header.bitrate()
+            || frame.header.sample_rate() != header.sample_rate()
+    }) {
+        return None;
+    }
+
+    let needed = *header.padded_frames(1).end() > 0;
+
+    let padded = frames
+        .iter()
+        .filter(|frame| frame.header.has_padding())
+        .count();
+    if needed && padded == 0 {
+        return Some(PaddingPattern::Never);
+    }
+    if !needed && padded == frames.len() {
+        return Some(PaddingPattern::Always);
+    }
+
+    let mut padded = 0;
+    for (n, frame) in frames.iter().enumerate() {
+        padded += frame.header.has_padding() as usize;
+        if !header.padded_frames(n + 1).contains(&padded) {
+            return Some(PaddingPattern::Irregular);
+        }
+    }
+
+    Some(PaddingPattern::Regular)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{frames, HEADER_128};
+
+    // `data` with the encoder string of its LAME tag replaced, zeros leaving a bare Xing header
+    fn with_encoder(data: &[u8], encoder: &[u8; 9]) -> Vec<u8> {
+        let mut data = data.to_vec();
+        let lame = data.windows(4).position(|bytes| bytes == b"LAME").unwrap();
+        data[lame..lame + 9].copy_from_slice(encoder);
+        data
+    }
+
+    #[test]
+    fn named_encoders() {
+        let cbr = detect_encoder(include_bytes!("../fixtures/cbr.mp3")).unwrap();
+        assert_eq!((cbr.encoder, cbr.certain), (Encoder::Lame, true));
+        assert_eq!(cbr.version().unwrap(), "LAME3.100");
+        assert!(cbr.xing.unwrap().cbr);
+        assert_eq!(cbr.padding, Some(PaddingPattern::Regular));
+
+        let vbr = include_bytes!("../fixtures/vbr.mp3");
+        let info = detect_encoder(vbr).unwrap();
+        assert_eq!(info.encoder, Encoder::Lame);
+        assert_eq!(info.padding, None);
+
+        let ffmpeg = detect_encoder(&with_encoder(vbr, b"Lavc58.54")).unwrap();
+        assert_eq!((ffmpeg.encoder, ffmpeg.certain), (Encoder::Ffmpeg, true));
+
+        // The Xing header alone, as old Xing encoders wrote it
+        let xing = detect_encoder(&with_encoder(vbr, &[0; 9])).unwrap();
+        assert_eq!((xing.encoder, xing.certain), (Encoder::Xing, false));
+        assert!(xing.lame.is_none() && xing.xing.is_some());
+
+        let fhg = detect_encoder(include_bytes!("../fixtures/vbri.mp3")).unwrap();
+        assert_eq!((fhg.encoder, fhg.certain), (Encoder::Fhg, true));
+        assert!(fhg.vbri.is_some());
+    }
+
+    #[test]
+    fn frame_patterns() {
+        let guess = |headers: &[[u8; 4]]| detect_encoder(&frames(headers)).unwrap();
+
+        // 128 kbit/s at 44100 Hz needs padding, LAME pads about every frame but one in 24
+        let never = guess(&[HEADER_128; 12]);
+        assert_eq!(never.padding, Some(PaddingPattern::Never));
+        assert_eq!((never.encoder, never.certain), (Encoder::Fhg, false));
+
+        // 128 kbit/s at 48000 Hz doesn't
+        let regular = guess(&[[0xFF, 0xFB, 0x94, 0x40]; 12]);
+        assert_eq!(regular.padding, Some(PaddingPattern::Regular));
+        assert_eq!(regular.encoder, Encoder::Unknown);
+        let always = guess(&[[0xFF, 0xFB, 0x96, 0x40]; 12]);
+        assert_eq!(always.padding, Some(PaddingPattern::Always));
+
+        let private = guess(&[[0xFF, 0xFB, 0x94, 0x40], [0xFF, 0xFB, 0x95, 0x40]].repeat(6));
+        assert_eq!(private.private_bit_frames, 6);
+        assert_eq!(private.encoder, Encoder::Fhg);
+
+        let irregular = guess(&[[0xFF, 0xFB, 0x94, 0x40], [0xFF, 0xFB, 0x96, 0x40]].repeat(6));
+        assert_eq!(irregular.padding, Some(PaddingPattern::Irregular));
+
+        assert!(matches!(detect_encoder(&[]), Err(ChompError::NoFrames)));
+    }
+}
@@ -0,0 +1,91 @@
+use crate::header::{Layer, MPEG_Version};
+
+#[derive(Debug)]
+pub enum ChompError {
+    // Not enough bytes to hold what is being parsed
+    TooShort,
+    // The 12 sync bits are not all set
+    NoSync,
+    // A header field holds a value the standard reserves
+    Reserved(&'static str),
+    // The version field holds 01, which no MPEG standard defines
+    ReservedVersion,
+    // Valid version and layer fields whose combination has no bitrate table
+    UnsupportedVersionLayer {
+        version: MPEG_Version,
+        layer: Layer,
+    },
+    // Bitrate index 0, the frame length can't be derived from the header alone
+    FreeFormat,
+    // Strict parsing expected a frame at `offset`
+    InvalidFrame {
+        offset: usize,
+    },
+    // Header fields that are valid on their own but not together
+    IllegalCombination(&'static str),
+    // The frame at `offset` runs past the end of the input
+    TruncatedFrame {
+        offset: usize,
+    },
+    // No valid frame could be found in the input
+    NoFrames,
+    // Layer III side information holding a forbidden or out of range value
+    InvalidSideInfo(&'static str),
+    // Audio data that can't be decoded, Layer III main data not matching its side information
+    InvalidMainData(&'static str),
+    // A requested time or frame range doesn't make sense for the stream
+    InvalidRange,
+    // Streams can't be joined as their sample rate, channel count, version or layer differ
+    IncompatibleStreams,
+    // The frame at `offset` doesn't use the sample rate of the first frame
+    SampleRateChanged {
+        offset: usize,
+        expected: usize,
+        found: usize,
+    },
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for ChompError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChompError::TooShort => write!(f, "not enough bytes"),
+            ChompError::NoSync => write!(f, "sync word not found"),
+            ChompError::Reserved(field) => write!(f, "reserved value in the {field} field"),
+            ChompError::ReservedVersion => write!(f, "reserved MPEG version"),
+            ChompError::UnsupportedVersionLayer { version, layer } => {
+                write!(f, "{} {} is not supported", version.name(), layer.name())
+            }
+            ChompError::FreeFormat => write!(f, "free format bitrate could not be measured"),
+            ChompError::InvalidFrame { offset } => write!(f, "invalid frame at offset {offset}"),
+            ChompError::IllegalCombination(reason) => {
+                write!(f, "illegal header combination: {reason}")
+            }
+            ChompError::TruncatedFrame { offset } => {
+                write!(f, "truncated frame at offset {offset}")
+            }
+            ChompError::NoFrames => write!(f, "no valid frame found"),
+            ChompError::InvalidSideInfo(reason) => write!(f, "invalid side info: {reason}"),
+            ChompError::InvalidMainData(reason) => write!(f, "invalid main data: {reason}"),
+            ChompError::InvalidRange => write!(f, "invalid range"),
+            ChompError::IncompatibleStreams => write!(f, "streams are not compatible"),
+            ChompError::SampleRateChanged {
+                offset,
+                expected,
+                found,
+            } => write!(
+                f,
+                "sample rate changes from {expected} Hz to {found} Hz at offset {offset}"
+            ),
+            ChompError::Io(e) => write!(f, "io error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ChompError {}
+
+impl From<std::io::Error> for ChompError {
+    fn from(e: std::io::Error) -> Self {
+        ChompError::Io(e)
+    }
+}
@@ -1,4 +1,703 @@
+use crate::{
+    ape::{find_ape_tag, find_leading_ape_tag},
+    constants::*,
+    error::ChompError,
+    free_format::FreeFormatContext,
+    header::*,
+    id3::{find_appended_id3v2, id3v2_size},
+    id3v1::strip_id3v1,
+    riff::unwrap_riff,
+    vbr::VbrInfo,
+};
+use std::{
+    io::{self, Write},
+    ops::Range,
+    time::Duration,
+};
+
 // pub struct RawFrame {
 //     headers: RawHeader,
 //     crc: Option<RawCRC>,
 // }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    // Stop at the first anomaly
+    Strict,
+    // Skip or tolerate anomalies and keep going
+    Lenient,
+}
+
+// Knobs of the frame parser, see `FrameIterator::with_config`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParserConfig {
+    pub mode: ParseMode,
+    pub sync: SyncMode,
+    // Lenient mode only, whether a frame found while looking for sync must be followed by a
+    // valid frame alike (or end the buffer) to be trusted. Without it a random sync pattern in
+    // garbage is taken for a frame.
+    pub confirm_sync: bool,
+    // Lenient mode only, how many bytes are scanned looking for sync before giving up, None to
+    // scan the whole buffer. Skipped ID3v2 tags don't count.
+    pub max_scan: Option<usize>,
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        Self {
+            mode: ParseMode::Lenient,
+            sync: SyncMode::default(),
+            confirm_sync: true,
+            max_scan: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame<'a> {
+    // Position of the first header byte in the parsed buffer
+    pub offset: usize,
+    pub header: Header,
+    // The whole frame, header included
+    pub data: &'a [u8],
+}
+
+impl<'a> Frame<'a> {
+    // Writes the frame back exactly as it was read, returns the number of bytes written
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<usize> {
+        w.write_all(self.data)?;
+        Ok(self.data.len())
+    }
+
+    // Bytes following the header and the optional CRC
+    pub fn body(&self) -> &'a [u8] {
+        let start = HEADER_SIZE / 8
+            + if self.header.is_protected() {
+                CRC_SIZE
+            } else {
+                0
+            };
+        &self.data[start..]
+    }
+
+    pub fn side_info(&self) -> Option<&'a [u8]> {
+        if self.header.layer() != Layer::Three {
+            return None;
+        }
+
+        self.body().get(..self.header.side_info_length())
+    }
+
+    // Number of main data bytes this frame carries for itself or the frames after it
+    pub fn main_data_size(&self) -> usize {
+        match self.header.layer() {
+            Layer::Three => self
+                .body()
+                .len()
+                .saturating_sub(self.header.side_info_length()),
+            _ => self.body().len(),
+        }
+    }
+
+    // Layer III only, how many bytes before this frame's side info its main data starts
+    // (9 bits for MPEG-1, 8 bits for MPEG-2 and 2.5)
+    pub fn main_data_begin(&self) -> Option<usize> {
+        let side_info = self.side_info()?;
+
+        match self.header.version() {
+            MPEG_Version::One => {
+                Some(((side_info[0] as usize) << 1) | (side_info[1] as usize >> 7))
+            }
+            _ => Some(side_info[0] as usize),
+        }
+    }
+}
+
+// Walks a buffer frame by frame.
+// In lenient mode (the default) any byte that isn't part of a frame is skipped and no error is
+// ever yielded. In strict mode the buffer must be made of back to back frames, the first anomaly
+// is yielded as an error and ends the iteration. Free format frames all get the bitrate measured
+// on the first of them. In both modes an ID3v2 tag between frames is skipped as a whole, so no
+// sync is looked for in its bytes.
+// The parser is tuned with `with_config`, see ParserConfig.
+pub struct FrameIterator<'a> {
+    data: &'a [u8],
+    position: usize,
+    synced: bool,
+    config: ParserConfig,
+    // Bitrate of the free format frames, once measured
+    free_format: FreeFormatContext,
+    halted: bool,
+    // Size of the ID3v2 tag skipped at the start of the buffer
+    id3v2_size: usize,
+    // ID3v2 tags found between frames while looking for the last frame yielded
+    skipped_tags: Vec<Range<usize>>,
+}
+
+impl<'a> FrameIterator<'a> {
+    // Tags are skipped, so no sync can be found in their bytes and the last frame ends the
+    // buffer: ID3v2 then APEv2 at the start, APE, appended ID3v2 and ID3v1 at the end. In a
+    // RIFF/WAVE file only the data chunk is read.
+    pub fn new(data: &'a [u8]) -> Self {
+        let audio = audio_region(data);
+        let id3v2_size = id3v2_size(data).unwrap_or(0).min(audio.start);
+
+        Self {
+            data: &data[..audio.end],
+            position: audio.start,
+            synced: false,
+            config: ParserConfig::default(),
+            free_format: FreeFormatContext::new(),
+            halted: false,
+            id3v2_size,
+            skipped_tags: Vec::new(),
+        }
+    }
+
+    // Size of the ID3v2 tag starting the buffer, 0 without one
+    pub fn id3v2_size(&self) -> usize {
+        self.id3v2_size
+    }
+
+    pub fn with_config(mut self, config: ParserConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_mode(mut self, mode: ParseMode) -> Self {
+        self.config.mode = mode;
+        self
+    }
+
+    pub fn with_sync_mode(mut self, sync: SyncMode) -> Self {
+        self.config.sync = sync;
+        self
+    }
+
+    // Groups the frames by their start time into windows of `duration`: [0, duration),
+    // [duration, 2 * duration)... Windows end on frame boundaries without drifting from the
+    // requested grid, so with a duration that isn't a multiple of the frame duration their frame
+    // counts alternate. In strict mode the first error ends the last window. Panics if
+    // `duration` is zero.
+    pub fn windows(self, duration: Duration) -> FrameWindows<'a> {
+        assert!(!duration.is_zero(), "window duration must not be zero");
+
+        FrameWindows {
+            frames: self,
+            duration,
+            elapsed: Duration::ZERO,
+            end: duration,
+            pending: None,
+        }
+    }
+
+    // Also yields the ID3v2 tags found between frames, as left by files joined end to end. The
+    // tag starting the buffer isn't reported, see `id3v2_size`.
+    pub fn events(self) -> StreamEvents<'a> {
+        StreamEvents {
+            frames: self,
+            pending: None,
+        }
+    }
+
+    // Skips an ID3v2 tag at the current position, the tag of a file appended to the previous
+    // one. That file can use another sample rate or free format bitrate, so sync starts over.
+    fn skip_id3v2(&mut self) -> bool {
+        let Some(size) = id3v2_size(&self.data[self.position..]) else {
+            return false;
+        };
+
+        let end = self.position.saturating_add(size).min(self.data.len());
+        self.skipped_tags.push(self.position..end);
+        self.position = end;
+        self.synced = false;
+        self.free_format = FreeFormatContext::new();
+        true
+    }
+
+    fn frame_at(&mut self, position: usize) -> Option<Frame<'a>> {
+        let header = self
+            .free_format
+            .header_at(self.data, position, self.config.sync)
+            .ok()?;
+        let data = self.data.get(position..position + header.frame_length())?;
+
+        Some(Frame {
+            offset: position,
+            header,
+            data,
+        })
+    }
+
+    // When looking for sync, a candidate is only trusted if the next frame is also valid and
+    // looks alike, or if the candidate ends exactly at the end of the buffer
+    fn confirmed(&mut self, frame: &Frame) -> bool {
+        let next = frame.offset + frame.data.len();
+        if next == self.data.len() {
+            return true;
+        }
+
+        match self.frame_at(next) {
+            Some(following) => {
+                following.header.version() == frame.header.version()
+                    && following.header.layer() == frame.header.layer()
+                    && following.header.sample_rate() == frame.header.sample_rate()
+            }
+            None => false,
+        }
+    }
+
+    fn next_lenient(&mut self) -> Option<Frame<'a>> {
+        let mut scan_start = self.position;
+        while self.position + HEADER_SIZE / 8 <= self.data.len() {
+            if self.skip_id3v2() {
+                scan_start = self.position;
+                continue;
+            }
+
+            if let Some(frame) = self.frame_at(self.position) {
+                if self.synced || !self.config.confirm_sync || self.confirmed(&frame) {
+                    self.synced = true;
+                    self.position += frame.data.len();
+                    return Some(frame);
+                }
+            }
+
+            self.synced = false;
+            if self
+                .config
+                .max_scan
+                .is_some_and(|max| self.position - scan_start >= max)
+            {
+                self.halted = true;
+                return None;
+            }
+            self.position += 1;
+        }
+
+        None
+    }
+
+    fn next_strict(&mut self) -> Option<Result<Frame<'a>, ChompError>> {
+        while self.position < self.data.len() && self.skip_id3v2() {}
+        if self.position >= self.data.len() {
+            return None;
+        }
+
+        let offset = self.position;
+        let frame = match self
+            .free_format
+            .header_at(self.data, offset, self.config.sync)
+        {
+            Ok(header) => match self.data.get(offset..offset + header.frame_length()) {
+                Some(data) => Ok(Frame {
+                    offset,
+                    header,
+                    data,
+                }),
+                None => Err(ChompError::TruncatedFrame { offset }),
+            },
+            Err(ChompError::TooShort) => Err(ChompError::TruncatedFrame { offset }),
+            Err(_) => Err(ChompError::InvalidFrame { offset }),
+        };
+
+        match &frame {
+            Ok(frame) => self.position += frame.data.len(),
+            Err(_) => self.halted = true,
+        }
+
+        Some(frame)
+    }
+}
+
+impl<'a> Iterator for FrameIterator<'a> {
+    type Item = Result<Frame<'a>, ChompError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.halted {
+            return None;
+        }
+
+        self.skipped_tags.clear();
+        match self.config.mode {
+            ParseMode::Strict => self.next_strict(),
+            ParseMode::Lenient => self.next_lenient().map(Ok),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StreamEvent<'a> {
+    Frame(Frame<'a>),
+    // Byte range of an ID3v2 tag found between frames
+    Id3v2(Range<usize>),
+}
+
+// Frames of a FrameIterator and the ID3v2 tags between them, see `FrameIterator::events`
+pub struct StreamEvents<'a> {
+    frames: FrameIterator<'a>,
+    // Next frame, read while reporting the tags before it
+    pending: Option<Result<Frame<'a>, ChompError>>,
+}
+
+impl<'a> Iterator for StreamEvents<'a> {
+    type Item = Result<StreamEvent<'a>, ChompError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pending.is_none() {
+            self.pending = self.frames.next();
+        }
+
+        if !self.frames.skipped_tags.is_empty() {
+            let tag = self.frames.skipped_tags.remove(0);
+            return Some(Ok(StreamEvent::Id3v2(tag)));
+        }
+
+        Some(self.pending.take()?.map(StreamEvent::Frame))
+    }
+}
+
+// Frames of a FrameIterator grouped by duration, see `FrameIterator::windows`
+pub struct FrameWindows<'a> {
+    frames: FrameIterator<'a>,
+    duration: Duration,
+    // Start time of the next frame
+    elapsed: Duration,
+    // End of the current window
+    end: Duration,
+    // First frame of the next window, read while closing the current one
+    pending: Option<Frame<'a>>,
+}
+
+impl<'a> Iterator for FrameWindows<'a> {
+    type Item = Vec<Frame<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut window = Vec::new();
+
+        while let Some(frame) = self.pending.take().or_else(|| self.frames.next()?.ok()) {
+            if self.elapsed >= self.end {
+                if !window.is_empty() {
+                    self.pending = Some(frame);
+                    return Some(window);
+                }
+
+                // A frame longer than the windows skips some of them
+                let windows = self.elapsed.as_nanos() / self.duration.as_nanos() + 1;
+                self.end = Duration::from_nanos((windows * self.duration.as_nanos()) as u64);
+            }
+
+            self.elapsed += frame.header.duration();
+            window.push(frame);
+        }
+
+        (!window.is_empty()).then_some(window)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TagKind {
+    Id3v2,
+    Ape,
+    Id3v1,
+}
+
+// Tags starting and ending the buffer in buffer order, and the bytes between them. Several tags
+// can be stacked at the start, left by taggers prepending theirs without noticing the previous one.
+// In a RIFF/WAVE file only the data chunk is read.
+pub(crate) fn surrounding_tags(data: &[u8]) -> (Vec<(TagKind, Range<usize>)>, Range<usize>) {
+    if let Some(riff) = unwrap_riff(data) {
+        let (tags, audio) = surrounding_tags(&data[riff.clone()]);
+        let shift = |range: Range<usize>| riff.start + range.start..riff.start + range.end;
+        let tags = tags
+            .into_iter()
+            .map(|(kind, range)| (kind, shift(range)))
+            .collect();
+        return (tags, shift(audio));
+    }
+
+    let mut tags = Vec::new();
+    let mut start = 0;
+    loop {
+        if let Some(size) = id3v2_size(&data[start..]) {
+            let end = start.saturating_add(size).min(data.len());
+            tags.push((TagKind::Id3v2, start..end));
+            start = end;
+        } else if let Some(tag) = find_leading_ape_tag(data, start) {
+            start = tag.end;
+            tags.push((TagKind::Ape, tag));
+        } else {
+            break;
+        }
+    }
+
+    let trailing = trailing_tags(data, start);
+    let end = trailing.first().map_or(data.len(), |(_, tag)| tag.start);
+    tags.extend(trailing);
+    (tags, start.min(end)..end)
+}
+
+// Tags ending the buffer in buffer order, none reaching before `start`
+pub(crate) fn trailing_tags(data: &[u8], start: usize) -> Vec<(TagKind, Range<usize>)> {
+    // APE and appended ID3v2 tags come in either order, both before the ID3v1 tag
+    let mut end = strip_id3v1(data).len();
+    let mut tags = Vec::new();
+    if end < data.len() {
+        tags.push((TagKind::Id3v1, end..data.len()));
+    }
+    while let Some((kind, tag)) = find_ape_tag(&data[..end])
+        .map(|tag| (TagKind::Ape, tag))
+        .or_else(|| find_appended_id3v2(&data[..end]).map(|tag| (TagKind::Id3v2, tag)))
+        .filter(|(_, tag)| tag.start < end && tag.start >= start)
+    {
+        end = tag.start;
+        tags.push((kind, tag));
+    }
+
+    tags.reverse();
+    tags
+}
+
+// Bytes between the tags starting and ending the buffer, or the data chunk of a RIFF/WAVE file
+pub(crate) fn audio_region(data: &[u8]) -> Range<usize> {
+    surrounding_tags(data).1
+}
+
+// Offset of the first frame at or after `from`, found the way a lenient FrameIterator regains
+// sync: tags are skipped, and `config.confirm_sync` and `config.max_scan` apply. None when no
+// frame is found in range.
+pub fn find_next_sync(data: &[u8], from: usize, config: ParserConfig) -> Option<usize> {
+    let mut frames = FrameIterator::new(data).with_config(ParserConfig {
+        mode: ParseMode::Lenient,
+        ..config
+    });
+    frames.position = frames.position.max(from);
+    frames.next_lenient().map(|frame| frame.offset)
+}
+
+// Frames of the stream, the leading Xing/Info or VBRI frame excluded as it holds no audio
+pub(crate) fn audio_frames(data: &[u8]) -> Vec<Frame<'_>> {
+    // Lenient iteration never yields errors
+    let mut frames = FrameIterator::new(data).flatten().collect::<Vec<_>>();
+
+    if frames
+        .first()
+        .is_some_and(|frame| VbrInfo::from_frame(frame).is_some())
+    {
+        frames.remove(0);
+    }
+
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    const CBR: &[u8] = include_bytes!("../fixtures/cbr.mp3");
+
+    // Five 417 bytes frames, the third one's sync broken
+    fn corrupted() -> Vec<u8> {
+        let mut data = frames(&[HEADER_128; 5]);
+        data[2 * 417] = 0;
+        data
+    }
+
+    #[test]
+    fn strict_mode_stops_at_the_corrupt_frame() {
+        let data = corrupted();
+        let mut frames = FrameIterator::new(&data).with_mode(ParseMode::Strict);
+        assert_eq!(frames.next().unwrap().unwrap().offset, 0);
+        assert_eq!(frames.next().unwrap().unwrap().offset, 417);
+        assert!(matches!(
+            frames.next(),
+            Some(Err(ChompError::InvalidFrame { offset: 834 }))
+        ));
+        assert!(frames.next().is_none());
+
+        let truncated = &data[..417 + 100];
+        let mut frames = FrameIterator::new(truncated).with_mode(ParseMode::Strict);
+        assert!(frames.next().unwrap().is_ok());
+        assert!(matches!(
+            frames.next(),
+            Some(Err(ChompError::TruncatedFrame { offset: 417 }))
+        ));
+    }
+
+    #[test]
+    fn lenient_mode_resyncs() {
+        let data = corrupted();
+        let offsets: Vec<_> = FrameIterator::new(&data)
+            .with_mode(ParseMode::Lenient)
+            .map(|frame| frame.unwrap().offset)
+            .collect();
+        assert_eq!(offsets, [0, 417, 3 * 417, 4 * 417]);
+    }
+
+    #[test]
+    fn write_back_round_trip() {
+        let mut data = b"ID3\x04\x00\x00\x00\x00\x00\x05hello".to_vec();
+        data.extend(CBR);
+
+        let mut output = Vec::new();
+        let mut written = 0;
+        for frame in FrameIterator::new(&data).flatten() {
+            written += frame.write_to(&mut output).unwrap();
+        }
+
+        assert_eq!(written, CBR.len());
+        assert_eq!(output, CBR);
+    }
+
+    #[test]
+    fn main_data_size_of_a_frame_shorter_than_its_side_info() {
+        let data = [0xFF, 0xFB, 0x00, 0x00, 0, 0];
+        let frame = Frame {
+            offset: 0,
+            header: Header::parse(&data, SyncMode::default(), Some(2)).unwrap(),
+            data: &data,
+        };
+        assert_eq!(frame.main_data_size(), 0);
+    }
+
+    #[test]
+    fn leading_id3v2_tag_is_skipped() {
+        // 300 bytes of tag, larger than 0x80, full of frame headers
+        let mut data = b"ID3\x04\x00\x00\x00\x00\x02\x2C".to_vec();
+        while data.len() < 310 {
+            data.extend(HEADER_128);
+            data.push(0);
+        }
+        data.truncate(310);
+        data.extend(frames(&[HEADER_128; 3]));
+
+        let parser = FrameIterator::new(&data);
+        assert_eq!(parser.id3v2_size(), 310);
+        let offsets: Vec<_> = parser.flatten().map(|frame| frame.offset).collect();
+        assert_eq!(offsets, [310, 310 + 417, 310 + 2 * 417]);
+    }
+
+    #[test]
+    fn concatenated_files() {
+        let first = [&include_bytes!("../fixtures/lame.id3")[..], CBR].concat();
+        let itunes = include_bytes!("../fixtures/itunes.id3");
+        let second = [&itunes[..], include_bytes!("../fixtures/mpeg2.mp3")].concat();
+        let data = [&first[..], &second].concat();
+        let count = |data: &[u8]| FrameIterator::new(data).count();
+
+        // The second tag is reported where the first file ends, the sample rate changing after it
+        let events: Vec<_> = FrameIterator::new(&data).events().flatten().collect();
+        assert_eq!(events.len(), count(&first) + 1 + count(&second));
+        let (before, after) = events.split_at(count(&first));
+        assert!(
+            matches!(&after[0], StreamEvent::Id3v2(tag) if *tag == (first.len()..first.len() + itunes.len()))
+        );
+
+        let rates = |events: &[StreamEvent]| -> Vec<_> {
+            events
+                .iter()
+                .map(|event| match event {
+                    StreamEvent::Frame(frame) => frame.header.sample_rate(),
+                    StreamEvent::Id3v2(_) => panic!("tag among the frames"),
+                })
+                .collect()
+        };
+        assert!(rates(before).iter().all(|rate| *rate == 44100));
+        assert!(rates(&after[1..]).iter().all(|rate| *rate == 22050));
+
+        // Strict mode takes the tag as part of the stream too
+        let strict = FrameIterator::new(&data).with_mode(ParseMode::Strict);
+        assert_eq!(
+            strict.map(Result::unwrap).count(),
+            count(&first) + count(&second)
+        );
+
+        // No sync is looked for inside a tag full of frame headers
+        let mut tag = b"ID3\x04\x00\x00\x00\x00\x02\x2C".to_vec();
+        while tag.len() < 310 {
+            tag.extend(HEADER_192);
+            tag.push(0);
+        }
+        tag.truncate(310);
+        let data = [frames(&[HEADER_128; 2]), tag, frames(&[HEADER_128; 2])].concat();
+        let offsets: Vec<_> = FrameIterator::new(&data)
+            .map(|frame| frame.unwrap().offset)
+            .collect();
+        assert_eq!(offsets, [0, 417, 1144, 1144 + 417]);
+    }
+
+    #[test]
+    fn resync_limits() {
+        let data = [
+            frames(&[HEADER_128; 2]),
+            vec![0x55; 100],
+            frames(&[HEADER_128; 3]),
+        ]
+        .concat();
+        let offsets = |config: ParserConfig| -> Vec<_> {
+            FrameIterator::new(&data)
+                .with_config(config)
+                .map(|frame| frame.unwrap().offset)
+                .collect()
+        };
+        let tiny = ParserConfig {
+            max_scan: Some(10),
+            ..ParserConfig::default()
+        };
+
+        // Giving up 10 bytes into the garbage, for good
+        assert_eq!(offsets(tiny), [0, 417]);
+        let mut parser = FrameIterator::new(&data).with_config(tiny);
+        assert_eq!(parser.by_ref().count(), 2);
+        assert!(parser.next().is_none());
+
+        let enough = ParserConfig {
+            max_scan: Some(100),
+            ..ParserConfig::default()
+        };
+        assert_eq!(offsets(enough), [0, 417, 934, 1351, 1768]);
+
+        assert_eq!(find_next_sync(&data, 835, tiny), None);
+        assert_eq!(
+            find_next_sync(&data, 835, ParserConfig::default()),
+            Some(934)
+        );
+        assert_eq!(find_next_sync(&data, 0, tiny), Some(0));
+
+        // A lone header in garbage is only taken for a frame without confirmation
+        let mut data = [vec![0x55; 20], frames(&[HEADER_128; 2])].concat();
+        data[5..9].copy_from_slice(&HEADER_128);
+        assert_eq!(find_next_sync(&data, 0, ParserConfig::default()), Some(20));
+        let unconfirmed = ParserConfig {
+            confirm_sync: false,
+            ..ParserConfig::default()
+        };
+        assert_eq!(find_next_sync(&data, 0, unconfirmed), Some(5));
+    }
+
+    #[test]
+    fn one_second_windows() {
+        // 38.28 frames per second, windows end on the first frame starting past the second
+        let data = frames(&[HEADER_128; 100]);
+        let windows: Vec<_> = FrameIterator::new(&data)
+            .windows(Duration::from_secs(1))
+            .collect();
+        let counts: Vec<_> = windows.iter().map(Vec::len).collect();
+        assert_eq!(counts, [39, 38, 23]);
+        assert_eq!(windows[1][0].offset, 39 * 417);
+
+        // 19.14 frames per half second
+        let counts: Vec<_> = FrameIterator::new(&data)
+            .windows(Duration::from_millis(500))
+            .map(|window| window.len())
+            .collect();
+        assert_eq!(counts, [20, 19, 19, 19, 19, 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "window duration must not be zero")]
+    fn zero_windows() {
+        FrameIterator::new(&[]).windows(Duration::ZERO);
+    }
+}
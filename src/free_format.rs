@@ -0,0 +1,129 @@
+use crate::{
+    constants::*,
+    error::ChompError,
+    header::{Header, Layer, SyncMode},
+};
+
+// Bitrate of a free format stream. It is measured once, from the distance between the first
+// frame and the next one, then reused for every other free format frame of the stream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FreeFormatContext {
+    // In kbit/s
+    bitrate: Option<usize>,
+}
+
+impl FreeFormatContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // In kbit/s, None until a free format frame has been measured
+    pub fn bitrate(&self) -> Option<usize> {
+        self.bitrate
+    }
+
+    // Parses the header at `position`, giving free format headers the bitrate of the stream
+    pub fn header_at(
+        &mut self,
+        data: &[u8],
+        position: usize,
+        sync: SyncMode,
+    ) -> Result<Header, ChompError> {
+        let bytes = data.get(position..).ok_or(ChompError::TooShort)?;
+        // A placeholder bitrate until it is measured, only the other fields are used
+        let header = Header::parse(bytes, sync, Some(self.bitrate.unwrap_or(0)))?;
+        if !header.is_free_format() || self.bitrate.is_some() {
+            return Ok(header);
+        }
+
+        let bitrate = measure(data, position, &header).ok_or(ChompError::FreeFormat)?;
+        let header = Header::parse(bytes, sync, Some(bitrate))?;
+
+        // Two headers closer than a header, CRC and side info can't be frames
+        let side_info = match header.layer() {
+            Layer::Three => header.side_info_length(),
+            _ => 0,
+        };
+        let crc = header.is_protected() as usize * CRC_SIZE;
+        if header.frame_length() < HEADER_SIZE / 8 + crc + side_info {
+            return Err(ChompError::FreeFormat);
+        }
+
+        self.bitrate = Some(bitrate);
+        Ok(header)
+    }
+}
+
+// Looks for the next header sharing everything but the padding and private bits with the one at
+// `position`, and derives the bitrate from the gap. Free format encoders use whole kbit/s, so the
+// rounding gives back the exact value.
+fn measure(data: &[u8], position: usize, header: &Header) -> Option<usize> {
+    let fixed = |bytes: &[u8]| [bytes[0], bytes[1], bytes[2] & 0xFC];
+    let expected = fixed(&data[position..]);
+
+    let next = (position + HEADER_SIZE / 8..data.len().checked_sub(HEADER_SIZE / 8)?)
+        .find(|&next| fixed(&data[next..]) == expected)?;
+
+    let padding = header.has_padding() as usize * header.slot_size();
+    let bits = (next - position - padding) * 8 * header.sample_rate();
+    let samples = header.samples_per_frame() * 1000;
+
+    match (bits + samples / 2) / samples {
+        0 => None,
+        bitrate => Some(bitrate),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::FrameIterator;
+
+    // MPEG-1 Layer III free format 44.1 kHz stereo headers, `gap` bytes apart
+    fn stream(gap: usize, frames: usize) -> Vec<u8> {
+        let mut data = vec![0; gap * frames];
+        for frame in data.chunks_mut(gap) {
+            frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x00, 0x00]);
+        }
+        data
+    }
+
+    #[test]
+    fn measures_the_bitrate() {
+        // 144 * 128000 / 44100 = 417 bytes
+        let data = stream(417, 4);
+        let mut context = FreeFormatContext::new();
+        let header = context.header_at(&data, 0, SyncMode::default()).unwrap();
+        assert_eq!(context.bitrate(), Some(128));
+        assert_eq!(header.frame_length(), 417);
+        assert_eq!(FrameIterator::new(&data).count(), 4);
+    }
+
+    #[test]
+    fn frames_shorter_than_their_side_info_are_rejected() {
+        let data = stream(6, 20);
+        let mut context = FreeFormatContext::new();
+        assert!(context.header_at(&data, 0, SyncMode::default()).is_err());
+        assert_eq!(context.bitrate(), None);
+        assert_eq!(FrameIterator::new(&data).count(), 0);
+    }
+
+    #[test]
+    fn three_frames_share_the_bitrate() {
+        // The second frame is padded, the last one has no header after it to be measured from
+        let mut data = Vec::new();
+        for (length, padding) in [(417, 0x00), (418, 0x02), (417, 0x00)] {
+            let start = data.len();
+            data.resize(start + length, 0);
+            data[start..start + 4].copy_from_slice(&[0xFF, 0xFB, padding, 0x00]);
+        }
+
+        let frames: Vec<_> = FrameIterator::new(&data).flatten().collect();
+        assert_eq!(frames.len(), 3);
+        for (frame, length) in frames.iter().zip([417, 418, 417]) {
+            assert!(frame.header.is_free_format());
+            assert_eq!(frame.header.bitrate(), 128);
+            assert_eq!(frame.data.len(), length);
+        }
+    }
+}
@@ -1,20 +1,24 @@
-use crate::{constants::*, utils::*};
+use crate::{bit_reader::BitReader, constants::*, error::ChompError, utils::*};
 use bitvec::prelude::*;
+use std::{
+    hash::{Hash, Hasher},
+    ops::RangeInclusive,
+};
 
-#[derive(Debug)]
-
+// Headers compare and hash without their CRC (see `Header::key`)
+#[derive(Debug, Clone)]
 pub struct Header {
-    // Sync (12 bits)
-    // This is the synchronization word described above. All 12 bits must be set, i.e.
-    // ‘1111 1111 1111’.
-    pub sync: BitVec<u8>,
+    // Sync (11 bits)
+    // This is the synchronization word described above. All 11 bits must be set, i.e.
+    // ‘1111 1111 111’.
+    pub sync: SyncWord,
 
-    // Id (1 bit)
+    // Id (2 bits)
     // Specifies the MPEG version. A set bit means that the frame is encoded with the MPEG-1
     // standard, if not MPEG-2 is used
 
-    // Some add-on standards only use 11 bits for the sync word in order to dedicate 2 bits for the id.
-    // In this case Table 5.1 is applied.
+    // The MPEG-2.5 add-on standard only uses 11 bits for the sync word in order to dedicate 2 bits
+    // for the id, this layout is the one parsed here. In this case Table 5.1 is applied.
     pub id: MPEG_Version,
 
     // Layer (2 bits)
@@ -22,7 +26,7 @@ pub struct Header {
     pub layer: Layer,
 
     // Protection Bit (1 bit)
-    // If the protection bit is set, the CRC field will be used.
+    // If the protection bit is cleared, a 16 bit CRC follows the header.
     protection_bit: Protected,
 
     // Bitrate (4 bits)
@@ -39,16 +43,21 @@ pub struct Header {
     // An encoded stream with bitrate 128 kbit/s and sampling frequency of 44100 Hz will create
     // frames of size 417 bytes. To exactly fit the bitrate some of these frames will have to be 418
     // bytes. These frames set the padding bit.
-    padding: BitVec<u8>,
+    padding: BitVec<u8, Msb0>,
 
     // Private bit (1 bit)
     // One bit for application-specific triggers.
-    private_bit: BitVec<u8>,
+    private_bit: BitVec<u8, Msb0>,
 
     // Mode (2 bits)
     // Specifies what channel mode is used according to Table 5.5.
     mode: Mode,
 
+    // Mode extension (2 bits)
+    // Only used in joint stereo mode. Layers I and II store the subband bound above which the
+    // channels are joined, Layer III the intensity and mid/side stereo flags.
+    mode_extension: ModeExtension,
+
     // Copyright Bit (1 bit)
     // If this bit is set it means that it is illegal to copy the contents.
     copyright_bit: Copyright,
@@ -61,9 +70,45 @@ pub struct Header {
     // The emphasis indication is used to tell the decoder that the file must be de-emphasized, i.e.
     // the decoder must 're-equalize' the sound after a Dolby- like noise supression. It is rarely used.
     emphasis: Emphasis,
+
+    // The 4 bytes the header was parsed from
+    raw: [u8; 4],
+
+    // CRC (16 bits)
+    // Stored right after the header when the protection bit says so. None for unprotected frames
+    // and when the bytes given to the parser stop at the header.
+    crc: Option<u16>,
 }
 
-#[derive(Debug)]
+impl PartialEq for Header {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Header {}
+
+impl Hash for Header {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state)
+    }
+}
+
+// How many leading bits must be set for a header to be in sync. The original standard uses 12,
+// MPEG-2.5 takes the 12th bit over for the version, so only 11 bits sync its frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncMode {
+    // MPEG-1 and MPEG-2 only
+    Strict12,
+    #[default]
+    Lenient11,
+}
+
+// The 11 sync bits, only built from bits that are all set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SyncWord(u16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MPEG_Version {
     Reserved,
     One,
@@ -71,7 +116,7 @@ pub enum MPEG_Version {
     TwoPointFive,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Layer {
     Reserved,
     Three,
@@ -79,20 +124,20 @@ pub enum Layer {
     One,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Protected {
     Yes,
     No,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Bitrate(usize);
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 // In Hz
 pub struct Frequency(usize);
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Mode {
     Stereo,
     JointStereo,
@@ -100,72 +145,509 @@ pub enum Mode {
     SingleChannel,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ModeExtension(u8);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Copyright {
     On,
     Off,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Home {
     On,
     Off,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Emphasis {
-    On,
-    Off,
+    None,
+    // 50/15 µs
+    FiftyFifteen,
+    Reserved,
+    CcittJ17,
 }
 
 #[derive(Debug)]
 pub struct RawHeader {
-    pub sync: BitVec<u8>,
-    pub id: BitVec<u8>,
-    pub layer: BitVec<u8>,
-    pub protection_bit: BitVec<u8>,
-    pub bitrate: BitVec<u8>,
-    pub frequency: BitVec<u8>,
-    pub padding_bit: BitVec<u8>,
-    pub private_bit: BitVec<u8>,
-    pub mode: BitVec<u8>,
-    pub mode_extension: BitVec<u8>,
-    pub copyright_bit: BitVec<u8>,
-    pub home: BitVec<u8>,
-    pub emphasis: BitVec<u8>,
+    pub sync: BitVec<u8, Msb0>,
+    pub id: BitVec<u8, Msb0>,
+    pub layer: BitVec<u8, Msb0>,
+    pub protection_bit: BitVec<u8, Msb0>,
+    pub bitrate: BitVec<u8, Msb0>,
+    pub frequency: BitVec<u8, Msb0>,
+    pub padding_bit: BitVec<u8, Msb0>,
+    pub private_bit: BitVec<u8, Msb0>,
+    pub mode: BitVec<u8, Msb0>,
+    pub mode_extension: BitVec<u8, Msb0>,
+    pub copyright_bit: BitVec<u8, Msb0>,
+    pub home: BitVec<u8, Msb0>,
+    pub emphasis: BitVec<u8, Msb0>,
 }
 
 impl RawHeader {
-    pub fn new(array: &mut BitSlice<u8>) -> Self {
-        Self {
-            sync: access(array, SYNC_SIZE),
-            id: access(array, ID_SIZE),
-            layer: access(array, LAYER_SIZE),
-            protection_bit: access(array, PROTECTION_BIT_SIZE),
-            bitrate: access(array, BITRATE_SIZE),
-            frequency: access(array, FREQUENCY_SIZE),
-            padding_bit: access(array, PADDING_BIT_SIZE),
-            private_bit: access(array, PRIVATE_BIT_SIZE),
-            mode: access(array, MODE_SIZE),
-            mode_extension: access(array, MODE_EXTENSION_SIZE),
-            copyright_bit: access(array, COPYRIGHT_BIT_SIZE),
-            home: access(array, HOME_SIZE),
-            emphasis: access(array, EMPHASIS_SIZE),
-        }
-    }
-}
-
-impl From<BitVec<u8>> for MPEG_Version {
-    fn from(bits: BitVec<u8>) -> MPEG_Version {
+    pub fn new(array: &BitSlice<u8, Msb0>) -> Result<Self, ChompError> {
+        let mut reader = BitReader::from_bits(array);
+        let mut field = |size: usize| reader.read_slice(size).map(BitSlice::to_bitvec);
+
+        Ok(Self {
+            sync: field(SYNC_SIZE)?,
+            id: field(ID_SIZE)?,
+            layer: field(LAYER_SIZE)?,
+            protection_bit: field(PROTECTION_BIT_SIZE)?,
+            bitrate: field(BITRATE_SIZE)?,
+            frequency: field(FREQUENCY_SIZE)?,
+            padding_bit: field(PADDING_BIT_SIZE)?,
+            private_bit: field(PRIVATE_BIT_SIZE)?,
+            mode: field(MODE_SIZE)?,
+            mode_extension: field(MODE_EXTENSION_SIZE)?,
+            copyright_bit: field(COPYRIGHT_BIT_SIZE)?,
+            home: field(HOME_SIZE)?,
+            emphasis: field(EMPHASIS_SIZE)?,
+        })
+    }
+}
+
+// Same fields as RawHeader, borrowed from the parsed buffer instead of copied. Nothing is
+// allocated, a field is only decoded when the caller reads it.
+#[derive(Debug, Clone, Copy)]
+pub struct RawHeaderRef<'a> {
+    pub sync: &'a BitSlice<u8, Msb0>,
+    pub id: &'a BitSlice<u8, Msb0>,
+    pub layer: &'a BitSlice<u8, Msb0>,
+    pub protection_bit: &'a BitSlice<u8, Msb0>,
+    pub bitrate: &'a BitSlice<u8, Msb0>,
+    pub frequency: &'a BitSlice<u8, Msb0>,
+    pub padding_bit: &'a BitSlice<u8, Msb0>,
+    pub private_bit: &'a BitSlice<u8, Msb0>,
+    pub mode: &'a BitSlice<u8, Msb0>,
+    pub mode_extension: &'a BitSlice<u8, Msb0>,
+    pub copyright_bit: &'a BitSlice<u8, Msb0>,
+    pub home: &'a BitSlice<u8, Msb0>,
+    pub emphasis: &'a BitSlice<u8, Msb0>,
+}
+
+impl<'a> RawHeaderRef<'a> {
+    pub fn new(array: &'a BitSlice<u8, Msb0>) -> Result<Self, ChompError> {
+        if array.len() < HEADER_SIZE {
+            return Err(ChompError::TooShort);
+        }
+        let field = |offset: usize, size: usize| &array[offset..offset + size];
+
+        Ok(Self {
+            sync: field(SYNC_OFFSET, SYNC_SIZE),
+            id: field(ID_OFFSET, ID_SIZE),
+            layer: field(LAYER_OFFSET, LAYER_SIZE),
+            protection_bit: field(PROTECTION_BIT_OFFSET, PROTECTION_BIT_SIZE),
+            bitrate: field(BITRATE_OFFSET, BITRATE_SIZE),
+            frequency: field(FREQUENCY_OFFSET, FREQUENCY_SIZE),
+            padding_bit: field(PADDING_BIT_OFFSET, PADDING_BIT_SIZE),
+            private_bit: field(PRIVATE_BIT_OFFSET, PRIVATE_BIT_SIZE),
+            mode: field(MODE_OFFSET, MODE_SIZE),
+            mode_extension: field(MODE_EXTENSION_OFFSET, MODE_EXTENSION_SIZE),
+            copyright_bit: field(COPYRIGHT_BIT_OFFSET, COPYRIGHT_BIT_SIZE),
+            home: field(HOME_OFFSET, HOME_SIZE),
+            emphasis: field(EMPHASIS_OFFSET, EMPHASIS_SIZE),
+        })
+    }
+}
+
+impl From<RawHeaderRef<'_>> for RawHeader {
+    fn from(view: RawHeaderRef) -> RawHeader {
+        RawHeader {
+            sync: view.sync.to_bitvec(),
+            id: view.id.to_bitvec(),
+            layer: view.layer.to_bitvec(),
+            protection_bit: view.protection_bit.to_bitvec(),
+            bitrate: view.bitrate.to_bitvec(),
+            frequency: view.frequency.to_bitvec(),
+            padding_bit: view.padding_bit.to_bitvec(),
+            private_bit: view.private_bit.to_bitvec(),
+            mode: view.mode.to_bitvec(),
+            mode_extension: view.mode_extension.to_bitvec(),
+            copyright_bit: view.copyright_bit.to_bitvec(),
+            home: view.home.to_bitvec(),
+            emphasis: view.emphasis.to_bitvec(),
+        }
+    }
+}
+
+// The most common configuration, see DEFAULT_HEADER
+impl Default for Header {
+    fn default() -> Self {
+        Self::from_bytes(&DEFAULT_HEADER).unwrap()
+    }
+}
+
+impl Header {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ChompError> {
+        Self::from_bytes_with_sync(bytes, SyncMode::default())
+    }
+
+    pub fn from_bytes_with_sync(bytes: &[u8], sync: SyncMode) -> Result<Self, ChompError> {
+        Self::parse(bytes, sync, None)
+    }
+
+    // Free format headers (bitrate index 0) are rejected unless their bitrate, in kbit/s, is
+    // already known from the stream
+    pub(crate) fn parse(
+        bytes: &[u8],
+        sync: SyncMode,
+        free_format_bitrate: Option<usize>,
+    ) -> Result<Self, ChompError> {
+        // Candidates are checked on the borrowed view, so rejecting one allocates nothing. The
+        // conversions below panic on reserved values, reject them first.
+        let view = RawHeaderRef::new(bytes.view_bits::<Msb0>())?;
+
+        if !view.sync.all() {
+            return Err(ChompError::NoSync);
+        }
+        // The 12th sync bit is the first version bit
+        if sync == SyncMode::Strict12 && !view.id[0] {
+            return Err(ChompError::NoSync);
+        }
+        if view.id.load_be::<u8>() == 0b01 {
+            return Err(ChompError::ReservedVersion);
+        }
+        if view.layer.not_any() {
+            return Err(ChompError::Reserved("layer"));
+        }
+        if view.bitrate.all() {
+            return Err(ChompError::Reserved("bitrate"));
+        }
+        if view.bitrate.not_any() && free_format_bitrate.is_none() {
+            return Err(ChompError::FreeFormat);
+        }
+        if view.frequency.all() {
+            return Err(ChompError::Reserved("frequency"));
+        }
+
+        let raw = RawHeader::from(view);
+        let id = MPEG_Version::from(raw.id);
+        let layer = Layer::from(raw.layer);
+        // MPEG-2.5 only defines Layer III
+        if !Bitrate::has_column(id, layer) {
+            return Err(ChompError::UnsupportedVersionLayer { version: id, layer });
+        }
+
+        let protection_bit = Protected::from(raw.protection_bit);
+        Ok(Self {
+            sync: SyncWord::try_from(raw.sync)?,
+            id,
+            layer,
+            protection_bit,
+            bitrate: match free_format_bitrate {
+                Some(bitrate) if raw.bitrate.not_any() => Bitrate(bitrate),
+                _ => Bitrate::from_bitvecu8(raw.bitrate, id, layer),
+            },
+            frequency: Frequency::from_bitvecu8(raw.frequency, id)?,
+            padding: raw.padding_bit,
+            private_bit: raw.private_bit,
+            mode: Mode::from(raw.mode),
+            mode_extension: ModeExtension::from(raw.mode_extension),
+            copyright_bit: Copyright::from(raw.copyright_bit),
+            home: Home::from(raw.home),
+            emphasis: Emphasis::try_from(raw.emphasis)?,
+            raw: bytes[..HEADER_SIZE / 8].try_into().unwrap(),
+            crc: match protection_bit {
+                Protected::Yes => bytes
+                    .get(HEADER_SIZE / 8..HEADER_SIZE / 8 + CRC_SIZE)
+                    .map(|crc| u16::from_be_bytes([crc[0], crc[1]])),
+                Protected::No => None,
+            },
+        })
+    }
+
+    // Checks the combinations of fields the standard forbids even though each value is valid.
+    // MPEG-1 Layer II restricts the modes allowed at each bitrate.
+    pub fn validate_strict(&self) -> Result<(), ChompError> {
+        if self.id == MPEG_Version::One && self.layer == Layer::Two {
+            let single_channel = self.mode == Mode::SingleChannel;
+
+            match self.bitrate() {
+                32 | 48 | 56 | 80 if !single_channel => {
+                    return Err(ChompError::IllegalCombination(
+                        "Layer II bitrate only allowed in single channel mode",
+                    ))
+                }
+                224 | 256 | 320 | 384 if single_channel => {
+                    return Err(ChompError::IllegalCombination(
+                        "Layer II bitrate not allowed in single channel mode",
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    // The header exactly as it was read
+    pub fn raw_bytes(&self) -> [u8; 4] {
+        self.raw
+    }
+
+    pub fn version(&self) -> MPEG_Version {
+        self.id
+    }
+
+    pub fn layer(&self) -> Layer {
+        self.layer
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn mode_extension(&self) -> ModeExtension {
+        self.mode_extension
+    }
+
+    pub fn is_protected(&self) -> bool {
+        self.protection_bit == Protected::Yes
+    }
+
+    // Every field but the CRC, which depends on the frame's content rather than on the header, so
+    // that the frames of a stream share their header. The BitVec fields compare and hash by their
+    // bits, not by their underlying storage.
+    fn key(&self) -> impl PartialEq + Hash + '_ {
+        let Self {
+            sync,
+            id,
+            layer,
+            protection_bit,
+            bitrate,
+            frequency,
+            padding,
+            private_bit,
+            mode,
+            mode_extension,
+            copyright_bit,
+            home,
+            emphasis,
+            raw,
+            crc: _,
+        } = self;
+
+        (
+            (sync, id, layer, protection_bit, bitrate, frequency, padding),
+            (
+                private_bit,
+                mode,
+                mode_extension,
+                copyright_bit,
+                home,
+                emphasis,
+                raw,
+            ),
+        )
+    }
+
+    // The CRC stored in the frame, not checked against its content (see `verify_crc`)
+    pub fn crc(&self) -> Option<u16> {
+        self.crc
+    }
+
+    // In kbit/s
+    pub fn bitrate(&self) -> usize {
+        self.bitrate.0
+    }
+
+    // The bitrate isn't in the header but has to be measured from the frame spacing
+    pub fn is_free_format(&self) -> bool {
+        self.raw[2] >> 4 == 0
+    }
+
+    // In bytes per second, as consumed by a player at constant bitrate
+    pub fn byte_rate(&self) -> usize {
+        self.bitrate() * 1000 / 8
+    }
+
+    // In Hz
+    pub fn sample_rate(&self) -> usize {
+        self.frequency.0
+    }
+
+    // Index of the sample rate in the Layer III scalefactor band tables
+    pub(crate) fn sample_rate_index(&self) -> usize {
+        SFB_SAMPLE_RATES
+            .iter()
+            .position(|rate| *rate == self.sample_rate())
+            .unwrap()
+    }
+
+    pub fn has_padding(&self) -> bool {
+        self.padding.any()
+    }
+
+    // Application specific, ignored by decoders
+    pub fn private_bit(&self) -> bool {
+        self.private_bit.any()
+    }
+
+    pub fn emphasis(&self) -> Emphasis {
+        self.emphasis
+    }
+
+    pub fn copyright(&self) -> Copyright {
+        self.copyright_bit
+    }
+
+    pub fn home(&self) -> Home {
+        self.home
+    }
+
+    // Layers I and II, first subband whose samples both channels share, each keeping its own
+    // scalefactors. Only joint stereo shares any, and never above `sblimit`, the number of
+    // subbands the frame carries.
+    pub fn joint_stereo_bound(&self, sblimit: usize) -> usize {
+        match self.mode {
+            Mode::JointStereo => self.mode_extension.bound().min(sblimit),
+            _ => sblimit,
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        match self.mode {
+            Mode::SingleChannel => 1,
+            _ => 2,
+        }
+    }
+
+    pub fn samples_per_frame(&self) -> usize {
+        match (self.layer, self.id) {
+            (Layer::One, _) => 384,
+            (Layer::Three, MPEG_Version::Two | MPEG_Version::TwoPointFive) => 576,
+            _ => 1152,
+        }
+    }
+
+    // In bytes, the unit of the frame length and the size of the padding. Layer I counts in 4
+    // bytes slots, the others in single bytes.
+    pub fn slot_size(&self) -> usize {
+        match self.layer {
+            Layer::One => 4,
+            _ => 1,
+        }
+    }
+
+    // Total size of the frame in bytes, header included. Counted in slots, rounded down, so
+    // Layer I frames are (12 * bitrate / sample rate + padding) * 4 bytes and their padding adds
+    // 4 bytes, against 144 * bitrate / sample rate + padding bytes for MPEG-1 Layers II and III.
+    pub fn frame_length(&self) -> usize {
+        let (bytes, slot_bytes) = self.exact_length();
+        (bytes / slot_bytes + self.has_padding() as usize) * self.slot_size()
+    }
+
+    // How many of `frames` frames in a row an encoder keeping the exact bitrate pads: the slot
+    // fractions `frame_length` drops add up to a padding slot every so often, give or take the one
+    // the encoder's accumulator starts on. 0..=0 when the bitrate needs no padding.
+    pub fn padded_frames(&self, frames: usize) -> RangeInclusive<usize> {
+        let (bytes, slot_bytes) = self.exact_length();
+        let remainder = bytes % slot_bytes;
+        let padded = (frames as u128 * remainder as u128 / slot_bytes as u128) as usize;
+
+        padded..=padded + (remainder != 0) as usize
+    }
+
+    // Length of a frame at the exact bitrate, as the fraction `bytes / slot_bytes` of slots
+    fn exact_length(&self) -> (usize, usize) {
+        let bytes = self.samples_per_frame() / 8 * self.bitrate() * 1000;
+        (bytes, self.sample_rate() * self.slot_size())
+    }
+
+    // Whether `length` bytes, e.g. measured between two headers, can be a frame sharing this one's
+    // version, layer, bitrate and sample rate: its length without padding or with one slot of it
+    pub fn is_valid_frame_length(&self, length: usize) -> bool {
+        let padding = self.has_padding() as usize * self.slot_size();
+        let unpadded = self.frame_length() - padding;
+
+        length == unpadded || length == unpadded + self.slot_size()
+    }
+
+    // Size of the Layer III side information that follows the header (and CRC)
+    pub fn side_info_length(&self) -> usize {
+        match (self.id, self.mode) {
+            (MPEG_Version::One, Mode::SingleChannel) => 17,
+            (MPEG_Version::One, _) => 32,
+            (_, Mode::SingleChannel) => 9,
+            (_, _) => 17,
+        }
+    }
+
+    // Whether frames with both headers can follow each other in one stream. The bitrate can vary
+    // (VBR) and so can the stereo mode between stereo, joint stereo and dual channel, as long as
+    // the channel count is kept.
+    pub fn is_compatible_with(&self, other: &Header) -> bool {
+        self.id == other.id
+            && self.layer == other.layer
+            && self.sample_rate() == other.sample_rate()
+            && self.channels() == other.channels()
+    }
+
+    // Coded bits per output sample and channel, to compare compression across sample rates and
+    // channel counts: about 1.45 for 128 kbit/s 44100 Hz stereo, against 16 for CD audio. Free
+    // format headers need their measured bitrate, see FreeFormatContext.
+    pub fn bits_per_sample(&self) -> f64 {
+        (self.bitrate() * 1000) as f64 / (self.sample_rate() * self.channels()) as f64
+    }
+
+    // About 38.28 for 44100 Hz Layer III
+    pub fn frames_per_second(&self) -> f64 {
+        self.sample_rate() as f64 / self.samples_per_frame() as f64
+    }
+
+    pub fn duration(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f64(
+            self.samples_per_frame() as f64 / self.sample_rate() as f64,
+        )
+    }
+}
+
+impl MPEG_Version {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MPEG_Version::Reserved => "reserved",
+            MPEG_Version::One => "MPEG-1",
+            MPEG_Version::Two => "MPEG-2",
+            MPEG_Version::TwoPointFive => "MPEG-2.5",
+        }
+    }
+
+    // In Hz, in the order of the frequency field values, empty for the reserved version
+    pub fn supported_sample_rates(&self) -> &'static [usize] {
+        match self {
+            MPEG_Version::Reserved => &[],
+            MPEG_Version::One => &MPEG1_SAMPLE_RATES,
+            MPEG_Version::Two => &MPEG2_SAMPLE_RATES,
+            MPEG_Version::TwoPointFive => &MPEG25_SAMPLE_RATES,
+        }
+    }
+}
+
+impl Layer {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::Reserved => "reserved",
+            Layer::One => "Layer I",
+            Layer::Two => "Layer II",
+            Layer::Three => "Layer III",
+        }
+    }
+}
+
+impl From<BitVec<u8, Msb0>> for MPEG_Version {
+    fn from(bits: BitVec<u8, Msb0>) -> MPEG_Version {
         match bits.len() {
-            1 => match bits.into_vec()[..] {
+            1 => match to_bits(bits)[..] {
                 [0] => MPEG_Version::Two,
                 [1] => MPEG_Version::One,
                 _ => unreachable!(),
             },
-            2 => match bits.into_vec()[..] {
+            2 => match to_bits(bits)[..] {
                 [0, 0] => MPEG_Version::TwoPointFive,
-                [0, 1] => panic!("reserved"),
+                [0, 1] => MPEG_Version::Reserved,
                 [1, 0] => MPEG_Version::Two,
                 [1, 1] => MPEG_Version::One,
                 _ => unreachable!(),
@@ -175,11 +657,11 @@ impl From<BitVec<u8>> for MPEG_Version {
     }
 }
 
-impl From<BitVec<u8>> for Layer {
-    fn from(bits: BitVec<u8>) -> Layer {
+impl From<BitVec<u8, Msb0>> for Layer {
+    fn from(bits: BitVec<u8, Msb0>) -> Layer {
         assert_eq!(bits.len(), LAYER_SIZE);
 
-        match bits.into_vec()[..] {
+        match to_bits(bits)[..] {
             [0, 0] => panic!("reserved"),
             [0, 1] => Layer::Three,
             [1, 0] => Layer::Two,
@@ -189,148 +671,158 @@ impl From<BitVec<u8>> for Layer {
     }
 }
 
-impl From<BitVec<u8>> for Protected {
-    fn from(bits: BitVec<u8>) -> Protected {
+impl From<BitVec<u8, Msb0>> for Protected {
+    fn from(bits: BitVec<u8, Msb0>) -> Protected {
         assert_eq!(bits.len(), PROTECTION_BIT_SIZE);
 
-        match bits.into_vec()[..] {
-            [0] => Protected::No,
-            [1] => Protected::Yes,
+        match to_bits(bits)[..] {
+            [0] => Protected::Yes,
+            [1] => Protected::No,
             _ => unreachable!(),
         }
     }
 }
 
 impl Bitrate {
-    pub fn from_bitvecu8(bits: BitVec<u8>, version: MPEG_Version, layer: Layer) -> Self {
+    // Whether the bitrate table defines a column for this version and layer
+    fn has_column(version: MPEG_Version, layer: Layer) -> bool {
+        matches!(
+            (version, layer),
+            (
+                MPEG_Version::One | MPEG_Version::Two,
+                Layer::One | Layer::Two | Layer::Three
+            ) | (MPEG_Version::TwoPointFive, Layer::Three)
+        )
+    }
+
+    pub fn from_bitvecu8(bits: BitVec<u8, Msb0>, version: MPEG_Version, layer: Layer) -> Self {
         assert_eq!(bits.len(), BITRATE_SIZE);
-        match bits.into_vec()[..] {
+        match to_bits(bits)[..] {
             [0, 0, 0, 0] => unreachable!(),
             [0, 0, 0, 1] => match (version, layer) {
-                (MPEG_Version::One, Layer::One)
-                | (MPEG_Version::One, Layer::Two)
-                | (MPEG_Version::One, Layer::Three)
-                | (MPEG_Version::Two, Layer::One)
-                | (MPEG_Version::Two, Layer::Two) => Bitrate(32),
-                (MPEG_Version::Two, Layer::Three) => Bitrate(8),
-
+                (MPEG_Version::One, Layer::One) => Bitrate(32),
+                (MPEG_Version::One, Layer::Two) => Bitrate(32),
+                (MPEG_Version::One, Layer::Three) => Bitrate(32),
+                (MPEG_Version::Two, Layer::One) => Bitrate(32),
+                (MPEG_Version::Two, Layer::Two) => Bitrate(8),
+                (MPEG_Version::Two | MPEG_Version::TwoPointFive, Layer::Three) => Bitrate(8),
                 _ => unreachable!(),
             },
             [0, 0, 1, 0] => match (version, layer) {
                 (MPEG_Version::One, Layer::One) => Bitrate(64),
                 (MPEG_Version::One, Layer::Two) => Bitrate(48),
                 (MPEG_Version::One, Layer::Three) => Bitrate(40),
-                (MPEG_Version::Two, Layer::One) => Bitrate(64),
-                (MPEG_Version::Two, Layer::Two) => Bitrate(48),
-                (MPEG_Version::Two, Layer::Three) => Bitrate(16),
+                (MPEG_Version::Two, Layer::One) => Bitrate(48),
+                (MPEG_Version::Two, Layer::Two) => Bitrate(16),
+                (MPEG_Version::Two | MPEG_Version::TwoPointFive, Layer::Three) => Bitrate(16),
                 _ => unreachable!(),
             },
             [0, 0, 1, 1] => match (version, layer) {
                 (MPEG_Version::One, Layer::One) => Bitrate(96),
                 (MPEG_Version::One, Layer::Two) => Bitrate(56),
                 (MPEG_Version::One, Layer::Three) => Bitrate(48),
-                (MPEG_Version::Two, Layer::One) => Bitrate(96),
-                (MPEG_Version::Two, Layer::Two) => Bitrate(56),
-                (MPEG_Version::Two, Layer::Three) => Bitrate(24),
+                (MPEG_Version::Two, Layer::One) => Bitrate(56),
+                (MPEG_Version::Two, Layer::Two) => Bitrate(24),
+                (MPEG_Version::Two | MPEG_Version::TwoPointFive, Layer::Three) => Bitrate(24),
                 _ => unreachable!(),
             },
             [0, 1, 0, 0] => match (version, layer) {
                 (MPEG_Version::One, Layer::One) => Bitrate(128),
                 (MPEG_Version::One, Layer::Two) => Bitrate(64),
                 (MPEG_Version::One, Layer::Three) => Bitrate(56),
-                (MPEG_Version::Two, Layer::One) => Bitrate(128),
-                (MPEG_Version::Two, Layer::Two) => Bitrate(64),
-                (MPEG_Version::Two, Layer::Three) => Bitrate(32),
+                (MPEG_Version::Two, Layer::One) => Bitrate(64),
+                (MPEG_Version::Two, Layer::Two) => Bitrate(32),
+                (MPEG_Version::Two | MPEG_Version::TwoPointFive, Layer::Three) => Bitrate(32),
                 _ => unreachable!(),
             },
             [0, 1, 0, 1] => match (version, layer) {
                 (MPEG_Version::One, Layer::One) => Bitrate(160),
                 (MPEG_Version::One, Layer::Two) => Bitrate(80),
                 (MPEG_Version::One, Layer::Three) => Bitrate(64),
-                (MPEG_Version::Two, Layer::One) => Bitrate(160),
-                (MPEG_Version::Two, Layer::Two) => Bitrate(80),
-                (MPEG_Version::Two, Layer::Three) => Bitrate(64),
+                (MPEG_Version::Two, Layer::One) => Bitrate(80),
+                (MPEG_Version::Two, Layer::Two) => Bitrate(40),
+                (MPEG_Version::Two | MPEG_Version::TwoPointFive, Layer::Three) => Bitrate(40),
                 _ => unreachable!(),
             },
             [0, 1, 1, 0] => match (version, layer) {
                 (MPEG_Version::One, Layer::One) => Bitrate(192),
                 (MPEG_Version::One, Layer::Two) => Bitrate(96),
                 (MPEG_Version::One, Layer::Three) => Bitrate(80),
-                (MPEG_Version::Two, Layer::One) => Bitrate(192),
-                (MPEG_Version::Two, Layer::Two) => Bitrate(96),
-                (MPEG_Version::Two, Layer::Three) => Bitrate(80),
+                (MPEG_Version::Two, Layer::One) => Bitrate(96),
+                (MPEG_Version::Two, Layer::Two) => Bitrate(48),
+                (MPEG_Version::Two | MPEG_Version::TwoPointFive, Layer::Three) => Bitrate(48),
                 _ => unreachable!(),
             },
             [0, 1, 1, 1] => match (version, layer) {
                 (MPEG_Version::One, Layer::One) => Bitrate(224),
                 (MPEG_Version::One, Layer::Two) => Bitrate(112),
                 (MPEG_Version::One, Layer::Three) => Bitrate(96),
-                (MPEG_Version::Two, Layer::One) => Bitrate(224),
-                (MPEG_Version::Two, Layer::Two) => Bitrate(112),
-                (MPEG_Version::Two, Layer::Three) => Bitrate(56),
+                (MPEG_Version::Two, Layer::One) => Bitrate(112),
+                (MPEG_Version::Two, Layer::Two) => Bitrate(56),
+                (MPEG_Version::Two | MPEG_Version::TwoPointFive, Layer::Three) => Bitrate(56),
                 _ => unreachable!(),
             },
             [1, 0, 0, 0] => match (version, layer) {
                 (MPEG_Version::One, Layer::One) => Bitrate(256),
                 (MPEG_Version::One, Layer::Two) => Bitrate(128),
                 (MPEG_Version::One, Layer::Three) => Bitrate(112),
-                (MPEG_Version::Two, Layer::One) => Bitrate(256),
-                (MPEG_Version::Two, Layer::Two) => Bitrate(128),
-                (MPEG_Version::Two, Layer::Three) => Bitrate(64),
+                (MPEG_Version::Two, Layer::One) => Bitrate(128),
+                (MPEG_Version::Two, Layer::Two) => Bitrate(64),
+                (MPEG_Version::Two | MPEG_Version::TwoPointFive, Layer::Three) => Bitrate(64),
                 _ => unreachable!(),
             },
             [1, 0, 0, 1] => match (version, layer) {
                 (MPEG_Version::One, Layer::One) => Bitrate(288),
                 (MPEG_Version::One, Layer::Two) => Bitrate(160),
                 (MPEG_Version::One, Layer::Three) => Bitrate(128),
-                (MPEG_Version::Two, Layer::One) => Bitrate(288),
-                (MPEG_Version::Two, Layer::Two) => Bitrate(160),
-                (MPEG_Version::Two, Layer::Three) => Bitrate(128),
+                (MPEG_Version::Two, Layer::One) => Bitrate(144),
+                (MPEG_Version::Two, Layer::Two) => Bitrate(80),
+                (MPEG_Version::Two | MPEG_Version::TwoPointFive, Layer::Three) => Bitrate(80),
                 _ => unreachable!(),
             },
             [1, 0, 1, 0] => match (version, layer) {
                 (MPEG_Version::One, Layer::One) => Bitrate(320),
                 (MPEG_Version::One, Layer::Two) => Bitrate(192),
                 (MPEG_Version::One, Layer::Three) => Bitrate(160),
-                (MPEG_Version::Two, Layer::One) => Bitrate(320),
-                (MPEG_Version::Two, Layer::Two) => Bitrate(192),
-                (MPEG_Version::Two, Layer::Three) => Bitrate(160),
+                (MPEG_Version::Two, Layer::One) => Bitrate(160),
+                (MPEG_Version::Two, Layer::Two) => Bitrate(96),
+                (MPEG_Version::Two | MPEG_Version::TwoPointFive, Layer::Three) => Bitrate(96),
                 _ => unreachable!(),
             },
             [1, 0, 1, 1] => match (version, layer) {
                 (MPEG_Version::One, Layer::One) => Bitrate(352),
                 (MPEG_Version::One, Layer::Two) => Bitrate(224),
                 (MPEG_Version::One, Layer::Three) => Bitrate(192),
-                (MPEG_Version::Two, Layer::One) => Bitrate(352),
-                (MPEG_Version::Two, Layer::Two) => Bitrate(224),
-                (MPEG_Version::Two, Layer::Three) => Bitrate(112),
+                (MPEG_Version::Two, Layer::One) => Bitrate(176),
+                (MPEG_Version::Two, Layer::Two) => Bitrate(112),
+                (MPEG_Version::Two | MPEG_Version::TwoPointFive, Layer::Three) => Bitrate(112),
                 _ => unreachable!(),
             },
             [1, 1, 0, 0] => match (version, layer) {
                 (MPEG_Version::One, Layer::One) => Bitrate(384),
                 (MPEG_Version::One, Layer::Two) => Bitrate(256),
                 (MPEG_Version::One, Layer::Three) => Bitrate(224),
-                (MPEG_Version::Two, Layer::One) => Bitrate(384),
-                (MPEG_Version::Two, Layer::Two) => Bitrate(256),
-                (MPEG_Version::Two, Layer::Three) => Bitrate(128),
+                (MPEG_Version::Two, Layer::One) => Bitrate(192),
+                (MPEG_Version::Two, Layer::Two) => Bitrate(128),
+                (MPEG_Version::Two | MPEG_Version::TwoPointFive, Layer::Three) => Bitrate(128),
                 _ => unreachable!(),
             },
             [1, 1, 0, 1] => match (version, layer) {
                 (MPEG_Version::One, Layer::One) => Bitrate(416),
                 (MPEG_Version::One, Layer::Two) => Bitrate(320),
                 (MPEG_Version::One, Layer::Three) => Bitrate(256),
-                (MPEG_Version::Two, Layer::One) => Bitrate(416),
-                (MPEG_Version::Two, Layer::Two) => Bitrate(320),
-                (MPEG_Version::Two, Layer::Three) => Bitrate(256),
+                (MPEG_Version::Two, Layer::One) => Bitrate(224),
+                (MPEG_Version::Two, Layer::Two) => Bitrate(144),
+                (MPEG_Version::Two | MPEG_Version::TwoPointFive, Layer::Three) => Bitrate(144),
                 _ => unreachable!(),
             },
             [1, 1, 1, 0] => match (version, layer) {
                 (MPEG_Version::One, Layer::One) => Bitrate(448),
                 (MPEG_Version::One, Layer::Two) => Bitrate(384),
                 (MPEG_Version::One, Layer::Three) => Bitrate(320),
-                (MPEG_Version::Two, Layer::One) => Bitrate(448),
-                (MPEG_Version::Two, Layer::Two) => Bitrate(384),
-                (MPEG_Version::Two, Layer::Three) => Bitrate(320),
+                (MPEG_Version::Two, Layer::One) => Bitrate(256),
+                (MPEG_Version::Two, Layer::Two) => Bitrate(160),
+                (MPEG_Version::Two | MPEG_Version::TwoPointFive, Layer::Three) => Bitrate(160),
                 _ => unreachable!(),
             },
             [1, 1, 1, 1] => unreachable!(),
@@ -341,41 +833,31 @@ impl Bitrate {
 }
 
 impl Frequency {
-    pub fn from_bitvecu8(bits: BitVec<u8>, version: MPEG_Version) -> Self {
+    // Looks the field value up in `MPEG_Version::supported_sample_rates`. Errors on the reserved
+    // version and the reserved frequency index instead of guessing.
+    pub fn from_bitvecu8(
+        bits: BitVec<u8, Msb0>,
+        version: MPEG_Version,
+    ) -> Result<Self, ChompError> {
         assert_eq!(bits.len(), FREQUENCY_SIZE);
 
-        match bits.into_vec()[..] {
-            [0, 0] => match version {
-                MPEG_Version::One => Frequency(44100),
-                MPEG_Version::Two => Frequency(22050),
-                MPEG_Version::TwoPointFive => Frequency(11025),
-                _ => unreachable!(),
-            },
-            [0, 1] => match version {
-                MPEG_Version::One => Frequency(48000),
-                MPEG_Version::Two => Frequency(24000),
-                MPEG_Version::TwoPointFive => Frequency(12000),
-                _ => unreachable!(),
-            },
-            [1, 0] => match version {
-                MPEG_Version::One => Frequency(32000),
-                MPEG_Version::Two => Frequency(16000),
-                MPEG_Version::TwoPointFive => Frequency(8000),
-                _ => unreachable!(),
-            },
-            [1, 1] => {
-                panic!("reserved")
-            }
-            _ => unreachable!(),
+        if version == MPEG_Version::Reserved {
+            return Err(ChompError::ReservedVersion);
+        }
+
+        let index = bits.load_be::<usize>();
+        match version.supported_sample_rates().get(index) {
+            Some(sample_rate) => Ok(Frequency(*sample_rate)),
+            None => Err(ChompError::Reserved("frequency")),
         }
     }
 }
 
-impl From<BitVec<u8>> for Mode {
-    fn from(bits: BitVec<u8>) -> Mode {
+impl From<BitVec<u8, Msb0>> for Mode {
+    fn from(bits: BitVec<u8, Msb0>) -> Mode {
         assert_eq!(bits.len(), MODE_SIZE);
 
-        match bits.into_vec()[..] {
+        match to_bits(bits)[..] {
             [0, 0] => Mode::Stereo,
             [0, 1] => Mode::JointStereo,
             [1, 0] => Mode::DualChannel,
@@ -385,11 +867,36 @@ impl From<BitVec<u8>> for Mode {
     }
 }
 
-impl From<BitVec<u8>> for Copyright {
-    fn from(bits: BitVec<u8>) -> Copyright {
+impl ModeExtension {
+    // Layers I and II, first subband shared by both channels
+    pub fn bound(&self) -> usize {
+        (self.0 as usize + 1) * 4
+    }
+
+    // Layer III
+    pub fn intensity_stereo(&self) -> bool {
+        self.0 & 0b01 != 0
+    }
+
+    // Layer III
+    pub fn ms_stereo(&self) -> bool {
+        self.0 & 0b10 != 0
+    }
+}
+
+impl From<BitVec<u8, Msb0>> for ModeExtension {
+    fn from(bits: BitVec<u8, Msb0>) -> ModeExtension {
+        assert_eq!(bits.len(), MODE_EXTENSION_SIZE);
+
+        ModeExtension(bits.load_be::<u8>())
+    }
+}
+
+impl From<BitVec<u8, Msb0>> for Copyright {
+    fn from(bits: BitVec<u8, Msb0>) -> Copyright {
         assert_eq!(bits.len(), COPYRIGHT_BIT_SIZE);
 
-        match bits.into_vec()[..] {
+        match to_bits(bits)[..] {
             [0] => Copyright::Off,
             [1] => Copyright::On,
 
@@ -398,11 +905,11 @@ impl From<BitVec<u8>> for Copyright {
     }
 }
 
-impl From<BitVec<u8>> for Home {
-    fn from(bits: BitVec<u8>) -> Home {
+impl From<BitVec<u8, Msb0>> for Home {
+    fn from(bits: BitVec<u8, Msb0>) -> Home {
         assert_eq!(bits.len(), HOME_SIZE);
 
-        match bits.into_vec()[..] {
+        match to_bits(bits)[..] {
             [0] => Home::Off,
             [1] => Home::On,
 
@@ -410,15 +917,558 @@ impl From<BitVec<u8>> for Home {
         }
     }
 }
-impl From<BitVec<u8>> for Emphasis {
-    fn from(bits: BitVec<u8>) -> Emphasis {
+impl SyncWord {
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+}
+
+impl TryFrom<BitVec<u8, Msb0>> for SyncWord {
+    type Error = ChompError;
+
+    fn try_from(bits: BitVec<u8, Msb0>) -> Result<SyncWord, ChompError> {
+        assert_eq!(bits.len(), SYNC_SIZE);
+
+        if !bits.all() {
+            return Err(ChompError::NoSync);
+        }
+        Ok(SyncWord(bits.load_be::<u16>()))
+    }
+}
+
+impl TryFrom<BitVec<u8, Msb0>> for Emphasis {
+    type Error = ChompError;
+
+    fn try_from(bits: BitVec<u8, Msb0>) -> Result<Emphasis, ChompError> {
         assert_eq!(bits.len(), EMPHASIS_SIZE);
 
-        match bits.into_vec()[..] {
-            [0] => Emphasis::Off,
-            [1] => Emphasis::On,
+        match to_bits(bits)[..] {
+            [0, 0] => Ok(Emphasis::None),
+            [0, 1] => Ok(Emphasis::FiftyFifteen),
+            [1, 0] => Err(ChompError::Reserved("emphasis")),
+            [1, 1] => Ok(Emphasis::CcittJ17),
 
             _ => unreachable!(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        frame::FrameIterator,
+        test_utils::{frames, HEADER_128, HEADER_192},
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn crc_is_left_out_of_equality() {
+        // MPEG-1 Layer III, protected, 128 kbit/s, 44100 Hz
+        let a = Header::from_bytes(&[0xFF, 0xFA, 0x90, 0x00, 0x12, 0x34]).unwrap();
+        let b = Header::from_bytes(&[0xFF, 0xFA, 0x90, 0x00, 0xAB, 0xCD]).unwrap();
+        assert_eq!(a.crc(), Some(0x1234));
+        assert_eq!(b.crc(), Some(0xABCD));
+        assert_eq!(a, b);
+
+        let mut counts = HashMap::new();
+        for header in [a, b] {
+            *counts.entry(header).or_insert(0) += 1;
+        }
+        assert_eq!(counts.len(), 1);
+
+        // Padded
+        let c = Header::from_bytes(&[0xFF, 0xFA, 0x92, 0x00, 0x12, 0x34]).unwrap();
+        assert!(!counts.contains_key(&c));
+    }
+
+    #[test]
+    fn emphasis_patterns() {
+        let emphasis = |bits: u8| Header::from_bytes(&[0xFF, 0xFB, 0x90, 0x40 | bits]);
+
+        assert_eq!(emphasis(0b00).unwrap().emphasis(), Emphasis::None);
+        assert_eq!(emphasis(0b01).unwrap().emphasis(), Emphasis::FiftyFifteen);
+        assert!(matches!(
+            emphasis(0b10),
+            Err(ChompError::Reserved("emphasis"))
+        ));
+        assert_eq!(emphasis(0b11).unwrap().emphasis(), Emphasis::CcittJ17);
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(MPEG_Version::One.name(), "MPEG-1");
+        assert_eq!(MPEG_Version::Two.name(), "MPEG-2");
+        assert_eq!(MPEG_Version::TwoPointFive.name(), "MPEG-2.5");
+        assert_eq!(MPEG_Version::Reserved.name(), "reserved");
+
+        assert_eq!(Layer::One.name(), "Layer I");
+        assert_eq!(Layer::Two.name(), "Layer II");
+        assert_eq!(Layer::Three.name(), "Layer III");
+        assert_eq!(Layer::Reserved.name(), "reserved");
+    }
+
+    #[test]
+    fn mpeg25_layer1_and_layer2() {
+        // MPEG-2.5 only defines Layer III
+        for (byte, layer) in [(0xE6, Layer::One), (0xE4, Layer::Two)] {
+            let error = Header::from_bytes(&[0xFF, byte, 0x98, 0x00]).unwrap_err();
+            assert!(matches!(
+                error,
+                ChompError::UnsupportedVersionLayer {
+                    version: MPEG_Version::TwoPointFive,
+                    layer: l,
+                } if l == layer
+            ));
+            assert_eq!(
+                error.to_string(),
+                format!("MPEG-2.5 {} is not supported", layer.name())
+            );
+        }
+        assert!(Header::from_bytes(&[0xFF, 0xE2, 0x98, 0x00]).is_ok());
+    }
+
+    #[test]
+    fn sync_modes() {
+        // MPEG-2.5, the 12th sync bit clear
+        let bytes = [0xFF, 0xE3, 0x48, 0x40];
+        assert!(matches!(
+            Header::from_bytes_with_sync(&bytes, SyncMode::Strict12),
+            Err(ChompError::NoSync)
+        ));
+        let header = Header::from_bytes_with_sync(&bytes, SyncMode::Lenient11).unwrap();
+        assert_eq!(header.version(), MPEG_Version::TwoPointFive);
+        assert_eq!(header, Header::from_bytes(&bytes).unwrap());
+
+        for sync in [SyncMode::Strict12, SyncMode::Lenient11] {
+            assert!(Header::from_bytes_with_sync(&HEADER_128, sync).is_ok());
+            assert!(matches!(
+                Header::from_bytes_with_sync(&[0xFF, 0xC3, 0x48, 0x40], sync),
+                Err(ChompError::NoSync)
+            ));
+        }
+
+        let data = include_bytes!("../fixtures/mpeg25.mp3");
+        let frames = |sync| FrameIterator::new(data).with_sync_mode(sync).flatten();
+        assert_eq!(frames(SyncMode::Strict12).count(), 0);
+        assert_eq!(frames(SyncMode::Lenient11).count(), 10);
+    }
+
+    #[test]
+    fn byte_rates() {
+        assert_eq!(Header::from_bytes(&HEADER_128).unwrap().byte_rate(), 16000);
+        assert_eq!(Header::from_bytes(&HEADER_192).unwrap().byte_rate(), 24000);
+        // MPEG-2.5 Layer III, 8 kbit/s
+        let header = Header::from_bytes(&[0xFF, 0xE3, 0x18, 0x40]).unwrap();
+        assert_eq!(header.byte_rate(), 1000);
+    }
+
+    #[test]
+    fn private_bit() {
+        assert!(!Header::from_bytes(&HEADER_128).unwrap().private_bit());
+        assert!(Header::from_bytes(&[0xFF, 0xFB, 0x91, 0x40])
+            .unwrap()
+            .private_bit());
+        // Next to the padding bit
+        let header = Header::from_bytes(&[0xFF, 0xFB, 0x93, 0x40]).unwrap();
+        assert!(header.private_bit() && header.has_padding());
+    }
+
+    #[test]
+    fn short_buffers() {
+        for length in 0..4 {
+            let bytes = &HEADER_128[..length];
+            assert!(matches!(
+                Header::from_bytes(bytes),
+                Err(ChompError::TooShort)
+            ));
+            assert!(matches!(
+                RawHeader::new(bytes.view_bits()),
+                Err(ChompError::TooShort)
+            ));
+            assert!(matches!(
+                RawHeaderRef::new(bytes.view_bits()),
+                Err(ChompError::TooShort)
+            ));
+        }
+
+        // 31 bits
+        let bits = &HEADER_128.view_bits::<Msb0>()[..HEADER_SIZE - 1];
+        assert!(matches!(RawHeader::new(bits), Err(ChompError::TooShort)));
+        assert!(RawHeader::new(HEADER_128.view_bits()).is_ok());
+    }
+
+    #[test]
+    fn raw_bytes_as_parsed() {
+        for data in [
+            &include_bytes!("../fixtures/cbr.mp3")[..],
+            include_bytes!("../fixtures/crc.mp3"),
+            include_bytes!("../fixtures/layer2.mp2"),
+        ] {
+            for frame in FrameIterator::new(data).flatten() {
+                assert_eq!(frame.header.raw_bytes(), frame.data[..4]);
+            }
+        }
+
+        // Private, copyright, original and emphasis bits, and trailing bytes
+        let bytes = [0xFF, 0xFA, 0x93, 0x4D, 0x12, 0x34];
+        assert_eq!(Header::from_bytes(&bytes).unwrap().raw_bytes(), bytes[..4]);
+    }
+
+    #[test]
+    fn borrowed_view_matches_owned_fields() {
+        // Spread over every value of the last 3 bytes, one header in 997
+        for i in (0..1 << 24).step_by(997) {
+            let bytes = (0xFF00_0000u32 | i).to_be_bytes();
+            let bits = bytes.view_bits::<Msb0>();
+            let view = RawHeaderRef::new(bits).unwrap();
+            let owned = RawHeader::new(bits).unwrap();
+
+            assert_eq!(view.sync, owned.sync);
+            assert_eq!(view.id, owned.id);
+            assert_eq!(view.layer, owned.layer);
+            assert_eq!(view.protection_bit, owned.protection_bit);
+            assert_eq!(view.bitrate, owned.bitrate);
+            assert_eq!(view.frequency, owned.frequency);
+            assert_eq!(view.padding_bit, owned.padding_bit);
+            assert_eq!(view.private_bit, owned.private_bit);
+            assert_eq!(view.mode, owned.mode);
+            assert_eq!(view.mode_extension, owned.mode_extension);
+            assert_eq!(view.copyright_bit, owned.copyright_bit);
+            assert_eq!(view.home, owned.home);
+            assert_eq!(view.emphasis, owned.emphasis);
+
+            let converted = RawHeader::from(view);
+            assert_eq!(converted.bitrate, owned.bitrate);
+            assert_eq!(converted.emphasis, owned.emphasis);
+        }
+
+        assert!(matches!(
+            RawHeaderRef::new([0xFF, 0xFB, 0x90].view_bits::<Msb0>()),
+            Err(ChompError::TooShort)
+        ));
+    }
+
+    #[test]
+    fn layer2_bitrate_and_mode_combinations() {
+        // MPEG-1 Layer II, 44100 Hz
+        let layer2 =
+            |index: u8, mode: u8| Header::from_bytes(&[0xFF, 0xFD, index << 4, mode << 6]).unwrap();
+
+        // 224 kbit/s single channel
+        assert!(matches!(
+            layer2(11, 3).validate_strict(),
+            Err(ChompError::IllegalCombination(_))
+        ));
+        // 48 kbit/s stereo
+        assert!(matches!(
+            layer2(2, 0).validate_strict(),
+            Err(ChompError::IllegalCombination(_))
+        ));
+
+        for index in 1..15 {
+            let bitrate = layer2(index, 0).bitrate();
+            let stereo_allowed = ![32, 48, 56, 80].contains(&bitrate);
+            let single_channel_allowed = ![224, 256, 320, 384].contains(&bitrate);
+
+            // Stereo, joint stereo and dual channel are restricted alike
+            for mode in 0..3 {
+                assert_eq!(
+                    layer2(index, mode).validate_strict().is_ok(),
+                    stereo_allowed
+                );
+            }
+            assert_eq!(
+                layer2(index, 3).validate_strict().is_ok(),
+                single_channel_allowed
+            );
+        }
+
+        // No restriction on the other layers
+        let layer3 = Header::from_bytes(&[0xFF, 0xFB, 0x10, 0x00]).unwrap();
+        assert_eq!(layer3.bitrate(), 32);
+        assert!(layer3.validate_strict().is_ok());
+    }
+
+    #[test]
+    fn headers_as_keys() {
+        use crate::frame::FrameIterator;
+        use std::collections::HashSet;
+
+        let a = Header::from_bytes(&[0xFF, 0xFB, 0x90, 0x40]).unwrap();
+        let b = Header::from_bytes(&[0xFF, 0xFB, 0x90, 0x40, 0x12]).unwrap();
+        assert_eq!(HashSet::from([a.clone(), b]).len(), 1);
+        // Padded
+        let c = Header::from_bytes(&[0xFF, 0xFB, 0x92, 0x40]).unwrap();
+        assert_eq!(HashSet::from([a, c]).len(), 2);
+
+        // Frequency of each configuration of a VBR stream
+        let data = include_bytes!("../fixtures/vbr.mp3");
+        let mut counts = HashMap::new();
+        let mut bitrates = HashMap::new();
+        for frame in FrameIterator::new(data).flatten() {
+            *bitrates.entry(frame.header.bitrate()).or_insert(0) += 1;
+            *counts.entry(frame.header).or_insert(0) += 1;
+        }
+        assert!(counts.len() > 1);
+        assert!(counts.len() >= bitrates.len());
+        assert_eq!(counts.values().sum::<usize>(), 22);
+
+        let modes = HashSet::from([Mode::Stereo, Mode::JointStereo, Mode::Stereo]);
+        assert_eq!(modes.len(), 2);
+        let versions = HashSet::from([MPEG_Version::One, MPEG_Version::Two, MPEG_Version::One]);
+        assert_eq!(versions.len(), 2);
+    }
+
+    #[test]
+    fn compatible_headers() {
+        let header = Header::from_bytes(&HEADER_128).unwrap();
+        assert!(header.is_compatible_with(&Header::from_bytes(&HEADER_192).unwrap()));
+        // Stereo instead of joint stereo, padded
+        assert!(header.is_compatible_with(&Header::from_bytes(&[0xFF, 0xFB, 0x92, 0x00]).unwrap()));
+
+        for bytes in [
+            // 48000 Hz
+            [0xFF, 0xFB, 0x94, 0x40],
+            // Mono
+            [0xFF, 0xFB, 0x90, 0xC0],
+            // MPEG-2 at 22050 Hz, Layer II
+            [0xFF, 0xF3, 0x90, 0x40],
+            [0xFF, 0xFD, 0x90, 0x40],
+        ] {
+            let other = Header::from_bytes(&bytes).unwrap();
+            assert!(!header.is_compatible_with(&other));
+            assert!(!other.is_compatible_with(&header));
+        }
+    }
+
+    #[test]
+    fn joint_stereo_bounds() {
+        // Layer II joint stereo, clamped to the sblimit of table C
+        for (extension, bound) in [(0, 4), (1, 8), (2, 12), (3, 16)] {
+            let header = Header::from_bytes(&[0xFF, 0xFD, 0x60, 0x40 | extension << 4]).unwrap();
+            assert_eq!(header.joint_stereo_bound(32), bound);
+            assert_eq!(header.joint_stereo_bound(30), bound);
+            assert_eq!(header.joint_stereo_bound(8), bound.min(8));
+        }
+
+        // Stereo shares nothing
+        let header = Header::from_bytes(&[0xFF, 0xFD, 0x60, 0x30]).unwrap();
+        assert_eq!(header.joint_stereo_bound(27), 27);
+    }
+
+    #[test]
+    fn sync_word() {
+        let header = Header::from_bytes(&HEADER_128).unwrap();
+        assert_eq!(header.sync.bits(), 0x7FF);
+        assert_eq!(header.sync, Header::from_bytes(&HEADER_192).unwrap().sync);
+
+        let raw = RawHeader::new(HEADER_128.view_bits()).unwrap();
+        assert_eq!(SyncWord::try_from(raw.sync).unwrap(), header.sync);
+
+        // One cleared bit anywhere in the 11
+        for bit in 0..11 {
+            let mut bytes = HEADER_128;
+            bytes[bit / 8] &= !(0x80 >> (bit % 8));
+            let raw = RawHeader::new(bytes.view_bits()).unwrap();
+            assert!(matches!(
+                SyncWord::try_from(raw.sync),
+                Err(ChompError::NoSync)
+            ));
+            assert!(matches!(
+                Header::from_bytes(&bytes),
+                Err(ChompError::NoSync)
+            ));
+        }
+    }
+
+    #[test]
+    fn frames_per_second() {
+        let header = Header::from_bytes(&HEADER_128).unwrap();
+        assert!((header.frames_per_second() - 44100.0 / 1152.0).abs() < 1e-9);
+        assert!((header.frames_per_second() - 38.28).abs() < 0.01);
+
+        // MPEG-2 Layer III at 22050 Hz, 576 samples, and Layer I at 48000 Hz, 384 samples
+        let mpeg2 = Header::from_bytes(&[0xFF, 0xF3, 0x80, 0xC0]).unwrap();
+        assert_eq!(mpeg2.frames_per_second(), 22050.0 / 576.0);
+        let layer1 = Header::from_bytes(&[0xFF, 0xFF, 0x94, 0x40]).unwrap();
+        assert_eq!(layer1.frames_per_second(), 125.0);
+    }
+
+    #[test]
+    fn default_header() {
+        let header = Header::default();
+        assert_eq!(header.version(), MPEG_Version::One);
+        assert_eq!(header.layer(), Layer::Three);
+        assert_eq!(header.bitrate(), 128);
+        assert_eq!(header.sample_rate(), 44100);
+        assert_eq!(header.mode(), Mode::Stereo);
+        assert!(!header.has_padding());
+        assert_eq!(Header::from_bytes(&header.raw_bytes()).unwrap(), header);
+
+        // A stream of zeroed frames carrying it parses back
+        let data = frames(&[header.raw_bytes(); 3]);
+        let parsed: Vec<_> = FrameIterator::new(&data).collect::<Result<_, _>>().unwrap();
+        assert_eq!(parsed.len(), 3);
+        assert!(parsed.iter().all(|frame| frame.header == header));
+        assert_eq!(parsed[2].offset, 2 * 417);
+    }
+
+    #[test]
+    fn stored_crc() {
+        // Protected, CRC bytes supplied or not
+        assert_eq!(
+            Header::from_bytes(&[0xFF, 0xFA, 0x90, 0x40, 0xBE, 0xEF])
+                .unwrap()
+                .crc(),
+            Some(0xBEEF)
+        );
+        assert_eq!(
+            Header::from_bytes(&[0xFF, 0xFA, 0x90, 0x40]).unwrap().crc(),
+            None
+        );
+        // Unprotected, the bytes after the header are audio
+        assert_eq!(
+            Header::from_bytes(&[0xFF, 0xFB, 0x90, 0x40, 0xBE, 0xEF])
+                .unwrap()
+                .crc(),
+            None
+        );
+
+        let data = include_bytes!("../fixtures/crc.mp3");
+        for frame in FrameIterator::new(data).flatten() {
+            let stored = u16::from_be_bytes([frame.data[4], frame.data[5]]);
+            assert_eq!(frame.header.crc(), Some(stored));
+        }
+        for frame in FrameIterator::new(include_bytes!("../fixtures/cbr.mp3")).flatten() {
+            assert_eq!(frame.header.crc(), None);
+        }
+    }
+
+    #[test]
+    fn padded_frames() {
+        // 417.96 bytes a frame at 128 kbit/s 44100 Hz
+        let header = Header::from_bytes(&HEADER_128).unwrap();
+        assert_eq!(header.padded_frames(0), 0..=1);
+        assert_eq!(header.padded_frames(10), 9..=10);
+        assert_eq!(header.padded_frames(25), 23..=24);
+        assert_eq!(
+            header.padded_frames(usize::MAX).start(),
+            &17693815744170386242
+        );
+
+        // Exactly 960 bytes at 320 kbit/s 48000 Hz
+        let header = Header::from_bytes(&[0xFF, 0xFB, 0xE4, 0x40]).unwrap();
+        assert_eq!(header.frame_length(), 960);
+        assert_eq!(header.padded_frames(1000), 0..=0);
+    }
+
+    #[test]
+    fn reserved_version() {
+        // Version bits 01, the other fields valid
+        assert!(matches!(
+            Header::from_bytes(&[0xFF, 0xEB, 0x90, 0x40]),
+            Err(ChompError::ReservedVersion)
+        ));
+        assert_eq!(
+            MPEG_Version::from(bitvec![u8, Msb0; 0, 1]),
+            MPEG_Version::Reserved
+        );
+
+        let frequency = |bits: [u8; 2], version| {
+            let bits: BitVec<u8, Msb0> = bits.iter().map(|bit| *bit == 1).collect();
+            Frequency::from_bitvecu8(bits, version)
+        };
+        for bits in [[0, 0], [0, 1], [1, 0]] {
+            assert!(matches!(
+                frequency(bits, MPEG_Version::Reserved),
+                Err(ChompError::ReservedVersion)
+            ));
+        }
+
+        for (version, rates) in [
+            (MPEG_Version::One, [44100, 48000, 32000]),
+            (MPEG_Version::Two, [22050, 24000, 16000]),
+            (MPEG_Version::TwoPointFive, [11025, 12000, 8000]),
+        ] {
+            for (bits, rate) in [[0, 0], [0, 1], [1, 0]].into_iter().zip(rates) {
+                assert_eq!(frequency(bits, version).unwrap().0, rate);
+            }
+            assert!(matches!(
+                frequency([1, 1], version),
+                Err(ChompError::Reserved("frequency"))
+            ));
+        }
+    }
+
+    #[test]
+    fn supported_sample_rates() {
+        assert_eq!(
+            MPEG_Version::Two.supported_sample_rates(),
+            [22050, 24000, 16000]
+        );
+        assert!(MPEG_Version::Reserved.supported_sample_rates().is_empty());
+
+        // The order of the frequency field, and of the scalefactor band tables
+        let versions = [
+            (MPEG_Version::One, 0xFB),
+            (MPEG_Version::Two, 0xF3),
+            (MPEG_Version::TwoPointFive, 0xE3),
+        ];
+        let all: Vec<_> = versions
+            .iter()
+            .flat_map(|(version, _)| version.supported_sample_rates())
+            .copied()
+            .collect();
+        assert_eq!(all, SFB_SAMPLE_RATES);
+
+        for (version, byte) in versions {
+            for (index, rate) in version.supported_sample_rates().iter().enumerate() {
+                let header = Header::from_bytes(&[0xFF, byte, 0x90 | (index as u8) << 2, 0x40]);
+                assert_eq!(header.unwrap().sample_rate(), *rate);
+            }
+        }
+    }
+
+    #[test]
+    fn layer1_slots() {
+        // MPEG-1 Layer I, 384 kbit/s, 44100 Hz: 104.49 slots of 4 bytes
+        let header = Header::from_bytes(&[0xFF, 0xFF, 0xC0, 0x00]).unwrap();
+        assert_eq!(header.slot_size(), 4);
+        assert_eq!(header.frame_length(), 416);
+        let padded = Header::from_bytes(&[0xFF, 0xFF, 0xC2, 0x00]).unwrap();
+        assert_eq!(padded.frame_length(), 420);
+
+        for header in [header, padded] {
+            assert!(header.is_valid_frame_length(416));
+            assert!(header.is_valid_frame_length(420));
+            assert!(!header.is_valid_frame_length(417));
+        }
+
+        // Single byte slots for Layers II and III
+        let layer3 = Header::from_bytes(&HEADER_128).unwrap();
+        assert_eq!(layer3.frame_length(), 417);
+        assert!(layer3.is_valid_frame_length(418));
+        assert!(!layer3.is_valid_frame_length(421));
+        let layer2 = Header::from_bytes(&[0xFF, 0xFD, 0xA0, 0x40]).unwrap();
+        assert_eq!((layer2.slot_size(), layer2.frame_length()), (1, 626));
+
+        // The fixture frames measured between their headers
+        let data = include_bytes!("../fixtures/layer1.mp1");
+        for frame in FrameIterator::new(data).flatten() {
+            assert!(frame.header.is_valid_frame_length(frame.data.len()));
+        }
+    }
+
+    #[test]
+    fn bits_per_sample() {
+        let bits = |bytes: [u8; 4]| Header::from_bytes(&bytes).unwrap().bits_per_sample();
+
+        // 128 kbit/s, 44100 Hz, joint stereo
+        assert!((bits(HEADER_128) - 1.4512).abs() < 1e-4);
+        // The same bitrate in mono
+        assert!((bits([0xFF, 0xFB, 0x90, 0xC0]) - 2.9025).abs() < 1e-4);
+        // MPEG-2, 64 kbit/s, 16000 Hz, mono
+        assert_eq!(bits([0xFF, 0xF3, 0x88, 0xC0]), 4.0);
+    }
+}
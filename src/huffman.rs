@@ -0,0 +1,846 @@
+use crate::{
+    bit_reader::BitReader,
+    constants::*,
+    error::ChompError,
+    header::{Header, MPEG_Version},
+    side_info::{BlockType, GranuleInfo},
+};
+use std::sync::OnceLock;
+
+// Layer III Huffman code table. Entry `x * size + y` holds the code of the pair (x, y), values
+// of 15 being followed by `linbits` extra bits in the tables that have some.
+struct HuffmanTable {
+    size: usize,
+    linbits: usize,
+    codes: &'static [u16],
+    lengths: &'static [u8],
+}
+
+impl HuffmanTable {
+    // Tables 0, 4 and 14, nothing is read and all values are 0
+    const EMPTY: HuffmanTable = HuffmanTable {
+        size: 0,
+        linbits: 0,
+        codes: &[],
+        lengths: &[],
+    };
+}
+
+// Decoding tree of a table, each node holding its two children. A child with LEAF set is the
+// index of the decoded entry, 0 is a missing branch (the root is never a child).
+type Tree = Vec<[u16; 2]>;
+
+const LEAF: u16 = 0x8000;
+
+fn build_tree(table: &HuffmanTable) -> Tree {
+    let mut tree = vec![[0; 2]];
+
+    for (index, (&code, &length)) in table.codes.iter().zip(table.lengths).enumerate() {
+        let mut node = 0;
+        for bit in (0..length).rev() {
+            let branch = (code as u32 >> bit) as usize & 1;
+
+            if bit == 0 {
+                tree[node][branch] = LEAF | index as u16;
+            } else {
+                if tree[node][branch] == 0 {
+                    tree.push([0; 2]);
+                    tree[node][branch] = (tree.len() - 1) as u16;
+                }
+                node = tree[node][branch] as usize;
+            }
+        }
+    }
+
+    tree
+}
+
+fn trees() -> &'static [Tree] {
+    static TREES: OnceLock<Vec<Tree>> = OnceLock::new();
+
+    TREES.get_or_init(|| HUFFMAN_TABLES.iter().map(build_tree).collect())
+}
+
+// Reads one code of the table, returns the index of its entry
+fn read_code(reader: &mut BitReader, table: usize) -> Result<usize, ChompError> {
+    let tree = &trees()[table];
+    let mut node = 0;
+
+    loop {
+        match tree[node][reader.read_bit()? as usize] {
+            0 => return Err(ChompError::InvalidMainData("unknown huffman code")),
+            child if child & LEAF != 0 => return Ok((child & !LEAF) as usize),
+            child => node = child as usize,
+        }
+    }
+}
+
+// Escape (linbits) then sign of a decoded value
+fn read_value(reader: &mut BitReader, value: usize, linbits: usize) -> Result<i32, ChompError> {
+    let mut value = value as i32;
+
+    if value == 15 && linbits > 0 {
+        value += reader.read_bits(linbits)? as i32;
+    }
+    if value != 0 && reader.read_bit()? {
+        value = -value;
+    }
+
+    Ok(value)
+}
+
+// Decodes one big_values pair with the given table (0 to 31)
+pub fn decode_pair(reader: &mut BitReader, table: usize) -> Result<(i32, i32), ChompError> {
+    let huffman = &HUFFMAN_TABLES[table];
+    if huffman.codes.is_empty() {
+        return Ok((0, 0));
+    }
+
+    let index = read_code(reader, table)?;
+    let x = read_value(reader, index / huffman.size, huffman.linbits)?;
+    let y = read_value(reader, index % huffman.size, huffman.linbits)?;
+
+    Ok((x, y))
+}
+
+// End of region 0 and region 1 of the big_values, in spectral lines. Region 2 goes on up to
+// the end of the big_values.
+fn region_bounds(header: &Header, info: &GranuleInfo) -> (usize, usize) {
+    let rate = header.sample_rate_index();
+    let long = &SFB_LONG[rate];
+
+    if info.window_switching {
+        // Region 0 covers the first 3 short bands, or the long bands the implied region0_count
+        // spans, region 1 the rest. MPEG-2.5 mixed blocks count in long bands, as other decoders
+        // do since no standard covers them.
+        let region1 = match (info.block_type, info.mixed_block, header.version()) {
+            (BlockType::Short, true, MPEG_Version::TwoPointFive) => long[info.region0_count + 1],
+            (BlockType::Short, ..) => SFB_SHORT[rate][3] * 3,
+            _ => long[info.region0_count + 1],
+        };
+        (region1, 576)
+    } else {
+        let region2 = info.region0_count + info.region1_count + 2;
+        (
+            long[info.region0_count + 1],
+            long.get(region2).copied().unwrap_or(576),
+        )
+    }
+}
+
+// Decodes the big_values region of one granule/channel into `samples`, the reader standing
+// right after the scalefactors. `end` is the reader position at which the granule/channel
+// part2_3_length ends, running past it is an error. Returns the number of values decoded.
+pub fn decode_big_values(
+    reader: &mut BitReader,
+    header: &Header,
+    info: &GranuleInfo,
+    end: usize,
+    samples: &mut [i32; 576],
+) -> Result<usize, ChompError> {
+    let count = info.big_values * 2;
+    let (region1, region2) = region_bounds(header, info);
+    let regions = [region1.min(count), region2.min(count), count];
+
+    let mut index = 0;
+    for (region, region_end) in regions.into_iter().enumerate() {
+        let table = info.table_select[region];
+
+        while index < region_end {
+            let (x, y) = decode_pair(reader, table)?;
+            if reader.position() > end {
+                return Err(ChompError::InvalidMainData(
+                    "big_values run past part2_3_length",
+                ));
+            }
+
+            samples[index] = x;
+            samples[index + 1] = y;
+            index += 2;
+        }
+    }
+
+    Ok(count)
+}
+
+// Decodes one count1 quadruple (v, w, x, y) with table A or B
+fn decode_quad(reader: &mut BitReader, table_b: bool) -> Result<[i32; 4], ChompError> {
+    let index = read_code(reader, if table_b { 33 } else { 32 })?;
+
+    let mut quad = [0; 4];
+    for (position, value) in quad.iter_mut().enumerate() {
+        *value = read_value(reader, (index >> (3 - position)) & 1, 0)?;
+    }
+
+    Ok(quad)
+}
+
+// Decodes the count1 region of one granule/channel into `samples`, starting at value `start`
+// (the end of the big_values) and going on until `end` is reached. A quadruple that runs past
+// `end` is discarded. Returns the index past the last decoded value, all the values from there
+// on are 0.
+pub fn decode_count1(
+    reader: &mut BitReader,
+    info: &GranuleInfo,
+    end: usize,
+    start: usize,
+    samples: &mut [i32; 576],
+) -> Result<usize, ChompError> {
+    let mut index = start;
+
+    while index + 4 <= 576 && reader.position() < end {
+        let quad = match decode_quad(reader, info.count1table_select) {
+            Ok(quad) if reader.position() <= end => quad,
+            Ok(_) | Err(ChompError::TooShort) => break,
+            Err(e) => return Err(e),
+        };
+
+        samples[index..index + 4].copy_from_slice(&quad);
+        index += 4;
+    }
+
+    Ok(index)
+}
+
+// Tables 0 to 31 code the big_values pairs, 32 (A) and 33 (B) the count1 quadruples
+static HUFFMAN_TABLES: [HuffmanTable; 34] = [
+    // Table 0
+    HuffmanTable::EMPTY,
+    // Table 1
+    HuffmanTable {
+        size: 2,
+        linbits: 0,
+        codes: &CODES_1,
+        lengths: &LENGTHS_1,
+    },
+    // Table 2
+    HuffmanTable {
+        size: 3,
+        linbits: 0,
+        codes: &CODES_2,
+        lengths: &LENGTHS_2,
+    },
+    // Table 3
+    HuffmanTable {
+        size: 3,
+        linbits: 0,
+        codes: &CODES_3,
+        lengths: &LENGTHS_3,
+    },
+    // Table 4 (undefined)
+    HuffmanTable::EMPTY,
+    // Table 5
+    HuffmanTable {
+        size: 4,
+        linbits: 0,
+        codes: &CODES_5,
+        lengths: &LENGTHS_5,
+    },
+    // Table 6
+    HuffmanTable {
+        size: 4,
+        linbits: 0,
+        codes: &CODES_6,
+        lengths: &LENGTHS_6,
+    },
+    // Table 7
+    HuffmanTable {
+        size: 6,
+        linbits: 0,
+        codes: &CODES_7,
+        lengths: &LENGTHS_7,
+    },
+    // Table 8
+    HuffmanTable {
+        size: 6,
+        linbits: 0,
+        codes: &CODES_8,
+        lengths: &LENGTHS_8,
+    },
+    // Table 9
+    HuffmanTable {
+        size: 6,
+        linbits: 0,
+        codes: &CODES_9,
+        lengths: &LENGTHS_9,
+    },
+    // Table 10
+    HuffmanTable {
+        size: 8,
+        linbits: 0,
+        codes: &CODES_10,
+        lengths: &LENGTHS_10,
+    },
+    // Table 11
+    HuffmanTable {
+        size: 8,
+        linbits: 0,
+        codes: &CODES_11,
+        lengths: &LENGTHS_11,
+    },
+    // Table 12
+    HuffmanTable {
+        size: 8,
+        linbits: 0,
+        codes: &CODES_12,
+        lengths: &LENGTHS_12,
+    },
+    // Table 13
+    HuffmanTable {
+        size: 16,
+        linbits: 0,
+        codes: &CODES_13,
+        lengths: &LENGTHS_13,
+    },
+    // Table 14 (undefined)
+    HuffmanTable::EMPTY,
+    // Table 15
+    HuffmanTable {
+        size: 16,
+        linbits: 0,
+        codes: &CODES_15,
+        lengths: &LENGTHS_15,
+    },
+    // Table 16
+    HuffmanTable {
+        size: 16,
+        linbits: 1,
+        codes: &CODES_16,
+        lengths: &LENGTHS_16,
+    },
+    // Table 17
+    HuffmanTable {
+        size: 16,
+        linbits: 2,
+        codes: &CODES_16,
+        lengths: &LENGTHS_16,
+    },
+    // Table 18
+    HuffmanTable {
+        size: 16,
+        linbits: 3,
+        codes: &CODES_16,
+        lengths: &LENGTHS_16,
+    },
+    // Table 19
+    HuffmanTable {
+        size: 16,
+        linbits: 4,
+        codes: &CODES_16,
+        lengths: &LENGTHS_16,
+    },
+    // Table 20
+    HuffmanTable {
+        size: 16,
+        linbits: 6,
+        codes: &CODES_16,
+        lengths: &LENGTHS_16,
+    },
+    // Table 21
+    HuffmanTable {
+        size: 16,
+        linbits: 8,
+        codes: &CODES_16,
+        lengths: &LENGTHS_16,
+    },
+    // Table 22
+    HuffmanTable {
+        size: 16,
+        linbits: 10,
+        codes: &CODES_16,
+        lengths: &LENGTHS_16,
+    },
+    // Table 23
+    HuffmanTable {
+        size: 16,
+        linbits: 13,
+        codes: &CODES_16,
+        lengths: &LENGTHS_16,
+    },
+    // Table 24
+    HuffmanTable {
+        size: 16,
+        linbits: 4,
+        codes: &CODES_24,
+        lengths: &LENGTHS_24,
+    },
+    // Table 25
+    HuffmanTable {
+        size: 16,
+        linbits: 5,
+        codes: &CODES_24,
+        lengths: &LENGTHS_24,
+    },
+    // Table 26
+    HuffmanTable {
+        size: 16,
+        linbits: 6,
+        codes: &CODES_24,
+        lengths: &LENGTHS_24,
+    },
+    // Table 27
+    HuffmanTable {
+        size: 16,
+        linbits: 7,
+        codes: &CODES_24,
+        lengths: &LENGTHS_24,
+    },
+    // Table 28
+    HuffmanTable {
+        size: 16,
+        linbits: 8,
+        codes: &CODES_24,
+        lengths: &LENGTHS_24,
+    },
+    // Table 29
+    HuffmanTable {
+        size: 16,
+        linbits: 9,
+        codes: &CODES_24,
+        lengths: &LENGTHS_24,
+    },
+    // Table 30
+    HuffmanTable {
+        size: 16,
+        linbits: 11,
+        codes: &CODES_24,
+        lengths: &LENGTHS_24,
+    },
+    // Table 31
+    HuffmanTable {
+        size: 16,
+        linbits: 13,
+        codes: &CODES_24,
+        lengths: &LENGTHS_24,
+    },
+    // Table A
+    HuffmanTable {
+        size: 16,
+        linbits: 0,
+        codes: &CODES_A,
+        lengths: &LENGTHS_A,
+    },
+    // Table B
+    HuffmanTable {
+        size: 16,
+        linbits: 0,
+        codes: &CODES_B,
+        lengths: &LENGTHS_B,
+    },
+];
+
+const CODES_1: [u16; 4] = [1, 1, 1, 0];
+const LENGTHS_1: [u8; 4] = [1, 3, 2, 3];
+
+const CODES_2: [u16; 9] = [1, 2, 1, 3, 1, 1, 3, 2, 0];
+const LENGTHS_2: [u8; 9] = [1, 3, 6, 3, 3, 5, 5, 5, 6];
+
+const CODES_3: [u16; 9] = [3, 2, 1, 1, 1, 1, 3, 2, 0];
+const LENGTHS_3: [u8; 9] = [2, 2, 6, 3, 2, 5, 5, 5, 6];
+
+const CODES_5: [u16; 16] = [1, 2, 6, 5, 3, 1, 4, 4, 7, 5, 7, 1, 6, 1, 1, 0];
+const LENGTHS_5: [u8; 16] = [1, 3, 6, 7, 3, 3, 6, 7, 6, 6, 7, 8, 7, 6, 7, 8];
+
+const CODES_6: [u16; 16] = [7, 3, 5, 1, 6, 2, 3, 2, 5, 4, 4, 1, 3, 3, 2, 0];
+const LENGTHS_6: [u8; 16] = [3, 3, 5, 7, 3, 2, 4, 5, 4, 4, 5, 6, 6, 5, 6, 7];
+
+const CODES_7: [u16; 36] = [
+    1, 2, 10, 19, 16, 10, 3, 3, 7, 10, 5, 3, 11, 4, 13, 17, 8, 4, 12, 11, 18, 15, 11, 2, 7, 6, 9,
+    14, 3, 1, 6, 4, 5, 3, 2, 0,
+];
+const LENGTHS_7: [u8; 36] = [
+    1, 3, 6, 8, 8, 9, 3, 4, 6, 7, 7, 8, 6, 5, 7, 8, 8, 9, 7, 7, 8, 9, 9, 9, 7, 7, 8, 9, 9, 10, 8,
+    8, 9, 10, 10, 10,
+];
+
+const CODES_8: [u16; 36] = [
+    3, 4, 6, 18, 12, 5, 5, 1, 2, 16, 9, 3, 7, 3, 5, 14, 7, 3, 19, 17, 15, 13, 10, 4, 13, 5, 8, 11,
+    5, 1, 12, 4, 4, 1, 1, 0,
+];
+const LENGTHS_8: [u8; 36] = [
+    2, 3, 6, 8, 8, 9, 3, 2, 4, 8, 8, 8, 6, 4, 6, 8, 8, 9, 8, 8, 8, 9, 9, 10, 8, 7, 8, 9, 10, 10, 9,
+    8, 9, 9, 11, 11,
+];
+
+const CODES_9: [u16; 36] = [
+    7, 5, 9, 14, 15, 7, 6, 4, 5, 5, 6, 7, 7, 6, 8, 8, 8, 5, 15, 6, 9, 10, 5, 1, 11, 7, 9, 6, 4, 1,
+    14, 4, 6, 2, 6, 0,
+];
+const LENGTHS_9: [u8; 36] = [
+    3, 3, 5, 6, 8, 9, 3, 3, 4, 5, 6, 8, 4, 4, 5, 6, 7, 8, 6, 5, 6, 7, 7, 8, 7, 6, 7, 7, 8, 9, 8, 7,
+    8, 8, 9, 9,
+];
+
+const CODES_10: [u16; 64] = [
+    1, 2, 10, 23, 35, 30, 12, 17, 3, 3, 8, 12, 18, 21, 12, 7, 11, 9, 15, 21, 32, 40, 19, 6, 14, 13,
+    22, 34, 46, 23, 18, 7, 20, 19, 33, 47, 27, 22, 9, 3, 31, 22, 41, 26, 21, 20, 5, 3, 14, 13, 10,
+    11, 16, 6, 5, 1, 9, 8, 7, 8, 4, 4, 2, 0,
+];
+const LENGTHS_10: [u8; 64] = [
+    1, 3, 6, 8, 9, 9, 9, 10, 3, 4, 6, 7, 8, 9, 8, 8, 6, 6, 7, 8, 9, 10, 9, 9, 7, 7, 8, 9, 10, 10,
+    9, 10, 8, 8, 9, 10, 10, 10, 10, 10, 9, 9, 10, 10, 11, 11, 10, 11, 8, 8, 9, 10, 10, 10, 11, 11,
+    9, 8, 9, 10, 10, 11, 11, 11,
+];
+
+const CODES_11: [u16; 64] = [
+    3, 4, 10, 24, 34, 33, 21, 15, 5, 3, 4, 10, 32, 17, 11, 10, 11, 7, 13, 18, 30, 31, 20, 5, 25,
+    11, 19, 59, 27, 18, 12, 5, 35, 33, 31, 58, 30, 16, 7, 5, 28, 26, 32, 19, 17, 15, 8, 14, 14, 12,
+    9, 13, 14, 9, 4, 1, 11, 4, 6, 6, 6, 3, 2, 0,
+];
+const LENGTHS_11: [u8; 64] = [
+    2, 3, 5, 7, 8, 9, 8, 9, 3, 3, 4, 6, 8, 8, 7, 8, 5, 5, 6, 7, 8, 9, 8, 8, 7, 6, 7, 9, 8, 10, 8,
+    9, 8, 8, 8, 9, 9, 10, 9, 10, 8, 8, 9, 10, 10, 11, 10, 11, 8, 7, 7, 8, 9, 10, 10, 10, 8, 7, 8,
+    9, 10, 10, 10, 10,
+];
+
+const CODES_12: [u16; 64] = [
+    9, 6, 16, 33, 41, 39, 38, 26, 7, 5, 6, 9, 23, 16, 26, 11, 17, 7, 11, 14, 21, 30, 10, 7, 17, 10,
+    15, 12, 18, 28, 14, 5, 32, 13, 22, 19, 18, 16, 9, 5, 40, 17, 31, 29, 17, 13, 4, 2, 27, 12, 11,
+    15, 10, 7, 4, 1, 27, 12, 8, 12, 6, 3, 1, 0,
+];
+const LENGTHS_12: [u8; 64] = [
+    4, 3, 5, 7, 8, 9, 9, 9, 3, 3, 4, 5, 7, 7, 8, 8, 5, 4, 5, 6, 7, 8, 7, 8, 6, 5, 6, 6, 7, 8, 8, 8,
+    7, 6, 7, 7, 8, 8, 8, 9, 8, 7, 8, 8, 8, 9, 8, 9, 8, 7, 7, 8, 8, 9, 9, 10, 9, 8, 8, 9, 9, 9, 9,
+    10,
+];
+
+const CODES_13: [u16; 256] = [
+    1, 5, 14, 21, 34, 51, 46, 71, 42, 52, 68, 52, 67, 44, 43, 19, 3, 4, 12, 19, 31, 26, 44, 33, 31,
+    24, 32, 24, 31, 35, 22, 14, 15, 13, 23, 36, 59, 49, 77, 65, 29, 40, 30, 40, 27, 33, 42, 16, 22,
+    20, 37, 61, 56, 79, 73, 64, 43, 76, 56, 37, 26, 31, 25, 14, 35, 16, 60, 57, 97, 75, 114, 91,
+    54, 73, 55, 41, 48, 53, 23, 24, 58, 27, 50, 96, 76, 70, 93, 84, 77, 58, 79, 29, 74, 49, 41, 17,
+    47, 45, 78, 74, 115, 94, 90, 79, 69, 83, 71, 50, 59, 38, 36, 15, 72, 34, 56, 95, 92, 85, 91,
+    90, 86, 73, 77, 65, 51, 44, 43, 42, 43, 20, 30, 44, 55, 78, 72, 87, 78, 61, 46, 54, 37, 30, 20,
+    16, 53, 25, 41, 37, 44, 59, 54, 81, 66, 76, 57, 54, 37, 18, 39, 11, 35, 33, 31, 57, 42, 82, 72,
+    80, 47, 58, 55, 21, 22, 26, 38, 22, 53, 25, 23, 38, 70, 60, 51, 36, 55, 26, 34, 23, 27, 14, 9,
+    7, 34, 32, 28, 39, 49, 75, 30, 52, 48, 40, 52, 28, 18, 17, 9, 5, 45, 21, 34, 64, 56, 50, 49,
+    45, 31, 19, 12, 15, 10, 7, 6, 3, 48, 23, 20, 39, 36, 35, 53, 21, 16, 23, 13, 10, 6, 1, 4, 2,
+    16, 15, 17, 27, 25, 20, 29, 11, 17, 12, 16, 8, 1, 1, 0, 1,
+];
+const LENGTHS_13: [u8; 256] = [
+    1, 4, 6, 7, 8, 9, 9, 10, 9, 10, 11, 11, 12, 12, 13, 13, 3, 4, 6, 7, 8, 8, 9, 9, 9, 9, 10, 10,
+    11, 12, 12, 12, 6, 6, 7, 8, 9, 9, 10, 10, 9, 10, 10, 11, 11, 12, 13, 13, 7, 7, 8, 9, 9, 10, 10,
+    10, 10, 11, 11, 11, 11, 12, 13, 13, 8, 7, 9, 9, 10, 10, 11, 11, 10, 11, 11, 12, 12, 13, 13, 14,
+    9, 8, 9, 10, 10, 10, 11, 11, 11, 11, 12, 11, 13, 13, 14, 14, 9, 9, 10, 10, 11, 11, 11, 11, 11,
+    12, 12, 12, 13, 13, 14, 14, 10, 9, 10, 11, 11, 11, 12, 12, 12, 12, 13, 13, 13, 14, 16, 16, 9,
+    8, 9, 10, 10, 11, 11, 12, 12, 12, 12, 13, 13, 14, 15, 15, 10, 9, 10, 10, 11, 11, 11, 13, 12,
+    13, 13, 14, 14, 14, 16, 15, 10, 10, 10, 11, 11, 12, 12, 13, 12, 13, 14, 13, 14, 15, 16, 17, 11,
+    10, 10, 11, 12, 12, 12, 12, 13, 13, 13, 14, 15, 15, 15, 16, 11, 11, 11, 12, 12, 13, 12, 13, 14,
+    14, 15, 15, 15, 16, 16, 16, 12, 11, 12, 13, 13, 13, 14, 14, 14, 14, 14, 15, 16, 15, 16, 16, 13,
+    12, 12, 13, 13, 13, 15, 14, 14, 17, 15, 15, 15, 17, 16, 16, 12, 12, 13, 14, 14, 14, 15, 14, 15,
+    15, 16, 16, 19, 18, 19, 16,
+];
+
+const CODES_15: [u16; 256] = [
+    7, 12, 18, 53, 47, 76, 124, 108, 89, 123, 108, 119, 107, 81, 122, 63, 13, 5, 16, 27, 46, 36,
+    61, 51, 42, 70, 52, 83, 65, 41, 59, 36, 19, 17, 15, 24, 41, 34, 59, 48, 40, 64, 50, 78, 62, 80,
+    56, 33, 29, 28, 25, 43, 39, 63, 55, 93, 76, 59, 93, 72, 54, 75, 50, 29, 52, 22, 42, 40, 67, 57,
+    95, 79, 72, 57, 89, 69, 49, 66, 46, 27, 77, 37, 35, 66, 58, 52, 91, 74, 62, 48, 79, 63, 90, 62,
+    40, 38, 125, 32, 60, 56, 50, 92, 78, 65, 55, 87, 71, 51, 73, 51, 70, 30, 109, 53, 49, 94, 88,
+    75, 66, 122, 91, 73, 56, 42, 64, 44, 21, 25, 90, 43, 41, 77, 73, 63, 56, 92, 77, 66, 47, 67,
+    48, 53, 36, 20, 71, 34, 67, 60, 58, 49, 88, 76, 67, 106, 71, 54, 38, 39, 23, 15, 109, 53, 51,
+    47, 90, 82, 58, 57, 48, 72, 57, 41, 23, 27, 62, 9, 86, 42, 40, 37, 70, 64, 52, 43, 70, 55, 42,
+    25, 29, 18, 11, 11, 118, 68, 30, 55, 50, 46, 74, 65, 49, 39, 24, 16, 22, 13, 14, 7, 91, 44, 39,
+    38, 34, 63, 52, 45, 31, 52, 28, 19, 14, 8, 9, 3, 123, 60, 58, 53, 47, 43, 32, 22, 37, 24, 17,
+    12, 15, 10, 2, 1, 71, 37, 34, 30, 28, 20, 17, 26, 21, 16, 10, 6, 8, 6, 2, 0,
+];
+const LENGTHS_15: [u8; 256] = [
+    3, 4, 5, 7, 7, 8, 9, 9, 9, 10, 10, 11, 11, 11, 12, 13, 4, 3, 5, 6, 7, 7, 8, 8, 8, 9, 9, 10, 10,
+    10, 11, 11, 5, 5, 5, 6, 7, 7, 8, 8, 8, 9, 9, 10, 10, 11, 11, 11, 6, 6, 6, 7, 7, 8, 8, 9, 9, 9,
+    10, 10, 10, 11, 11, 11, 7, 6, 7, 7, 8, 8, 9, 9, 9, 9, 10, 10, 10, 11, 11, 11, 8, 7, 7, 8, 8, 8,
+    9, 9, 9, 9, 10, 10, 11, 11, 11, 12, 9, 7, 8, 8, 8, 9, 9, 9, 9, 10, 10, 10, 11, 11, 12, 12, 9,
+    8, 8, 9, 9, 9, 9, 10, 10, 10, 10, 10, 11, 11, 11, 12, 9, 8, 8, 9, 9, 9, 9, 10, 10, 10, 10, 11,
+    11, 12, 12, 12, 9, 8, 9, 9, 9, 9, 10, 10, 10, 11, 11, 11, 11, 12, 12, 12, 10, 9, 9, 9, 10, 10,
+    10, 10, 10, 11, 11, 11, 11, 12, 13, 12, 10, 9, 9, 9, 10, 10, 10, 10, 11, 11, 11, 11, 12, 12,
+    12, 13, 11, 10, 9, 10, 10, 10, 11, 11, 11, 11, 11, 11, 12, 12, 13, 13, 11, 10, 10, 10, 10, 11,
+    11, 11, 11, 12, 12, 12, 12, 12, 13, 13, 12, 11, 11, 11, 11, 11, 11, 11, 12, 12, 12, 12, 13, 13,
+    12, 13, 12, 11, 11, 11, 11, 11, 11, 12, 12, 12, 12, 12, 13, 13, 13, 13,
+];
+
+const CODES_16: [u16; 256] = [
+    1, 5, 14, 44, 74, 63, 110, 93, 172, 149, 138, 242, 225, 195, 376, 17, 3, 4, 12, 20, 35, 62, 53,
+    47, 83, 75, 68, 119, 201, 107, 207, 9, 15, 13, 23, 38, 67, 58, 103, 90, 161, 72, 127, 117, 110,
+    209, 206, 16, 45, 21, 39, 69, 64, 114, 99, 87, 158, 140, 252, 212, 199, 387, 365, 26, 75, 36,
+    68, 65, 115, 101, 179, 164, 155, 264, 246, 226, 395, 382, 362, 9, 66, 30, 59, 56, 102, 185,
+    173, 265, 142, 253, 232, 400, 388, 378, 445, 16, 111, 54, 52, 100, 184, 178, 160, 133, 257,
+    244, 228, 217, 385, 366, 715, 10, 98, 48, 91, 88, 165, 157, 148, 261, 248, 407, 397, 372, 380,
+    889, 884, 8, 85, 84, 81, 159, 156, 143, 260, 249, 427, 401, 392, 383, 727, 713, 708, 7, 154,
+    76, 73, 141, 131, 256, 245, 426, 406, 394, 384, 735, 359, 710, 352, 11, 139, 129, 67, 125, 247,
+    233, 229, 219, 393, 743, 737, 720, 885, 882, 439, 4, 243, 120, 118, 115, 227, 223, 396, 746,
+    742, 736, 721, 712, 706, 223, 436, 6, 202, 224, 222, 218, 216, 389, 386, 381, 364, 888, 443,
+    707, 440, 437, 1728, 4, 747, 211, 210, 208, 370, 379, 734, 723, 714, 1735, 883, 877, 876, 3459,
+    865, 2, 377, 369, 102, 187, 726, 722, 358, 711, 709, 866, 1734, 871, 3458, 870, 434, 0, 12, 10,
+    7, 11, 10, 17, 11, 9, 13, 12, 10, 7, 5, 3, 1, 3,
+];
+const LENGTHS_16: [u8; 256] = [
+    1, 4, 6, 8, 9, 9, 10, 10, 11, 11, 11, 12, 12, 12, 13, 9, 3, 4, 6, 7, 8, 9, 9, 9, 10, 10, 10,
+    11, 12, 11, 12, 8, 6, 6, 7, 8, 9, 9, 10, 10, 11, 10, 11, 11, 11, 12, 12, 9, 8, 7, 8, 9, 9, 10,
+    10, 10, 11, 11, 12, 12, 12, 13, 13, 10, 9, 8, 9, 9, 10, 10, 11, 11, 11, 12, 12, 12, 13, 13, 13,
+    9, 9, 8, 9, 9, 10, 11, 11, 12, 11, 12, 12, 13, 13, 13, 14, 10, 10, 9, 9, 10, 11, 11, 11, 11,
+    12, 12, 12, 12, 13, 13, 14, 10, 10, 9, 10, 10, 11, 11, 11, 12, 12, 13, 13, 13, 13, 15, 15, 10,
+    10, 10, 10, 11, 11, 11, 12, 12, 13, 13, 13, 13, 14, 14, 14, 10, 11, 10, 10, 11, 11, 12, 12, 13,
+    13, 13, 13, 14, 13, 14, 13, 11, 11, 11, 10, 11, 12, 12, 12, 12, 13, 14, 14, 14, 15, 15, 14, 10,
+    12, 11, 11, 11, 12, 12, 13, 14, 14, 14, 14, 14, 14, 13, 14, 11, 12, 12, 12, 12, 12, 13, 13, 13,
+    13, 15, 14, 14, 14, 14, 16, 11, 14, 12, 12, 12, 13, 13, 14, 14, 14, 16, 15, 15, 15, 17, 15, 11,
+    13, 13, 11, 12, 14, 14, 13, 14, 14, 15, 16, 15, 17, 15, 14, 11, 9, 8, 8, 9, 9, 10, 10, 10, 11,
+    11, 11, 11, 11, 11, 11, 8,
+];
+
+const CODES_24: [u16; 256] = [
+    15, 13, 46, 80, 146, 262, 248, 434, 426, 669, 653, 649, 621, 517, 1032, 88, 14, 12, 21, 38, 71,
+    130, 122, 216, 209, 198, 327, 345, 319, 297, 279, 42, 47, 22, 41, 74, 68, 128, 120, 221, 207,
+    194, 182, 340, 315, 295, 541, 18, 81, 39, 75, 70, 134, 125, 116, 220, 204, 190, 178, 325, 311,
+    293, 271, 16, 147, 72, 69, 135, 127, 118, 112, 210, 200, 188, 352, 323, 306, 285, 540, 14, 263,
+    66, 129, 126, 119, 114, 214, 202, 192, 180, 341, 317, 301, 281, 262, 12, 249, 123, 121, 117,
+    113, 215, 206, 195, 185, 347, 330, 308, 291, 272, 520, 10, 435, 115, 111, 109, 211, 203, 196,
+    187, 353, 332, 313, 298, 283, 531, 381, 17, 427, 212, 208, 205, 201, 193, 186, 177, 169, 320,
+    303, 286, 268, 514, 377, 16, 335, 199, 197, 191, 189, 181, 174, 333, 321, 305, 289, 275, 521,
+    379, 371, 11, 668, 184, 183, 179, 175, 344, 331, 314, 304, 290, 277, 530, 383, 373, 366, 10,
+    652, 346, 171, 168, 164, 318, 309, 299, 287, 276, 263, 513, 375, 368, 362, 6, 648, 322, 316,
+    312, 307, 302, 292, 284, 269, 261, 512, 376, 370, 364, 359, 4, 620, 300, 296, 294, 288, 282,
+    273, 266, 515, 380, 374, 369, 365, 361, 357, 2, 1033, 280, 278, 274, 267, 264, 259, 382, 378,
+    372, 367, 363, 360, 358, 356, 0, 43, 20, 19, 17, 15, 13, 11, 9, 7, 6, 4, 7, 5, 3, 1, 3,
+];
+const LENGTHS_24: [u8; 256] = [
+    4, 4, 6, 7, 8, 9, 9, 10, 10, 11, 11, 11, 11, 11, 12, 9, 4, 4, 5, 6, 7, 8, 8, 9, 9, 9, 10, 10,
+    10, 10, 10, 8, 6, 5, 6, 7, 7, 8, 8, 9, 9, 9, 9, 10, 10, 10, 11, 7, 7, 6, 7, 7, 8, 8, 8, 9, 9,
+    9, 9, 10, 10, 10, 10, 7, 8, 7, 7, 8, 8, 8, 8, 9, 9, 9, 10, 10, 10, 10, 11, 7, 9, 7, 8, 8, 8, 8,
+    9, 9, 9, 9, 10, 10, 10, 10, 10, 7, 9, 8, 8, 8, 8, 9, 9, 9, 9, 10, 10, 10, 10, 10, 11, 7, 10, 8,
+    8, 8, 9, 9, 9, 9, 10, 10, 10, 10, 10, 11, 11, 8, 10, 9, 9, 9, 9, 9, 9, 9, 9, 10, 10, 10, 10,
+    11, 11, 8, 10, 9, 9, 9, 9, 9, 9, 10, 10, 10, 10, 10, 11, 11, 11, 8, 11, 9, 9, 9, 9, 10, 10, 10,
+    10, 10, 10, 11, 11, 11, 11, 8, 11, 10, 9, 9, 9, 10, 10, 10, 10, 10, 10, 11, 11, 11, 11, 8, 11,
+    10, 10, 10, 10, 10, 10, 10, 10, 10, 11, 11, 11, 11, 11, 8, 11, 10, 10, 10, 10, 10, 10, 10, 11,
+    11, 11, 11, 11, 11, 11, 8, 12, 10, 10, 10, 10, 10, 10, 11, 11, 11, 11, 11, 11, 11, 11, 8, 8, 7,
+    7, 7, 7, 7, 7, 7, 7, 7, 7, 8, 8, 8, 8, 4,
+];
+
+const CODES_A: [u16; 16] = [1, 5, 4, 5, 6, 5, 4, 4, 7, 3, 6, 0, 7, 2, 3, 1];
+const LENGTHS_A: [u8; 16] = [1, 4, 4, 5, 4, 6, 5, 6, 4, 5, 5, 6, 5, 6, 6, 6];
+
+const CODES_B: [u16; 16] = [15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0];
+const LENGTHS_B: [u8; 16] = [4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scalefactors::Scalefactors, test_utils::*};
+
+    const VBR: &[u8] = include_bytes!("../fixtures/vbr.mp3");
+
+    // Spectrum of one granule/channel of a frame, the main data read up to it
+    struct Spectrum {
+        samples: [i32; 576],
+        big_values: usize,
+        // Index past the last count1 value
+        rzero: usize,
+        // Left over bits of part2_3_length
+        unused: usize,
+    }
+
+    fn spectrum(data: &[u8], index: usize, granule: usize, channel: usize) -> Spectrum {
+        let (frame, side_info, main_data) = main_data(data, index);
+        let mut reader = BitReader::new(&main_data);
+        let mut first = [Scalefactors::default(); 2];
+
+        let mut position = 0;
+        for (g, c) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            reader.seek(position);
+            let scalefactors =
+                Scalefactors::read(&mut reader, &side_info, g, c, Some(&first[c])).unwrap();
+            if g == 0 {
+                first[c] = scalefactors;
+            }
+
+            let info = &side_info.granules[g][c];
+            position += info.part2_3_length;
+            if (g, c) == (granule, channel) {
+                let mut samples = [0; 576];
+                let big_values =
+                    decode_big_values(&mut reader, &frame.header, info, position, &mut samples)
+                        .unwrap();
+                assert_eq!(big_values, info.big_values * 2);
+                let rzero =
+                    decode_count1(&mut reader, info, position, big_values, &mut samples).unwrap();
+                assert!(samples[rzero..].iter().all(|value| *value == 0));
+
+                return Spectrum {
+                    samples,
+                    big_values,
+                    rzero,
+                    unused: position - reader.position(),
+                };
+            }
+        }
+        unreachable!()
+    }
+
+    fn big_values(data: &[u8], index: usize, granule: usize, channel: usize) -> Vec<i32> {
+        let spectrum = spectrum(data, index, granule, channel);
+        spectrum.samples[..spectrum.big_values].to_vec()
+    }
+
+    #[test]
+    fn pairs() {
+        // Table 1: "01" (1, 0) then a sign bit, "001" (0, 1) then a sign bit
+        let mut reader = BitReader::new(&[0b0100_0110]);
+        assert_eq!(decode_pair(&mut reader, 1).unwrap(), (1, 0));
+        assert_eq!(decode_pair(&mut reader, 1).unwrap(), (0, -1));
+        assert_eq!(reader.position(), 7);
+
+        // Nothing is read with table 0
+        assert_eq!(decode_pair(&mut reader, 0).unwrap(), (0, 0));
+        assert_eq!(reader.position(), 7);
+    }
+
+    // Expected values printed by symphonia for frames 1 and 3 of fixtures/vbr.mp3
+    #[test]
+    fn start_block() {
+        // Table 15, region 1 empty
+        assert_eq!(
+            big_values(VBR, 1, 0, 0),
+            [
+                -9, -9, -9, -8, -8, -6, -4, -1, 3, 7, 9, 11, 12, 13, 12, 11, 9, 6, -5, -3, -3, -3,
+                -3, -4, -4, -3, -3, -3, -3, -2, -2, -2, -2, -1
+            ]
+        );
+    }
+
+    #[test]
+    fn short_block_with_linbits() {
+        // Tables 27 (7 linbits) and 13
+        assert_eq!(
+            big_values(VBR, 1, 1, 0),
+            [
+                -15, 27, -17, 141, 1, 2, -7, 40, 5, 2, -25, 43, 73, -30, -5, 8, 35, 6, 0, -1, 118,
+                11, 0, -4, -42, 27, -25, 27, -51, 10, -5, -1, -43, 25, -3, -5, 6, -11, 10, -10, -2,
+                3, -2, 0, 0, 0, 0, 0, 7, -8, -3, 5, -5, 5, 1, -1, 0, -1, 1, 0, 0, 0, 0, 0, 0, 0,
+                -5, 5, 2, -3, 3, -3, 3, -4, -1, 1, 0, 1, -1, 0, 0, -1, 0, 0, 0, 0, 0, 0, 0, 0, -2,
+                4, -4, 4, -4, 5, 2, -3, 3, -3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 0, 3, -4, -1, 2, -3, 3, -3, 3, 1, -2, 2, -2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, -3, -1, 2, -2, 2, -2, 3, 1, -2, 2, -2, 2,
+                -2, 0, 0
+            ]
+        );
+    }
+
+    #[test]
+    fn three_regions() {
+        // Tables 27, 24 and 12, regions starting at lines 12 and 24
+        assert_eq!(
+            big_values(VBR, 3, 0, 0),
+            [
+                -10, 11, 11, -14, -5, 7, 8, -13, -5, 11, 30, 84, -30, 11, 4, -3, -21, 17, 11, -13,
+                -5, 4, 6, -8, 7, -5, -2, 1, 1, 0, -3, 2, 2, -1, -1, 0
+            ]
+        );
+        assert_eq!(
+            big_values(VBR, 3, 0, 1),
+            [
+                -6, 4, 8, -2, -9, 0, 13, 1, -9, 0, 14, -4, -3, 2, 10, -15, -61, -71, -12, 17, 8,
+                -5, -3, 3, 7, -6, -4, 4, 3, -3, -6, 5, 4, -3, -2, 1, 2, -4, -5, 4, 3, -2, -1, 0
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_at_part2_3_length() {
+        // Table 1 reads "000" as (1, 1), each value followed by its sign bit
+        let header = Header::from_bytes(&HEADER_128).unwrap();
+        let info = GranuleInfo {
+            big_values: 288,
+            table_select: [1; 3],
+            region0_count: 7,
+            region1_count: 7,
+            ..Default::default()
+        };
+
+        let mut samples = [0; 576];
+        let result = decode_big_values(
+            &mut BitReader::new(&[0; 100]),
+            &header,
+            &info,
+            50,
+            &mut samples,
+        );
+        assert!(matches!(
+            result,
+            Err(ChompError::InvalidMainData(
+                "big_values run past part2_3_length"
+            ))
+        ));
+        // 10 pairs of 5 bits fit
+        assert_eq!(samples[..20], [1; 20]);
+        assert_eq!(samples[20..], [0; 556]);
+    }
+
+    // Expected values printed by symphonia for frames 1 and 2 of fixtures/vbr.mp3: index past the
+    // last count1 value, nonzero values and sum of the count1 region, and its first values
+    #[test]
+    fn count1_region() {
+        let expected = [
+            // Table B
+            (1, 0, 0, 342, 177, 3, [-1, -1, 1, 1]),
+            (1, 0, 1, 416, 216, 20, [1; 4]),
+            // Table A
+            (1, 1, 0, 216, 16, 0, [0; 4]),
+            (1, 1, 1, 274, 30, 2, [0; 4]),
+            (2, 0, 0, 38, 2, 2, [0, 1, 1, 0]),
+        ];
+
+        for (index, granule, channel, rzero, nonzero, sum, first) in expected {
+            let spectrum = spectrum(VBR, index, granule, channel);
+            assert_eq!(spectrum.rzero, rzero);
+
+            let count1 = &spectrum.samples[spectrum.big_values..rzero];
+            assert_eq!(count1.iter().filter(|value| **value != 0).count(), nonzero);
+            assert_eq!(count1.iter().sum::<i32>(), sum);
+            assert_eq!(count1[..4], first);
+            // LAME fills part2_3_length exactly
+            assert_eq!(spectrum.unused, 0);
+        }
+    }
+
+    #[test]
+    fn count1_bit_budget() {
+        // Table B codes (1, 1, 1, 1) as "0000", each value followed by its sign bit: 8 bits
+        let info = GranuleInfo {
+            count1table_select: true,
+            ..Default::default()
+        };
+        let mut samples = [0; 576];
+
+        // Ends exactly on the budget
+        let mut reader = BitReader::new(&[0; 4]);
+        assert_eq!(
+            decode_count1(&mut reader, &info, 16, 100, &mut samples).unwrap(),
+            108
+        );
+        assert_eq!(reader.position(), 16);
+        assert_eq!(samples[100..108], [1; 8]);
+
+        // The second quadruple would run 2 bits past the budget, it is discarded
+        let mut samples = [0; 576];
+        let mut reader = BitReader::new(&[0; 4]);
+        assert_eq!(
+            decode_count1(&mut reader, &info, 14, 100, &mut samples).unwrap(),
+            104
+        );
+        assert_eq!(samples[100..104], [1; 4]);
+        assert_eq!(samples[104..], [0; 472]);
+
+        // Nor does the main data running out go past the last complete quadruple
+        let mut reader = BitReader::new(&[0; 3]);
+        reader.seek(4);
+        assert_eq!(
+            decode_count1(&mut reader, &info, 100, 0, &mut samples).unwrap(),
+            8
+        );
+
+        // No more than 576 values
+        let mut reader = BitReader::new(&[0; 200]);
+        assert_eq!(
+            decode_count1(&mut reader, &info, 1600, 572, &mut samples).unwrap(),
+            576
+        );
+    }
+}
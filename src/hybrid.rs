@@ -0,0 +1,467 @@
+use crate::{
+    constants::*,
+    side_info::{BlockType, GranuleInfo},
+};
+use std::{f32::consts::PI, sync::OnceLock};
+
+// Alias reduction, 8 butterflies across each boundary between two subbands of long blocks.
+// Short blocks have none, mixed blocks only the one between the first 2 (long) subbands.
+pub fn antialias(info: &GranuleInfo, samples: &mut [f32; 576]) {
+    let subbands = match (info.block_type, info.mixed_block) {
+        (BlockType::Short, false) => return,
+        (BlockType::Short, true) => 2,
+        _ => 32,
+    };
+
+    for boundary in (1..subbands).map(|subband| subband * 18) {
+        for i in 0..8 {
+            let below = samples[boundary - 1 - i];
+            let above = samples[boundary + i];
+            samples[boundary - 1 - i] = below * ALIAS_CS[i] - above * ALIAS_CA[i];
+            samples[boundary + i] = above * ALIAS_CS[i] + below * ALIAS_CA[i];
+        }
+    }
+}
+
+// Windows applied to the 36 IMDCT outputs of a long subband, indexed by block type. The short
+// one applies to each 12 point IMDCT.
+fn windows() -> &'static [[f32; 36]; 4] {
+    static WINDOWS: OnceLock<[[f32; 36]; 4]> = OnceLock::new();
+
+    WINDOWS.get_or_init(|| {
+        let long = |i: usize| (PI / 36.0 * (i as f32 + 0.5)).sin();
+        let short = |i: usize| (PI / 12.0 * (i as f32 + 0.5)).sin();
+        [
+            std::array::from_fn(long),
+            std::array::from_fn(|i| match i {
+                0..18 => long(i),
+                18..24 => 1.0,
+                24..30 => short(i - 18),
+                _ => 0.0,
+            }),
+            std::array::from_fn(|i| if i < 12 { short(i) } else { 0.0 }),
+            std::array::from_fn(|i| match i {
+                0..6 => 0.0,
+                6..12 => short(i - 6),
+                12..18 => 1.0,
+                _ => long(i),
+            }),
+        ]
+    })
+}
+
+// 36 point IMDCT of one long subband: x[i] = Σ X[k] cos(π / 72 (2i + 1 + 18)(2k + 1))
+fn imdct_long(input: &[f32], output: &mut [f32; 36]) {
+    for (i, output) in output.iter_mut().enumerate() {
+        *output = input
+            .iter()
+            .enumerate()
+            .map(|(k, value)| {
+                value * (PI / 72.0 * (2 * i + 1 + 18) as f32 * (2 * k + 1) as f32).cos()
+            })
+            .sum();
+    }
+}
+
+// 12 point IMDCT of one short window: x[i] = Σ X[k] cos(π / 24 (2i + 1 + 6)(2k + 1))
+fn imdct_short(input: [f32; 6], output: &mut [f32; 12]) {
+    for (i, output) in output.iter_mut().enumerate() {
+        *output = input
+            .iter()
+            .enumerate()
+            .map(|(k, value)| {
+                value * (PI / 24.0 * (2 * i + 1 + 6) as f32 * (2 * k + 1) as f32).cos()
+            })
+            .sum();
+    }
+}
+
+// IMDCT, windowing and overlap-add of one granule/channel. `samples` holds the 18 values of
+// each subband (short windows interleaved, as left by the reorder), `output` receives the 18
+// time samples of each subband. The second half of every subband output is kept in `overlap`
+// and added to the first half of the next granule. The first 2 subbands of a mixed block are
+// long blocks with the normal window.
+pub fn imdct(
+    info: &GranuleInfo,
+    samples: &[f32; 576],
+    overlap: &mut [[f32; 18]; 32],
+    output: &mut [f32; 576],
+) {
+    let windows = windows();
+
+    for subband in 0..32 {
+        let input = &samples[subband * 18..subband * 18 + 18];
+        let block_type = match info.block_type {
+            BlockType::Short if info.mixed_block && subband < 2 => BlockType::Normal,
+            block_type => block_type,
+        };
+
+        let mut windowed = [0.0; 36];
+        if block_type == BlockType::Short {
+            // The 3 windows overlap each other by half, starting 6 samples in
+            for window in 0..3 {
+                let values = std::array::from_fn(|k| input[k * 3 + window]);
+                let mut short = [0.0; 12];
+                imdct_short(values, &mut short);
+
+                for i in 0..12 {
+                    windowed[6 + window * 6 + i] += short[i] * windows[2][i];
+                }
+            }
+        } else {
+            imdct_long(input, &mut windowed);
+
+            let window = &windows[block_type as usize];
+            for i in 0..36 {
+                windowed[i] *= window[i];
+            }
+        }
+
+        for i in 0..18 {
+            output[subband * 18 + i] = windowed[i] + overlap[subband][i];
+            overlap[subband][i] = windowed[18 + i];
+        }
+    }
+}
+
+// Negates every odd time sample of every odd subband, compensating for the frequency inversion
+// the synthesis filterbank applies to them
+pub fn frequency_inversion(samples: &mut [f32; 576]) {
+    for subband in (1..32).step_by(2) {
+        for i in (1..18).step_by(2) {
+            samples[subband * 18 + i] = -samples[subband * 18 + i];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{reorder::reorder, test_utils::*};
+
+    // Both channels of a granule up to the alias reduction, and as they were before it
+    fn antialiased(decoded: &Decoded, granule: usize) -> ([[f32; 576]; 2], [[f32; 576]; 2]) {
+        let mut samples = stereo_granule(decoded, granule);
+        for (channel, samples) in samples.iter_mut().enumerate() {
+            reorder(
+                &decoded.header,
+                &decoded.side_info.granules[granule][channel],
+                samples,
+            );
+        }
+
+        let before = samples;
+        for (channel, samples) in samples.iter_mut().enumerate() {
+            antialias(&decoded.side_info.granules[granule][channel], samples);
+        }
+        (samples, before)
+    }
+
+    // Expected values printed by symphonia for frame 2 of fixtures/cbr.mp3
+    #[test]
+    fn long_block() {
+        let decoded = decode_values(include_bytes!("../fixtures/cbr.mp3"), 2);
+        assert_eq!(
+            decoded.side_info.granules[1][0].block_type,
+            BlockType::Normal
+        );
+
+        let (samples, _) = antialiased(&decoded, 1);
+        // Around the boundary between subbands 0 and 1
+        assert_close(
+            &samples[0][10..26],
+            &[
+                0.08007529,
+                -0.18867378,
+                -0.07760721,
+                -0.012834251,
+                0.0070362543,
+                0.0035654705,
+                -0.0029692482,
+                -0.002375418,
+                5.306676e-6,
+                1.6040402e-5,
+                -2.7241535e-5,
+                -6.345427e-5,
+                0.00013534748,
+                0.00082930666,
+                0.00205429,
+                -0.00086759473,
+            ],
+        );
+        assert!((energy(&samples[0]) / 4.8505298412307836e-2 - 1.0).abs() < 1e-5);
+        assert!((energy(&samples[1]) / 5.1085790500193906e-2 - 1.0).abs() < 1e-5);
+    }
+
+    // Expected values printed by symphonia for frame 1 of fixtures/mixed.mp3
+    #[test]
+    fn mixed_block() {
+        let decoded = decode_values(include_bytes!("../fixtures/mixed.mp3"), 1);
+        assert!(decoded.side_info.granules[0][0].mixed_block);
+
+        let (samples, before) = antialiased(&decoded, 0);
+        assert_close(
+            &samples[0][10..26],
+            &[
+                -0.00010060335,
+                -0.000122058,
+                -9.408828e-5,
+                8.3596795e-5,
+                -8.585789e-5,
+                0.00010109589,
+                -2.2119207e-6,
+                -0.00011207603,
+                6.095425e-5,
+                7.301717e-6,
+                3.091101e-5,
+                1.03973725e-5,
+                4.7949175e-5,
+                -1.5417099e-6,
+                1.7332236e-6,
+                -3.0145553e-5,
+            ],
+        );
+        assert_close(
+            &samples[1][10..26],
+            &[
+                -0.0001435356,
+                -0.000122058,
+                -7.84003e-5,
+                9.980761e-5,
+                -8.389513e-5,
+                0.00010109589,
+                2.2119207e-6,
+                -9.727282e-5,
+                6.4655054e-5,
+                -7.301717e-6,
+                3.091101e-5,
+                2.1006937e-5,
+                5.724732e-5,
+                8.613742e-6,
+                1.7332236e-6,
+                3.1048865e-5,
+            ],
+        );
+        assert!((energy(&samples[0]) / 9.530029660895286e-7 - 1.0).abs() < 1e-5);
+        assert!((energy(&samples[1]) / 7.153586893313553e-7 - 1.0).abs() < 1e-5);
+
+        // Only the boundary between the 2 long subbands is reduced
+        assert_eq!(samples[0][26..], before[0][26..]);
+        assert_eq!(samples[1][26..], before[1][26..]);
+    }
+
+    #[test]
+    fn short_block() {
+        let info = GranuleInfo {
+            window_switching: true,
+            block_type: BlockType::Short,
+            ..Default::default()
+        };
+        let mut samples = std::array::from_fn(|i| i as f32);
+        antialias(&info, &mut samples);
+        assert_eq!(samples, std::array::from_fn(|i| i as f32));
+    }
+
+    #[test]
+    fn odd_samples_of_odd_subbands_negated() {
+        let mut samples = std::array::from_fn(|i| i as f32 + 1.0);
+        frequency_inversion(&mut samples);
+
+        for (i, sample) in samples.iter().enumerate() {
+            let (subband, time) = (i / 18, i % 18);
+            let sign = match subband % 2 == 1 && time % 2 == 1 {
+                true => -1.0,
+                false => 1.0,
+            };
+            assert_eq!(*sample, sign * (i as f32 + 1.0), "{subband} {time}");
+        }
+
+        // Its own inverse
+        frequency_inversion(&mut samples);
+        assert_eq!(samples, std::array::from_fn(|i| i as f32 + 1.0));
+    }
+
+    // Output of the 36 point IMDCT and normal window, and of the 3 short IMDCTs and window, for
+    // the 18 values of `input()`, computed in f64
+    const LONG: [f32; 36] = [
+        -0.08658012,
+        1.383062,
+        1.077498,
+        -2.862124,
+        -11.24264,
+        3.616092,
+        -6.867814,
+        -0.004565074,
+        0.3070714,
+        -0.3351097,
+        0.00594932,
+        10.78031,
+        -6.946448,
+        27.14214,
+        9.077496,
+        -4.860281,
+        -10.5054,
+        1.983011,
+        8.279361,
+        -2.348988,
+        10.8748,
+        5.044606,
+        2.757359,
+        12.17553,
+        0.9885233,
+        0.2633841,
+        0.8232041,
+        0.7543276,
+        0.2021017,
+        0.6297588,
+        6.338177,
+        1.142136,
+        1.590558,
+        2.410885,
+        -0.3092502,
+        0.3614847,
+    ];
+    const SHORT: [f32; 36] = [
+        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.8836225, -1.464466, -1.007347, 1.3128, 3.535534, 6.71178,
+        8.033933, 7.853553, -3.314536, -4.810038, 5.181981, -1.411058, 1.279468, -4.914214,
+        0.9650547, -3.132084, -4.207107, 8.205034, -11.13809, 2.767767, 6.772244, 5.196526,
+        1.146447, -1.466357, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+    ];
+
+    fn input() -> [f32; 576] {
+        std::array::from_fn(|i| ((i % 18) * 7 % 11) as f32 - 5.0)
+    }
+
+    fn assert_near(actual: &[f32], expected: &[f32]) {
+        for (i, (actual, expected)) in actual.iter().zip(expected).enumerate() {
+            assert!(
+                (actual - expected).abs() < 1e-4,
+                "{i}: {actual} != {expected}"
+            );
+        }
+    }
+
+    fn imdct_blocks(info: &GranuleInfo) {
+        let mut overlap = [[0.0; 18]; 32];
+        let mut output = [0.0; 576];
+        imdct(info, &input(), &mut overlap, &mut output);
+
+        for subband in 0..32 {
+            let expected = match info.block_type {
+                BlockType::Short if !info.mixed_block || subband >= 2 => &SHORT,
+                _ => &LONG,
+            };
+            assert_near(&output[subband * 18..subband * 18 + 18], &expected[..18]);
+            assert_near(&overlap[subband], &expected[18..]);
+        }
+
+        // Overlap-add of the second half into the next granule
+        let previous = overlap;
+        imdct(info, &[0.0; 576], &mut overlap, &mut output);
+        assert_eq!(output, *previous.as_flattened());
+        assert_eq!(overlap, [[0.0; 18]; 32]);
+    }
+
+    #[test]
+    fn long_short_and_mixed_imdct() {
+        imdct_blocks(&GranuleInfo::default());
+        let short = GranuleInfo {
+            window_switching: true,
+            block_type: BlockType::Short,
+            ..Default::default()
+        };
+        imdct_blocks(&short);
+        imdct_blocks(&GranuleInfo {
+            mixed_block: true,
+            ..short
+        });
+    }
+
+    // Expected values printed by symphonia for frames 1 and 2 of fixtures/vbr.mp3: start, short,
+    // stop then long blocks, each granule overlapping the previous one
+    #[test]
+    fn window_switching_sequence() {
+        let data = include_bytes!("../fixtures/vbr.mp3");
+        let mut overlap = [[0.0; 18]; 32];
+        let mut outputs = Vec::new();
+        for index in 1..3 {
+            let decoded = decode_values(data, index);
+            for granule in 0..2 {
+                let (samples, _) = antialiased(&decoded, granule);
+                let mut output = [0.0; 576];
+                imdct(
+                    &decoded.side_info.granules[granule][0],
+                    &samples[0],
+                    &mut overlap,
+                    &mut output,
+                );
+                outputs.push((decoded.side_info.granules[granule][0].block_type, output));
+            }
+        }
+
+        let block_types = outputs.iter().map(|(block_type, _)| *block_type);
+        assert!(block_types.eq([
+            BlockType::Start,
+            BlockType::Short,
+            BlockType::Stop,
+            BlockType::Normal
+        ]));
+
+        // Subband 1 of the first 3 granules
+        assert_close(
+            &outputs[0].1[18..30],
+            &[
+                6.9398266e-5,
+                -9.1569585e-5,
+                -5.9346406e-5,
+                -0.00021364167,
+                -2.9434907e-5,
+                -3.2267388e-5,
+                0.00026094983,
+                -7.363986e-5,
+                -0.0004317012,
+                0.00047111916,
+                9.596933e-5,
+                -0.00040960917,
+            ],
+        );
+        assert_close(
+            &outputs[1].1[18..30],
+            &[
+                -0.0017361765,
+                0.0003516789,
+                -0.00014032572,
+                0.00045699743,
+                0.0005357561,
+                0.0021034563,
+                0.0039989594,
+                0.0041154325,
+                0.013763156,
+                0.036866523,
+                0.0126182735,
+                0.0036037862,
+            ],
+        );
+        assert_close(
+            &outputs[2].1[18..30],
+            &[
+                -0.0020943158,
+                0.0004046358,
+                0.0023870089,
+                0.0014724073,
+                -0.0013087491,
+                -0.0027056108,
+                -0.000997806,
+                0.0019864968,
+                0.0027167012,
+                0.0002740936,
+                -0.0025774324,
+                -0.0026656874,
+            ],
+        );
+        // symphonia's fast IMDCT rounds differently from the plain sums
+        assert!((energy(&outputs[0].1) / 1.6865350455572845e-5 - 1.0).abs() < 1e-4);
+        assert!((energy(&outputs[1].1) / 2.767791381265564e-1 - 1.0).abs() < 1e-4);
+        assert!((energy(&outputs[2].1) / 5.752889283006571e-1 - 1.0).abs() < 1e-4);
+    }
+}
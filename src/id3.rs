@@ -0,0 +1,1272 @@
+use crate::{
+    constants::*,
+    id3v1::{strip_id3v1, Genre},
+};
+use std::{ops::Range, time::Duration};
+
+// Size of an ID3v2 tag starting at the beginning of `data`, header and footer included. The
+// extended header, if any, is part of the size field.
+pub fn id3v2_size(data: &[u8]) -> Option<usize> {
+    if data.len() < ID3V2_HEADER_SIZE || &data[..3] != b"ID3" {
+        return None;
+    }
+
+    let size = syncsafe(&data[6..10])?;
+    let footer = if data[5] & ID3V2_FOOTER_FLAG != 0 {
+        ID3V2_HEADER_SIZE
+    } else {
+        0
+    };
+
+    Some(ID3V2_HEADER_SIZE + size + footer)
+}
+
+// Byte range of an ID3v2.4 tag appended at the end of the data, found through its "3DI" footer.
+// The tag can be followed by an ID3v1 tag.
+pub fn find_appended_id3v2(data: &[u8]) -> Option<Range<usize>> {
+    let end = strip_id3v1(data).len();
+
+    let footer = data.get(end.checked_sub(ID3V2_HEADER_SIZE)?..end)?;
+    if &footer[..3] != b"3DI" {
+        return None;
+    }
+
+    // The footer repeats the header, size excluding both
+    let size = syncsafe(&footer[6..10])?;
+    let start = end.checked_sub(size + 2 * ID3V2_HEADER_SIZE)?;
+    (id3v2_size(&data[start..]) == Some(end - start)).then_some(start..end)
+}
+
+// Position of the first ID3v2 tag starting at or after `from`
+pub(crate) fn find_id3v2(data: &[u8], from: usize) -> Option<usize> {
+    (from..data.len().saturating_sub(2)).find(|&position| id3v2_size(&data[position..]).is_some())
+}
+
+// 7 bits per byte, the most significant one of each must be clear
+fn syncsafe(bytes: &[u8]) -> Option<usize> {
+    bytes.iter().try_fold(0usize, |size, byte| {
+        (byte & 0x80 == 0).then_some(size << 7 | *byte as usize)
+    })
+}
+
+// Removes the 0x00 inserted after every 0xFF to keep the tag from looking like a frame sync
+fn resynchronise(bytes: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(bytes.len());
+    for (i, byte) in bytes.iter().enumerate() {
+        if *byte != 0x00 || i == 0 || bytes[i - 1] != 0xFF {
+            output.push(*byte);
+        }
+    }
+    output
+}
+
+// Frame ids are upper case letters or digits, 3 of them in 2.2 and 4 since
+fn is_frame_id(bytes: &[u8], length: usize) -> bool {
+    bytes.len() >= length
+        && bytes[..length]
+            .iter()
+            .all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit())
+}
+
+// 2.2 equivalent of the 2.3 frames read through the accessors
+fn v22_id(id: &str) -> Option<&'static str> {
+    match id {
+        "TIT2" => Some("TT2"),
+        "TPE1" => Some("TP1"),
+        "TALB" => Some("TAL"),
+        "TRCK" => Some("TRK"),
+        "TYER" => Some("TYE"),
+        "TCON" => Some("TCO"),
+        _ => None,
+    }
+}
+
+// Up to the first null, the terminator of Latin-1 and UTF-8 strings
+fn until_null(bytes: &[u8]) -> &[u8] {
+    let end = bytes
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(bytes.len());
+    &bytes[..end]
+}
+
+// Encoding 1 starts with a byte order mark, encoding 2 is UTF-16BE without one. Some taggers
+// repeat the mark or use it with encoding 2, a missing one is taken as big endian. The string ends
+// at the first null unit, an odd byte left at the end of unterminated data is dropped.
+fn decode_utf16(bytes: &[u8]) -> (String, bool) {
+    let (big_endian, bytes) = match bytes {
+        [0xFF, 0xFE, bytes @ ..] => (false, bytes),
+        [0xFE, 0xFF, bytes @ ..] => (true, bytes),
+        bytes => (true, bytes),
+    };
+
+    let chunks = bytes.chunks_exact(2);
+    let odd = !chunks.remainder().is_empty();
+    let mut units = chunks
+        .map(|unit| {
+            if big_endian {
+                u16::from_be_bytes([unit[0], unit[1]])
+            } else {
+                u16::from_le_bytes([unit[0], unit[1]])
+            }
+        })
+        .skip_while(|unit| *unit == 0xFEFF)
+        .collect::<Vec<_>>();
+
+    let terminated = match units.iter().position(|unit| *unit == 0) {
+        Some(end) => {
+            units.truncate(end);
+            true
+        }
+        None => false,
+    };
+
+    match String::from_utf16(&units) {
+        Ok(text) => (text, odd && !terminated),
+        Err(_) => (String::from_utf16_lossy(&units), true),
+    }
+}
+
+// String in one of the 4 text encodings, up to its terminator. None for an unknown encoding.
+fn decode_string(encoding: u8, bytes: &[u8]) -> Option<(String, bool)> {
+    Some(match encoding {
+        0 => (
+            until_null(bytes).iter().map(|byte| *byte as char).collect(),
+            false,
+        ),
+        1 | 2 => decode_utf16(bytes),
+        3 => match String::from_utf8(until_null(bytes).to_vec()) {
+            Ok(text) => (text, false),
+            Err(error) => (String::from_utf8_lossy(error.as_bytes()).into_owned(), true),
+        },
+        _ => return None,
+    })
+}
+
+// Splits a terminated string off the front of `bytes`, the terminator being a null unit (2 bytes,
+// aligned) for UTF-16 and a null byte otherwise. Without terminator the string takes everything.
+fn split_string(encoding: u8, bytes: &[u8]) -> (&[u8], &[u8]) {
+    let (width, end) = match encoding {
+        1 | 2 => (
+            2,
+            bytes
+                .chunks_exact(2)
+                .position(|unit| unit == [0, 0])
+                .map(|unit| unit * 2),
+        ),
+        _ => (1, bytes.iter().position(|byte| *byte == 0)),
+    };
+
+    match end {
+        Some(end) => (&bytes[..end], &bytes[end + width..]),
+        None => (bytes, &[]),
+    }
+}
+
+// Every value of a text frame, 2.4 separating them with nulls
+fn text_values(content: &[u8]) -> Vec<String> {
+    let Some((encoding, mut content)) = content.split_first() else {
+        return Vec::new();
+    };
+
+    let mut values = Vec::new();
+    while !content.is_empty() {
+        let (value, rest) = split_string(*encoding, content);
+        let Some((value, _)) = decode_string(*encoding, value) else {
+            break;
+        };
+        if !value.is_empty() {
+            values.push(value);
+        }
+        content = rest;
+    }
+
+    values
+}
+
+fn genre_name(reference: &str) -> Option<String> {
+    match reference {
+        "RX" => Some("Remix".to_owned()),
+        "CR" => Some("Cover".to_owned()),
+        index => Some(Genre::from_index(index.parse().ok()?)?.name().to_owned()),
+    }
+}
+
+// One TCON value. 2.3 writes references in parentheses, "(17)" or "(17)(80)", possibly followed
+// by a refinement of the last one: "(4)Eurodisco". "((" starts a name beginning with "(". 2.4
+// writes a single reference without parentheses, "17", or a name.
+fn parse_genres(value: &str) -> Vec<String> {
+    let mut genres = Vec::new();
+    let mut rest = value;
+
+    while let Some(inner) = rest.strip_prefix('(') {
+        if inner.starts_with('(') {
+            rest = inner;
+            break;
+        }
+        let Some((reference, after)) = inner.split_once(')') else {
+            break;
+        };
+        let Some(name) = genre_name(reference) else {
+            break;
+        };
+
+        genres.push(name);
+        rest = after;
+    }
+
+    if !rest.is_empty() {
+        let name = genre_name(rest).unwrap_or_else(|| rest.to_owned());
+        // The refinement replaces the reference it refines, often repeating its name
+        genres.pop_if(|_| rest.len() != value.len());
+        genres.push(name);
+    }
+
+    genres
+}
+
+// Value of a text frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextFrame {
+    pub text: String,
+    // Part of the text wasn't valid in its encoding, it was replaced by U+FFFD (or dropped, for
+    // an odd byte of UTF-16)
+    pub lossy: bool,
+}
+
+impl TextFrame {
+    // Frame content: an encoding byte then the text. 2.4 can hold several null separated values,
+    // only the first is kept. None for an unknown encoding.
+    pub fn decode(content: &[u8]) -> Option<Self> {
+        let (encoding, text) = content.split_first()?;
+        let (text, lossy) = decode_string(*encoding, text)?;
+
+        Some(Self { text, lossy })
+    }
+}
+
+// What an attached picture shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PictureType {
+    Other,
+    // 32x32 PNG
+    FileIcon,
+    OtherFileIcon,
+    FrontCover,
+    BackCover,
+    Leaflet,
+    // Label side of the CD
+    Media,
+    LeadArtist,
+    Artist,
+    Conductor,
+    Band,
+    Composer,
+    Lyricist,
+    RecordingLocation,
+    DuringRecording,
+    DuringPerformance,
+    ScreenCapture,
+    BrightColouredFish,
+    Illustration,
+    BandLogo,
+    PublisherLogo,
+    // Outside of the 21 defined types
+    Unknown(u8),
+}
+
+impl From<u8> for PictureType {
+    fn from(value: u8) -> Self {
+        match value {
+            0x00 => PictureType::Other,
+            0x01 => PictureType::FileIcon,
+            0x02 => PictureType::OtherFileIcon,
+            0x03 => PictureType::FrontCover,
+            0x04 => PictureType::BackCover,
+            0x05 => PictureType::Leaflet,
+            0x06 => PictureType::Media,
+            0x07 => PictureType::LeadArtist,
+            0x08 => PictureType::Artist,
+            0x09 => PictureType::Conductor,
+            0x0A => PictureType::Band,
+            0x0B => PictureType::Composer,
+            0x0C => PictureType::Lyricist,
+            0x0D => PictureType::RecordingLocation,
+            0x0E => PictureType::DuringRecording,
+            0x0F => PictureType::DuringPerformance,
+            0x10 => PictureType::ScreenCapture,
+            0x11 => PictureType::BrightColouredFish,
+            0x12 => PictureType::Illustration,
+            0x13 => PictureType::BandLogo,
+            0x14 => PictureType::PublisherLogo,
+            value => PictureType::Unknown(value),
+        }
+    }
+}
+
+// Attached picture (APIC frame, PIC in 2.2), its image borrowed from the tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Picture<'a> {
+    // "image/jpeg", "image/png"... In 2.2 the image format instead: "JPG", "PNG"...
+    pub mime_type: String,
+    pub picture_type: PictureType,
+    pub description: String,
+    pub data: &'a [u8],
+}
+
+impl<'a> Picture<'a> {
+    // APIC: encoding, Latin-1 MIME type, picture type, description in the frame encoding, image.
+    // PIC replaces the MIME type with a 3 character image format.
+    fn parse(version: u8, content: &'a [u8]) -> Option<Self> {
+        let (encoding, content) = content.split_first()?;
+
+        let (mime_type, content) = match version {
+            2 => (content.get(..3)?, content.get(3..)?),
+            _ => split_string(0, content),
+        };
+        let (picture_type, content) = content.split_first()?;
+        let (description, data) = split_string(*encoding, content);
+
+        Some(Self {
+            mime_type: decode_string(0, mime_type)?.0,
+            picture_type: PictureType::from(*picture_type),
+            description: decode_string(*encoding, description)?.0,
+            data,
+        })
+    }
+}
+
+// Unsynchronised lyrics (USLT frame, ULT in 2.2), told apart from other USLT frames by their
+// language and descriptor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lyrics {
+    // ISO 639-2 code, "eng", "fra"...
+    pub language: String,
+    pub descriptor: String,
+    // Line endings normalized to "\n"
+    pub text: String,
+}
+
+impl Lyrics {
+    // Encoding, 3 byte language, descriptor then lyrics, both in the frame encoding and the
+    // descriptor terminated as such
+    fn parse(content: &[u8]) -> Option<Self> {
+        let (encoding, content) = content.split_first()?;
+        let language = content.get(..3)?;
+        let (descriptor, text) = split_string(*encoding, content.get(3..)?);
+
+        Some(Self {
+            language: decode_string(0, language)?.0,
+            descriptor: decode_string(*encoding, descriptor)?.0,
+            text: decode_string(*encoding, text)?
+                .0
+                .replace("\r\n", "\n")
+                .replace('\r', "\n"),
+        })
+    }
+}
+
+// Chapter of a CHAP frame: its time span, the byte span of its audio when the tagger wrote
+// one, and the frames describing it (title, URL, picture...)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    // Element id, how the table of contents refers to the chapter
+    pub id: String,
+    pub start: Duration,
+    pub end: Duration,
+    // From the start of the file, None when left unset (0xFFFFFFFF)
+    pub start_offset: Option<u32>,
+    pub end_offset: Option<u32>,
+    frames: Vec<(String, Vec<u8>)>,
+}
+
+impl Chapter {
+    // Element id (null terminated), start and end time in ms, start and end byte offset, then
+    // sub-frames laid out as in the tag. None when a time or offset is missing.
+    fn parse(version: u8, content: &[u8]) -> Option<Self> {
+        let (id, content) = split_string(0, content);
+        let field = |index: usize| {
+            let bytes = content.get(4 * index..4 * index + 4)?;
+            Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+        };
+        let offset = |index: usize| field(index).filter(|offset| *offset != u32::MAX);
+
+        Some(Self {
+            id: decode_string(0, id)?.0,
+            start: Duration::from_millis(field(0)? as u64),
+            end: Duration::from_millis(field(1)? as u64),
+            start_offset: offset(2),
+            end_offset: offset(3),
+            // Unsynchronisation was undone on the whole CHAP frame
+            frames: Tag::parse_frames(version, content.get(16..)?, false),
+        })
+    }
+
+    // Sub-frames as (id, content)
+    pub fn frames(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.frames
+            .iter()
+            .map(|(id, content)| (id.as_str(), content.as_slice()))
+    }
+
+    pub fn title(&self) -> Option<String> {
+        self.frames()
+            .filter(|(id, _)| *id == "TIT2")
+            .find_map(|(_, content)| TextFrame::decode(content))
+            .map(|frame| frame.text)
+    }
+
+    // WXXX: encoding, description in that encoding, then the Latin-1 URL
+    pub fn url(&self) -> Option<String> {
+        let (_, content) = self.frames().find(|(id, _)| *id == "WXXX")?;
+        let (encoding, content) = content.split_first()?;
+        let (_, url) = split_string(*encoding, content);
+
+        Some(decode_string(0, url)?.0)
+    }
+
+    pub fn pictures(&self) -> impl Iterator<Item = Picture<'_>> {
+        self.frames()
+            .filter(|(id, _)| *id == "APIC")
+            .filter_map(|(_, content)| Picture::parse(3, content))
+    }
+}
+
+// CTOC frame, a list of chapters or nested tables
+struct TableOfContents<'a> {
+    id: &'a [u8],
+    top_level: bool,
+    // Element ids
+    entries: Vec<&'a [u8]>,
+}
+
+impl<'a> TableOfContents<'a> {
+    // Element id (null terminated), flags, entry count, then the null terminated element ids of
+    // the entries
+    fn parse(content: &'a [u8]) -> Option<Self> {
+        let (id, content) = split_string(0, content);
+        let (flags, content) = content.split_first()?;
+        let (count, mut content) = content.split_first()?;
+
+        let mut entries = Vec::new();
+        for _ in 0..*count {
+            let (entry, rest) = split_string(0, content);
+            entries.push(entry);
+            content = rest;
+        }
+
+        Some(Self {
+            id,
+            top_level: flags & ID3V2_CTOC_TOP_LEVEL_FLAG != 0,
+            entries,
+        })
+    }
+}
+
+// ID3v2.2, 2.3 or 2.4 tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    // 2, 3 or 4
+    version: u8,
+    // Id and content of every frame, in tag order
+    frames: Vec<(String, Vec<u8>)>,
+}
+
+impl Tag {
+    // Reads the tag at the start of `data`. Frames that can't be read (compressed or encrypted)
+    // are left out, parsing stops at the padding or at the first bytes that aren't a frame, and a
+    // tag cut short by the end of `data` keeps the frames before the cut.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let size = id3v2_size(data)?;
+        let version = data[3];
+        if !(2..=4).contains(&version) {
+            return None;
+        }
+
+        let flags = data[5];
+        // 2.2 never defined a compression scheme, such a tag can't be read
+        if version == 2 && flags & ID3V22_COMPRESSION_FLAG != 0 {
+            return Some(Self {
+                version,
+                frames: Vec::new(),
+            });
+        }
+
+        let footer = if flags & ID3V2_FOOTER_FLAG != 0 {
+            ID3V2_HEADER_SIZE
+        } else {
+            0
+        };
+        let body = &data[ID3V2_HEADER_SIZE..(size - footer).min(data.len())];
+        // 2.2 and 2.3 unsynchronise the whole tag, their frame sizes count the resynchronised
+        // bytes. 2.4 unsynchronises each frame on its own, its sizes count the bytes as stored. Its
+        // tag flag is meant to be repeated in the frame flags, some taggers only set the former.
+        let unsynchronised = flags & ID3V2_UNSYNCHRONISATION_FLAG != 0;
+        let body = if version < 4 && unsynchronised {
+            resynchronise(body)
+        } else {
+            body.to_vec()
+        };
+
+        let mut position = 0;
+        if version > 2 && flags & ID3V2_EXTENDED_HEADER_FLAG != 0 {
+            let bytes = body.get(..4)?;
+            // 2.3 doesn't count the size field itself and doesn't make it syncsafe
+            position = match version {
+                3 => 4 + u32::from_be_bytes(bytes.try_into().unwrap()) as usize,
+                _ => syncsafe(bytes)?,
+            };
+        }
+
+        let frames = Self::parse_frames(version, &body[position.min(body.len())..], unsynchronised);
+
+        Some(Self { version, frames })
+    }
+
+    // Frames back to back from the start of `body`, up to the padding or the first bytes that
+    // aren't a frame
+    fn parse_frames(version: u8, body: &[u8], unsynchronised: bool) -> Vec<(String, Vec<u8>)> {
+        let (id_length, header_size) = match version {
+            2 => (3, ID3V22_FRAME_HEADER_SIZE),
+            _ => (4, ID3V2_FRAME_HEADER_SIZE),
+        };
+
+        let mut position = 0;
+        let mut frames = Vec::new();
+        while let Some(header) = body.get(position..position + header_size) {
+            if !is_frame_id(header, id_length) {
+                break;
+            }
+
+            let start = position + header_size;
+            let size = Self::frame_size(version, body, position);
+            let end = (start + size).min(body.len());
+            position = start + size;
+
+            // 2.2 frames have no flags
+            let flags = if version == 2 { 0 } else { header[9] };
+            let content = Self::frame_content(version, flags, unsynchronised, &body[start..end]);
+            if let Some(content) = content {
+                let id = String::from_utf8_lossy(&header[..id_length]).into_owned();
+                frames.push((id, content));
+            }
+        }
+
+        frames
+    }
+
+    // 2.2 sizes are 3 bytes long. 2.4 sizes are syncsafe but some taggers wrote them as plain
+    // integers anyway, the one leading to another frame (or to the end of the tag) wins.
+    fn frame_size(version: u8, body: &[u8], position: usize) -> usize {
+        if version == 2 {
+            let bytes = &body[position + 3..position + 6];
+            return bytes
+                .iter()
+                .fold(0, |size, byte| size << 8 | *byte as usize);
+        }
+
+        let bytes = &body[position + 4..position + 8];
+        let plain = u32::from_be_bytes(bytes.try_into().unwrap()) as usize;
+        if version == 3 {
+            return plain;
+        }
+
+        let Some(syncsafe) = syncsafe(bytes) else {
+            return plain;
+        };
+        let followed = |size: usize| {
+            let next = position + ID3V2_FRAME_HEADER_SIZE + size;
+            match body.get(next..) {
+                Some(rest) => rest.first().is_none_or(|byte| *byte == 0) || is_frame_id(rest, 4),
+                None => false,
+            }
+        };
+
+        if followed(syncsafe) || !followed(plain) {
+            syncsafe
+        } else {
+            plain
+        }
+    }
+
+    // Strips what the frame flags (second byte) put in front of the content, and resynchronises
+    // 2.4 frames
+    fn frame_content(
+        version: u8,
+        flags: u8,
+        unsynchronised: bool,
+        content: &[u8],
+    ) -> Option<Vec<u8>> {
+        let (compressed, encrypted, extra) = match version {
+            2 => (false, false, 0),
+            3 => (
+                flags & 0x80 != 0,
+                flags & 0x40 != 0,
+                // Decompressed size, encryption method, group
+                4 * (flags >> 7 & 1) + (flags >> 6 & 1) + (flags >> 5 & 1),
+            ),
+            _ => (
+                flags & 0x08 != 0,
+                flags & 0x04 != 0,
+                // Group, encryption method, data length indicator
+                (flags >> 6 & 1) + (flags >> 2 & 1) + 4 * (flags & 1),
+            ),
+        };
+        if compressed || encrypted {
+            return None;
+        }
+
+        let content = content.get(extra as usize..)?;
+        if version == 4 && (unsynchronised || flags & 0x02 != 0) {
+            Some(resynchronise(content))
+        } else {
+            Some(content.to_vec())
+        }
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    // Every frame as (id, content), text frames included
+    pub fn frames(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.frames
+            .iter()
+            .map(|(id, content)| (id.as_str(), content.as_slice()))
+    }
+
+    // Value of the first text frame with this id. 2.3 ids of the frames the accessors read also
+    // find their 2.2 equivalent.
+    pub fn text(&self, id: &str) -> Option<String> {
+        self.text_frame(id).map(|frame| frame.text)
+    }
+
+    // Same as `text`, with the lossy flag for callers that can't accept replaced characters
+    pub fn text_frame(&self, id: &str) -> Option<TextFrame> {
+        let id = match self.version {
+            2 => v22_id(id).unwrap_or(id),
+            _ => id,
+        };
+
+        self.frames()
+            .filter(|(frame_id, _)| *frame_id == id)
+            .find_map(|(_, content)| TextFrame::decode(content))
+    }
+
+    // Value of the user defined text frame (TXXX) with this description, descriptions being case
+    // insensitive
+    pub fn user_text(&self, description: &str) -> Option<String> {
+        let id = match self.version {
+            2 => "TXX",
+            _ => "TXXX",
+        };
+
+        self.frames()
+            .filter(|(frame_id, _)| *frame_id == id)
+            .find_map(|(_, content)| {
+                let (encoding, content) = content.split_first()?;
+                let (name, value) = split_string(*encoding, content);
+                let (name, _) = decode_string(*encoding, name)?;
+
+                match name.eq_ignore_ascii_case(description) {
+                    true => Some(decode_string(*encoding, value)?.0),
+                    false => None,
+                }
+            })
+    }
+
+    pub fn title(&self) -> Option<String> {
+        self.text("TIT2")
+    }
+
+    pub fn artist(&self) -> Option<String> {
+        self.text("TPE1")
+    }
+
+    pub fn album(&self) -> Option<String> {
+        self.text("TALB")
+    }
+
+    // Track number, without the track count that may follow ("3/12")
+    pub fn track(&self) -> Option<u32> {
+        self.text("TRCK")?.split('/').next()?.trim().parse().ok()
+    }
+
+    // 2.4 stores a timestamp ("2004-05-01") in TDRC, 2.2 and 2.3 the year alone in TYE / TYER
+    pub fn year(&self) -> Option<u32> {
+        let text = self.text("TDRC").or_else(|| self.text("TYER"))?;
+        text.get(..4)?.parse().ok()
+    }
+
+    // First of `genres`
+    pub fn genre(&self) -> Option<String> {
+        self.genres().into_iter().next()
+    }
+
+    // Genres of the TCON frame, references to the ID3v1 genre list resolved to their name. The
+    // raw value is still available through `text("TCON")`.
+    pub fn genres(&self) -> Vec<String> {
+        let id = match self.version {
+            2 => "TCO",
+            _ => "TCON",
+        };
+
+        match self.frames().find(|(frame_id, _)| *frame_id == id) {
+            Some((_, content)) => text_values(content)
+                .iter()
+                .flat_map(|value| parse_genres(value))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Attached pictures in tag order, the ones with an unknown encoding left out
+    pub fn pictures(&self) -> impl Iterator<Item = Picture<'_>> {
+        let id = match self.version {
+            2 => "PIC",
+            _ => "APIC",
+        };
+
+        self.frames()
+            .filter(move |(frame_id, _)| *frame_id == id)
+            .filter_map(|(_, content)| Picture::parse(self.version, content))
+    }
+
+    // Lyrics in tag order, the ones with an unknown encoding left out
+    pub fn lyrics(&self) -> impl Iterator<Item = Lyrics> + '_ {
+        let id = match self.version {
+            2 => "ULT",
+            _ => "USLT",
+        };
+
+        self.frames()
+            .filter(move |(frame_id, _)| *frame_id == id)
+            .filter_map(|(_, content)| Lyrics::parse(content))
+    }
+
+    // Chapters in the order of the top level table of contents, nested tables flattened. Chapters
+    // it doesn't list follow, and without table of contents all of them are sorted by start time.
+    pub fn chapters(&self) -> Vec<Chapter> {
+        let mut chapters = self
+            .frames()
+            .filter(|(id, _)| *id == "CHAP")
+            .filter_map(|(_, content)| Chapter::parse(self.version, content))
+            .collect::<Vec<_>>();
+
+        let tables = self
+            .frames()
+            .filter(|(id, _)| *id == "CTOC")
+            .filter_map(|(_, content)| TableOfContents::parse(content))
+            .collect::<Vec<_>>();
+        let Some(top) = tables
+            .iter()
+            .find(|table| table.top_level)
+            .or(tables.first())
+        else {
+            chapters.sort_by_key(|chapter| chapter.start);
+            return chapters;
+        };
+
+        // Depth first through the tables, each one visited once in case they loop
+        let mut ordered = Vec::new();
+        let mut visited = vec![top.id];
+        let mut pending = top.entries.iter().rev().copied().collect::<Vec<_>>();
+        while let Some(entry) = pending.pop() {
+            if let Some(position) = chapters
+                .iter()
+                .position(|chapter| chapter.id.as_bytes() == entry)
+            {
+                ordered.push(chapters.remove(position));
+            } else if let Some(table) = tables.iter().find(|table| table.id == entry) {
+                if !visited.contains(&entry) {
+                    visited.push(entry);
+                    pending.extend(table.entries.iter().rev());
+                }
+            }
+        }
+
+        ordered.extend(chapters);
+        ordered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        frame::FrameIterator,
+        test_utils::{frames, HEADER_128},
+    };
+
+    // ID3v2.3 or 2.4 tag holding `frames`, without unsynchronisation
+    fn tag(version: u8, frames: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let size = |size: usize| match version {
+            3 => (size as u32).to_be_bytes(),
+            _ => [
+                (size >> 21) as u8 & 0x7F,
+                (size >> 14) as u8 & 0x7F,
+                (size >> 7) as u8 & 0x7F,
+                size as u8 & 0x7F,
+            ],
+        };
+
+        let mut body = Vec::new();
+        for (id, content) in frames {
+            body.extend(id.as_bytes());
+            body.extend(size(content.len()));
+            body.extend([0, 0]);
+            body.extend(content);
+        }
+
+        let mut tag = vec![b'I', b'D', b'3', version, 0, 0];
+        let length = body.len();
+        tag.extend([
+            (length >> 21) as u8 & 0x7F,
+            (length >> 14) as u8 & 0x7F,
+            (length >> 7) as u8 & 0x7F,
+            length as u8 & 0x7F,
+        ]);
+        tag.extend(body);
+        tag
+    }
+
+    #[test]
+    fn syncsafe_tag_sizes() {
+        // 300 bytes, 0x12C: 0x02 0x2C once split in 7 bit bytes
+        let mut data = b"ID3\x04\x00\x00\x00\x00\x02\x2C".to_vec();
+        data.resize(ID3V2_HEADER_SIZE + 300, 0);
+        assert_eq!(id3v2_size(&data), Some(310));
+
+        // The extended header is counted in the size, the footer isn't
+        data[5] = ID3V2_EXTENDED_HEADER_FLAG;
+        assert_eq!(id3v2_size(&data), Some(310));
+        data[5] = ID3V2_FOOTER_FLAG;
+        assert_eq!(id3v2_size(&data), Some(320));
+
+        // Largest size, 28 bits
+        assert_eq!(
+            id3v2_size(b"ID3\x04\x00\x00\x7F\x7F\x7F\x7F"),
+            Some(ID3V2_HEADER_SIZE + (1 << 28) - 1)
+        );
+        // Not syncsafe, or not a tag
+        assert_eq!(id3v2_size(b"ID3\x04\x00\x00\x00\x00\x02\x80"), None);
+        assert_eq!(id3v2_size(b"ID3\x04\x00\x00\x00\x00"), None);
+        assert_eq!(id3v2_size(b"TAG\x04\x00\x00\x00\x00\x02\x2C"), None);
+    }
+
+    #[test]
+    fn lame_tag() {
+        let data = include_bytes!("../fixtures/lame.id3");
+        // 384 bytes after the header, 0x03 0x00 as a syncsafe size
+        assert_eq!(id3v2_size(data), Some(data.len()));
+
+        let tag = Tag::parse(data).unwrap();
+        assert_eq!(tag.version(), 3);
+        assert_eq!(
+            tag.title().unwrap(),
+            "A title that is long enough to push the tag size past 127 bytes, for syncsafe"
+        );
+        assert_eq!(tag.artist().unwrap(), "Some Artist");
+        assert_eq!(tag.album().unwrap(), "The Album");
+        assert_eq!(tag.year(), Some(2004));
+        assert_eq!(tag.track(), Some(3));
+        assert_eq!(tag.genre().unwrap(), "Rock");
+        assert!(tag.text("TSSE").unwrap().starts_with("LAME"));
+
+        // The padding isn't read as frames
+        let ids: Vec<_> = tag.frames().map(|(id, _)| id).collect();
+        assert_eq!(
+            ids,
+            ["TSSE", "TIT2", "TPE1", "TALB", "TYER", "TRCK", "TCON", "COMM"]
+        );
+        let (_, comment) = tag.frames().last().unwrap();
+        assert_eq!(&comment[..4], b"\0eng");
+    }
+
+    #[test]
+    fn malformed_frames_are_left_out() {
+        let mut data = include_bytes!("../fixtures/lame.id3").to_vec();
+        // TPE1 flagged as compressed, TALB with an unknown text encoding
+        data[0xA4] = 0x80;
+        data[0xBB] = 7;
+
+        let tag = Tag::parse(&data).unwrap();
+        assert_eq!(tag.artist(), None);
+        assert_eq!(tag.album(), None);
+        assert_eq!(tag.frames().count(), 7);
+        assert_eq!(tag.year(), Some(2004));
+        assert_eq!(tag.genre().unwrap(), "Rock");
+    }
+
+    #[test]
+    fn unsynchronised_tags() {
+        // The JPEG-like picture of fixtures/unsync23.id3 and unsync24.id3, as written
+        let image = [
+            &[0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10][..],
+            b"JFIF\0",
+            &[0xFF; 8],
+            &[0xFF, 0x00].repeat(4),
+            &(0xE0..=0xFF).collect::<Vec<u8>>(),
+            &[0xFF, 0xD9, 0xFF],
+        ]
+        .concat();
+
+        for data in [
+            &include_bytes!("../fixtures/unsync23.id3")[..],
+            include_bytes!("../fixtures/unsync24.id3"),
+        ] {
+            // Skipping goes by the bytes as stored
+            assert_eq!(id3v2_size(data), Some(data.len()));
+            let stream = [data, &frames(&[HEADER_128; 2])].concat();
+            let first = FrameIterator::new(&stream).flatten().next().unwrap();
+            assert_eq!(first.offset, data.len());
+
+            let tag = Tag::parse(data).unwrap();
+            assert_eq!(tag.title().unwrap(), "Unsynchronised");
+            let pictures: Vec<_> = tag.pictures().collect();
+            assert_eq!(pictures.len(), 1);
+            assert_eq!(pictures[0].mime_type, "image/jpeg");
+            assert_eq!(pictures[0].description, "cover");
+            assert_eq!(pictures[0].data, image);
+        }
+    }
+
+    #[test]
+    fn v22_tag() {
+        let data = include_bytes!("../fixtures/itunes.id3");
+        let tag = Tag::parse(data).unwrap();
+        assert_eq!(tag.version(), 2);
+        assert_eq!(tag.title().unwrap(), "Old Rip");
+        assert_eq!(tag.artist().unwrap(), "Some Artist");
+        assert_eq!(tag.album().unwrap(), "The Album");
+        assert_eq!(tag.track(), Some(3));
+        assert_eq!(tag.year(), Some(2003));
+        assert_eq!(tag.genre().unwrap(), "Rock");
+
+        // Frames without an accessor keep their 3 character id
+        let ids: Vec<_> = tag.frames().map(|(id, _)| id).collect();
+        assert_eq!(
+            ids,
+            ["TT2", "TP1", "TAL", "TRK", "TYE", "TCO", "TEN", "COM"]
+        );
+        assert_eq!(tag.text("TEN").unwrap(), "iTunes v4.2");
+
+        // A compressed tag has no readable frame, but is still skipped whole
+        let mut compressed = data.to_vec();
+        compressed[5] = ID3V22_COMPRESSION_FLAG;
+        assert_eq!(Tag::parse(&compressed).unwrap().frames().count(), 0);
+
+        for data in [&data[..], &compressed] {
+            let stream = [data, &frames(&[HEADER_128; 2])].concat();
+            let first = FrameIterator::new(&stream).flatten().next().unwrap();
+            assert_eq!(first.offset, data.len());
+        }
+    }
+
+    // Latin-1 text frame content
+    fn text(value: &str) -> Vec<u8> {
+        [&[0], value.as_bytes()].concat()
+    }
+
+    #[test]
+    fn v24_frame_sizes() {
+        // 300 bytes, 0x02 0x2C once syncsafe
+        let title = "t".repeat(299);
+        let data = tag(4, &[("TIT2", text(&title)), ("TPE1", text("Artist"))]);
+        assert_eq!(data[14..18], [0, 0, 0x02, 0x2C]);
+        let tag_24 = Tag::parse(&data).unwrap();
+        assert_eq!(tag_24.title().unwrap(), title);
+        assert_eq!(tag_24.artist().unwrap(), "Artist");
+
+        // The same frames sized as 2.3 does, 0x100 read as syncsafe would be 128 bytes. The plain
+        // size is the one leading to the next frame.
+        let title = "t".repeat(255);
+        let mut data = tag(4, &[("TIT2", text(&title)), ("TPE1", text("Artist"))]);
+        data[14..18].copy_from_slice(&256u32.to_be_bytes());
+        let broken = Tag::parse(&data).unwrap();
+        assert_eq!(broken.title().unwrap(), title);
+        assert_eq!(broken.artist().unwrap(), "Artist");
+    }
+
+    #[test]
+    fn appended_tag() {
+        let mut appended = tag(4, &[("TIT2", text("At the end"))]);
+        appended[5] = ID3V2_FOOTER_FLAG;
+        let footer = [b"3DI", &appended[3..ID3V2_HEADER_SIZE]].concat();
+        appended.extend(footer);
+
+        let audio = frames(&[HEADER_128; 3]);
+        let data = [&audio[..], &appended].concat();
+        assert_eq!(id3v2_size(&appended), Some(appended.len()));
+        assert_eq!(find_appended_id3v2(&data), Some(audio.len()..data.len()));
+        assert_eq!(
+            Tag::parse(&appended).unwrap().title().unwrap(),
+            "At the end"
+        );
+
+        // Followed by an ID3v1 tag
+        let mut id3v1 = b"TAG".to_vec();
+        id3v1.resize(128, 0);
+        let with_v1 = [&data[..], &id3v1].concat();
+        assert_eq!(find_appended_id3v2(&with_v1), Some(audio.len()..data.len()));
+
+        // The frame scan stops at the tag
+        for data in [&data, &with_v1] {
+            let frames: Vec<_> = FrameIterator::new(data).collect();
+            assert_eq!(frames.len(), 3);
+            assert!(frames.iter().all(Result::is_ok));
+        }
+        assert_eq!(find_appended_id3v2(&audio), None);
+    }
+
+    fn decode(content: &[u8]) -> (String, bool) {
+        let frame = TextFrame::decode(content).unwrap();
+        (frame.text, frame.lossy)
+    }
+
+    #[test]
+    fn text_encodings() {
+        let owned = |text: &str, lossy| (text.to_owned(), lossy);
+
+        // Latin-1 bytes from 0x80 up are their code point
+        assert_eq!(decode(b"\0Caf\xE9 \xDF\xFF"), owned("Café ßÿ", false));
+        assert_eq!(decode(b"\0Title\0Other"), owned("Title", false));
+
+        // UTF-16 with a little endian BOM, up to the null unit
+        let mut title = vec![1, 0xFF, 0xFE];
+        title.extend("Zoë ♪".encode_utf16().flat_map(u16::to_le_bytes));
+        title.extend([0, 0, b'x', 0]);
+        assert_eq!(decode(&title), owned("Zoë ♪", false));
+
+        // The BOM repeated, or missing and taken as big endian
+        assert_eq!(decode(b"\x01\xFF\xFE\xFF\xFEA\0"), owned("A", false));
+        assert_eq!(decode(b"\x01\0A\0B"), owned("AB", false));
+        // UTF-16BE without BOM
+        assert_eq!(decode(b"\x02\0A\0\xE9\0\0"), owned("Aé", false));
+        // An odd byte left at the end, an unpaired surrogate
+        assert_eq!(decode(b"\x02\0A\0"), owned("A", true));
+        assert_eq!(decode(b"\x02\xD8\0\0A"), owned("\u{FFFD}A", true));
+
+        // UTF-8, with an invalid byte
+        assert_eq!(decode("\u{3}Zoë ♪\0x".as_bytes()), owned("Zoë ♪", false));
+        assert_eq!(decode(b"\x03A\xFFB"), owned("A\u{FFFD}B", true));
+
+        assert_eq!(TextFrame::decode(b"\x04Title"), None);
+        assert_eq!(TextFrame::decode(b""), None);
+        assert_eq!(decode(b"\x01"), owned("", false));
+    }
+
+    #[test]
+    fn attached_pictures() {
+        let image = [0x00, 0x89, b'P', b'N', b'G', 0x00, 0x00, 0xFF];
+        // UTF-16 description "A": its last byte and the terminator make 3 nulls in a row
+        let front = [b"\x01image/png\0\x03\xFF\xFEA\0\0\0", &image[..]].concat();
+        let back = [b"\0image/jpeg\0\x04Back\0", &image[..]].concat();
+        let data = tag(
+            3,
+            &[("APIC", front), ("TIT2", text("Title")), ("APIC", back)],
+        );
+
+        let tag = Tag::parse(&data).unwrap();
+        let pictures: Vec<_> = tag.pictures().collect();
+        assert_eq!(pictures.len(), 2);
+        assert_eq!(pictures[0].mime_type, "image/png");
+        assert_eq!(pictures[0].picture_type, PictureType::FrontCover);
+        assert_eq!(pictures[0].description, "A");
+        assert_eq!(pictures[0].data, image);
+        assert_eq!(pictures[1].mime_type, "image/jpeg");
+        assert_eq!(pictures[1].picture_type, PictureType::BackCover);
+        assert_eq!(pictures[1].description, "Back");
+        assert_eq!(pictures[1].data, image);
+
+        // Borrowed from the frame, not copied
+        let (_, content) = tag.frames().next().unwrap();
+        assert_eq!(
+            pictures[0].data.as_ptr(),
+            content[content.len() - image.len()..].as_ptr()
+        );
+
+        // 2.2 PIC, a 3 character image format instead of the MIME type
+        let content = [b"\0PNG\x03\0", &image[..]].concat();
+        let mut data = b"ID3\x02\0\0\0\0\0".to_vec();
+        data.push(6 + content.len() as u8);
+        data.extend(b"PIC\0\0");
+        data.push(content.len() as u8);
+        data.extend(&content);
+
+        let tag = Tag::parse(&data).unwrap();
+        let pictures: Vec<_> = tag.pictures().collect();
+        assert_eq!(pictures.len(), 1);
+        assert_eq!(pictures[0].mime_type, "PNG");
+        assert_eq!(pictures[0].picture_type, PictureType::FrontCover);
+        assert_eq!(pictures[0].description, "");
+        assert_eq!(pictures[0].data, image);
+    }
+
+    fn chap(id: &str, start: u32, end: u32) -> Vec<u8> {
+        let mut content = id.as_bytes().to_vec();
+        content.push(0);
+        for field in [start, end, u32::MAX, u32::MAX] {
+            content.extend(field.to_be_bytes());
+        }
+        content
+    }
+
+    #[test]
+    fn truncated_chapter_is_skipped() {
+        for length in 8..16 {
+            let mut short = b"bad\0".to_vec();
+            short.extend(vec![0; length]);
+            let data = tag(4, &[("CHAP", short), ("CHAP", chap("ch1", 0, 1000))]);
+
+            let chapters = Tag::parse(&data).unwrap().chapters();
+            assert_eq!(chapters.len(), 1);
+            assert_eq!(chapters[0].id, "ch1");
+            assert_eq!(chapters[0].end, Duration::from_millis(1000));
+        }
+    }
+
+    #[test]
+    fn podcast_chapters() {
+        let tag = Tag::parse(include_bytes!("../fixtures/chapters.id3")).unwrap();
+        assert_eq!(tag.title().unwrap(), "Episode 12");
+
+        // In the order of the table of contents, not of the tag
+        let chapters = tag.chapters();
+        let ids: Vec<_> = chapters.iter().map(|chapter| chapter.id.as_str()).collect();
+        assert_eq!(ids, ["chp0", "chp1", "chp2"]);
+
+        let titles: Vec<_> = chapters
+            .iter()
+            .map(|chapter| chapter.title().unwrap())
+            .collect();
+        assert_eq!(titles, ["Intro", "Main topic", "Outro"]);
+        assert_eq!(chapters[1].start, Duration::from_millis(95000));
+        assert_eq!(chapters[1].end, Duration::from_millis(1800500));
+        assert_eq!(chapters[1].start_offset, None);
+        assert_eq!(chapters[1].end_offset, None);
+
+        assert_eq!(chapters[1].url().unwrap(), "https://example.com/notes");
+        assert_eq!(chapters[0].url(), None);
+
+        let pictures: Vec<_> = chapters[0].pictures().collect();
+        assert_eq!(pictures.len(), 1);
+        assert_eq!(pictures[0].mime_type, "image/png");
+        assert_eq!(&pictures[0].data[1..4], b"PNG");
+        assert_eq!(pictures[0].data.len(), 24);
+        assert_eq!(chapters[2].pictures().count(), 0);
+    }
+
+    #[test]
+    fn tcon_genres() {
+        let genres = |version, value: &str| {
+            Tag::parse(&tag(version, &[("TCON", text(value))]))
+                .unwrap()
+                .genres()
+        };
+
+        assert_eq!(genres(3, "(17)"), ["Rock"]);
+        assert_eq!(genres(3, "(17)(80)"), ["Rock", "Folk"]);
+        // A refinement replaces the reference before it
+        assert_eq!(genres(3, "(17)Rock"), ["Rock"]);
+        assert_eq!(genres(3, "(4)Eurodisco"), ["Eurodisco"]);
+        assert_eq!(genres(3, "(RX)(CR)"), ["Remix", "Cover"]);
+        assert_eq!(genres(3, "((Parenthesised)"), ["(Parenthesised)"]);
+        assert_eq!(genres(3, "Shoegaze"), ["Shoegaze"]);
+
+        // 2.4 references without parentheses, several null separated values
+        assert_eq!(genres(4, "17"), ["Rock"]);
+        assert_eq!(genres(4, "17\080\0Shoegaze"), ["Rock", "Folk", "Shoegaze"]);
+
+        // The raw value is still there
+        let data = tag(3, &[("TCON", text("(17)Rock"))]);
+        let tag = Tag::parse(&data).unwrap();
+        assert_eq!(tag.text("TCON").unwrap(), "(17)Rock");
+        assert_eq!(tag.genre().unwrap(), "Rock");
+    }
+
+    #[test]
+    fn user_text_frames() {
+        let txxx = |encoding: u8, description: &[u8], value: &[u8]| {
+            [&[encoding], description, value].concat()
+        };
+        let utf16 = |text: &str| {
+            let mut bytes = vec![0xFF, 0xFE];
+            bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            bytes
+        };
+
+        let data = tag(
+            3,
+            &[
+                ("TXXX", txxx(0, b"replaygain_track_gain\0", b"-6.48 dB")),
+                (
+                    "TXXX",
+                    txxx(
+                        1,
+                        &[utf16("REPLAYGAIN_ALBUM_GAIN"), vec![0, 0]].concat(),
+                        &utf16("-7.01 dB"),
+                    ),
+                ),
+                ("TXXX", txxx(0, b"iTunNORM\0", b" 0000044E")),
+            ],
+        );
+        let tag = Tag::parse(&data).unwrap();
+
+        // Descriptions are case insensitive
+        assert_eq!(tag.user_text("REPLAYGAIN_TRACK_GAIN").unwrap(), "-6.48 dB");
+        assert_eq!(tag.user_text("replaygain_album_gain").unwrap(), "-7.01 dB");
+        assert_eq!(tag.user_text("iTunNORM").unwrap(), " 0000044E");
+        assert_eq!(tag.user_text("REPLAYGAIN_TRACK_PEAK"), None);
+    }
+
+    #[test]
+    fn lyrics() {
+        let utf16 = |text: &str| -> Vec<u8> {
+            [0xFF, 0xFE]
+                .into_iter()
+                .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+                .collect()
+        };
+        let french = [
+            &[1][..],
+            b"fra",
+            &utf16("Refrain"),
+            &[0, 0],
+            &utf16("Un\r\nDeux"),
+        ]
+        .concat();
+
+        let data = tag(
+            3,
+            &[
+                ("USLT", b"\0engVerse\0One\r\nTwo\rThree\n".to_vec()),
+                ("USLT", french),
+                ("USLT", b"\x05engBad\0Encoding".to_vec()),
+            ],
+        );
+        let lyrics: Vec<_> = Tag::parse(&data).unwrap().lyrics().collect();
+        let entry = |language: &str, descriptor: &str, text: &str| Lyrics {
+            language: language.to_owned(),
+            descriptor: descriptor.to_owned(),
+            text: text.to_owned(),
+        };
+        assert_eq!(
+            lyrics,
+            [
+                entry("eng", "Verse", "One\nTwo\nThree\n"),
+                entry("fra", "Refrain", "Un\nDeux"),
+            ]
+        );
+
+        // ULT in 2.2, with an empty descriptor
+        let content = b"\0eng\0La la";
+        let mut data = b"ID3\x02\0\0\0\0\0".to_vec();
+        data.push(6 + content.len() as u8);
+        data.extend(b"ULT\0\0");
+        data.push(content.len() as u8);
+        data.extend(content);
+        let lyrics: Vec<_> = Tag::parse(&data).unwrap().lyrics().collect();
+        assert_eq!(lyrics, [entry("eng", "", "La la")]);
+    }
+}
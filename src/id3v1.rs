@@ -0,0 +1,165 @@
+use crate::constants::*;
+
+// `data` without the ID3v1 tag ending it, if any
+pub(crate) fn strip_id3v1(data: &[u8]) -> &[u8] {
+    match data.len().checked_sub(ID3V1_SIZE) {
+        Some(start) if data[start..].starts_with(b"TAG") => &data[..start],
+        _ => data,
+    }
+}
+
+// Fixed width Latin-1 field, padded with spaces or nulls
+fn field(bytes: &[u8]) -> String {
+    let end = bytes
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(bytes.len());
+    let text = bytes[..end]
+        .iter()
+        .map(|byte| *byte as char)
+        .collect::<String>();
+
+    text.trim_end().to_owned()
+}
+
+// Entry of the ID3v1 genre list, also referenced by ID3v2 genre frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Genre(u8);
+
+impl Genre {
+    // None past the 192 listed genres
+    pub fn from_index(index: u8) -> Option<Self> {
+        (usize::from(index) < GENRES.len()).then_some(Self(index))
+    }
+
+    pub fn index(&self) -> u8 {
+        self.0
+    }
+
+    pub fn name(&self) -> &'static str {
+        GENRES[self.0 as usize]
+    }
+}
+
+// The 128 bytes ending the file: "TAG", title (30), artist (30), album (30), year (4),
+// comment (30) and genre (1). ID3v1.1 takes the last 2 comment bytes for a null then the track.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Id3v1Tag {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub year: Option<u32>,
+    pub comment: String,
+    // ID3v1.1 only
+    pub track: Option<u8>,
+    // Index in the ID3v1 genre list as stored, None when unset (255)
+    pub genre_index: Option<u8>,
+}
+
+impl Id3v1Tag {
+    // Reads the tag at the end of `data`
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let tag = data.get(data.len().checked_sub(ID3V1_SIZE)?..)?;
+        if &tag[..3] != b"TAG" {
+            return None;
+        }
+
+        let comment = &tag[97..127];
+        let (comment, track) = match comment[28..] {
+            [0, track] if track != 0 => (&comment[..28], Some(track)),
+            _ => (comment, None),
+        };
+
+        Some(Self {
+            title: field(&tag[3..33]),
+            artist: field(&tag[33..63]),
+            album: field(&tag[63..93]),
+            year: field(&tag[93..97]).trim().parse().ok(),
+            comment: field(comment),
+            track,
+            genre_index: (tag[127] != 255).then_some(tag[127]),
+        })
+    }
+
+    // None when unset or past the listed genres
+    pub fn genre(&self) -> Option<Genre> {
+        Genre::from_index(self.genre_index?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analysis::total_samples, frame::FrameIterator};
+
+    #[test]
+    fn lame_tag() {
+        let tag = Id3v1Tag::parse(include_bytes!("../fixtures/lame.id3v1")).unwrap();
+        assert_eq!(tag.title, "Short title");
+        assert_eq!(tag.artist, "Some Artist");
+        assert_eq!(tag.album, "The Album");
+        assert_eq!(tag.year, Some(2004));
+        assert_eq!(tag.comment, "a comment");
+        assert_eq!(tag.track, Some(7));
+        assert_eq!(tag.genre().unwrap().name(), "Jazz");
+    }
+
+    #[test]
+    fn v10_tag() {
+        // Space padded, the comment running to its 30th byte, no genre
+        let mut data = b"TAG".to_vec();
+        for (value, width) in [("Title", 30), ("Artist", 30), ("Album", 30), ("1999", 4)] {
+            data.extend(format!("{value:width$}").bytes());
+        }
+        data.extend(b"A comment that takes 30 bytes.");
+        data.push(255);
+
+        let tag = Id3v1Tag::parse(&data).unwrap();
+        assert_eq!(tag.title, "Title");
+        assert_eq!(tag.artist, "Artist");
+        assert_eq!(tag.comment, "A comment that takes 30 bytes.");
+        assert_eq!(tag.year, Some(1999));
+        assert_eq!(tag.track, None);
+        assert_eq!(tag.genre_index, None);
+        assert!(Id3v1Tag::parse(&data[1..]).is_none());
+    }
+
+    #[test]
+    fn tags_at_both_ends() {
+        let cbr = include_bytes!("../fixtures/cbr.mp3");
+        let front = include_bytes!("../fixtures/lame.id3");
+        let back = include_bytes!("../fixtures/lame.id3v1");
+        let data = [&front[..], cbr, back].concat();
+
+        assert_eq!(strip_id3v1(&data).len(), data.len() - ID3V1_SIZE);
+        assert_eq!(Id3v1Tag::parse(&data).unwrap().track, Some(7));
+
+        // Neither tag is taken for audio or garbage
+        let frames: Vec<_> = FrameIterator::new(&data).collect();
+        assert!(frames.iter().all(Result::is_ok));
+        assert_eq!(frames.len(), FrameIterator::new(cbr).count());
+        let last = frames.last().unwrap().as_ref().unwrap();
+        assert_eq!(last.offset + last.data.len(), data.len() - ID3V1_SIZE);
+        assert_eq!(total_samples(&data, true).unwrap(), 11025);
+    }
+
+    #[test]
+    fn genre_list() {
+        let name = |index| Genre::from_index(index).map(|genre| genre.name());
+        assert_eq!(name(0), Some("Blues"));
+        assert_eq!(name(17), Some("Rock"));
+        // The last standard genre, the first and last Winamp extensions
+        assert_eq!(name(79), Some("Hard Rock"));
+        assert_eq!(name(80), Some("Folk"));
+        assert_eq!(name(191), Some("Psybient"));
+        assert_eq!(name(192), None);
+        assert_eq!(Genre::from_index(42).unwrap().index(), 42);
+
+        // The raw index is kept even when it isn't listed
+        let mut data = include_bytes!("../fixtures/lame.id3v1").to_vec();
+        data[127] = 200;
+        let tag = Id3v1Tag::parse(&data).unwrap();
+        assert_eq!(tag.genre_index, Some(200));
+        assert_eq!(tag.genre(), None);
+    }
+}
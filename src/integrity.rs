@@ -0,0 +1,175 @@
+use crate::{
+    crc::verify_crc,
+    error::ChompError,
+    frame::{audio_region, FrameIterator, StreamEvent},
+    header::Header,
+};
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    // The frame's CRC doesn't match its protected bits
+    CrcMismatch,
+    // The frame doesn't use the sample rate of the frames before it
+    SampleRateChanged { expected: usize, found: usize },
+    // A header with a reserved value where a frame was expected
+    Reserved(&'static str),
+    // A frame cut short, by the next frame or by the end of the audio
+    Truncated,
+    // Bytes between frames that don't start with a header
+    InvalidData { length: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntegrityIssue {
+    // Of the frame, or of the first byte that isn't part of one
+    pub offset: usize,
+    pub kind: IssueKind,
+}
+
+// Every anomaly of the audio, in stream order. Frames are found as `FrameIterator` does, the
+// bytes between them are then looked at to tell a damaged header from a truncated frame or
+// garbage. ID3v2 tags between frames aren't reported, a sample rate change after them is.
+pub fn scan_integrity(data: &[u8]) -> Vec<IntegrityIssue> {
+    let audio = audio_region(data);
+    let mut issues = Vec::new();
+    let mut sample_rate = None;
+    let mut position = audio.start;
+
+    for event in FrameIterator::new(data).events().flatten() {
+        let range = match &event {
+            StreamEvent::Frame(frame) => frame.offset..frame.offset + frame.data.len(),
+            StreamEvent::Id3v2(tag) => tag.clone(),
+        };
+        if range.start > position {
+            issues.push(gap_issue(&data[..audio.end], position..range.start));
+        }
+        position = range.end;
+
+        let StreamEvent::Frame(frame) = event else {
+            continue;
+        };
+        if verify_crc(frame.data, &frame.header) == Some(false) {
+            issues.push(IntegrityIssue {
+                offset: frame.offset,
+                kind: IssueKind::CrcMismatch,
+            });
+        }
+
+        let found = frame.header.sample_rate();
+        if let Some(expected) = sample_rate.filter(|expected| *expected != found) {
+            issues.push(IntegrityIssue {
+                offset: frame.offset,
+                kind: IssueKind::SampleRateChanged { expected, found },
+            });
+        }
+        sample_rate = Some(found);
+    }
+
+    if position < audio.end {
+        issues.push(gap_issue(&data[..audio.end], position..audio.end));
+    }
+
+    issues
+}
+
+// What the bytes of `gap`, between two frames, were meant to be
+fn gap_issue(data: &[u8], gap: Range<usize>) -> IntegrityIssue {
+    let kind = match Header::from_bytes(&data[gap.start..]) {
+        Ok(header) if gap.start + header.frame_length() > gap.end => IssueKind::Truncated,
+        Err(ChompError::Reserved(field)) => IssueKind::Reserved(field),
+        Err(ChompError::ReservedVersion) => IssueKind::Reserved("version"),
+        _ => IssueKind::InvalidData { length: gap.len() },
+    };
+
+    IntegrityIssue {
+        offset: gap.start,
+        kind,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{frames, HEADER_128};
+
+    #[test]
+    fn one_broken_crc() {
+        let mut data = include_bytes!("../fixtures/crc.mp3").to_vec();
+        let offsets: Vec<_> = FrameIterator::new(&data)
+            .flatten()
+            .map(|frame| frame.offset)
+            .collect();
+
+        // A side info byte, after the header and its CRC
+        data[offsets[3] + 8] ^= 0x10;
+        assert_eq!(
+            scan_integrity(&data),
+            [IntegrityIssue {
+                offset: offsets[3],
+                kind: IssueKind::CrcMismatch,
+            }]
+        );
+    }
+
+    #[test]
+    fn clean_files() {
+        for data in [
+            &include_bytes!("../fixtures/cbr.mp3")[..],
+            include_bytes!("../fixtures/crc.mp3"),
+            include_bytes!("../fixtures/vbr.mp3"),
+        ] {
+            assert_eq!(scan_integrity(data), []);
+        }
+    }
+
+    #[test]
+    fn damaged_streams() {
+        let issue = |offset, kind| IntegrityIssue { offset, kind };
+
+        // 128 kbit/s at 48000 Hz, 384 bytes
+        let data = frames(&[[HEADER_128; 3], [[0xFF, 0xFB, 0x94, 0x40]; 3]].concat());
+        assert_eq!(
+            scan_integrity(&data),
+            [issue(
+                3 * 417,
+                IssueKind::SampleRateChanged {
+                    expected: 44100,
+                    found: 48000
+                }
+            )]
+        );
+
+        // Reserved emphasis
+        let mut data = frames(&[HEADER_128; 4]);
+        data[2 * 417 + 3] = 0x42;
+        assert_eq!(
+            scan_integrity(&data),
+            [issue(2 * 417, IssueKind::Reserved("emphasis"))]
+        );
+
+        // Cut short by the next frame, at the start of the audio, then by the end of the data
+        let mut data = frames(&[HEADER_128; 5]);
+        data.drain(100..417);
+        data.truncate(data.len() - 17);
+        assert_eq!(
+            scan_integrity(&data),
+            [
+                issue(0, IssueKind::Truncated),
+                issue(100 + 3 * 417, IssueKind::Truncated),
+            ]
+        );
+
+        // Garbage between frames
+        let data = [
+            frames(&[HEADER_128; 2]),
+            vec![0x55; 50],
+            frames(&[HEADER_128; 2]),
+        ]
+        .concat();
+        assert_eq!(
+            scan_integrity(&data),
+            [issue(2 * 417, IssueKind::InvalidData { length: 50 })]
+        );
+    }
+}
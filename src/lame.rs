@@ -0,0 +1,393 @@
+use crate::{constants::*, crc::lame_crc, frame::Frame, xing::XingHeader};
+
+// How LAME allocated the bitrate, the low nibble of the tag revision byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VbrMethod {
+    Unknown,
+    Cbr,
+    Abr,
+    // VBR methods 1 to 4, 1 being the old "rh" algorithm and 2 the default "mtrh" one
+    V1,
+    V2,
+    V3,
+    V4,
+    CbrTwoPass,
+    AbrTwoPass,
+    Reserved(u8),
+}
+
+impl From<u8> for VbrMethod {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => VbrMethod::Unknown,
+            1 => VbrMethod::Cbr,
+            2 => VbrMethod::Abr,
+            3 => VbrMethod::V1,
+            4 => VbrMethod::V2,
+            5 => VbrMethod::V3,
+            6 => VbrMethod::V4,
+            8 => VbrMethod::CbrTwoPass,
+            9 => VbrMethod::AbrTwoPass,
+            value => VbrMethod::Reserved(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LameTag {
+    // Encoder version (9 bytes)
+    // "LAME3.100", "LAME3.99r"... padded with nulls or spaces when shorter.
+    encoder: [u8; LAME_ENCODER_SIZE],
+
+    // Tag revision (4 bits) and VBR method (4 bits)
+    revision: u8,
+    vbr_method: VbrMethod,
+
+    // Lowpass filter frequency (8 bits), in 100 Hz units, 0 when unknown
+    lowpass: u8,
+
+    // Peak signal amplitude (32 bits), a big endian float where 1.0 is full scale, 0 when unknown
+    peak: u32,
+
+    // Replay gain fields (16 bits each)
+    // 3 bits name code (0 = not set, 1 = radio, 2 = audiophile), 3 bits originator code,
+    // 1 sign bit (set means negative) and 9 bits of magnitude in 0.1 dB units.
+    radio_gain: u16,
+    audiophile_gain: u16,
+
+    // Encoding flags (4 bits) and ATH type (4 bits)
+    // Flags are nspsytune, nssafejoint, nogap with the next track and nogap with the previous one.
+    flags: u8,
+    ath_type: u8,
+
+    // Bitrate (8 bits), in kbit/s: the target for ABR, the minimum for VBR. 255 means 255 or more.
+    bitrate: u8,
+
+    // Encoder delay and padding (12 bits each)
+    // Samples the encoder added at the start and at the end of the stream.
+    delay: u16,
+    padding: u16,
+
+    // Source sample rate (2 bits), unwise settings (1 bit), stereo mode (3 bits) and noise
+    // shaping (2 bits)
+    misc: u8,
+
+    // MP3 gain (8 bits), signed, in 1.5 dB steps
+    mp3_gain: i8,
+
+    // Surround info (3 bits) and preset (11 bits), 2 unused bits first
+    preset: u16,
+
+    // Bytes from the start of this frame to the end of the audio
+    music_length: u32,
+
+    // CRC-16 of the audio frames, and of this frame up to the tag CRC
+    music_crc: u16,
+    tag_crc: u16,
+    // Whether `tag_crc` matches the frame, only known when the tag was read from a frame
+    crc_valid: Option<bool>,
+}
+
+impl LameTag {
+    // `bytes` must start at the encoder version string that follows the Xing/Info data
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < LAME_TAG_SIZE {
+            return None;
+        }
+
+        let delay_padding = &bytes[LAME_DELAY_PADDING_OFFSET..LAME_DELAY_PADDING_OFFSET + 3];
+
+        Some(Self {
+            encoder: bytes[..LAME_ENCODER_SIZE].try_into().unwrap(),
+            revision: bytes[LAME_VBR_METHOD_OFFSET] >> 4,
+            vbr_method: VbrMethod::from(bytes[LAME_VBR_METHOD_OFFSET] & 0x0F),
+            lowpass: bytes[LAME_LOWPASS_OFFSET],
+            peak: read_u32(bytes, LAME_PEAK_OFFSET),
+            radio_gain: read_u16(bytes, LAME_RADIO_GAIN_OFFSET),
+            audiophile_gain: read_u16(bytes, LAME_AUDIOPHILE_GAIN_OFFSET),
+            flags: bytes[LAME_FLAGS_OFFSET] >> 4,
+            ath_type: bytes[LAME_FLAGS_OFFSET] & 0x0F,
+            bitrate: bytes[LAME_BITRATE_OFFSET],
+            delay: ((delay_padding[0] as u16) << 4) | (delay_padding[1] as u16 >> 4),
+            padding: ((delay_padding[1] as u16 & 0x0F) << 8) | delay_padding[2] as u16,
+            misc: bytes[LAME_MISC_OFFSET],
+            mp3_gain: bytes[LAME_MP3_GAIN_OFFSET] as i8,
+            preset: read_u16(bytes, LAME_PRESET_OFFSET),
+            music_length: read_u32(bytes, LAME_MUSIC_LENGTH_OFFSET),
+            music_crc: read_u16(bytes, LAME_MUSIC_CRC_OFFSET),
+            tag_crc: read_u16(bytes, LAME_TAG_CRC_OFFSET),
+            crc_valid: None,
+        })
+    }
+
+    // Looks for the tag right after the Xing/Info header of `frame`, and checks its CRC
+    pub fn from_frame(frame: &Frame) -> Option<Self> {
+        let xing = XingHeader::from_frame(frame)?;
+        let start = frame.header.side_info_length() + xing.size();
+        let bytes = frame.body().get(start..)?;
+
+        // The encoder string ("LAME3.100", "Lavc58.54", ...) tells a tag from zero padding
+        if !bytes.get(..4)?.iter().all(u8::is_ascii_graphic) {
+            return None;
+        }
+
+        let mut tag = Self::from_bytes(bytes)?;
+        let covered = frame.data.len() - frame.body().len() + start + LAME_TAG_CRC_OFFSET;
+        tag.crc_valid = Some(lame_crc(&frame.data[..covered]) == tag.tag_crc);
+        Some(tag)
+    }
+
+    pub fn encoder(&self) -> String {
+        let encoder = String::from_utf8_lossy(&self.encoder);
+        encoder.trim_end_matches(['\0', ' ']).to_owned()
+    }
+
+    pub fn revision(&self) -> u8 {
+        self.revision
+    }
+
+    pub fn vbr_method(&self) -> VbrMethod {
+        self.vbr_method
+    }
+
+    // In Hz, if the encoder stored it
+    pub fn lowpass(&self) -> Option<usize> {
+        (self.lowpass != 0).then_some(self.lowpass as usize * 100)
+    }
+
+    // Highest sample amplitude, 1.0 being full scale, if the encoder stored it
+    pub fn peak(&self) -> Option<f32> {
+        (self.peak != 0).then(|| f32::from_bits(self.peak))
+    }
+
+    // Radio (track) gain in dB, if the encoder stored one
+    pub fn radio_gain(&self) -> Option<f32> {
+        decode_gain(self.radio_gain)
+    }
+
+    // Audiophile (album) gain in dB, if the encoder stored one
+    pub fn audiophile_gain(&self) -> Option<f32> {
+        decode_gain(self.audiophile_gain)
+    }
+
+    // nspsytune, nssafejoint, nogap next and nogap previous, from the lowest bit
+    pub fn encoding_flags(&self) -> u8 {
+        self.flags
+    }
+
+    // Absolute threshold of hearing model
+    pub fn ath_type(&self) -> u8 {
+        self.ath_type
+    }
+
+    // In kbit/s, the target bitrate for ABR, the minimum one for VBR and the bitrate for CBR.
+    // None when unknown, 255 stands for 255 and above.
+    pub fn bitrate(&self) -> Option<usize> {
+        (self.bitrate != 0).then_some(self.bitrate as usize)
+    }
+
+    pub fn delay(&self) -> usize {
+        self.delay as usize
+    }
+
+    pub fn padding(&self) -> usize {
+        self.padding as usize
+    }
+
+    // 0 (none) to 3
+    pub fn noise_shaping(&self) -> u8 {
+        self.misc & 0x03
+    }
+
+    // 0 mono, 1 stereo, 2 dual, 3 joint, 4 forced, 5 auto, 6 intensity, 7 undefined
+    pub fn stereo_mode(&self) -> u8 {
+        (self.misc >> 2) & 0x07
+    }
+
+    // Whether unwise settings were used
+    pub fn unwise_settings(&self) -> bool {
+        self.misc & 0x20 != 0
+    }
+
+    // Sample rate of the encoder input in Hz, None when above 48 kHz. 32 kHz stands for 32 kHz
+    // and below.
+    pub fn source_sample_rate(&self) -> Option<usize> {
+        match self.misc >> 6 {
+            0 => Some(32000),
+            1 => Some(44100),
+            2 => Some(48000),
+            _ => None,
+        }
+    }
+
+    // Gain applied to the whole stream (mp3gain), in dB
+    pub fn mp3_gain(&self) -> f32 {
+        self.mp3_gain as f32 * 1.5
+    }
+
+    // LAME preset: 410 to 500 for -V9 to -V0, the bitrate in kbit/s for ABR and CBR, 0 when none
+    pub fn preset(&self) -> u16 {
+        self.preset & 0x07FF
+    }
+
+    pub fn surround(&self) -> u8 {
+        ((self.preset >> 11) & 0x07) as u8
+    }
+
+    // Bytes from the start of the Xing/Info frame to the end of the audio
+    pub fn music_length(&self) -> u32 {
+        self.music_length
+    }
+
+    // CRC-16 of the audio frames, the Xing/Info frame excluded
+    pub fn music_crc(&self) -> u16 {
+        self.music_crc
+    }
+
+    pub fn tag_crc(&self) -> u16 {
+        self.tag_crc
+    }
+
+    // Whether the tag CRC matches the Xing/Info frame, None when the tag wasn't read from a frame
+    pub fn crc_valid(&self) -> Option<bool> {
+        self.crc_valid
+    }
+
+    // Samples to drop at the start and at the end of the decoded stream for gapless playback.
+    // The decoder's own latency shifts the whole signal, so it's added to the leading count and
+    // taken back from the trailing one.
+    pub fn gapless_samples(&self, total_frames: usize, samples_per_frame: usize) -> (usize, usize) {
+        let total = total_frames * samples_per_frame;
+
+        let leading = (self.delay() + DECODER_DELAY).min(total);
+        let trailing = self
+            .padding()
+            .saturating_sub(DECODER_DELAY)
+            .min(total - leading);
+
+        (leading, trailing)
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn decode_gain(field: u16) -> Option<f32> {
+    let name = field >> 13;
+    if name == 0 {
+        return None;
+    }
+
+    let gain = (field & 0x1FF) as f32 / 10.0;
+
+    if field & 0x200 != 0 {
+        Some(-gain)
+    } else {
+        Some(gain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::FrameIterator;
+
+    // LAME tag with every field cleared but the encoder string and those `fields` sets
+    fn tag(fields: &[(usize, &[u8])]) -> LameTag {
+        let mut bytes = b"LAME3.100".to_vec();
+        bytes.resize(LAME_TAG_SIZE, 0);
+        for (offset, value) in fields {
+            bytes[*offset..*offset + value.len()].copy_from_slice(value);
+        }
+        LameTag::from_bytes(&bytes).unwrap()
+    }
+
+    #[test]
+    fn replay_gain() {
+        // Radio, set by the user, +3.2 dB
+        let radio: u16 = (1 << 13) | (2 << 10) | 32;
+        // Audiophile, set automatically, -1.5 dB
+        let audiophile: u16 = (2 << 13) | (3 << 10) | (1 << 9) | 15;
+        let tag = tag(&[
+            (LAME_RADIO_GAIN_OFFSET, &radio.to_be_bytes()),
+            (LAME_AUDIOPHILE_GAIN_OFFSET, &audiophile.to_be_bytes()),
+        ]);
+        assert_eq!(tag.radio_gain(), Some(3.2));
+        assert_eq!(tag.audiophile_gain(), Some(-1.5));
+    }
+
+    #[test]
+    fn gapless_samples() {
+        // Delay 576 (0x240) and padding 1800 (0x708)
+        let tag = tag(&[(LAME_DELAY_PADDING_OFFSET, &[0x24, 0x07, 0x08])]);
+        assert_eq!((tag.delay(), tag.padding()), (576, 1800));
+
+        // The decoder delay (529 samples) moves both ends of the signal
+        assert_eq!(tag.gapless_samples(100, 1152), (576 + 529, 1800 - 529));
+        // Never more than the stream holds
+        assert_eq!(tag.gapless_samples(1, 1152), (1105, 47));
+        assert_eq!(tag.gapless_samples(0, 1152), (0, 0));
+    }
+
+    #[test]
+    fn unset_replay_gain() {
+        // A magnitude without name code isn't a gain
+        let tag = tag(&[(LAME_RADIO_GAIN_OFFSET, &0x0020u16.to_be_bytes())]);
+        assert_eq!(tag.radio_gain(), None);
+        assert_eq!(tag.audiophile_gain(), None);
+    }
+
+    #[test]
+    fn encoder_string() {
+        // 9 bytes, no room for a terminator
+        assert_eq!(tag(&[]).encoder(), "LAME3.100");
+        assert_eq!(tag(&[(0, b"LAME3.99r")]).encoder(), "LAME3.99r");
+        // Shorter strings padded with nulls or spaces
+        assert_eq!(tag(&[(0, b"LAME3.98\0")]).encoder(), "LAME3.98");
+        assert_eq!(tag(&[(0, b"LAME3.97 ")]).encoder(), "LAME3.97");
+
+        for data in [
+            &include_bytes!("../fixtures/cbr.mp3")[..],
+            include_bytes!("../fixtures/vbr.mp3"),
+        ] {
+            let first = FrameIterator::new(data).flatten().next().unwrap();
+            assert_eq!(LameTag::from_frame(&first).unwrap().encoder(), "LAME3.100");
+        }
+    }
+
+    #[test]
+    fn lame_files() {
+        for (data, samples) in [
+            (&include_bytes!("../fixtures/cbr.mp3")[..], 11025),
+            (include_bytes!("../fixtures/vbr.mp3"), 22050),
+        ] {
+            let frames: Vec<_> = FrameIterator::new(data).flatten().collect();
+            let tag = LameTag::from_frame(&frames[0]).unwrap();
+
+            // Whatever is left of the audio frames past the delay and the samples is padding
+            let audio = (frames.len() - 1) * 1152;
+            assert_eq!(tag.delay(), 576);
+            assert_eq!(tag.padding(), audio - 576 - samples);
+            assert_eq!(tag.crc_valid(), Some(true));
+            assert_eq!(tag.music_length() as usize, data.len());
+            assert_eq!(tag.music_crc(), lame_crc(&data[frames[0].data.len()..]));
+
+            // Any change to the frame before the CRC breaks it
+            let mut corrupted = frames[0].data.to_vec();
+            corrupted[100] ^= 1;
+            let frame = Frame {
+                data: &corrupted,
+                ..frames[0].clone()
+            };
+            assert_eq!(
+                LameTag::from_frame(&frame).unwrap().crc_valid(),
+                Some(false)
+            );
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{bit_reader::BitReader, error::ChompError, frame::Frame};
+
+// Scalefactor index 63 isn't in the standard table, some encoders still write it: the subband is
+// silenced instead of rejecting the frame
+pub(crate) fn scalefactor(index: u32) -> f32 {
+    match index {
+        63 => 0.0,
+        index => 2.0f32.powf(1.0 - index as f32 / 3.0),
+    }
+}
+
+// `bits` long sample with its most significant bit inverted, a two's complement fraction, to
+// [-1.0, 1.0]
+fn dequantize(raw: u32, bits: usize) -> f32 {
+    let shift = 32 - bits;
+    let value = (((raw ^ (1 << (bits - 1))) << shift) as i32) >> shift;
+    let steps = (1u32 << bits) as f32;
+
+    steps / (steps - 1.0) * (value + 1) as f32 / (steps / 2.0)
+}
+
+// Subband samples of one Layer I frame, indexed [slot][channel][subband]: 12 samples of each of
+// the 32 subbands, 384 PCM samples once synthesized. In joint stereo the subbands from the bound
+// up carry one set of samples for both channels, each channel keeping its own scalefactors.
+pub fn decode_layer1(frame: &Frame) -> Result<[[[f32; 32]; 2]; 12], ChompError> {
+    let header = &frame.header;
+    let channels = header.channels();
+    let bound = header.joint_stereo_bound(32);
+    let shared = |subband: usize, channel: usize| subband >= bound && channel == 1;
+    let mut reader = BitReader::new(frame.body());
+
+    // Bits per sample, 0 when the subband isn't transmitted
+    let mut allocation = [[0; 2]; 32];
+    for (subband, allocation) in allocation.iter_mut().enumerate() {
+        for channel in 0..channels {
+            allocation[channel] = if shared(subband, channel) {
+                allocation[0]
+            } else {
+                match reader.read_bits(4)? {
+                    0 => 0,
+                    15 => {
+                        return Err(ChompError::InvalidMainData(
+                            "Layer I bit allocation 15 is forbidden",
+                        ))
+                    }
+                    bits => bits as usize + 1,
+                }
+            };
+        }
+    }
+
+    let mut scalefactors = [[0.0; 2]; 32];
+    for subband in 0..32 {
+        for channel in 0..channels {
+            if allocation[subband][channel] != 0 {
+                scalefactors[subband][channel] = scalefactor(reader.read_bits(6)?);
+            }
+        }
+    }
+
+    let mut samples = [[[0.0; 32]; 2]; 12];
+    for slot in &mut samples {
+        for subband in 0..32 {
+            let mut raw = 0;
+
+            for channel in 0..channels {
+                let bits = allocation[subband][channel];
+                if bits == 0 {
+                    continue;
+                }
+                if !shared(subband, channel) {
+                    raw = reader.read_bits(bits)?;
+                }
+
+                slot[channel][subband] = dequantize(raw, bits) * scalefactors[subband][channel];
+            }
+        }
+    }
+
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::FrameIterator;
+
+    #[test]
+    fn dequantization() {
+        assert_eq!(scalefactor(0), 2.0);
+        assert_eq!(scalefactor(3), 1.0);
+        assert_eq!(scalefactor(63), 0.0);
+
+        // 3 levels out of 2 bits, the all ones code is never used
+        assert_eq!(
+            [0, 1, 2].map(|raw| dequantize(raw, 2)),
+            [-2.0 / 3.0, 0.0, 2.0 / 3.0]
+        );
+        // Symmetric around the middle code
+        for bits in 2..=15 {
+            let top = (1 << bits) - 2;
+            assert_eq!(dequantize(0, bits), -dequantize(top, bits));
+            assert_eq!(dequantize(top / 2, bits), 0.0);
+            assert!(dequantize(top, bits) < 1.0);
+        }
+    }
+
+    #[test]
+    fn joint_stereo_bound() {
+        let data = include_bytes!("../fixtures/layer1.mp1");
+        let frames: Vec<_> = FrameIterator::new(data).flatten().collect();
+        assert_eq!(frames.len(), 12);
+        assert_eq!(frames[0].header.joint_stereo_bound(32), 8);
+
+        for frame in &frames {
+            let samples = decode_layer1(frame).unwrap();
+
+            // Above the bound both channels scale the same samples, each with its own
+            // scalefactor
+            for subband in 8..32 {
+                let ratios: Vec<_> = samples
+                    .iter()
+                    .filter(|slot| slot[0][subband] != 0.0)
+                    .map(|slot| slot[1][subband] / slot[0][subband])
+                    .collect();
+                for ratio in &ratios {
+                    assert!((ratio / ratios[0] - 1.0).abs() < 1e-6);
+                }
+            }
+            // Nothing above the subbands the encoder allocated
+            assert!(samples.iter().flatten().all(|slot| slot[20..] == [0.0; 12]));
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use crate::{
+    bit_reader::BitReader,
+    error::ChompError,
+    frame::Frame,
+    header::{Header, MPEG_Version},
+    layer1::scalefactor,
+    tables::*,
+};
+
+// The Layer II bit allocation tables. MPEG-1 picks one of the 4 tables of ISO/IEC 11172-3
+// (3-B.2a to d) from the sample rate and bitrate per channel, MPEG-2 and 2.5 always use the one
+// of ISO/IEC 13818-3 (B.1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AllocTable {
+    A,
+    B,
+    C,
+    D,
+    Lsf,
+}
+
+impl AllocTable {
+    pub fn table(&self) -> &'static AllocationTable {
+        match self {
+            AllocTable::A => &TABLE_A,
+            AllocTable::B => &TABLE_B,
+            AllocTable::C => &TABLE_C,
+            AllocTable::D => &TABLE_D,
+            AllocTable::Lsf => &TABLE_LSF,
+        }
+    }
+
+    // Number of subbands carrying samples, the ones above are silent
+    pub fn sblimit(&self) -> usize {
+        self.table().sblimit()
+    }
+}
+
+pub fn layer2_alloc_table(header: &Header) -> AllocTable {
+    if header.version() != MPEG_Version::One {
+        return AllocTable::Lsf;
+    }
+
+    match (header.bitrate() / header.channels(), header.sample_rate()) {
+        (..=48, 32000) => AllocTable::D,
+        (..=48, _) => AllocTable::C,
+        (..=80, _) | (_, 48000) => AllocTable::A,
+        _ => AllocTable::B,
+    }
+}
+
+// The 3 consecutive samples of a subband coded with a `levels` quantizer. The 3, 5 and 9 level
+// ones pack them in a single codeword.
+fn read_triplet(reader: &mut BitReader, levels: u32) -> Result<[u32; 3], ChompError> {
+    let grouped_bits = match levels {
+        3 => 5,
+        5 => 7,
+        9 => 10,
+        _ => {
+            let bits = (levels + 1).trailing_zeros() as usize;
+            return Ok([
+                reader.read_bits(bits)?,
+                reader.read_bits(bits)?,
+                reader.read_bits(bits)?,
+            ]);
+        }
+    };
+
+    let mut code = reader.read_bits(grouped_bits)?;
+    Ok(std::array::from_fn(|_| {
+        let sample = code % levels;
+        code /= levels;
+        sample
+    }))
+}
+
+// Step `raw` of a `levels` quantizer to its value within [-1.0, 1.0], levels being spread
+// evenly around 0
+fn dequantize(raw: u32, levels: u32) -> f32 {
+    (2.0 * raw as f32 + 1.0 - levels as f32) / levels as f32
+}
+
+// Subband samples of one Layer II frame, indexed [slot][channel][subband]: 36 samples of each of
+// the 32 subbands, 1152 PCM samples once synthesized. Every third of the frame has its own
+// scalefactor, the scalefactor selection information telling which ones are transmitted. In
+// joint stereo the subbands from the bound up carry one set of samples for both channels.
+pub fn decode_layer2(frame: &Frame) -> Result<[[[f32; 32]; 2]; 36], ChompError> {
+    let header = &frame.header;
+    let channels = header.channels();
+    let table = allocation_table(header);
+    let sblimit = table.sblimit();
+    let bound = header.joint_stereo_bound(sblimit);
+    let shared = |subband: usize, channel: usize| subband >= bound && channel == 1;
+    let mut reader = BitReader::new(frame.body());
+
+    // Levels of the quantizer of each subband, 0 when it isn't transmitted
+    let mut levels = [[0; 2]; 32];
+    for (subband, levels) in levels.iter_mut().enumerate().take(sblimit) {
+        for channel in 0..channels {
+            levels[channel] = if shared(subband, channel) {
+                levels[0]
+            } else {
+                table.quantizers[subband][reader.read_bits(table.nbal(subband))? as usize]
+            };
+        }
+    }
+
+    let mut selection = [[0; 2]; 32];
+    for subband in 0..sblimit {
+        for channel in 0..channels {
+            if levels[subband][channel] != 0 {
+                selection[subband][channel] = reader.read_bits(2)?;
+            }
+        }
+    }
+
+    // One per third of the frame
+    let mut scalefactors = [[[0.0; 3]; 2]; 32];
+    for subband in 0..sblimit {
+        for channel in 0..channels {
+            if levels[subband][channel] == 0 {
+                continue;
+            }
+
+            let first = reader.read_bits(6)?;
+            let indices = match selection[subband][channel] {
+                0 => [first, reader.read_bits(6)?, reader.read_bits(6)?],
+                1 => {
+                    let last = reader.read_bits(6)?;
+                    [first, first, last]
+                }
+                2 => [first; 3],
+                _ => {
+                    let last = reader.read_bits(6)?;
+                    [first, last, last]
+                }
+            };
+            scalefactors[subband][channel] = indices.map(scalefactor);
+        }
+    }
+
+    let mut samples = [[[0.0; 32]; 2]; 36];
+    for (granule, slots) in samples.chunks_exact_mut(3).enumerate() {
+        for subband in 0..sblimit {
+            let mut triplet = [0; 3];
+
+            for channel in 0..channels {
+                let levels = levels[subband][channel];
+                if levels == 0 {
+                    continue;
+                }
+                if !shared(subband, channel) {
+                    triplet = read_triplet(&mut reader, levels)?;
+                }
+
+                let scalefactor = scalefactors[subband][channel][granule / 4];
+                for (slot, raw) in slots.iter_mut().zip(triplet) {
+                    slot[channel][subband] = dequantize(raw, levels) * scalefactor;
+                }
+            }
+        }
+    }
+
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(bytes: [u8; 4]) -> AllocTable {
+        layer2_alloc_table(&Header::from_bytes(&bytes).unwrap())
+    }
+
+    #[test]
+    fn table_selection() {
+        // 48 kbit/s mono at 32000 and 44100 Hz
+        assert_eq!(table([0xFF, 0xFD, 0x28, 0xC0]), AllocTable::D);
+        assert_eq!(table([0xFF, 0xFD, 0x20, 0xC0]), AllocTable::C);
+        // 56 and 96 kbit/s mono at 44100 Hz
+        assert_eq!(table([0xFF, 0xFD, 0x30, 0xC0]), AllocTable::A);
+        assert_eq!(table([0xFF, 0xFD, 0x60, 0xC0]), AllocTable::B);
+        // 96 kbit/s stereo is 48 per channel (48000 Hz)
+        assert_eq!(table([0xFF, 0xFD, 0x64, 0x00]), AllocTable::C);
+        // 192 kbit/s stereo at 48000 and 44100 Hz
+        assert_eq!(table([0xFF, 0xFD, 0xA4, 0x00]), AllocTable::A);
+        assert_eq!(table([0xFF, 0xFD, 0xA0, 0x00]), AllocTable::B);
+        // MPEG-2, whatever the bitrate
+        assert_eq!(table([0xFF, 0xF5, 0xA0, 0x00]), AllocTable::Lsf);
+
+        let sblimits = [
+            AllocTable::A,
+            AllocTable::B,
+            AllocTable::C,
+            AllocTable::D,
+            AllocTable::Lsf,
+        ]
+        .map(|table| table.sblimit());
+        assert_eq!(sblimits, [27, 30, 8, 12, 30]);
+    }
+
+    #[test]
+    fn degrouping() {
+        // 3, 5 and 9 level codewords of 5, 7 and 10 bits, then 3 separate 4 bit samples
+        let bytes = [0x2F, 0xCB, 0x60, 0x5F, 0x80];
+        let mut reader = BitReader::new(&bytes);
+        assert_eq!(read_triplet(&mut reader, 3).unwrap(), [2, 1, 0]);
+        assert_eq!(read_triplet(&mut reader, 5).unwrap(), [4, 4, 4]);
+        assert_eq!(read_triplet(&mut reader, 9).unwrap(), [8, 8, 8]);
+        assert_eq!(read_triplet(&mut reader, 15).unwrap(), [1, 7, 14]);
+        assert_eq!(reader.position(), 34);
+
+        assert_eq!(
+            [0, 1, 2].map(|raw| dequantize(raw, 3)),
+            [-2.0 / 3.0, 0.0, 2.0 / 3.0]
+        );
+        assert_eq!(dequantize(0, 9), -dequantize(8, 9));
+        assert_eq!(dequantize(7, 15), 0.0);
+    }
+
+    #[test]
+    fn nothing_above_the_sblimit() {
+        let data = include_bytes!("../fixtures/layer2.mp2");
+        let frames: Vec<_> = crate::frame::FrameIterator::new(data).flatten().collect();
+        assert_eq!(frames.len(), 12);
+        assert_eq!(layer2_alloc_table(&frames[0].header), AllocTable::B);
+
+        for frame in &frames {
+            let samples = decode_layer2(frame).unwrap();
+            assert!(samples.iter().flatten().all(|slot| slot[30..] == [0.0; 2]));
+            assert!(samples.iter().flatten().any(|slot| slot[..30] != [0.0; 30]));
+        }
+    }
+
+    #[test]
+    fn joint_stereo_bound() {
+        let data = include_bytes!("../fixtures/layer2.mp2");
+        for frame in crate::frame::FrameIterator::new(data).flatten() {
+            assert_eq!(frame.header.joint_stereo_bound(30), 8);
+            let samples = decode_layer2(&frame).unwrap();
+
+            // Below the bound each channel has its own samples
+            let below =
+                |channel: usize| samples.iter().map(move |slot| slot[channel][..8].to_vec());
+            assert!(below(0).zip(below(1)).any(|(left, right)| left != right));
+
+            // Above it both scale the same samples, each third of the frame with its own
+            // scalefactors
+            for subband in 8..30 {
+                for third in samples.chunks_exact(12) {
+                    let ratios: Vec<_> = third
+                        .iter()
+                        .filter(|slot| slot[0][subband] != 0.0)
+                        .map(|slot| slot[1][subband] / slot[0][subband])
+                        .collect();
+                    for ratio in &ratios {
+                        assert!((ratio / ratios[0] - 1.0).abs() < 1e-6);
+                    }
+                }
+            }
+        }
+    }
+}
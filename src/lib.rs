@@ -4,12 +4,97 @@
 
 // use bitvec::prelude::*;
 
+mod analysis;
+mod ape;
+mod bit_reader;
 mod constants;
+mod crc;
+mod decoder;
+mod edit;
+mod encoder;
+mod error;
 mod frame;
+mod free_format;
 mod header;
+mod huffman;
+mod hybrid;
+mod id3;
+mod id3v1;
+mod integrity;
+mod lame;
+mod layer1;
+mod layer2;
+mod probe;
+mod reorder;
+mod replaygain;
+mod requantize;
+mod riff;
+mod scalefactors;
+mod side_info;
+mod stereo;
+mod stream;
+mod synthesis;
+mod tables;
+#[cfg(test)]
+mod test_utils;
 mod utils;
+mod vbr;
+mod vbri;
+mod xing;
 
 // use constants::*;
 // use frame::*;
 // use header::*;
 // use utils::*;
+
+pub use analysis::{
+    analyze, average_bitrate, bitrate_changes, bitrate_range, channel_layout,
+    check_sample_rate_consistency, duration, estimate_quality, first_frame_offset, frame_offset,
+    frames_for_duration, padding_byte_count, split_streams, total_samples, Analysis, ChannelLayout,
+    DurationInfo, DurationMethod, Encoding, QualityInfo,
+};
+pub use ape::{find_ape_tag, find_leading_ape_tag, ApeItem, ApeTag};
+pub use bit_reader::BitReader;
+pub use crc::{recompute_crc, verify_crc};
+pub use decoder::{Decoded, DecodedFrame, Decoder, NoReader};
+pub use edit::{
+    concat, cut, rewrite_headers, strip_tags, Concat, ConcatReport, ConcatWarning, CutReport,
+    HeaderEdits, StripReport,
+};
+pub use encoder::{detect_encoder, Encoder, EncoderInfo, PaddingPattern};
+pub use error::ChompError;
+pub use frame::{
+    find_next_sync, Frame, FrameIterator, FrameWindows, ParseMode, ParserConfig, StreamEvent,
+    StreamEvents,
+};
+pub use free_format::FreeFormatContext;
+pub use header::{
+    Copyright, Emphasis, Header, Home, Layer, MPEG_Version, Mode, ModeExtension, RawHeader,
+    RawHeaderRef, SyncMode, SyncWord,
+};
+pub use huffman::{decode_big_values, decode_count1, decode_pair};
+pub use hybrid::{antialias, frequency_inversion, imdct};
+pub use id3::{
+    find_appended_id3v2, id3v2_size, Chapter, Lyrics, Picture, PictureType, Tag, TextFrame,
+};
+pub use id3v1::{Genre, Id3v1Tag};
+pub use integrity::{scan_integrity, IntegrityIssue, IssueKind};
+pub use lame::{LameTag, VbrMethod};
+pub use layer1::decode_layer1;
+pub use layer2::{decode_layer2, layer2_alloc_table, AllocTable};
+pub use probe::{probe, sync_confidence, NotMp3Reason, ProbeResult};
+pub use reorder::reorder;
+pub use replaygain::{Clipping, GainMode, ReplayGain, ReplayGainOptions};
+pub use requantize::requantize;
+pub use riff::unwrap_riff;
+pub use scalefactors::Scalefactors;
+pub use side_info::{BlockType, GranuleInfo, SideInfo};
+pub use stereo::{ms_stereo, stereo};
+pub use stream::{read_and_sync, StreamParser};
+pub use synthesis::synthesis;
+pub use tables::{
+    allocation_table, AllocationTable, TABLE_A, TABLE_B, TABLE_C, TABLE_D, TABLE_LSF,
+};
+pub use vbr::VbrInfo;
+pub use vbri::VbriHeader;
+pub use xing::XingHeader;
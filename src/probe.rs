@@ -0,0 +1,324 @@
+use crate::{
+    constants::*,
+    header::{Copyright, Emphasis, Header, Layer, MPEG_Version, Mode},
+    id3::id3v2_size,
+    riff::unwrap_riff,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeResult {
+    Mp3 {
+        version: MPEG_Version,
+        layer: Layer,
+        sample_rate: usize,
+        channels: usize,
+    },
+    NotMp3(NotMp3Reason),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotMp3Reason {
+    Empty,
+    Ogg,
+    Flac,
+    // A RIFF container not holding MPEG audio, PCM WAVE files included
+    Riff,
+    // The 12 bit sync is there but with the (reserved for MPEG audio) layer 00
+    AdtsAac,
+    // No run of consistent frames in the first `searched` bytes
+    NoSync { searched: usize },
+}
+
+impl std::fmt::Display for NotMp3Reason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotMp3Reason::Empty => write!(f, "empty input"),
+            NotMp3Reason::Ogg => write!(f, "Ogg signature"),
+            NotMp3Reason::Flac => write!(f, "FLAC signature"),
+            NotMp3Reason::Riff => write!(f, "RIFF container"),
+            NotMp3Reason::AdtsAac => write!(f, "looks like ADTS AAC"),
+            NotMp3Reason::NoSync { searched } => {
+                write!(f, "no sync found in first {} KiB", searched / 1024)
+            }
+        }
+    }
+}
+
+// Cheap check before committing to a full parse: skips a leading ID3v2 tag then looks for
+// PROBE_FRAMES consecutive, consistent frames (two are enough if the input ends there) within
+// PROBE_LIMIT bytes. Never looks further than that and never panics. A RIFF/WAVE file holding
+// MPEG audio is probed from the start of its data chunk.
+pub fn probe(data: &[u8]) -> ProbeResult {
+    let data = unwrap_riff(data).map_or(data, |range| &data[range]);
+
+    let start = id3v2_size(data).unwrap_or(0).min(data.len());
+    let window = &data[start..data.len().min(start + PROBE_LIMIT)];
+
+    if window.is_empty() {
+        return ProbeResult::NotMp3(NotMp3Reason::Empty);
+    }
+
+    let signature = |magic: &[u8]| window.starts_with(magic);
+    if signature(b"OggS") {
+        return ProbeResult::NotMp3(NotMp3Reason::Ogg);
+    }
+    if signature(b"fLaC") {
+        return ProbeResult::NotMp3(NotMp3Reason::Flac);
+    }
+    if signature(b"RIFF") {
+        return ProbeResult::NotMp3(NotMp3Reason::Riff);
+    }
+
+    let mut adts = false;
+    for position in 0..window.len() {
+        if window[position] != 0xFF {
+            continue;
+        }
+
+        if let Some(header) = confirmed_run(window, position) {
+            return ProbeResult::Mp3 {
+                version: header.version(),
+                layer: header.layer(),
+                sample_rate: header.sample_rate(),
+                channels: header.channels(),
+            };
+        }
+
+        adts |= adts_run(window, position);
+    }
+
+    ProbeResult::NotMp3(if adts {
+        NotMp3Reason::AdtsAac
+    } else {
+        NotMp3Reason::NoSync {
+            searched: window.len(),
+        }
+    })
+}
+
+// How much the bytes at `offset` look like a real frame, from 0 (not a header) to 100. A valid
+// header scores 40, fields holding the values encoders actually write (no emphasis, copyright
+// and private bits clear, mode extension only in joint stereo) up to 25 more, a frame fitting in
+// `data` 5 and a compatible frame right after it 30. A sync pattern met by chance in audio data
+// rarely gets the last 30: among close candidates, the highest score is the real frame.
+pub fn sync_confidence(data: &[u8], offset: usize) -> u8 {
+    let Some(header) = data
+        .get(offset..)
+        .and_then(|bytes| Header::from_bytes(bytes).ok())
+    else {
+        return 0;
+    };
+
+    let mut score = 40;
+    if header.emphasis() == Emphasis::None {
+        score += 10;
+    }
+    if header.copyright() == Copyright::Off {
+        score += 5;
+    }
+    if !header.private_bit() {
+        score += 5;
+    }
+    let mode_extension = header.raw_bytes()[3] & 0x30 != 0;
+    if header.mode() == Mode::JointStereo || !mode_extension {
+        score += 5;
+    }
+
+    let next = offset + header.frame_length();
+    if next <= data.len() {
+        score += 5;
+
+        let following = data
+            .get(next..)
+            .and_then(|bytes| Header::from_bytes(bytes).ok());
+        if following.is_some_and(|following| following.is_compatible_with(&header)) {
+            score += 30;
+        }
+    }
+
+    score
+}
+
+fn confirmed_run(window: &[u8], start: usize) -> Option<Header> {
+    let first = Header::from_bytes(&window[start..]).ok()?;
+    let mut position = start;
+
+    for found in 0..PROBE_FRAMES {
+        if found >= 2 && position == window.len() {
+            break;
+        }
+
+        let header = Header::from_bytes(window.get(position..)?).ok()?;
+        if header.version() != first.version()
+            || header.layer() != first.layer()
+            || header.sample_rate() != first.sample_rate()
+        {
+            return None;
+        }
+
+        position += header.frame_length();
+    }
+
+    Some(first)
+}
+
+// Two chained ADTS headers, the frame length (header included) is a 13 bits field
+fn adts_run(window: &[u8], start: usize) -> bool {
+    let is_adts = |position: usize| {
+        window
+            .get(position..position + 2)
+            .is_some_and(|bytes| bytes[0] == 0xFF && bytes[1] & 0xF6 == 0xF0)
+    };
+
+    if !is_adts(start) {
+        return false;
+    }
+
+    match window.get(start + 3..start + 6) {
+        Some(bytes) => {
+            let length = ((bytes[0] as usize & 0x03) << 11)
+                | ((bytes[1] as usize) << 3)
+                | (bytes[2] as usize >> 5);
+            length > 0 && is_adts(start + length)
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CBR: &[u8] = include_bytes!("../fixtures/cbr.mp3");
+
+    // Pseudo random bytes, never twice the same
+    fn noise(length: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..length)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn mp3_behind_an_id3v2_tag() {
+        let mut data = b"ID3\x03\x00\x00\x00\x00\x08\x00".to_vec();
+        data.resize(10 + 1024, 0);
+        data.extend(include_bytes!("../fixtures/mpeg2.mp3"));
+        assert_eq!(
+            probe(&data),
+            ProbeResult::Mp3 {
+                version: MPEG_Version::Two,
+                layer: Layer::Three,
+                sample_rate: 22050,
+                channels: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn two_frames_ending_the_input() {
+        let frames = &CBR[..2 * 417];
+        assert!(matches!(probe(frames), ProbeResult::Mp3 { .. }));
+        assert!(matches!(
+            probe(&CBR[..417 + 200]),
+            ProbeResult::NotMp3(NotMp3Reason::NoSync { .. })
+        ));
+    }
+
+    #[test]
+    fn other_formats() {
+        let reason = |data: &[u8]| match probe(data) {
+            ProbeResult::NotMp3(reason) => reason,
+            result => panic!("{result:?}"),
+        };
+
+        assert_eq!(reason(b""), NotMp3Reason::Empty);
+        assert_eq!(reason(b"OggS\x00\x02"), NotMp3Reason::Ogg);
+        assert_eq!(reason(b"fLaC\x00\x00\x00\x22"), NotMp3Reason::Flac);
+
+        // Two chained 100 bytes ADTS frames
+        let mut adts = Vec::new();
+        for _ in 0..2 {
+            adts.extend([0xFF, 0xF1, 0x50, 0x80, 0x0C, 0x9F, 0xFC]);
+            adts.resize(adts.len() + 93, 0);
+        }
+        assert_eq!(reason(&adts), NotMp3Reason::AdtsAac);
+        assert_eq!(NotMp3Reason::AdtsAac.to_string(), "looks like ADTS AAC");
+    }
+
+    #[test]
+    fn bounded_search() {
+        let mut data = vec![0; PROBE_LIMIT];
+        data.extend(CBR);
+        let reason = NotMp3Reason::NoSync {
+            searched: PROBE_LIMIT,
+        };
+        assert_eq!(probe(&data), ProbeResult::NotMp3(reason));
+        assert_eq!(reason.to_string(), "no sync found in first 64 KiB");
+
+        data.drain(..2000);
+        assert!(matches!(probe(&data), ProbeResult::Mp3 { .. }));
+    }
+
+    #[test]
+    fn arbitrary_bytes() {
+        for seed in 0..200 {
+            let data = noise(seed as usize * 37, seed);
+            probe(&data);
+        }
+        for length in 0..CBR.len() {
+            probe(&CBR[..length]);
+        }
+    }
+
+    #[test]
+    fn riff_wrapped_mp3() {
+        let expected = ProbeResult::Mp3 {
+            version: MPEG_Version::One,
+            layer: Layer::Three,
+            sample_rate: 44100,
+            channels: 2,
+        };
+        assert_eq!(probe(include_bytes!("../fixtures/riff.wav")), expected);
+        assert_eq!(probe(include_bytes!("../fixtures/cbr.mp3")), expected);
+    }
+
+    #[test]
+    fn riff_without_mpeg_audio() {
+        let mut data = include_bytes!("../fixtures/riff.wav").to_vec();
+        // PCM format tag
+        data[20..22].copy_from_slice(&1u16.to_le_bytes());
+        assert_eq!(probe(&data), ProbeResult::NotMp3(NotMp3Reason::Riff));
+    }
+
+    #[test]
+    fn real_frames_score_higher() {
+        let offsets: Vec<_> = crate::frame::FrameIterator::new(CBR)
+            .flatten()
+            .map(|frame| frame.offset)
+            .collect();
+        let (last, followed) = offsets.split_last().unwrap();
+        for offset in followed {
+            assert_eq!(sync_confidence(CBR, *offset), 100);
+        }
+        // Nothing after the last frame to confirm it
+        assert_eq!(sync_confidence(CBR, *last), 70);
+
+        // A sync pattern in the payload of frame 3, then one with unusual fields
+        let mut data = CBR.to_vec();
+        let fake = offsets[3] + 100;
+        data[fake..fake + 4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x40]);
+        let unusual = offsets[5] + 100;
+        data[unusual..unusual + 4].copy_from_slice(&[0xFF, 0xFB, 0x91, 0x3D]);
+
+        assert_eq!(sync_confidence(&data, offsets[3]), 100);
+        assert_eq!(sync_confidence(&data, fake), 70);
+        // Private, copyright and emphasis set, a mode extension in stereo
+        assert_eq!(sync_confidence(&data, unusual), 45);
+        assert_eq!(sync_confidence(&data, fake + 1), 0);
+        assert_eq!(sync_confidence(&data, data.len()), 0);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::{
+    header::Header,
+    side_info::{mixed_bands, short_band_lines, BlockType, GranuleInfo},
+};
+
+// Short blocks are decoded band by band, each band holding its 3 windows one after the other.
+// The IMDCT wants the windows interleaved line by line instead, so each subband finds its 3
+// short windows in its own 18 values. The long part of a mixed block (its first 2 subbands) is
+// left untouched. Other blocks are not reordered.
+pub fn reorder(header: &Header, info: &GranuleInfo, samples: &mut [f32; 576]) {
+    if info.block_type != BlockType::Short {
+        return;
+    }
+
+    let rate = header.sample_rate_index();
+    let first_band = match info.mixed_block {
+        true => mixed_bands(rate).1,
+        false => 0,
+    };
+    let decoded = *samples;
+
+    for band in first_band..13 {
+        let lines = short_band_lines(rate, band, info.mixed_block);
+        let start = lines.start * 3;
+        let width = lines.len();
+
+        for window in 0..3 {
+            for line in 0..width {
+                samples[start + line * 3 + window] = decoded[start + window * width + line];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{requantize::requantize, test_utils::*};
+
+    fn short_block(mixed_block: bool) -> GranuleInfo {
+        GranuleInfo {
+            window_switching: true,
+            block_type: BlockType::Short,
+            mixed_block,
+            ..Default::default()
+        }
+    }
+
+    fn indices() -> [f32; 576] {
+        std::array::from_fn(|i| i as f32)
+    }
+
+    #[test]
+    fn windows_interleaved_line_by_line() {
+        let header = Header::from_bytes(&HEADER_128).unwrap();
+        let mut samples = indices();
+        reorder(&header, &short_block(false), &mut samples);
+
+        // 44100 Hz short band 0 is 4 lines wide, band 1 as well
+        assert_eq!(
+            samples[..24],
+            [
+                0., 4., 8., 1., 5., 9., 2., 6., 10., 3., 7., 11., 12., 16., 20., 13., 17., 21.,
+                14., 18., 22., 15., 19., 23.
+            ]
+        );
+        // Last band, lines 136 to 192 of each window
+        assert_eq!(samples[408..411], [408., 408. + 56., 408. + 112.]);
+
+        let mut sorted = samples;
+        sorted.sort_by(f32::total_cmp);
+        assert_eq!(sorted, indices());
+    }
+
+    #[test]
+    fn mixed_and_long_blocks() {
+        for rate in [
+            [0xFF, 0xFB, 0x90, 0x40],
+            [0xFF, 0xF3, 0x80, 0x40],
+            [0xFF, 0xE3, 0x48, 0x40],
+        ] {
+            let header = Header::from_bytes(&rate).unwrap();
+
+            let mut samples = indices();
+            reorder(&header, &short_block(true), &mut samples);
+            // The first 2 subbands are left as they are
+            assert_eq!(samples[..36], indices()[..36]);
+            assert_ne!(samples, indices());
+            let mut sorted = samples;
+            sorted.sort_by(f32::total_cmp);
+            assert_eq!(sorted, indices());
+
+            let mut samples = indices();
+            reorder(&header, &GranuleInfo::default(), &mut samples);
+            assert_eq!(samples, indices());
+        }
+    }
+
+    // Expected values printed by symphonia for frame 2 of fixtures/mpeg2.mp3, 22050 Hz
+    #[test]
+    fn lsf_short_block() {
+        let decoded = decode_values(include_bytes!("../fixtures/mpeg2.mp3"), 2);
+        let info = &decoded.side_info.granules[0][0];
+        assert_eq!(info.block_type, BlockType::Short);
+
+        let mut samples = [0.0; 576];
+        requantize(
+            &decoded.values[0][0],
+            &decoded.header,
+            info,
+            &decoded.scalefactors[0][0],
+            &mut samples,
+        );
+        reorder(&decoded.header, info, &mut samples);
+
+        assert_close(
+            &samples[..12],
+            &[
+                -0.0046163984,
+                -6.523041e-5,
+                -0.00020960346,
+                0.009887697,
+                0.00030759795,
+                0.00016437034,
+                -0.011823683,
+                0.000257431,
+                0.0010786203,
+                0.009644314,
+                -0.008275087,
+                -0.0028513381,
+            ],
+        );
+        assert_close(
+            &samples[90..102],
+            &[
+                -0.0003599041,
+                7.6293945e-6,
+                0.0,
+                0.0007750983,
+                -7.6293945e-6,
+                0.0,
+                -0.0008075611,
+                7.6293945e-6,
+                0.0,
+                0.0007750983,
+                0.0,
+                0.0,
+            ],
+        );
+        assert!((energy(&samples) / 1.695483094775957e-1 - 1.0).abs() < 1e-5);
+    }
+
+    // Expected values printed by symphonia for frame 1 of fixtures/mixed.mp3
+    #[test]
+    fn mixed_block() {
+        let decoded = decode_values(include_bytes!("../fixtures/mixed.mp3"), 1);
+        let info = &decoded.side_info.granules[0][0];
+        assert!(info.mixed_block);
+
+        let mut samples = stereo_granule(&decoded, 0)[0];
+        let long = samples;
+        reorder(&decoded.header, info, &mut samples);
+
+        // Short band 3 starts right after the 2 long subbands, at line 36
+        assert_eq!(samples[..36], long[..36]);
+        assert_close(
+            &samples[34..46],
+            &[
+                -5.3947965e-6,
+                -6.642995e-5,
+                4.3158372e-5,
+                -7.629394e-6,
+                0.0,
+                -7.3675954e-5,
+                0.0001296997,
+                0.0,
+                -7.3675954e-5,
+                -0.000122070305,
+                -0.00017263349,
+                3.0517576e-5,
+            ],
+        );
+        assert!((energy(&samples) / 9.530029670144619e-7 - 1.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,271 @@
+use crate::{ape::ApeTag, frame::FrameIterator, id3::Tag, lame::LameTag};
+
+// Which of the stored gains to apply
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GainMode {
+    Track,
+    Album,
+}
+
+// What to do with samples the gain pushes past full scale
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Clipping {
+    // Clamp them to [-1.0, 1.0]
+    Hard,
+    // Lower the gain so the stored peak stays within full scale, clamping what's left
+    // without peak
+    Peak,
+}
+
+// ReplayGain values of a stream, gains in dB and peaks with 1.0 as full scale
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReplayGain {
+    pub track_gain: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain: Option<f32>,
+    pub album_peak: Option<f32>,
+}
+
+impl ReplayGain {
+    // Values of the tags, in order of precedence: ID3v2 TXXX frames, APE items, then the LAME
+    // tag. Taggers write the first two when they analyse the decoded audio, the encoder only
+    // measured its input. None when no source holds a gain.
+    pub fn from_stream(data: &[u8]) -> Option<Self> {
+        let id3 = Tag::parse(data).map_or_else(Self::default, |tag| Self::from_id3(&tag));
+        let ape = ApeTag::parse(data).map_or_else(Self::default, |tag| Self::from_ape(&tag));
+        let lame = FrameIterator::new(data)
+            .flatten()
+            .next()
+            .and_then(|frame| LameTag::from_frame(&frame))
+            .map_or_else(Self::default, |tag| Self::from_lame(&tag));
+
+        let gain = id3.or(ape).or(lame);
+        (gain.track_gain.is_some() || gain.album_gain.is_some()).then_some(gain)
+    }
+
+    // TXXX frames described REPLAYGAIN_TRACK_GAIN, REPLAYGAIN_TRACK_PEAK... as foobar2000 and
+    // most taggers write them, descriptions being case insensitive
+    pub fn from_id3(tag: &Tag) -> Self {
+        Self::from_text(|key| tag.user_text(key))
+    }
+
+    // Items with the same keys as the TXXX frames of `from_id3`
+    pub fn from_ape(tag: &ApeTag) -> Self {
+        Self::from_text(|key| tag.text(key))
+    }
+
+    // Radio gain for the track, audiophile gain for the album
+    pub fn from_lame(tag: &LameTag) -> Self {
+        Self {
+            track_gain: tag.radio_gain(),
+            track_peak: tag.peak(),
+            album_gain: tag.audiophile_gain(),
+            album_peak: None,
+        }
+    }
+
+    // Values missing from `self` taken from `other`
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            track_gain: self.track_gain.or(other.track_gain),
+            track_peak: self.track_peak.or(other.track_peak),
+            album_gain: self.album_gain.or(other.album_gain),
+            album_peak: self.album_peak.or(other.album_peak),
+        }
+    }
+
+    fn from_text(text: impl Fn(&str) -> Option<String>) -> Self {
+        let value = |key: &str| parse_value(&text(key)?);
+
+        Self {
+            track_gain: value("REPLAYGAIN_TRACK_GAIN"),
+            track_peak: value("REPLAYGAIN_TRACK_PEAK"),
+            album_gain: value("REPLAYGAIN_ALBUM_GAIN"),
+            album_peak: value("REPLAYGAIN_ALBUM_PEAK"),
+        }
+    }
+
+    // Factor to multiply the samples by, falling back to the other gain when the requested one
+    // is missing. `preamp` is in dB. None without any gain.
+    pub fn scale(&self, mode: GainMode, preamp: f32, clipping: Clipping) -> Option<f32> {
+        let track = self.track_gain.map(|gain| (gain, self.track_peak));
+        let album = self.album_gain.map(|gain| (gain, self.album_peak));
+        let (gain, peak) = match mode {
+            GainMode::Track => track.or(album),
+            GainMode::Album => album.or(track),
+        }?;
+
+        let scale = 10f32.powf((gain + preamp) / 20.0);
+        match (clipping, peak) {
+            (Clipping::Peak, Some(peak)) if peak > 0.0 => Some(scale.min(1.0 / peak)),
+            _ => Some(scale),
+        }
+    }
+}
+
+// "-6.54 dB", "+1.2dB", "0.988553", "-6,54 dB": the unit is optional and the decimal separator
+// can be a comma
+fn parse_value(text: &str) -> Option<f32> {
+    let text = text.trim();
+    let number = match text.len().checked_sub(2) {
+        Some(end) if text.is_char_boundary(end) && text[end..].eq_ignore_ascii_case("db") => {
+            text[..end].trim_end()
+        }
+        _ => text,
+    };
+
+    number.replace(',', ".").parse().ok()
+}
+
+// How the decoder applies ReplayGain, see `Decoder::with_replay_gain`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplayGainOptions {
+    pub mode: GainMode,
+    // Added to the stored gain, in dB
+    pub preamp: f32,
+    pub clipping: Clipping,
+}
+
+impl Default for ReplayGainOptions {
+    fn default() -> Self {
+        Self {
+            mode: GainMode::Track,
+            preamp: 0.0,
+            clipping: Clipping::Peak,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::{LAME_AUDIOPHILE_GAIN_OFFSET, LAME_RADIO_GAIN_OFFSET},
+        test_utils::{frames, HEADER_128},
+    };
+
+    // ID3v2.3 tag of Latin-1 TXXX frames
+    fn txxx_tag(values: &[(&str, &str)]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (description, value) in values {
+            let content = [&[0], description.as_bytes(), &[0], value.as_bytes()].concat();
+            body.extend(b"TXXX");
+            body.extend((content.len() as u32).to_be_bytes());
+            body.extend([0, 0]);
+            body.extend(content);
+        }
+
+        let mut tag = vec![b'I', b'D', b'3', 3, 0, 0, 0, 0, 0, body.len() as u8];
+        tag.extend(body);
+        tag
+    }
+
+    #[test]
+    fn scale() {
+        let gain = ReplayGain {
+            track_gain: Some(-6.0),
+            track_peak: Some(0.5),
+            album_gain: Some(6.0),
+            album_peak: Some(0.8),
+        };
+        let scale =
+            |gain: &ReplayGain, mode, preamp, clipping| gain.scale(mode, preamp, clipping).unwrap();
+
+        assert!((scale(&gain, GainMode::Track, 0.0, Clipping::Hard) - 0.501).abs() < 0.001);
+        assert!((scale(&gain, GainMode::Track, 6.0, Clipping::Hard) - 1.0).abs() < 0.001);
+        assert!((scale(&gain, GainMode::Album, 0.0, Clipping::Hard) - 1.995).abs() < 0.001);
+
+        // The peaks allow up to 2.0 and 1.25
+        assert!((scale(&gain, GainMode::Track, 18.0, Clipping::Peak) - 2.0).abs() < 0.001);
+        assert!((scale(&gain, GainMode::Album, 0.0, Clipping::Peak) - 1.25).abs() < 0.001);
+
+        // Falling back to the other gain, peak limiting only applying with a peak
+        let track = ReplayGain {
+            track_gain: Some(-6.0),
+            ..ReplayGain::default()
+        };
+        assert!((scale(&track, GainMode::Album, 12.0, Clipping::Peak) - 1.995).abs() < 0.001);
+        assert_eq!(
+            ReplayGain::default().scale(GainMode::Track, 0.0, Clipping::Peak),
+            None
+        );
+    }
+
+    #[test]
+    fn gain_of_a_tagged_stream() {
+        let tag = txxx_tag(&[
+            ("replaygain_track_gain", "-6.48 dB"),
+            ("REPLAYGAIN_TRACK_PEAK", "0.988553"),
+        ]);
+        let stream = frames(&[HEADER_128; 3]);
+        let data = [tag, stream.clone()].concat();
+
+        assert_eq!(
+            ReplayGain::from_stream(&data),
+            Some(ReplayGain {
+                track_gain: Some(-6.48),
+                track_peak: Some(0.988553),
+                ..ReplayGain::default()
+            })
+        );
+        assert_eq!(ReplayGain::from_stream(&stream), None);
+        assert_eq!(
+            ReplayGain::from_stream(include_bytes!("../fixtures/cbr.mp3")),
+            None
+        );
+    }
+
+    #[test]
+    fn lenient_values() {
+        assert_eq!(parse_value("-6.54 dB"), Some(-6.54));
+        assert_eq!(parse_value("+1.2dB"), Some(1.2));
+        assert_eq!(parse_value(" -3.00 DB "), Some(-3.0));
+        assert_eq!(parse_value("0.988553"), Some(0.988553));
+        assert_eq!(parse_value("-6,54 dB"), Some(-6.54));
+        assert_eq!(parse_value("dB"), None);
+        assert_eq!(parse_value("loud"), None);
+        assert_eq!(parse_value(""), None);
+    }
+
+    #[test]
+    fn precedence() {
+        // LAME tag of the Info frame with a radio gain of -3.2 dB and an audiophile gain of
+        // -1.5 dB. Its CRC no longer matches, which doesn't matter here.
+        let mut audio = include_bytes!("../fixtures/cbr.mp3").to_vec();
+        let lame = audio.windows(4).position(|bytes| bytes == b"LAME").unwrap();
+        let radio: u16 = (1 << 13) | (1 << 9) | 32;
+        let audiophile: u16 = (2 << 13) | (1 << 9) | 15;
+        audio[lame + LAME_RADIO_GAIN_OFFSET..][..2].copy_from_slice(&radio.to_be_bytes());
+        audio[lame + LAME_AUDIOPHILE_GAIN_OFFSET..][..2].copy_from_slice(&audiophile.to_be_bytes());
+
+        let lame = ReplayGain::from_stream(&audio).unwrap();
+        assert_eq!(
+            lame,
+            ReplayGain {
+                track_gain: Some(-3.2),
+                album_gain: Some(-1.5),
+                ..ReplayGain::default()
+            }
+        );
+
+        // The mp3gain APE tag has a track gain and peak, the TXXX frames a track gain only
+        let ape = include_bytes!("../fixtures/mp3gain.ape");
+        let id3 = txxx_tag(&[("REPLAYGAIN_TRACK_GAIN", "-6.48 dB")]);
+        let data = [&id3[..], &audio, ape].concat();
+        assert_eq!(
+            ReplayGain::from_stream(&data).unwrap(),
+            ReplayGain {
+                track_gain: Some(-6.48),
+                track_peak: Some(0.845602),
+                album_gain: Some(-1.5),
+                album_peak: None,
+            }
+        );
+
+        let data = [&audio[..], ape].concat();
+        assert_eq!(
+            ReplayGain::from_stream(&data).unwrap().track_gain,
+            Some(-6.155)
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use crate::{
+    constants::*,
+    header::Header,
+    scalefactors::Scalefactors,
+    side_info::{mixed_bands, short_band_lines, BlockType, GranuleInfo},
+};
+use std::sync::OnceLock;
+
+// |x|^(4/3) of the magnitudes the Huffman tables code without linbits, plus some margin
+const POW43_TABLE_SIZE: usize = 256;
+
+fn pow43(value: i32) -> f32 {
+    static TABLE: OnceLock<Vec<f32>> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        (0..POW43_TABLE_SIZE)
+            .map(|magnitude| (magnitude as f64).powf(4.0 / 3.0) as f32)
+            .collect()
+    });
+
+    let magnitude = value.unsigned_abs() as usize;
+    let result = match table.get(magnitude) {
+        Some(result) => *result,
+        // Only reachable with linbits
+        None => (magnitude as f64).powf(4.0 / 3.0) as f32,
+    };
+
+    if value < 0 {
+        -result
+    } else {
+        result
+    }
+}
+
+// Turns the decoded Huffman values of one granule/channel into spectral values:
+// sign(x) * |x|^(4/3) * 2^((global_gain - 210) / 4) * 2^-(scalefac_multiplier * scalefactor)
+// Long bands add the pretab to the scalefactor when preflag is set, short bands (kept in the
+// decoded order, band by band then window by window) lower the gain by 8 per subblock_gain step.
+pub fn requantize(
+    samples: &[i32; 576],
+    header: &Header,
+    info: &GranuleInfo,
+    scalefactors: &Scalefactors,
+    output: &mut [f32; 576],
+) {
+    let rate = header.sample_rate_index();
+    let global = info.global_gain as f32 - 210.0;
+    let multiplier = if info.scalefac_scale { 1.0 } else { 0.5 };
+
+    let mut requantize_band = |lines: std::ops::Range<usize>, exponent: f32| {
+        let scale = 2f32.powf(exponent);
+        for line in lines {
+            output[line] = match samples[line] {
+                0 => 0.0,
+                value => pow43(value) * scale,
+            };
+        }
+    };
+
+    // Long bands up to the end of the spectrum, or up to the short part of a mixed block
+    let (long_bands, first_short) = match (info.block_type, info.mixed_block) {
+        (BlockType::Short, false) => (0, 0),
+        (BlockType::Short, true) => mixed_bands(rate),
+        _ => (22, 13),
+    };
+
+    for band in 0..long_bands {
+        let scalefactor = scalefactors.long[band]
+            + if scalefactors.preflag {
+                PRETAB[band]
+            } else {
+                0
+            };
+        requantize_band(
+            SFB_LONG[rate][band]..SFB_LONG[rate][band + 1],
+            global / 4.0 - multiplier * scalefactor as f32,
+        );
+    }
+
+    for band in first_short..13 {
+        let lines = short_band_lines(rate, band, info.mixed_block);
+        let width = lines.len();
+
+        for window in 0..3 {
+            let first = lines.start * 3 + window * width;
+            requantize_band(
+                first..first + width,
+                (global - 8.0 * info.subblock_gain[window] as f32) / 4.0
+                    - multiplier * scalefactors.short[band][window] as f32,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    const VBR: &[u8] = include_bytes!("../fixtures/vbr.mp3");
+
+    fn requantized(data: &[u8], index: usize, granule: usize, channel: usize) -> [f32; 576] {
+        let decoded = decode_values(data, index);
+        let mut output = [0.0; 576];
+        requantize(
+            &decoded.values[granule][channel],
+            &decoded.header,
+            &decoded.side_info.granules[granule][channel],
+            &decoded.scalefactors[granule][channel],
+            &mut output,
+        );
+        output
+    }
+
+    #[test]
+    fn pow43_beyond_the_table() {
+        assert_eq!(pow43(0), 0.0);
+        assert_eq!(pow43(8), 16.0);
+        assert_eq!(pow43(-8), -16.0);
+        // Largest magnitude with 13 linbits
+        let expected = (8206f64).powf(4.0 / 3.0) as f32;
+        assert_eq!(pow43(8206), expected);
+        assert_eq!(pow43(-8206), -expected);
+    }
+
+    // Expected values printed by symphonia for frame 1 of fixtures/vbr.mp3
+    #[test]
+    fn long_block_with_pretab() {
+        let output = requantized(VBR, 1, 0, 0);
+        assert_close(
+            &output[..16],
+            &[
+                -0.0076866294,
+                -0.0076866294,
+                -0.0076866294,
+                -0.0065695043,
+                -0.0065695043,
+                -0.004476593,
+                -0.0026071093,
+                -0.00041059396,
+                0.0017765369,
+                0.005498066,
+                0.0076866294,
+                0.010044682,
+                0.011280308,
+                0.012550771,
+                0.011280308,
+                0.010044682,
+            ],
+        );
+        assert_close(
+            &output[300..308],
+            &[
+                -2.5662122e-5,
+                -2.5662122e-5,
+                -2.5662122e-5,
+                -2.5662122e-5,
+                -2.5662122e-5,
+                -2.5662122e-5,
+                2.5662122e-5,
+                2.5662122e-5,
+            ],
+        );
+        assert!((energy(&output) / 1.1352545415379152e-3 - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn short_block_with_subblock_gain() {
+        let output = requantized(VBR, 1, 1, 0);
+        assert_close(
+            &output[..16],
+            &[
+                -0.012772526,
+                0.027966628,
+                -0.015092238,
+                0.25338483,
+                0.0013810679,
+                0.003480073,
+                -0.018493216,
+                0.18892746,
+                0.0029519913,
+                0.00087001827,
+                -0.025239173,
+                0.052013136,
+                0.105338134,
+                -0.032184746,
+                -0.0029519913,
+                0.005524272,
+            ],
+        );
+        assert_close(
+            &output[48..60],
+            &[
+                0.0016345849,
+                -0.0019531252,
+                -0.0005281676,
+                0.0010436865,
+                -0.0010436865,
+                0.0010436865,
+                1.5258789e-5,
+                -1.5258789e-5,
+                0.0,
+                -1.5258789e-5,
+                1.5258789e-5,
+                0.0,
+            ],
+        );
+        assert!((energy(&output) / 1.8236849712519204e-1 - 1.0).abs() < 1e-5);
+
+        let output = requantized(VBR, 1, 1, 1);
+        assert!((energy(&output) / 9.950194799574283e-2 - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn lsf_long_block() {
+        let data = include_bytes!("../fixtures/mpeg2_stereo.mp3");
+        let output = requantized(data, 5, 0, 1);
+        assert_close(
+            &output[..12],
+            &[
+                0.0,
+                0.0,
+                0.0,
+                1.9073486e-6,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                -7.6293945e-6,
+                -7.6293945e-6,
+            ],
+        );
+        assert!((energy(&output) / 4.4941543774473985e-2 - 1.0).abs() < 1e-5);
+    }
+}
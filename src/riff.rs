@@ -0,0 +1,112 @@
+use crate::constants::*;
+use std::ops::Range;
+
+// Byte range of the data chunk of a RIFF/WAVE file holding MPEG audio (format tag 0x0055 for
+// Layer III, 0x0050 for Layers I and II). None for anything else, PCM WAVE files included.
+// A data chunk announcing more bytes than there are is cut at the end of the buffer.
+pub fn unwrap_riff(data: &[u8]) -> Option<Range<usize>> {
+    if data.get(..4)? != b"RIFF" || data.get(8..12)? != b"WAVE" {
+        return None;
+    }
+
+    let mut position = RIFF_HEADER_SIZE;
+    let mut mpeg = false;
+
+    // Chunks are an id, a little endian size and the content, padded to an even size
+    while let Some(chunk) = data.get(position..position + RIFF_CHUNK_HEADER_SIZE) {
+        let size = u32::from_le_bytes(chunk[4..8].try_into().unwrap()) as usize;
+        let start = position + RIFF_CHUNK_HEADER_SIZE;
+
+        match &chunk[..4] {
+            b"fmt " => {
+                let format = data.get(start..start + 2)?;
+                let format = u16::from_le_bytes(format.try_into().unwrap());
+                mpeg = format == WAVE_FORMAT_MPEG_LAYER3 || format == WAVE_FORMAT_MPEG;
+            }
+            b"data" => {
+                return mpeg.then_some(start..start.saturating_add(size).min(data.len()));
+            }
+            _ => {}
+        }
+
+        position = start.checked_add(size)?.checked_add(size % 2)?;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        frame::FrameIterator,
+        test_utils::{frames, HEADER_128},
+    };
+
+    fn chunk(id: &[u8], content: &[u8]) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.extend((content.len() as u32).to_le_bytes());
+        chunk.extend(content);
+        if content.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    // RIFF/WAVE file made of these chunks
+    fn riff(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body = chunks.concat();
+        let mut data = b"RIFF".to_vec();
+        data.extend((body.len() as u32 + 4).to_le_bytes());
+        data.extend(b"WAVE");
+        data.extend(body);
+        data
+    }
+
+    #[test]
+    fn minimal_riff() {
+        let audio = frames(&[HEADER_128; 3]);
+        let mut fmt = WAVE_FORMAT_MPEG_LAYER3.to_le_bytes().to_vec();
+        fmt.resize(16, 0);
+        // An odd sized chunk before the data, padded to an even size
+        let data = riff(&[
+            chunk(b"fmt ", &fmt),
+            chunk(b"LIST", b"odd"),
+            chunk(b"data", &audio),
+        ]);
+
+        let range = unwrap_riff(&data).unwrap();
+        // 417 bytes frames, the data chunk is padded as well
+        assert_eq!(range, 12 + 24 + 12 + 8..data.len() - 1);
+        assert_eq!(data[range], audio);
+        let offsets: Vec<_> = FrameIterator::new(&data)
+            .flatten()
+            .map(|frame| frame.offset)
+            .collect();
+        assert_eq!(offsets, [56, 56 + 417, 56 + 2 * 417]);
+
+        // PCM, or a data chunk before the format
+        fmt[..2].copy_from_slice(&1u16.to_le_bytes());
+        assert_eq!(
+            unwrap_riff(&riff(&[chunk(b"fmt ", &fmt), chunk(b"data", &audio)])),
+            None
+        );
+        assert_eq!(unwrap_riff(&riff(&[chunk(b"data", &audio)])), None);
+        assert_eq!(unwrap_riff(&audio), None);
+
+        // Cut short
+        assert_eq!(unwrap_riff(&data[..100]), Some(56..100));
+    }
+
+    #[test]
+    fn lame_file_in_riff() {
+        let data = include_bytes!("../fixtures/riff.wav");
+        let cbr = include_bytes!("../fixtures/cbr.mp3");
+
+        // Format, fact then data chunks
+        assert_eq!(unwrap_riff(data), Some(70..70 + cbr.len()));
+        let frames: Vec<_> = FrameIterator::new(data).collect();
+        assert_eq!(frames.len(), 12);
+        assert!(frames.iter().all(Result::is_ok));
+    }
+}
@@ -0,0 +1,358 @@
+use crate::{
+    bit_reader::BitReader,
+    constants::*,
+    error::ChompError,
+    header::MPEG_Version,
+    side_info::{mixed_bands, BlockType, SideInfo},
+};
+
+// Scalefactors of one channel in one granule
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Scalefactors {
+    // Long block bands, the last one is never transmitted and stays 0
+    pub long: [usize; 22],
+    // Short block bands, indexed by [band][window], the last band stays 0
+    pub short: [[usize; 3]; 13],
+    // Bits read from the main data (the "part 2" of part2_3_length)
+    pub bits: usize,
+    // Transmitted by MPEG-1, implied by scalefac_compress for MPEG-2 and 2.5
+    pub preflag: bool,
+    // MPEG-2 and 2.5 intensity coded channel only: bit length of each band scalefactor, the
+    // largest value ((1 << slen) - 1) being an illegal intensity position
+    pub slen_long: [usize; 22],
+    pub slen_short: [usize; 13],
+    // MPEG-2 and 2.5 intensity coded channel only: selects the intensity position scale
+    pub intensity_scale: usize,
+}
+
+impl Scalefactors {
+    // Reads the scalefactors at the start of a granule/channel main data. With MPEG-1, granule 1
+    // reuses the scalefactors of granule 0 (`prev`) for the band groups flagged in scfsi.
+    pub fn read(
+        reader: &mut BitReader,
+        side_info: &SideInfo,
+        granule: usize,
+        channel: usize,
+        prev: Option<&Scalefactors>,
+    ) -> Result<Self, ChompError> {
+        let start = reader.position();
+
+        let mut scalefactors = match side_info.version() {
+            MPEG_Version::One => Self::read_mpeg1(reader, side_info, granule, channel, prev)?,
+            _ => Self::read_lsf(reader, side_info, channel)?,
+        };
+
+        scalefactors.bits = reader.position() - start;
+        Ok(scalefactors)
+    }
+
+    fn read_mpeg1(
+        reader: &mut BitReader,
+        side_info: &SideInfo,
+        granule: usize,
+        channel: usize,
+        prev: Option<&Scalefactors>,
+    ) -> Result<Self, ChompError> {
+        let info = &side_info.granules[granule][channel];
+        let (slen1, slen2) = SLEN[info.scalefac_compress];
+        let mut scalefactors = Self {
+            preflag: info.preflag,
+            ..Default::default()
+        };
+
+        if info.block_type == BlockType::Short {
+            // Mixed blocks start with 8 long bands, then go on with short band 3
+            let first_short = if info.mixed_block {
+                let (long_bands, first_short) = mixed_bands(side_info.sample_rate_index());
+                for band in 0..long_bands {
+                    scalefactors.long[band] = reader.read_bits(slen1)? as usize;
+                }
+                first_short
+            } else {
+                0
+            };
+
+            for band in first_short..12 {
+                let slen = if band < 6 { slen1 } else { slen2 };
+                for window in 0..3 {
+                    scalefactors.short[band][window] = reader.read_bits(slen)? as usize;
+                }
+            }
+        } else {
+            for group in 0..4 {
+                let bands = SCFSI_BANDS[group]..SCFSI_BANDS[group + 1];
+                let slen = if group < 2 { slen1 } else { slen2 };
+
+                if granule == 1 && side_info.scfsi[channel][group] {
+                    let prev = prev.ok_or(ChompError::InvalidSideInfo(
+                        "scfsi set without the first granule scalefactors",
+                    ))?;
+                    scalefactors.long[bands.clone()].copy_from_slice(&prev.long[bands]);
+                } else {
+                    for band in bands {
+                        scalefactors.long[band] = reader.read_bits(slen)? as usize;
+                    }
+                }
+            }
+        }
+
+        Ok(scalefactors)
+    }
+
+    // MPEG-2 and 2.5 use a single granule without scfsi. The 9 bits scalefac_compress gives up
+    // to 4 slen, each used for a partition of the bands, with a different decomposition for the
+    // right channel when intensity stereo is on.
+    fn read_lsf(
+        reader: &mut BitReader,
+        side_info: &SideInfo,
+        channel: usize,
+    ) -> Result<Self, ChompError> {
+        let info = &side_info.granules[0][channel];
+        let compress = info.scalefac_compress;
+        let intensity = channel == 1
+            && side_info
+                .mode_extension()
+                .is_some_and(|extension| extension.intensity_stereo());
+        let mut scalefactors = Self::default();
+
+        let (table, slen) = if intensity {
+            scalefactors.intensity_scale = compress & 1;
+            match compress >> 1 {
+                c if c < 180 => (3, [c / 36, c % 36 / 6, c % 36 % 6, 0]),
+                c if c < 244 => {
+                    let c = c - 180;
+                    (4, [c >> 4, (c & 15) >> 2, c & 3, 0])
+                }
+                c => {
+                    let c = c - 244;
+                    (5, [c / 3, c % 3, 0, 0])
+                }
+            }
+        } else {
+            match compress {
+                c if c < 400 => (0, [(c >> 4) / 5, (c >> 4) % 5, (c & 15) >> 2, c & 3]),
+                c if c < 500 => {
+                    let c = c - 400;
+                    (1, [(c >> 2) / 5, (c >> 2) % 5, c & 3, 0])
+                }
+                c => {
+                    // The only case using the pretab, which has no flag of its own here
+                    scalefactors.preflag = true;
+                    let c = c - 500;
+                    (2, [c / 3, c % 3, 0, 0])
+                }
+            }
+        };
+
+        let block = match (info.block_type, info.mixed_block) {
+            (BlockType::Short, false) => 1,
+            (BlockType::Short, true) => 2,
+            _ => 0,
+        };
+
+        // Scalefactors are stored in band order, short bands with one per window and mixed
+        // blocks starting with their long bands then going on with their first short band
+        let (long_bands, first_short) = match block {
+            0 => (22, 0),
+            1 => (0, 0),
+            _ => mixed_bands(side_info.sample_rate_index()),
+        };
+        let mut index = 0;
+        for (partition, count) in NR_OF_SFB[table][block].into_iter().enumerate() {
+            let slen = slen[partition];
+            for _ in 0..count {
+                let value = reader.read_bits(slen)? as usize;
+                if index < long_bands {
+                    scalefactors.long[index] = value;
+                    scalefactors.slen_long[index] = slen;
+                } else {
+                    let window_index = index - long_bands + first_short * 3;
+                    let band = window_index / 3;
+                    scalefactors.short[band][window_index % 3] = value;
+                    scalefactors.slen_short[band] = slen;
+                }
+                index += 1;
+            }
+        }
+
+        Ok(scalefactors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{header::Header, test_utils::main_data};
+
+    const VBR: &[u8] = include_bytes!("../fixtures/vbr.mp3");
+
+    // Scalefactors of every granule and channel of one frame
+    fn read_frame(data: &[u8], index: usize) -> [[Scalefactors; 2]; 2] {
+        let (_, side_info, main_data) = main_data(data, index);
+
+        let mut reader = BitReader::new(&main_data);
+        let mut scalefactors = [[Scalefactors::default(); 2]; 2];
+        for (granule, channel) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            if granule >= side_info.granules() {
+                break;
+            }
+            let start = reader.position();
+            let prev = scalefactors[0][channel];
+            scalefactors[granule][channel] =
+                Scalefactors::read(&mut reader, &side_info, granule, channel, Some(&prev)).unwrap();
+            reader.seek(start + side_info.granules[granule][channel].part2_3_length);
+        }
+        scalefactors
+    }
+
+    // Short scalefactors laid out band after band, one per window
+    fn flatten(short: &[[usize; 3]; 13]) -> Vec<usize> {
+        short[..12].iter().flatten().copied().collect()
+    }
+
+    // Expected values printed by symphonia
+    #[test]
+    fn long_and_short_blocks() {
+        let [[start, _], [short_left, short_right]] = read_frame(VBR, 1);
+
+        assert_eq!(
+            start.long[..21],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 3, 5, 7, 6, 5, 4, 5, 5, 0]
+        );
+        assert_eq!(start.bits, 52);
+        assert!(start.preflag);
+
+        assert_eq!(
+            flatten(&short_left.short),
+            [
+                0, 0, 0, 0, 0, 0, 5, 14, 13, 4, 15, 0, 3, 13, 0, 3, 14, 0, 5, 0, 0, 5, 0, 0, 6, 0,
+                0, 5, 0, 0, 0, 0, 0, 0, 0, 0
+            ]
+        );
+        assert_eq!(short_left.long, [0; 22]);
+        assert_eq!(short_left.bits, 126);
+        assert_eq!(
+            flatten(&short_right.short),
+            [
+                1, 11, 14, 0, 2, 0, 4, 15, 13, 2, 15, 0, 4, 14, 0, 3, 15, 0, 5, 0, 0, 5, 0, 0, 6,
+                0, 0, 5, 0, 0, 5, 0, 0, 0, 0, 0
+            ]
+        );
+    }
+
+    #[test]
+    fn scfsi_reuses_the_first_granule() {
+        // scfsi set for the last two band groups (bands 11 to 20) of both channels
+        let [[left0, right0], [left1, right1]] = read_frame(VBR, 2);
+
+        assert_eq!(left0.long[..9], [10, 7, 0, 0, 5, 6, 7, 15, 14]);
+        assert_eq!(right0.long[..9], [15, 8, 6, 2, 0, 4, 8, 11, 13]);
+        assert_eq!(left0.bits, 64);
+
+        assert_eq!(left1.long[..9], [10, 5, 0, 0, 7, 6, 8, 15, 0]);
+        assert_eq!(right1.long[..9], [15, 14, 11, 2, 0, 2, 7, 11, 15]);
+        assert_eq!(left1.long[11..], left0.long[11..]);
+        assert_eq!(right1.long[11..], right0.long[11..]);
+        // Only the first two groups, 11 bands of 4 bits, are read again
+        assert_eq!(left1.bits, 44);
+        assert_eq!(right1.bits, 44);
+    }
+
+    // Expected values printed by symphonia, 24 kHz joint stereo
+    #[test]
+    fn lsf_long_and_short_blocks() {
+        let data = include_bytes!("../fixtures/mpeg2_stereo.mp3");
+
+        let [[left, right], _] = read_frame(data, 5);
+        assert_eq!(
+            left.long[..21],
+            [0, 0, 0, 0, 0, 0, 0, 2, 3, 2, 1, 4, 4, 4, 1, 2, 1, 2, 1, 2, 1]
+        );
+        assert_eq!(left.bits, 35);
+        assert_eq!(
+            right.long[..21],
+            [4, 0, 0, 0, 0, 0, 0, 0, 0, 3, 4, 4, 5, 3, 1, 2, 2, 3, 3, 3, 4]
+        );
+        assert_eq!(right.bits, 63);
+        assert!(!left.preflag && !right.preflag);
+
+        let [[left, right], _] = read_frame(data, 2);
+        let mut expected = [[0; 3]; 13];
+        expected[9][0] = 1;
+        assert_eq!(left.short, expected);
+        assert_eq!(left.bits, 9);
+        expected[7][0] = 1;
+        assert_eq!(right.short, expected);
+        assert_eq!(right.bits, 18);
+    }
+
+    fn set_bits(bytes: &mut [u8], position: usize, count: usize, value: usize) {
+        for i in 0..count {
+            let bit = position + i;
+            if value >> (count - 1 - i) & 1 == 1 {
+                bytes[bit / 8] |= 0x80 >> (bit % 8);
+            }
+        }
+    }
+
+    #[test]
+    fn lsf_intensity_and_preflag() {
+        // MPEG-2 Layer III, 64 kbit/s, 24000 Hz, joint stereo with intensity stereo only
+        let header = Header::from_bytes(&[0xFF, 0xF3, 0x84, 0x50]).unwrap();
+
+        // scalefac_compress of each channel, 63 bits apart after 10 bits
+        let mut blob = [0; 17];
+        // Left: slen (1, 2), implied preflag
+        set_bits(&mut blob, 10 + 29, 9, 500 + 3 + 2);
+        // Right, intensity coded: slen (2, 1, 3) and intensity_scale 1
+        set_bits(&mut blob, 10 + 63 + 29, 9, ((2 * 36 + 6 + 3) << 1) | 1);
+        let side_info = SideInfo::parse(&mut BitReader::new(&blob), &header).unwrap();
+
+        let main_data = [0xFF; 16];
+        let mut reader = BitReader::new(&main_data);
+        let left = Scalefactors::read(&mut reader, &side_info, 0, 0, None).unwrap();
+        assert!(left.preflag);
+        assert_eq!(left.bits, 11 + 10 * 2);
+        assert_eq!(left.long[..11], [1; 11]);
+        assert_eq!(left.long[11..21], [3; 10]);
+        assert_eq!(left.intensity_scale, 0);
+
+        let mut reader = BitReader::new(&main_data);
+        let right = Scalefactors::read(&mut reader, &side_info, 0, 1, None).unwrap();
+        assert!(!right.preflag);
+        assert_eq!(right.intensity_scale, 1);
+        assert_eq!(right.bits, 7 * 2 + 7 + 7 * 3);
+        assert_eq!(right.long[..7], [3; 7]);
+        assert_eq!(right.long[7..14], [1; 7]);
+        assert_eq!(right.long[14..21], [7; 7]);
+        // The largest value of each band, an illegal intensity position, is found from slen
+        assert_eq!(right.slen_long[..7], [2; 7]);
+        assert_eq!(right.slen_long[7..14], [1; 7]);
+        assert_eq!(right.slen_long[14..21], [3; 7]);
+        assert_eq!(right.long[21], 0);
+    }
+
+    // Expected values printed by symphonia for frame 0 of fixtures/mixed.mp3
+    #[test]
+    fn mixed_block() {
+        let [[left, right], _] = read_frame(include_bytes!("../fixtures/mixed.mp3"), 0);
+
+        // 8 long bands, then short bands 3 to 11
+        assert_eq!(left.long[..8], [0, 1, 2, 1, 3, 3, 3, 1]);
+        assert_eq!(left.long[8..], [0; 14]);
+        assert_eq!(
+            flatten(&left.short),
+            [
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 3, 2, 0, 3, 1, 1, 0, 3, 2, 0, 2, 0, 0, 2, 1, 2, 1,
+                2, 1, 3, 2, 1, 3, 1, 3
+            ]
+        );
+        assert_eq!(left.bits, 105);
+
+        assert_eq!(right.long[..8], [5, 3, 2, 2, 5, 0, 0, 1]);
+        assert_eq!(
+            flatten(&right.short)[9..],
+            [3, 0, 1, 6, 6, 2, 4, 3, 5, 1, 3, 4, 4, 6, 5, 6, 0, 2, 0, 2, 0, 4, 0, 3, 3, 2, 4]
+        );
+    }
+}
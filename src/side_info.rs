@@ -0,0 +1,432 @@
+use crate::{
+    bit_reader::BitReader,
+    constants::*,
+    error::ChompError,
+    header::{Header, MPEG_Version, Mode, ModeExtension},
+};
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BlockType {
+    #[default]
+    Normal,
+    Start,
+    Short,
+    Stop,
+}
+
+// Side information of one channel in one granule
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GranuleInfo {
+    // Bits used by the scalefactors and the huffman coded data
+    pub part2_3_length: usize,
+    // Number of value pairs in the big_values region
+    pub big_values: usize,
+    pub global_gain: usize,
+    pub scalefac_compress: usize,
+    pub window_switching: bool,
+    pub block_type: BlockType,
+    pub mixed_block: bool,
+    // Huffman table of each big_values region (only two regions with window switching)
+    pub table_select: [usize; 3],
+    pub subblock_gain: [usize; 3],
+    // Explicit without window switching, implied by the block type otherwise
+    pub region0_count: usize,
+    pub region1_count: usize,
+    pub preflag: bool,
+    pub scalefac_scale: bool,
+    pub count1table_select: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SideInfo {
+    // Negative offset, in bytes, from the first byte of the side info to the main data
+    pub main_data_begin: usize,
+    pub private_bits: usize,
+    // Scalefactor selection information, per channel and band group (MPEG-1 only)
+    pub scfsi: [[bool; 4]; 2],
+    // Indexed by [granule][channel], MPEG-2 and 2.5 only use the first granule
+    pub granules: [[GranuleInfo; 2]; 2],
+    channels: usize,
+    granule_count: usize,
+    version: MPEG_Version,
+    // Index in the scalefactor band tables
+    sample_rate_index: usize,
+    // Only set in joint stereo mode
+    mode_extension: Option<ModeExtension>,
+}
+
+impl SideInfo {
+    // Reads the side information that follows the header (and CRC) of a Layer III frame.
+    // MPEG-1 uses 2 granules (17/32 bytes), the low sampling frequency extensions (MPEG-2 and
+    // 2.5) a single one (9/17 bytes) with a shorter main_data_begin and a 9 bits scalefac_compress.
+    pub fn parse(reader: &mut BitReader, header: &Header) -> Result<Self, ChompError> {
+        let lsf = header.version() != MPEG_Version::One;
+        let channels = header.channels();
+
+        let (main_data_begin_size, private_bits_size) = match (lsf, channels) {
+            (false, 1) => (9, 5),
+            (false, _) => (9, 3),
+            (true, 1) => (8, 1),
+            (true, _) => (8, 2),
+        };
+
+        let mut side_info = Self {
+            main_data_begin: reader.read_bits(main_data_begin_size)? as usize,
+            private_bits: reader.read_bits(private_bits_size)? as usize,
+            scfsi: [[false; 4]; 2],
+            granules: [[GranuleInfo::default(); 2]; 2],
+            channels,
+            granule_count: if lsf { 1 } else { 2 },
+            version: header.version(),
+            sample_rate_index: header.sample_rate_index(),
+            mode_extension: match header.mode() {
+                Mode::JointStereo => Some(header.mode_extension()),
+                _ => None,
+            },
+        };
+
+        if !lsf {
+            for scfsi in side_info.scfsi.iter_mut().take(channels) {
+                for band in scfsi.iter_mut() {
+                    *band = reader.read_bit()?;
+                }
+            }
+        }
+
+        for granule in side_info.granules.iter_mut().take(side_info.granule_count) {
+            for info in granule.iter_mut().take(channels) {
+                *info = GranuleInfo::parse(reader, lsf)?;
+            }
+        }
+
+        Ok(side_info)
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    // 2 for MPEG-1, 1 for MPEG-2 and 2.5
+    pub fn granules(&self) -> usize {
+        self.granule_count
+    }
+
+    pub fn version(&self) -> MPEG_Version {
+        self.version
+    }
+
+    pub fn mode_extension(&self) -> Option<ModeExtension> {
+        self.mode_extension
+    }
+
+    pub(crate) fn sample_rate_index(&self) -> usize {
+        self.sample_rate_index
+    }
+}
+
+// Number of long bands a mixed block starts with, and the short band it goes on with. The long
+// part always covers the first 2 subbands (36 lines), so the short part resumes at line 12 of
+// each window. At 8 kHz that line falls within short band 1, which is then cut there, as other
+// decoders do since no standard covers it.
+pub(crate) fn mixed_bands(rate: usize) -> (usize, usize) {
+    let long_bands = SFB_LONG[rate].iter().position(|line| *line == 36).unwrap();
+    let first_short = SFB_SHORT[rate]
+        .iter()
+        .rposition(|line| *line <= 12)
+        .unwrap();
+
+    (long_bands, first_short)
+}
+
+// Lines of a short band within one window, the first short band of a mixed block starting at
+// line 12
+pub(crate) fn short_band_lines(rate: usize, band: usize, mixed: bool) -> Range<usize> {
+    let start = SFB_SHORT[rate][band];
+    let end = SFB_SHORT[rate][band + 1];
+
+    match mixed {
+        true => start.max(12)..end,
+        false => start..end,
+    }
+}
+
+impl GranuleInfo {
+    fn parse(reader: &mut BitReader, lsf: bool) -> Result<Self, ChompError> {
+        let mut info = Self {
+            part2_3_length: reader.read_bits(12)? as usize,
+            big_values: reader.read_bits(9)? as usize,
+            global_gain: reader.read_bits(8)? as usize,
+            scalefac_compress: reader.read_bits(if lsf { 9 } else { 4 })? as usize,
+            window_switching: reader.read_bit()?,
+            ..Default::default()
+        };
+
+        // 576 values per granule, so at most 288 pairs
+        if info.big_values > 288 {
+            return Err(ChompError::InvalidSideInfo("big_values above 288"));
+        }
+
+        if info.window_switching {
+            info.block_type = match reader.read_bits(2)? {
+                0 => {
+                    return Err(ChompError::InvalidSideInfo(
+                        "block_type 0 with window switching",
+                    ))
+                }
+                1 => BlockType::Start,
+                2 => BlockType::Short,
+                _ => BlockType::Stop,
+            };
+            info.mixed_block = reader.read_bit()?;
+            for table in info.table_select.iter_mut().take(2) {
+                *table = reader.read_bits(5)? as usize;
+            }
+            for gain in info.subblock_gain.iter_mut() {
+                *gain = reader.read_bits(3)? as usize;
+            }
+
+            // Implied, region 2 is empty (region1_count reaches past the last band)
+            info.region0_count = match (info.block_type, info.mixed_block) {
+                (BlockType::Short, false) => 8,
+                _ => 7,
+            };
+            info.region1_count = 20 - info.region0_count;
+        } else {
+            for table in info.table_select.iter_mut() {
+                *table = reader.read_bits(5)? as usize;
+            }
+            info.region0_count = reader.read_bits(4)? as usize;
+            info.region1_count = reader.read_bits(3)? as usize;
+        }
+
+        for table in info.table_select {
+            // Tables 4 and 14 are not defined
+            if table == 4 || table == 14 {
+                return Err(ChompError::InvalidSideInfo("undefined huffman table"));
+            }
+        }
+
+        // Not transmitted by MPEG-2 and 2.5, implied by scalefac_compress instead
+        if !lsf {
+            info.preflag = reader.read_bit()?;
+        }
+        info.scalefac_scale = reader.read_bit()?;
+        info.count1table_select = reader.read_bit()?;
+
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{Frame, FrameIterator};
+
+    const VBR: &[u8] = include_bytes!("../fixtures/vbr.mp3");
+
+    fn parse(frame: &Frame) -> SideInfo {
+        let mut reader = BitReader::new(frame.side_info().unwrap());
+        SideInfo::parse(&mut reader, &frame.header).unwrap()
+    }
+
+    // First lines of region 1 and 2, as other decoders report them
+    fn region_starts(info: &GranuleInfo) -> (usize, usize) {
+        match info.window_switching {
+            true => (36, 576),
+            false => (
+                SFB_LONG[0][info.region0_count + 1],
+                SFB_LONG[0][info.region0_count + info.region1_count + 2],
+            ),
+        }
+    }
+
+    // Expected values printed by symphonia for frames 1 and 3 of fixtures/vbr.mp3
+    #[test]
+    fn window_switching_frame() {
+        let frame = FrameIterator::new(VBR).flatten().nth(1).unwrap();
+        let side_info = parse(&frame);
+
+        assert_eq!(side_info.main_data_begin, 0);
+        assert_eq!(side_info.scfsi, [[false; 4]; 2]);
+        assert_eq!(side_info.granules(), 2);
+        assert_eq!(side_info.channels(), 2);
+
+        let [[start, _], [short, _]] = side_info.granules;
+        assert_eq!(start.part2_3_length, 712);
+        assert_eq!(start.big_values, 17);
+        assert_eq!(start.global_gain, 165);
+        assert_eq!(start.scalefac_compress, 10);
+        assert_eq!(start.block_type, BlockType::Start);
+        assert!(!start.mixed_block);
+        assert_eq!(start.table_select, [15, 0, 0]);
+        assert_eq!(start.subblock_gain, [0, 0, 0]);
+        assert_eq!(region_starts(&start), (36, 576));
+        assert!(start.preflag);
+        assert!(!start.scalefac_scale);
+        assert!(start.count1table_select);
+
+        assert_eq!(short.part2_3_length, 818);
+        assert_eq!(short.big_values, 86);
+        assert_eq!(short.global_gain, 172);
+        assert_eq!(short.scalefac_compress, 15);
+        assert_eq!(short.block_type, BlockType::Short);
+        assert_eq!(short.table_select, [27, 13, 0]);
+        assert_eq!(short.subblock_gain, [1, 0, 1]);
+        assert_eq!((short.region0_count, short.region1_count), (8, 12));
+        assert!(!short.preflag);
+        assert!(!short.count1table_select);
+
+        assert_eq!(side_info.granules[1][1].subblock_gain, [1, 0, 0]);
+    }
+
+    #[test]
+    fn long_blocks_frame() {
+        let frame = FrameIterator::new(VBR).flatten().nth(3).unwrap();
+        let side_info = parse(&frame);
+
+        assert_eq!(side_info.main_data_begin, 475);
+        assert_eq!(side_info.scfsi, [[false, false, true, true]; 2]);
+
+        let expected = [
+            [
+                (257, 18, 168, [27, 24, 12], (12, 24)),
+                (291, 22, 166, [15, 26, 12], (16, 24)),
+            ],
+            [
+                (253, 18, 167, [26, 24, 15], (16, 20)),
+                (269, 21, 168, [24, 26, 12], (16, 30)),
+            ],
+        ];
+        for (granule, expected) in side_info.granules.iter().zip(expected) {
+            for (info, (part2_3, big_values, gain, tables, regions)) in granule.iter().zip(expected)
+            {
+                assert!(!info.window_switching);
+                assert_eq!(info.block_type, BlockType::Normal);
+                assert_eq!(info.part2_3_length, part2_3);
+                assert_eq!(info.big_values, big_values);
+                assert_eq!(info.global_gain, gain);
+                assert_eq!(info.scalefac_compress, 14);
+                assert_eq!(info.table_select, tables);
+                assert_eq!(region_starts(info), regions);
+                assert!(info.preflag);
+            }
+        }
+    }
+
+    // MPEG-1 Layer III, 128 kbit/s, 44100 Hz, mono: 17 bytes of side info
+    fn mono_side_info(blob: [u8; 17]) -> Result<SideInfo, ChompError> {
+        let header = Header::from_bytes(&[0xFF, 0xFB, 0x90, 0xC0]).unwrap();
+        SideInfo::parse(&mut BitReader::new(&blob), &header)
+    }
+
+    #[test]
+    fn mono_layout() {
+        // main_data_begin 1, private bits 0b10101, scfsi 0b0110, part2_3_length 0xFFF
+        let mut blob = [0; 17];
+        blob[..5].copy_from_slice(&[0x00, 0xD5, 0xBF, 0xFC, 0x00]);
+        let side_info = mono_side_info(blob).unwrap();
+
+        assert_eq!(side_info.channels(), 1);
+        assert_eq!(side_info.main_data_begin, 1);
+        assert_eq!(side_info.private_bits, 0b10101);
+        assert_eq!(side_info.scfsi[0], [false, true, true, false]);
+        assert_eq!(side_info.granules[0][0].part2_3_length, 0xFFF);
+        assert_eq!(side_info.granules[1][0], GranuleInfo::default());
+    }
+
+    #[test]
+    fn invalid_side_info() {
+        // Window switching flag (bit 51) followed by block_type 0
+        let mut blob = [0; 17];
+        blob[6] = 0x10;
+        assert!(matches!(
+            mono_side_info(blob),
+            Err(ChompError::InvalidSideInfo(
+                "block_type 0 with window switching"
+            ))
+        ));
+
+        // First table_select (bits 52 to 56) set to 4
+        let mut blob = [0; 17];
+        blob[6] = 0x02;
+        assert!(matches!(
+            mono_side_info(blob),
+            Err(ChompError::InvalidSideInfo("undefined huffman table"))
+        ));
+
+        assert!(matches!(
+            mono_side_info([0xFF; 17]),
+            Err(ChompError::InvalidSideInfo("big_values above 288"))
+        ));
+    }
+
+    // Expected values printed by symphonia for the first audio frames of fixtures/mpeg2.mp3 and
+    // fixtures/mpeg25.mp3
+    #[test]
+    fn lsf_frames() {
+        let mpeg2 = include_bytes!("../fixtures/mpeg2.mp3");
+        let frame = FrameIterator::new(mpeg2).flatten().nth(2).unwrap();
+        assert_eq!(frame.header.version(), MPEG_Version::Two);
+        assert_eq!(frame.side_info().unwrap().len(), 9);
+
+        let side_info = parse(&frame);
+        assert_eq!(side_info.granules(), 1);
+        assert_eq!(side_info.main_data_begin, 74);
+        let info = side_info.granules[0][0];
+        assert_eq!(info.part2_3_length, 1947);
+        assert_eq!(info.big_values, 219);
+        assert_eq!(info.global_gain, 142);
+        assert_eq!(info.scalefac_compress, 0);
+        assert_eq!(info.block_type, BlockType::Short);
+        assert_eq!(info.table_select, [31, 21, 0]);
+        assert_eq!(side_info.granules[1][0], GranuleInfo::default());
+
+        let frame = FrameIterator::new(mpeg2).flatten().nth(1).unwrap();
+        let info = parse(&frame).granules[0][0];
+        assert_eq!(info.block_type, BlockType::Start);
+        // 9 bits wide in MPEG-2
+        assert_eq!(info.scalefac_compress, 10);
+        assert_eq!(info.table_select, [25, 6, 0]);
+
+        let frame = FrameIterator::new(mpeg2).flatten().nth(4).unwrap();
+        let side_info = parse(&frame);
+        assert_eq!(side_info.main_data_begin, 108);
+        let info = side_info.granules[0][0];
+        assert_eq!(info.block_type, BlockType::Normal);
+        assert_eq!(info.table_select, [15, 31, 28]);
+        assert_eq!((info.region0_count, info.region1_count), (1, 1));
+
+        let mpeg25 = include_bytes!("../fixtures/mpeg25.mp3");
+        let frame = FrameIterator::new(mpeg25).flatten().nth(2).unwrap();
+        assert_eq!(frame.header.version(), MPEG_Version::TwoPointFive);
+
+        let side_info = parse(&frame);
+        assert_eq!(side_info.granules(), 1);
+        assert_eq!(side_info.main_data_begin, 45);
+        let info = side_info.granules[0][0];
+        assert_eq!(info.part2_3_length, 2270);
+        assert_eq!(info.big_values, 205);
+        assert_eq!(info.global_gain, 141);
+        assert_eq!(info.block_type, BlockType::Short);
+        assert_eq!(info.table_select, [31, 21, 0]);
+    }
+
+    #[test]
+    fn lsf_stereo_layout() {
+        // MPEG-2 Layer III, 64 kbit/s, 22050 Hz, stereo: 17 bytes with 2 private bits
+        let header = Header::from_bytes(&[0xFF, 0xF3, 0x80, 0x00]).unwrap();
+        assert_eq!(header.side_info_length(), 17);
+
+        // main_data_begin 0xA5, private bits 0b11, then the first channel's part2_3_length
+        let mut blob = [0; 17];
+        blob[..3].copy_from_slice(&[0xA5, 0xC0, 0x04]);
+        let side_info = SideInfo::parse(&mut BitReader::new(&blob), &header).unwrap();
+
+        assert_eq!(side_info.channels(), 2);
+        assert_eq!(side_info.granules(), 1);
+        assert_eq!(side_info.main_data_begin, 0xA5);
+        assert_eq!(side_info.private_bits, 0b11);
+        assert_eq!(side_info.granules[0][0].part2_3_length, 1);
+    }
+}
@@ -0,0 +1,572 @@
+use crate::{
+    constants::*,
+    header::{Header, MPEG_Version, Mode},
+    scalefactors::Scalefactors,
+    side_info::{mixed_bands, short_band_lines, BlockType, GranuleInfo},
+};
+use std::{
+    f32::consts::{FRAC_1_SQRT_2, PI},
+    ops::Range,
+};
+
+// Mid/side to left/right over the given spectral lines, the left channel holding the mid
+// values and the right one the side values: L = (M + S) / √2, R = (M - S) / √2
+pub fn ms_stereo(samples: &mut [[f32; 576]; 2], lines: Range<usize>) {
+    let [left, right] = samples;
+
+    for line in lines {
+        let (mid, side) = (left[line], right[line]);
+        left[line] = (mid + side) * FRAC_1_SQRT_2;
+        right[line] = (mid - side) * FRAC_1_SQRT_2;
+    }
+}
+
+// Factors applied to the left channel values to get both channels of an intensity coded band,
+// None when the position is illegal and the band is left alone. MPEG-1 positions give the
+// left/right ratio tan(position * π / 12), 7 being illegal. MPEG-2 and 2.5 attenuate one of
+// the channels by a power of 2^(-1/4) or 2^(-1/2) (intensity_scale), the largest value the
+// band scalefactor can hold being illegal.
+fn intensity_factors(
+    header: &Header,
+    scalefactors: &Scalefactors,
+    position: usize,
+    slen: usize,
+) -> Option<(f32, f32)> {
+    if header.version() == MPEG_Version::One {
+        if position == 7 {
+            return None;
+        }
+
+        let angle = position as f32 * PI / 12.0;
+        let (sin, cos) = angle.sin_cos();
+        return Some((sin / (sin + cos), cos / (sin + cos)));
+    }
+
+    if position == (1 << slen) - 1 {
+        return None;
+    }
+
+    let io: f32 = match scalefactors.intensity_scale {
+        0 => 2f32.powf(-0.25),
+        _ => FRAC_1_SQRT_2,
+    };
+    Some(match position {
+        0 => (1.0, 1.0),
+        odd if odd % 2 == 1 => (io.powi(odd.div_ceil(2) as i32), 1.0),
+        even => (1.0, io.powi(even as i32 / 2)),
+    })
+}
+
+// Factors of the lines of `band` (within one window for short bands) when intensity stereo
+// applies to it. The last band has no scalefactor of its own and reuses the one of the band
+// below, provided that band is also intensity coded.
+fn band_factors(
+    header: &Header,
+    scalefactors: &Scalefactors,
+    (positions, slens): (&[usize], &[usize]),
+    band: usize,
+    first_band: usize,
+) -> Option<(f32, f32)> {
+    let last = positions.len() - 1;
+    if band == last && first_band == last {
+        return None;
+    }
+
+    let band = band.min(last - 1);
+    intensity_factors(header, scalefactors, positions[band], slens[band])
+}
+
+// Intensity factors of each spectral line, in the decoded order (short bands holding their
+// windows one after the other), None where intensity stereo doesn't apply. It applies from the
+// band following the last nonzero value of the right channel, separately for each window of
+// short blocks. The long part of a mixed block is only intensity coded when the short part of
+// the right channel is entirely 0.
+fn intensity_lines(
+    header: &Header,
+    info: &GranuleInfo,
+    scalefactors: &Scalefactors,
+    right: &[f32; 576],
+) -> [Option<(f32, f32)>; 576] {
+    let rate = header.sample_rate_index();
+    let long = &SFB_LONG[rate];
+    let mut factors = [None; 576];
+
+    let mut long_bands = 22;
+    if info.block_type == BlockType::Short {
+        let (mixed_long_bands, first_short) = match info.mixed_block {
+            true => mixed_bands(rate),
+            false => (0, 0),
+        };
+        let mut short_nonzero = false;
+
+        for window in 0..3 {
+            let lines = |band: usize| {
+                let lines = short_band_lines(rate, band, info.mixed_block);
+                let start = lines.start * 3 + window * lines.len();
+                start..start + lines.len()
+            };
+
+            let first_band = (first_short..13)
+                .rev()
+                .find(|band| right[lines(*band)].iter().any(|value| *value != 0.0))
+                .map_or(first_short, |band| band + 1);
+            short_nonzero |= first_band > first_short;
+
+            let positions = scalefactors.short.map(|band| band[window]);
+            for band in first_band..13 {
+                factors[lines(band)].fill(band_factors(
+                    header,
+                    scalefactors,
+                    (&positions, &scalefactors.slen_short),
+                    band,
+                    first_band,
+                ));
+            }
+        }
+
+        long_bands = match short_nonzero {
+            false => mixed_long_bands,
+            true => 0,
+        };
+    }
+
+    let end = long[long_bands];
+    let first_band = match right[..end].iter().rposition(|value| *value != 0.0) {
+        Some(line) => long.iter().position(|start| *start > line).unwrap(),
+        None => 0,
+    };
+    for band in first_band..long_bands {
+        factors[long[band]..long[band + 1]].fill(band_factors(
+            header,
+            scalefactors,
+            (&scalefactors.long, &scalefactors.slen_long),
+            band,
+            first_band,
+        ));
+    }
+
+    factors
+}
+
+// Joint stereo processing of one granule, on the requantized values of both channels before
+// the short blocks are reordered. `info` and `scalefactors` are the ones of the right channel,
+// which carries the intensity positions. Lines coded with intensity stereo get their values
+// from the left channel, the others are mid/side decoded when it is on.
+pub fn stereo(
+    header: &Header,
+    info: &GranuleInfo,
+    scalefactors: &Scalefactors,
+    samples: &mut [[f32; 576]; 2],
+) {
+    if header.mode() != Mode::JointStereo {
+        return;
+    }
+
+    let extension = header.mode_extension();
+    if !extension.intensity_stereo() {
+        if extension.ms_stereo() {
+            ms_stereo(samples, 0..576);
+        }
+        return;
+    }
+
+    let factors = intensity_lines(header, info, scalefactors, &samples[1]);
+    for (line, factors) in factors.into_iter().enumerate() {
+        match factors {
+            Some((left, right)) => {
+                let value = samples[0][line];
+                samples[0][line] = value * left;
+                samples[1][line] = value * right;
+            }
+            None if extension.ms_stereo() => ms_stereo(samples, line..line + 1),
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{requantize::requantize, test_utils::*};
+
+    // Expected values printed by symphonia for the second granule of frame 1 of
+    // fixtures/cbr.mp3, whose header turns mid/side stereo on
+    #[test]
+    fn ms_stereo_frame() {
+        let decoded = decode_values(include_bytes!("../fixtures/cbr.mp3"), 1);
+        assert!(decoded.header.mode_extension().ms_stereo());
+        assert!(!decoded.header.mode_extension().intensity_stereo());
+
+        let mut samples = [[0.0; 576]; 2];
+        for (channel, samples) in samples.iter_mut().enumerate() {
+            requantize(
+                &decoded.values[1][channel],
+                &decoded.header,
+                &decoded.side_info.granules[1][channel],
+                &decoded.scalefactors[1][channel],
+                samples,
+            );
+        }
+        stereo(
+            &decoded.header,
+            &decoded.side_info.granules[1][1],
+            &decoded.scalefactors[1][1],
+            &mut samples,
+        );
+
+        assert_close(
+            &samples[0][..8],
+            &[
+                -0.012007368,
+                0.026580976,
+                -0.0138642425,
+                0.24144217,
+                0.001465108,
+                0.0038800677,
+                -0.018623725,
+                0.17791061,
+            ],
+        );
+        assert_close(
+            &samples[1][..8],
+            &[
+                -0.004893996,
+                0.010197865,
+                -0.011977713,
+                0.019631011,
+                -0.00042142163,
+                -0.0012501422,
+                0.0035734046,
+                0.0061795707,
+            ],
+        );
+        assert!((energy(&samples[0]) / 1.641207697558884e-1 - 1.0).abs() < 1e-5);
+        assert!((energy(&samples[1]) / 9.074384106685575e-2 - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn ms_below_the_intensity_bound() {
+        // MPEG-1 Layer III, 128 kbit/s, 44100 Hz, joint stereo with mid/side and intensity stereo
+        let header = Header::from_bytes(&[0xFF, 0xFB, 0x90, 0x70]).unwrap();
+        let info = GranuleInfo::default();
+        // Intensity position 0 everywhere: the left value goes all to the right channel
+        let scalefactors = Scalefactors::default();
+
+        // The right channel ends in long band 12 (lines 90 to 110), intensity stereo starts with
+        // band 13
+        let mut samples = [[1.0; 576], [0.0; 576]];
+        samples[1][..100].fill(0.5);
+        stereo(&header, &info, &scalefactors, &mut samples);
+
+        let [left, right] = samples;
+        assert_eq!(left[..100], [1.5 * FRAC_1_SQRT_2; 100]);
+        assert_eq!(right[..100], [0.5 * FRAC_1_SQRT_2; 100]);
+        assert_eq!(left[100..110], [FRAC_1_SQRT_2; 10]);
+        assert_eq!(right[100..110], [FRAC_1_SQRT_2; 10]);
+        assert_eq!(left[110..418], [0.0; 308]);
+        assert_eq!(right[110..418], [1.0; 308]);
+
+        // Mid/side alone covers the whole spectrum
+        let header = Header::from_bytes(&[0xFF, 0xFB, 0x90, 0x60]).unwrap();
+        let mut samples = [[1.0; 576], [1.0; 576]];
+        stereo(&header, &info, &scalefactors, &mut samples);
+        assert_eq!(samples, [[2.0 * FRAC_1_SQRT_2; 576], [0.0; 576]]);
+
+        // Nor does plain stereo change anything
+        let header = Header::from_bytes(&[0xFF, 0xFB, 0x90, 0x00]).unwrap();
+        let mut samples = [[1.0; 576], [0.5; 576]];
+        stereo(&header, &info, &scalefactors, &mut samples);
+        assert_eq!(samples, [[1.0; 576], [0.5; 576]]);
+    }
+
+    // Expected values printed by symphonia for frame 1 of fixtures/intensity.mp3
+    #[test]
+    fn intensity_stereo_frame() {
+        let decoded = decode_values(include_bytes!("../fixtures/intensity.mp3"), 1);
+        assert!(decoded.header.mode_extension().ms_stereo());
+        assert!(decoded.header.mode_extension().intensity_stereo());
+
+        // Long blocks: the right channel ends at line 40, intensity stereo starts with band 9
+        // (line 44)
+        assert!(decoded.values[0][1][41..].iter().all(|value| *value == 0));
+        let samples = stereo_granule(&decoded, 0);
+        assert_close(
+            &samples[0][36..56],
+            &[
+                0.00037665048,
+                0.00037665048,
+                0.00020401702,
+                0.00020401702,
+                0.00037665048,
+                -0.00029033376,
+                0.00029033376,
+                0.0,
+                0.0,
+                0.00041059396,
+                -0.00041059396,
+                0.0,
+                0.0,
+                0.0,
+                -0.00041059396,
+                0.0,
+                0.0,
+                0.0005806675,
+                0.0005806675,
+                0.0005806675,
+            ],
+        );
+        assert_close(
+            &samples[1][36..56],
+            &[
+                0.00020401702,
+                0.00020401702,
+                0.00037665048,
+                0.00037665048,
+                0.00020401702,
+                -0.00029033376,
+                0.00029033376,
+                0.0,
+                0.0,
+                0.00041059396,
+                -0.00041059396,
+                0.0,
+                0.0,
+                0.0,
+                -0.00041059396,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+            ],
+        );
+        assert!((energy(&samples[0]) / 1.961563579493893e-5 - 1.0).abs() < 1e-5);
+        assert!((energy(&samples[1]) / 1.2867773200575107e-5 - 1.0).abs() < 1e-5);
+
+        // Short blocks: each window has its own bound, the third one ending in band 0
+        let samples = stereo_granule(&decoded, 1);
+        assert_close(
+            &samples[0][12..24],
+            &[
+                3.629172e-5,
+                3.629172e-5,
+                0.00010887517,
+                7.258344e-5,
+                -0.00010264848,
+                0.00010264848,
+                0.00014516688,
+                4.25184e-5,
+                -5.132424e-5,
+                0.0,
+                0.0,
+                0.0,
+            ],
+        );
+        assert_close(
+            &samples[1][12..24],
+            &[
+                -0.00010887517,
+                3.629172e-5,
+                -3.629172e-5,
+                -7.258344e-5,
+                -0.00010264848,
+                0.00010264848,
+                -0.00014516688,
+                -0.00024781536,
+                -5.132424e-5,
+                0.0,
+                0.0,
+                0.0,
+            ],
+        );
+        assert!((energy(&samples[0]) / 7.475188904542821e-7 - 1.0).abs() < 1e-5);
+        assert!((energy(&samples[1]) / 8.326303033653713e-7 - 1.0).abs() < 1e-5);
+    }
+
+    // Expected values printed by symphonia for frames 0 and 1 of fixtures/intensity_lsf.mp3
+    #[test]
+    fn lsf_intensity_stereo() {
+        let data = include_bytes!("../fixtures/intensity_lsf.mp3");
+
+        // Long blocks, intensity_scale 0: the right channel ends at line 40, intensity stereo
+        // starts with band 4 (line 48)
+        let decoded = decode_values(data, 0);
+        assert_eq!(decoded.header.version(), MPEG_Version::TwoPointFive);
+        assert!(decoded.header.mode_extension().intensity_stereo());
+        assert_eq!(decoded.side_info.granules[0][1].scalefac_compress & 1, 0);
+        let samples = stereo_granule(&decoded, 0);
+        assert_close(
+            &samples[0][40..52],
+            &[
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                -7.258344e-5,
+                0.0,
+                0.0,
+                0.0,
+                3.0517578e-5,
+                0.0,
+                0.0,
+                -3.0517578e-5,
+            ],
+        );
+        assert_close(
+            &samples[1][40..52],
+            &[
+                -0.00014516688,
+                0.0,
+                0.0,
+                0.0,
+                -7.258344e-5,
+                0.0,
+                0.0,
+                0.0,
+                5.1324245e-5,
+                0.0,
+                0.0,
+                -5.1324245e-5,
+            ],
+        );
+        assert!((energy(&samples[0]) / 1.3695739256401805e-6 - 1.0).abs() < 1e-5);
+        assert!((energy(&samples[1]) / 1.4252480832557306e-6 - 1.0).abs() < 1e-5);
+
+        // Short blocks, intensity_scale 1: the third window is intensity coded from band 1
+        // (lines 40 to 48)
+        let mut decoded = decode_values(data, 1);
+        assert_eq!(decoded.side_info.granules[0][1].scalefac_compress & 1, 1);
+        let samples = stereo_granule(&decoded, 0);
+        assert_close(
+            &samples[0][36..48],
+            &[
+                0.00013316606,
+                0.00013316606,
+                -7.2130904e-5,
+                -7.2130904e-5,
+                -5.132424e-5,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                -5.132424e-5,
+                0.0,
+                0.0,
+            ],
+        );
+        assert_close(
+            &samples[1][36..48],
+            &[
+                7.2130904e-5,
+                7.2130904e-5,
+                -0.00013316606,
+                -0.00013316606,
+                -0.00014516688,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                -0.00014516688,
+                0.0,
+                0.0,
+            ],
+        );
+        assert!((energy(&samples[0]) / 3.3050816810683452e-6 - 1.0).abs() < 1e-5);
+        assert!((energy(&samples[1]) / 2.8067829174299587e-6 - 1.0).abs() < 1e-5);
+
+        // 7 is the largest position 3 bit scalefactors hold, illegal: the band is mid/side
+        // decoded instead
+        decoded.scalefactors[0][1].short[1][2] = 7;
+        let samples = stereo_granule(&decoded, 0);
+        assert_eq!(samples[0][40..48], samples[1][40..48]);
+        assert_close(&samples[0][40..41], &[-0.00014516688 * FRAC_1_SQRT_2]);
+    }
+
+    // Expected values printed by symphonia for frames 0 and 1 of fixtures/mixed.mp3
+    #[test]
+    fn mixed_block_intensity_bound() {
+        let data = include_bytes!("../fixtures/mixed.mp3");
+
+        // The short part of the right channel is 0: the long part is intensity coded from long
+        // band 5 (line 20), above the last nonzero value at line 18
+        let decoded = decode_values(data, 0);
+        assert!(decoded.side_info.granules[0][1].mixed_block);
+        let samples = stereo_granule(&decoded, 0);
+        assert_close(
+            &samples[0][14..26],
+            &[
+                0.00016900524,
+                -3.629172e-5,
+                0.0,
+                -0.00011547955,
+                1.283106e-5,
+                0.00010264848,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+            ],
+        );
+        assert_close(
+            &samples[1][14..26],
+            &[
+                0.0002415887,
+                3.629172e-5,
+                0.0,
+                -8.9817426e-5,
+                -1.283106e-5,
+                0.00010264848,
+                0.00014516688,
+                -0.00014516688,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+            ],
+        );
+        assert!((energy(&samples[0]) / 4.48503008488775e-6 - 1.0).abs() < 1e-5);
+        assert!((energy(&samples[1]) / 4.1842130052391294e-6 - 1.0).abs() < 1e-5);
+
+        // Nonzero short bands: only they can be intensity coded, the long part is mid/side
+        let decoded = decode_values(data, 1);
+        let samples = stereo_granule(&decoded, 0);
+        assert_close(
+            &samples[0][14..26],
+            &[
+                -8.6316744e-5,
+                8.6316744e-5,
+                -5.3947965e-6,
+                -0.0001274651,
+                -5.3947965e-6,
+                5.3947965e-6,
+                6.1035153e-5,
+                -5.3947965e-6,
+                5.5640357e-5,
+                -5.3947965e-6,
+                0.0,
+                -3.0517576e-5,
+            ],
+        );
+        assert_close(
+            &samples[1][14..26],
+            &[
+                -8.6316744e-5,
+                8.6316744e-5,
+                5.3947965e-6,
+                -0.00011667551,
+                5.3947965e-6,
+                -5.3947965e-6,
+                6.1035153e-5,
+                5.3947965e-6,
+                6.642995e-5,
+                5.3947965e-6,
+                0.0,
+                3.0517576e-5,
+            ],
+        );
+        assert!((energy(&samples[0]) / 9.530029670144617e-7 - 1.0).abs() < 1e-5);
+        assert!((energy(&samples[1]) / 7.153586914531834e-7 - 1.0).abs() < 1e-5);
+    }
+}
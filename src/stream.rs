@@ -0,0 +1,669 @@
+use std::io::{BufRead, ErrorKind, Read, Seek, SeekFrom};
+
+use crate::{
+    ape::find_leading_ape_tag,
+    constants::*,
+    decoder::{priming_start, Decoded, Decoder, StreamSamples},
+    error::ChompError,
+    frame::{trailing_tags, Frame},
+    free_format::FreeFormatContext,
+    header::{Header, SyncMode},
+    id3::id3v2_size,
+    vbr::VbrInfo,
+};
+
+// Reads up to and including the next valid header. Bytes are consumed one at a time through the
+// reader's buffer, so nothing past the header is taken: the reader is left right after it, with
+// the remaining `frame_length() - 4` bytes of the frame still to be read. A header split across
+// two refills of the buffer is still found. Errors with NoFrames once the reader runs dry.
+pub fn read_and_sync<R: BufRead>(reader: &mut R) -> Result<Header, ChompError> {
+    // Last bytes read, a header candidate once full
+    let mut window = [0; HEADER_SIZE / 8];
+    let mut filled = 0;
+
+    loop {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            return Err(ChompError::NoFrames);
+        }
+
+        let mut used = 0;
+        let mut header = None;
+        for byte in buffer {
+            used += 1;
+            window.rotate_left(1);
+            window[window.len() - 1] = *byte;
+            filled = (filled + 1).min(window.len());
+
+            if filled == window.len() {
+                if let Ok(found) = Header::from_bytes(&window) {
+                    header = Some(found);
+                    break;
+                }
+            }
+        }
+
+        reader.consume(used);
+        if let Some(header) = header {
+            return Ok(header);
+        }
+    }
+}
+
+// Incremental parser for data arriving in chunks (e.g. an HTTP stream). Bytes are buffered
+// across `push` calls and a frame is only returned once all of it has been received.
+// Anything before the first frame is skipped silently. Once synced, bytes that aren't a frame
+// yield one InvalidFrame error, then are skipped until the next frame.
+#[derive(Debug, Default)]
+pub struct StreamParser {
+    buffer: Vec<u8>,
+    // Stream position of the first buffered byte
+    offset: usize,
+    synced: bool,
+}
+
+impl StreamParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    // Header of the next complete frame, None until more bytes are pushed
+    pub fn next_frame(&mut self) -> Option<Result<Header, ChompError>> {
+        while self.buffer.len() >= HEADER_SIZE / 8 {
+            match Header::from_bytes(&self.buffer) {
+                Ok(header) => {
+                    let length = header.frame_length();
+                    if self.buffer.len() < length {
+                        return None;
+                    }
+
+                    self.consume(length);
+                    self.synced = true;
+                    return Some(Ok(header));
+                }
+                Err(_) => {
+                    let offset = self.offset;
+                    self.skip();
+
+                    if self.synced {
+                        self.synced = false;
+                        return Some(Err(ChompError::InvalidFrame { offset }));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    fn consume(&mut self, count: usize) {
+        self.buffer.drain(..count);
+        self.offset += count;
+    }
+
+    // Drops bytes up to the next possible sync
+    fn skip(&mut self) {
+        let count = self.buffer[1..]
+            .iter()
+            .position(|byte| *byte == 0xFF)
+            .map_or(self.buffer.len(), |position| position + 1);
+        self.consume(count);
+    }
+}
+
+// The reader of a decoder made with `Decoder::new`, and the fixed size buffer its frames are
+// read into
+#[derive(Debug, Clone)]
+pub(crate) struct Input<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    // Unread bytes of `buffer`
+    start: usize,
+    end: usize,
+    // Stream position of `buffer[start]`
+    offset: usize,
+    // The reader returned 0 bytes
+    eof: bool,
+    // Stream position where the tags ending the stream start, once the reader is exhausted
+    audio_end: Option<usize>,
+    // Bytes of a tag left to skip, possibly not read yet
+    skip: usize,
+    // Nothing but tags read so far, an APEv2 tag can still start the stream
+    at_start: bool,
+    synced: bool,
+    sync: SyncMode,
+    free_format: FreeFormatContext,
+}
+
+impl<R> Input<R> {
+    pub(crate) fn new(reader: R, buffer_size: usize) -> Self {
+        Self {
+            reader,
+            buffer: vec![0; buffer_size],
+            start: 0,
+            end: 0,
+            offset: 0,
+            eof: false,
+            audio_end: None,
+            skip: 0,
+            at_start: true,
+            synced: false,
+            sync: SyncMode::default(),
+            free_format: FreeFormatContext::new(),
+        }
+    }
+
+    fn consume(&mut self, count: usize) {
+        self.start += count;
+        self.offset += count;
+    }
+}
+
+impl<R: Read> Input<R> {
+    // Position in the buffer, stream offset and header of the next frame, consumed
+    fn find_frame(&mut self) -> Result<Option<(usize, usize, Header)>, ChompError> {
+        loop {
+            self.fill()?;
+
+            if self.skip > 0 {
+                let count = self.skip.min(self.end - self.start);
+                if count == 0 {
+                    return Ok(None);
+                }
+                self.consume(count);
+                self.skip -= count;
+                continue;
+            }
+
+            let available = self.audio_end.map_or(self.end, |end| {
+                (self.start + end.saturating_sub(self.offset)).min(self.end)
+            });
+            let data = &self.buffer[self.start..available];
+            if data.len() < HEADER_SIZE / 8 {
+                return Ok(None);
+            }
+
+            // The tag of a file appended to the previous one, which can use another sample rate
+            // or free format bitrate, so sync starts over
+            if let Some(size) = id3v2_size(data) {
+                self.skip = size;
+                self.synced = false;
+                self.free_format = FreeFormatContext::new();
+                continue;
+            }
+
+            if self.at_start {
+                if let Some(tag) = find_leading_ape_tag(data, 0) {
+                    self.skip = tag.len();
+                    continue;
+                }
+            }
+
+            self.at_start = false;
+            match frame_at(data, 0, &mut self.free_format, self.sync) {
+                Some(header)
+                    if self.synced
+                        || confirmed(data, &header, &mut self.free_format, self.sync) =>
+                {
+                    let length = header.frame_length();
+                    let found = (self.start, self.offset, header);
+                    self.synced = true;
+                    self.consume(length);
+                    return Ok(Some(found));
+                }
+                _ => {
+                    self.synced = false;
+                    self.consume(1);
+                }
+            }
+        }
+    }
+
+    // Refills the buffer once fewer than STREAM_LOOKAHEAD bytes are left, reading until it is
+    // full or the reader is exhausted. Short reads are retried.
+    fn fill(&mut self) -> Result<(), ChompError> {
+        if self.eof || self.end - self.start >= STREAM_LOOKAHEAD {
+            return Ok(());
+        }
+
+        self.buffer.copy_within(self.start..self.end, 0);
+        self.end -= self.start;
+        self.start = 0;
+
+        while self.end < self.buffer.len() {
+            match self.reader.read(&mut self.buffer[self.end..]) {
+                Ok(0) => {
+                    self.eof = true;
+                    let tags = trailing_tags(&self.buffer[..self.end], 0);
+                    self.audio_end = tags.first().map(|(_, tag)| self.offset + tag.start);
+                    break;
+                }
+                Ok(count) => self.end += count,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Seek> Input<R> {
+    // Back to the start of the stream, where the reader was when given to the decoder
+    fn rewind(&mut self) -> Result<(), ChompError> {
+        let buffered = (self.end - self.start) as u64;
+        let origin = self.reader.stream_position()? - buffered - self.offset as u64;
+        self.reader.seek(SeekFrom::Start(origin))?;
+
+        self.start = 0;
+        self.end = 0;
+        self.offset = 0;
+        self.eof = false;
+        self.audio_end = None;
+        self.skip = 0;
+        self.at_start = true;
+        self.synced = false;
+        self.free_format = FreeFormatContext::new();
+        Ok(())
+    }
+}
+
+// Decodes a stream pulled from any reader (a file, a socket...) through a fixed size buffer, so
+// the whole stream never has to be in memory. Frames are found as a lenient FrameIterator finds
+// them in a buffer holding the whole file, and decode to the same samples: ID3v2 tags at the
+// start or between frames are skipped whatever their size, so are APEv2 tags at the start and
+// garbage, and while looking for sync a frame is only trusted if the next one confirms it. Tags
+// ending the stream are recognized once the reader is exhausted, if they fit in the buffer.
+impl<R: Read> Decoder<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_input(Input::new(reader, STREAM_BUFFER_SIZE))
+    }
+
+    // At least twice STREAM_LOOKAHEAD, smaller sizes are rounded up. Only matters for the tags
+    // ending the stream, which are only recognized if they fit.
+    pub fn with_buffer_size(mut self, size: usize) -> Self {
+        self.input.buffer = vec![0; size.max(STREAM_LOOKAHEAD * 2)];
+        self
+    }
+
+    pub fn with_sync_mode(mut self, sync: SyncMode) -> Self {
+        self.input.sync = sync;
+        self
+    }
+
+    pub fn into_inner(self) -> R {
+        self.input.reader
+    }
+
+    // Next frame of the stream, without decoding it. Its offset is its position in the stream.
+    // None once the reader is exhausted. A reader error, WouldBlock from a non-blocking socket
+    // included, is returned as is and leaves the decoder unchanged, to be called again once more
+    // data is available.
+    pub fn next_frame(&mut self) -> Option<Result<Frame<'_>, ChompError>> {
+        match self.input.find_frame() {
+            Ok(Some((position, offset, header))) => Some(Ok(Frame {
+                offset,
+                data: &self.input.buffer[position..position + header.frame_length()],
+                header,
+            })),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    // Decodes the next frame, see `decode_frame` and `next_frame`
+    pub fn decode_next(&mut self) -> Option<Result<Decoded, ChompError>> {
+        match self.input.find_frame() {
+            Ok(found) => Some(self.decode_found(found?)),
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    fn decode_found(
+        &mut self,
+        (position, offset, header): (usize, usize, Header),
+    ) -> Result<Decoded, ChompError> {
+        // Out of the input while decoding, which needs the whole decoder
+        let buffer = std::mem::take(&mut self.input.buffer);
+        let frame = Frame {
+            offset,
+            data: &buffer[position..position + header.frame_length()],
+            header,
+        };
+        let decoded = self.decode_frame(&frame);
+        self.input.buffer = buffer;
+        decoded
+    }
+}
+
+impl<R: Read + Seek> Decoder<R> {
+    // Gets ready to decode frame `target` of the stream, as `seek` does for a decoder fed frames
+    // (the Xing/Info frame, when there is one, being frame 0), so the samples decoded are the
+    // same. Frames are counted from the start of the stream, which is read up to the target
+    // twice: once for the size of the frames, then for the frames priming the decoder. A target
+    // past the last frame seeks to the end of the stream.
+    pub fn seek(&mut self, target: usize) -> Result<(), ChompError> {
+        self.input.rewind()?;
+        self.reset();
+
+        let mut main_data_sizes = Vec::new();
+        let mut info = false;
+        while main_data_sizes.len() < target.max(1) {
+            let Some(frame) = self.next_frame() else {
+                break;
+            };
+            let frame = frame?;
+
+            let stream = (main_data_sizes.is_empty() && VbrInfo::from_frame(&frame).is_some())
+                .then(|| StreamSamples::from_frame(&frame));
+            main_data_sizes.push(frame.main_data_size());
+            if stream.is_some() {
+                self.stream = stream;
+                info = true;
+            }
+        }
+        let target = target.min(main_data_sizes.len());
+        let start = priming_start(target, |index| main_data_sizes[index]);
+
+        self.input.rewind()?;
+        let mut position = 0;
+        for index in 0..target {
+            let Some(found) = self.input.find_frame()? else {
+                break;
+            };
+
+            if index < start {
+                let (_, _, header) = found;
+                if index > 0 || !info {
+                    position += header.samples_per_frame() as u64;
+                }
+                continue;
+            }
+
+            if index == start {
+                self.position = Some(position);
+            }
+            let _ = self.decode_found(found);
+        }
+        if target == 0 {
+            self.position = Some(0);
+        }
+
+        Ok(())
+    }
+}
+
+// Header of the frame at `position` if all of it is in `data`
+fn frame_at(
+    data: &[u8],
+    position: usize,
+    free_format: &mut FreeFormatContext,
+    sync: SyncMode,
+) -> Option<Header> {
+    let header = free_format.header_at(data, position, sync).ok()?;
+    data.get(position..position + header.frame_length())?;
+    Some(header)
+}
+
+// Whether the frame starting `data` is followed by a frame alike, or ends the data
+fn confirmed(
+    data: &[u8],
+    header: &Header,
+    free_format: &mut FreeFormatContext,
+    sync: SyncMode,
+) -> bool {
+    let next = header.frame_length();
+    if next == data.len() {
+        return true;
+    }
+
+    match frame_at(data, next, free_format, sync) {
+        Some(following) => {
+            following.version() == header.version()
+                && following.layer() == header.layer()
+                && following.sample_rate() == header.sample_rate()
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        frame::FrameIterator,
+        test_utils::{frames, HEADER_128, HEADER_192},
+    };
+    use std::io::Cursor;
+
+    // At most 7 bytes per read and Interrupted every other call, as a socket can
+    struct ShortReads<R> {
+        inner: R,
+        interrupt: bool,
+    }
+
+    impl<R: Read> Read for ShortReads<R> {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(ErrorKind::Interrupted.into());
+            }
+
+            let count = buffer.len().min(7);
+            self.inner.read(&mut buffer[..count])
+        }
+    }
+
+    fn id3v2_tag(size: usize) -> Vec<u8> {
+        let mut tag = b"ID3\x03\x00\x00".to_vec();
+        tag.extend((0..4).rev().map(|i| (size >> (7 * i)) as u8 & 0x7F));
+        tag.resize(10 + size, 0);
+        tag
+    }
+
+    fn decode_slice(data: &[u8]) -> Vec<Option<Decoded>> {
+        let mut decoder = Decoder::default();
+        FrameIterator::new(data)
+            .flatten()
+            .map(|frame| decoder.decode_frame(&frame).ok())
+            .collect()
+    }
+
+    fn decode_reader<R: Read>(mut decoder: Decoder<R>) -> Vec<Option<Decoded>> {
+        let mut decoded = Vec::new();
+        while let Some(result) = decoder.decode_next() {
+            decoded.push(result.ok());
+        }
+        decoded
+    }
+
+    #[test]
+    fn short_reads() {
+        let mut data = id3v2_tag(3000);
+        data.extend(include_bytes!("../fixtures/cbr.mp3"));
+        data.extend(b"garbage");
+        // Straddles several refills of the smallest buffer
+        data.extend(id3v2_tag(100_000));
+        data.extend(include_bytes!("../fixtures/vbr.mp3"));
+        data.extend(b"TAG");
+        data.resize(data.len() + 125, 0);
+
+        let expected = decode_slice(&data);
+        assert_eq!(expected.iter().flatten().count(), 12 + 22);
+
+        let reader = ShortReads {
+            inner: &data[..],
+            interrupt: false,
+        };
+        assert_eq!(
+            decode_reader(Decoder::new(reader).with_buffer_size(0)),
+            expected
+        );
+    }
+
+    #[test]
+    fn seek_matches_the_slice_path() {
+        let data = include_bytes!("../fixtures/cbr.mp3");
+        let frames: Vec<_> = FrameIterator::new(data).flatten().collect();
+
+        let mut reader = Decoder::new(Cursor::new(&data[..]));
+        for target in [5, 0, 1, 2, frames.len() - 1, frames.len(), frames.len() + 3] {
+            let mut decoder = Decoder::default();
+            decoder.seek(&frames, target);
+            let expected: Vec<_> = frames[target.min(frames.len())..]
+                .iter()
+                .map(|frame| decoder.decode_frame(frame).ok())
+                .collect();
+
+            reader.seek(target).unwrap();
+            let mut decoded = Vec::new();
+            while let Some(result) = reader.decode_next() {
+                decoded.push(result.ok());
+            }
+            assert_eq!(decoded, expected, "target {target}");
+        }
+    }
+
+    // 200 MB, mostly the tags between the appended files so that decoding stays quick
+    #[test]
+    fn large_stream_through_a_64k_buffer() {
+        let mut data = Vec::new();
+        for i in 0..10 {
+            data.extend(id3v2_tag(20_000_000 + i * 4099));
+            data.extend(include_bytes!("../fixtures/cbr.mp3"));
+            data.extend(include_bytes!("../fixtures/vbr.mp3"));
+        }
+        assert!(data.len() > 200_000_000);
+
+        let expected = decode_slice(&data);
+        assert_eq!(expected.len(), 10 * (12 + 22));
+
+        let decoder = Decoder::new(Cursor::new(&data[..]));
+        assert_eq!(decoder.input.buffer.len(), 64 * 1024);
+        assert_eq!(decode_reader(decoder), expected);
+    }
+
+    // WouldBlock every third call, as a non-blocking socket waiting for data
+    struct NonBlocking<R> {
+        inner: R,
+        calls: usize,
+    }
+
+    impl<R: Read> Read for NonBlocking<R> {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            self.calls += 1;
+            if self.calls.is_multiple_of(3) {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+
+            let count = buffer.len().min(1000);
+            self.inner.read(&mut buffer[..count])
+        }
+    }
+
+    #[test]
+    fn would_block_then_end_of_stream() {
+        // The last frame cut short, which the end of the stream drops
+        let mut data = id3v2_tag(5000);
+        data.extend(include_bytes!("../fixtures/cbr.mp3"));
+        data.truncate(data.len() - 100);
+        let expected = decode_slice(&data);
+        assert_eq!(expected.len(), 11);
+
+        let mut decoder = Decoder::new(NonBlocking {
+            inner: &data[..],
+            calls: 0,
+        })
+        .with_buffer_size(0);
+        let mut decoded = Vec::new();
+        let mut blocked = 0;
+        while let Some(result) = decoder.decode_next() {
+            match result {
+                Err(ChompError::Io(e)) if e.kind() == ErrorKind::WouldBlock => blocked += 1,
+                result => decoded.push(result.ok()),
+            }
+        }
+        assert!(blocked > 0);
+        assert_eq!(decoded, expected);
+
+        // Exhausted for good
+        assert!(decoder.decode_next().is_none());
+        assert!(decoder.next_frame().is_none());
+    }
+
+    #[test]
+    fn frames_pushed_in_7_byte_chunks() {
+        let mut data = b"junk".to_vec();
+        data.extend(frames(&[HEADER_128, HEADER_192]));
+
+        let mut parser = StreamParser::new();
+        let mut headers = Vec::new();
+        let mut pushed = 0;
+        for chunk in data.chunks(7) {
+            parser.push(chunk);
+            pushed += chunk.len();
+            while let Some(result) = parser.next_frame() {
+                headers.push((pushed, result.unwrap()));
+            }
+        }
+
+        // Each frame is returned with the chunk completing it
+        let lengths: [usize; 2] = [4 + 417, 4 + 417 + 626];
+        assert_eq!(headers.len(), 2);
+        for ((pushed, header), (length, bytes)) in headers
+            .iter()
+            .zip(lengths.iter().zip([HEADER_128, HEADER_192]))
+        {
+            assert_eq!(*pushed, length.next_multiple_of(7).min(data.len()));
+            assert_eq!(*header, Header::from_bytes(&bytes).unwrap());
+        }
+        assert!(parser.next_frame().is_none());
+    }
+
+    #[test]
+    fn garbage_after_the_first_frame() {
+        let mut data = frames(&[HEADER_128]);
+        data.extend(b"garbage");
+        data.extend(frames(&[HEADER_128]));
+
+        let mut parser = StreamParser::new();
+        parser.push(&data[..400]);
+        assert!(parser.next_frame().is_none());
+        parser.push(&data[400..]);
+        assert!(parser.next_frame().unwrap().is_ok());
+        assert!(matches!(
+            parser.next_frame(),
+            Some(Err(ChompError::InvalidFrame { offset: 417 }))
+        ));
+        assert!(parser.next_frame().unwrap().is_ok());
+        assert!(parser.next_frame().is_none());
+    }
+
+    #[test]
+    fn read_and_sync_after_garbage() {
+        let garbage = [0x12, 0xFF, 0x00, 0x34, 0xFF];
+        let data = [&garbage[..], &frames(&[HEADER_192, HEADER_128])].concat();
+
+        // A small buffer splits the header across refills
+        for capacity in [1, 3, 5, 64, 4096] {
+            let mut reader = std::io::BufReader::with_capacity(capacity, Cursor::new(&data));
+            let header = read_and_sync(&mut reader).unwrap();
+            assert_eq!(header, Header::from_bytes(&HEADER_192).unwrap());
+
+            // The rest of the frame is left in the reader
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest).unwrap();
+            assert_eq!(rest, data[garbage.len() + 4..]);
+        }
+
+        let mut reader = Cursor::new(garbage);
+        assert!(matches!(
+            read_and_sync(&mut reader),
+            Err(ChompError::NoFrames)
+        ));
+    }
+}
@@ -0,0 +1,97 @@
+use crate::constants::*;
+use std::{f32::consts::PI, sync::OnceLock};
+
+// Matrixing coefficients, N[i][k] = cos((16 + i) * (2k + 1) * π / 64)
+fn matrix() -> &'static [[f32; 32]; 64] {
+    static MATRIX: OnceLock<[[f32; 32]; 64]> = OnceLock::new();
+
+    MATRIX.get_or_init(|| {
+        std::array::from_fn(|i| {
+            std::array::from_fn(|k| (PI / 64.0 * ((16 + i) * (2 * k + 1)) as f32).cos())
+        })
+    })
+}
+
+// Polyphase synthesis of one granule. `samples` holds the 18 time samples of each subband, as
+// left by the IMDCT, `output` receives 18 blocks of 32 PCM samples, nominally within
+// [-1.0, 1.0]. `fifo` is the V vector of the channel, carried over from one granule to the next.
+// The whole pipeline stays within an RMS error of 1e-6 of full scale from reference decoders.
+pub fn synthesis(samples: &[f32; 576], fifo: &mut [f32; 1024], output: &mut [f32; 576]) {
+    for (slot, output) in output.chunks_exact_mut(32).enumerate() {
+        let subbands = std::array::from_fn(|subband| samples[subband * 18 + slot]);
+        synthesize_slot(&subbands, fifo, output);
+    }
+}
+
+// One step of the filterbank: the samples of the 32 subbands at one instant to 32 PCM samples.
+// Layers I and II feed their subband samples here directly.
+pub(crate) fn synthesize_slot(subbands: &[f32; 32], fifo: &mut [f32; 1024], output: &mut [f32]) {
+    let matrix = matrix();
+
+    // Shift in 64 new values
+    fifo.copy_within(..1024 - 64, 64);
+    for (i, row) in matrix.iter().enumerate() {
+        fifo[i] = row.iter().zip(subbands).map(|(n, sample)| n * sample).sum();
+    }
+
+    // U is built from the first 32 values of each even 64 value block of V, the last 32 of each
+    // odd one, then windowed and summed into the 32 samples
+    for (i, output) in output.iter_mut().enumerate().take(32) {
+        *output = (0..8)
+            .map(|j| {
+                fifo[128 * j + i] * SYNTH_WINDOW[64 * j + i]
+                    + fifo[128 * j + 96 + i] * SYNTH_WINDOW[64 * j + 32 + i]
+            })
+            .sum();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        decoder::{Decoded, Decoder},
+        frame::FrameIterator,
+    };
+
+    #[test]
+    fn silence() {
+        let mut fifo = [0.0; 1024];
+        let mut output = [1.0; 576];
+        synthesis(&[0.0; 576], &mut fifo, &mut output);
+        assert_eq!(output, [0.0; 576]);
+        assert_eq!(fifo, [0.0; 1024]);
+    }
+
+    // fixtures/cbr_frame5.f32 is the output of symphonia for frame 5 of fixtures/cbr.mp3,
+    // interleaved little endian f32
+    #[test]
+    fn frame_against_reference() {
+        let data = include_bytes!("../fixtures/cbr.mp3");
+        let mut decoder = Decoder::default().with_gapless(false);
+        let decoded: Vec<_> = FrameIterator::new(data)
+            .flatten()
+            .take(6)
+            .map(|frame| decoder.decode_frame(&frame).unwrap())
+            .collect();
+        let Decoded::Frame(frame) = &decoded[5] else {
+            panic!("{:?}", decoded[5]);
+        };
+
+        let expected: Vec<f32> = include_bytes!("../fixtures/cbr_frame5.f32")
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        assert_eq!(frame.samples().len(), expected.len());
+
+        // The threshold documented on `synthesis`
+        let error = frame
+            .samples()
+            .iter()
+            .zip(&expected)
+            .map(|(sample, expected)| (sample - expected) as f64 * (sample - expected) as f64)
+            .sum::<f64>()
+            / expected.len() as f64;
+        assert!(error.sqrt() < 1e-6, "{}", error.sqrt());
+    }
+}
@@ -0,0 +1,248 @@
+use crate::{header::Header, layer2::layer2_alloc_table};
+
+// Layer II bit allocation tables. Each subband below the table's sblimit can use a handful of
+// quantizers, listed by number of levels: the allocation read from the stream indexes them, 0
+// meaning the subband carries no samples, and its width (nbal) is log2 of their count.
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct AllocationTable {
+    // One entry per subband carrying samples, the ones above are silent
+    pub quantizers: &'static [&'static [u32]],
+}
+
+impl AllocationTable {
+    pub fn sblimit(&self) -> usize {
+        self.quantizers.len()
+    }
+
+    // Width of the allocation of `subband`, in bits
+    pub fn nbal(&self, subband: usize) -> usize {
+        self.quantizers[subband].len().trailing_zeros() as usize
+    }
+}
+
+const HIGH_RATE_LOW: [u32; 16] = [
+    0, 3, 7, 15, 31, 63, 127, 255, 511, 1023, 2047, 4095, 8191, 16383, 32767, 65535,
+];
+const HIGH_RATE_MID: [u32; 16] = [
+    0, 3, 5, 7, 9, 15, 31, 63, 127, 255, 511, 1023, 2047, 4095, 8191, 65535,
+];
+const HIGH_RATE_HIGH: [u32; 8] = [0, 3, 5, 7, 9, 15, 31, 65535];
+const HIGH_RATE_TOP: [u32; 4] = [0, 3, 5, 65535];
+const LOW_RATE_LOW: [u32; 16] = [
+    0, 3, 5, 9, 15, 31, 63, 127, 255, 511, 1023, 2047, 4095, 8191, 16383, 32767,
+];
+const LOW_RATE_HIGH: [u32; 8] = [0, 3, 5, 9, 15, 31, 63, 127];
+const LSF_LOW: [u32; 16] = [
+    0, 3, 5, 7, 9, 15, 31, 63, 127, 255, 511, 1023, 2047, 4095, 8191, 16383,
+];
+const LSF_HIGH: [u32; 4] = [0, 3, 5, 9];
+
+// ISO/IEC 11172-3 table 3-B.2a
+pub static TABLE_A: AllocationTable = AllocationTable {
+    quantizers: &[
+        &HIGH_RATE_LOW,
+        &HIGH_RATE_LOW,
+        &HIGH_RATE_LOW,
+        &HIGH_RATE_MID,
+        &HIGH_RATE_MID,
+        &HIGH_RATE_MID,
+        &HIGH_RATE_MID,
+        &HIGH_RATE_MID,
+        &HIGH_RATE_MID,
+        &HIGH_RATE_MID,
+        &HIGH_RATE_MID,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_TOP,
+        &HIGH_RATE_TOP,
+        &HIGH_RATE_TOP,
+        &HIGH_RATE_TOP,
+    ],
+};
+
+// ISO/IEC 11172-3 table 3-B.2b
+pub static TABLE_B: AllocationTable = AllocationTable {
+    quantizers: &[
+        &HIGH_RATE_LOW,
+        &HIGH_RATE_LOW,
+        &HIGH_RATE_LOW,
+        &HIGH_RATE_MID,
+        &HIGH_RATE_MID,
+        &HIGH_RATE_MID,
+        &HIGH_RATE_MID,
+        &HIGH_RATE_MID,
+        &HIGH_RATE_MID,
+        &HIGH_RATE_MID,
+        &HIGH_RATE_MID,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_HIGH,
+        &HIGH_RATE_TOP,
+        &HIGH_RATE_TOP,
+        &HIGH_RATE_TOP,
+        &HIGH_RATE_TOP,
+        &HIGH_RATE_TOP,
+        &HIGH_RATE_TOP,
+        &HIGH_RATE_TOP,
+    ],
+};
+
+// ISO/IEC 11172-3 table 3-B.2c
+pub static TABLE_C: AllocationTable = AllocationTable {
+    quantizers: &[
+        &LOW_RATE_LOW,
+        &LOW_RATE_LOW,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+    ],
+};
+
+// ISO/IEC 11172-3 table 3-B.2d
+pub static TABLE_D: AllocationTable = AllocationTable {
+    quantizers: &[
+        &LOW_RATE_LOW,
+        &LOW_RATE_LOW,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+    ],
+};
+
+// ISO/IEC 13818-3 table B.1
+pub static TABLE_LSF: AllocationTable = AllocationTable {
+    quantizers: &[
+        &LSF_LOW,
+        &LSF_LOW,
+        &LSF_LOW,
+        &LSF_LOW,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+        &LOW_RATE_HIGH,
+        &LSF_HIGH,
+        &LSF_HIGH,
+        &LSF_HIGH,
+        &LSF_HIGH,
+        &LSF_HIGH,
+        &LSF_HIGH,
+        &LSF_HIGH,
+        &LSF_HIGH,
+        &LSF_HIGH,
+        &LSF_HIGH,
+        &LSF_HIGH,
+        &LSF_HIGH,
+        &LSF_HIGH,
+        &LSF_HIGH,
+        &LSF_HIGH,
+        &LSF_HIGH,
+        &LSF_HIGH,
+        &LSF_HIGH,
+        &LSF_HIGH,
+    ],
+};
+
+// The table the Layer II frames with this header are coded with
+pub fn allocation_table(header: &Header) -> &'static AllocationTable {
+    layer2_alloc_table(header).table()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // nbal of every subband, as listed by the standard
+    fn nbal(table: &AllocationTable) -> Vec<usize> {
+        (0..table.sblimit())
+            .map(|subband| table.nbal(subband))
+            .collect()
+    }
+
+    #[test]
+    fn nbal_widths() {
+        let a = [[4; 11].as_slice(), &[3; 12], &[2; 4]].concat();
+        assert_eq!(nbal(&TABLE_A), a);
+        assert_eq!(nbal(&TABLE_B), [a.as_slice(), &[2; 3]].concat());
+        assert_eq!(nbal(&TABLE_C), [[4; 2].as_slice(), &[3; 6]].concat());
+        assert_eq!(nbal(&TABLE_D), [[4; 2].as_slice(), &[3; 10]].concat());
+        assert_eq!(
+            nbal(&TABLE_LSF),
+            [[4; 4].as_slice(), &[3; 7], &[2; 19]].concat()
+        );
+    }
+
+    #[test]
+    fn every_legal_combination() {
+        const BITRATES: [usize; 15] = [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+        ];
+
+        for (rate, sample_rate) in [44100, 48000, 32000].into_iter().enumerate() {
+            for (index, bitrate) in BITRATES.into_iter().enumerate().skip(1) {
+                // Mono up to 192 kbit/s, the other modes from 64 kbit/s but 80
+                for mode in 0..4u8 {
+                    let mono = mode == 3;
+                    if mono && index > 10 || !mono && matches!(index, 1..=3 | 5) {
+                        continue;
+                    }
+
+                    let bytes = [0xFF, 0xFD, (index << 4 | rate << 2) as u8, mode << 6];
+                    let header = Header::from_bytes(&bytes).unwrap();
+                    let per_channel = bitrate / if mono { 1 } else { 2 };
+                    let expected = match per_channel {
+                        32 | 48 if sample_rate == 32000 => &TABLE_D,
+                        32 | 48 => &TABLE_C,
+                        56 | 64 | 80 => &TABLE_A,
+                        _ if sample_rate == 48000 => &TABLE_A,
+                        _ => &TABLE_B,
+                    };
+                    assert_eq!(allocation_table(&header), expected, "{bytes:02X?}");
+                }
+            }
+        }
+
+        // Any MPEG-2 rate, bitrate and mode
+        for byte2 in 0x10..0xEC {
+            if byte2 & 0x0C == 0x0C {
+                continue;
+            }
+            for mode in 0..4u8 {
+                let header = Header::from_bytes(&[0xFF, 0xF5, byte2, mode << 6]).unwrap();
+                assert_eq!(allocation_table(&header), &TABLE_LSF);
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    bit_reader::BitReader,
+    frame::{Frame, FrameIterator},
+    header::Header,
+    huffman::{decode_big_values, decode_count1},
+    requantize::requantize,
+    scalefactors::Scalefactors,
+    side_info::SideInfo,
+    stereo::stereo,
+};
+
+// MPEG-1 Layer III, 44100 Hz, joint stereo, at 128 and 192 kbit/s (417 and 626 bytes)
+pub const HEADER_128: [u8; 4] = [0xFF, 0xFB, 0x90, 0x40];
+pub const HEADER_192: [u8; 4] = [0xFF, 0xFB, 0xB0, 0x40];
+
+// Frames carrying these headers and a zeroed body, back to back
+pub fn frames(headers: &[[u8; 4]]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for header in headers {
+        let length = Header::from_bytes(header).unwrap().frame_length();
+        data.extend(header);
+        data.resize(data.len() + length - header.len(), 0);
+    }
+    data
+}
+
+// Layer III frame `index` of a stream, its side info and its main data, gathered from the bodies
+// of the frames before it
+pub fn main_data(data: &[u8], index: usize) -> (Frame<'_>, SideInfo, Vec<u8>) {
+    let frames: Vec<_> = FrameIterator::new(data).flatten().take(index + 1).collect();
+
+    let mut reservoir = Vec::new();
+    for frame in &frames {
+        let side_info = frame.side_info().unwrap();
+        reservoir.extend_from_slice(&frame.body()[side_info.len()..]);
+    }
+
+    let frame = frames[index].clone();
+    let side_info_bytes = frame.side_info().unwrap();
+    let side_info = SideInfo::parse(&mut BitReader::new(side_info_bytes), &frame.header).unwrap();
+    let body = frame.body().len() - side_info_bytes.len();
+    let start = reservoir.len() - body - side_info.main_data_begin;
+
+    (frame, side_info, reservoir.split_off(start))
+}
+
+// Scalefactors and Huffman decoded values of every granule and channel of a Layer III frame
+pub struct Decoded {
+    pub header: Header,
+    pub side_info: SideInfo,
+    pub scalefactors: [[Scalefactors; 2]; 2],
+    pub values: [[[i32; 576]; 2]; 2],
+}
+
+pub fn decode_values(data: &[u8], index: usize) -> Decoded {
+    let (frame, side_info, main_data) = main_data(data, index);
+    let mut reader = BitReader::new(&main_data);
+    let mut scalefactors = [[Scalefactors::default(); 2]; 2];
+    let mut values = [[[0; 576]; 2]; 2];
+
+    for granule in 0..side_info.granules() {
+        for channel in 0..side_info.channels() {
+            let info = &side_info.granules[granule][channel];
+            let end = reader.position() + info.part2_3_length;
+            let prev = scalefactors[0][channel];
+            scalefactors[granule][channel] =
+                Scalefactors::read(&mut reader, &side_info, granule, channel, Some(&prev)).unwrap();
+
+            let values = &mut values[granule][channel];
+            let big_values =
+                decode_big_values(&mut reader, &frame.header, info, end, values).unwrap();
+            decode_count1(&mut reader, info, end, big_values, values).unwrap();
+            reader.seek(end);
+        }
+    }
+
+    Decoded {
+        header: frame.header,
+        side_info,
+        scalefactors,
+        values,
+    }
+}
+
+// Both channels of a granule, requantized then through the stereo processing
+pub fn stereo_granule(decoded: &Decoded, granule: usize) -> [[f32; 576]; 2] {
+    let mut samples = [[0.0; 576]; 2];
+    for (channel, samples) in samples.iter_mut().enumerate() {
+        requantize(
+            &decoded.values[granule][channel],
+            &decoded.header,
+            &decoded.side_info.granules[granule][channel],
+            &decoded.scalefactors[granule][channel],
+            samples,
+        );
+    }
+    stereo(
+        &decoded.header,
+        &decoded.side_info.granules[granule][1],
+        &decoded.scalefactors[granule][1],
+        &mut samples,
+    );
+    samples
+}
+
+// Compares to reference decoder output, up to a relative tolerance
+pub fn assert_close(actual: &[f32], expected: &[f32]) {
+    assert_eq!(actual.len(), expected.len());
+    for (i, (actual, expected)) in actual.iter().zip(expected).enumerate() {
+        let tolerance = 1e-5 * expected.abs().max(1e-3);
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{i}: {actual} != {expected}"
+        );
+    }
+}
+
+// Sum of the squares of the values
+pub fn energy(values: &[f32]) -> f64 {
+    values
+        .iter()
+        .map(|value| *value as f64 * *value as f64)
+        .sum()
+}
+
+// Channels, sample rate and 16 bit samples of a PCM WAV file with a 44 byte header
+pub fn wav(data: &[u8]) -> (usize, usize, Vec<i16>) {
+    assert_eq!(&data[36..40], b"data");
+    let channels = u16::from_le_bytes([data[22], data[23]]) as usize;
+    let sample_rate = u32::from_le_bytes(data[24..28].try_into().unwrap()) as usize;
+    let samples = data[44..]
+        .chunks_exact(2)
+        .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+        .collect();
+    (channels, sample_rate, samples)
+}
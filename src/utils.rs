@@ -1,20 +1,6 @@
-use bitvec::prelude::BitSlice;
+use bitvec::prelude::*;
 
-static CURSOR: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
-
-pub fn access(array: &mut BitSlice<u8>, size: usize) -> bitvec::vec::BitVec<u8> {
-    let cursor = CURSOR.load(std::sync::atomic::Ordering::Relaxed);
-    let o = array[cursor..cursor + size].to_bitvec();
-    CURSOR.store(cursor + size, std::sync::atomic::Ordering::Relaxed);
-    o
+// Spreads the bits into one 0/1 byte each, in stream order, so fields can be matched as [0, 1]
+pub fn to_bits(vec: BitVec<u8, Msb0>) -> Vec<u8> {
+    vec.iter().map(|bit| *bit as u8).collect()
 }
-
-// pub fn to_bool_array(vec: bitvec::vec::BitVec<u8>) -> Vec<bool> {
-//     let mut o: Vec<bool> = Vec::new();
-
-//     for e in vec.into_vec().iter() {
-//         o.push(*e != 0);
-//     }
-
-//     o
-// }
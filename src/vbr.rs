@@ -0,0 +1,141 @@
+use crate::{
+    frame::{Frame, FrameIterator},
+    vbri::VbriHeader,
+    xing::XingHeader,
+};
+
+// What a Xing/Info or VBRI header tells about the stream, in the same terms for both
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VbrInfo {
+    // Number of audio frames, the frame holding the header excluded
+    pub frames: Option<u32>,
+    // Size of the stream in bytes, the frame holding the header included
+    pub bytes: Option<u32>,
+    // (frame index, byte offset from the frame holding the header) pairs in increasing order,
+    // empty without toc
+    pub seek_points: Vec<(u64, u64)>,
+}
+
+impl VbrInfo {
+    // Xing/Info header first, VBRI otherwise
+    pub fn from_frame(frame: &Frame) -> Option<Self> {
+        if let Some(xing) = XingHeader::from_frame(frame) {
+            return Some(Self::from(xing));
+        }
+
+        // VBRI toc offsets start after the VBRI frame, Xing ones at the frame holding the header
+        let mut info = Self::from(VbriHeader::from_frame(frame)?);
+        for (_, byte) in &mut info.seek_points {
+            *byte += frame.data.len() as u64;
+        }
+        Some(info)
+    }
+
+    // Header of the first frame of the stream, tags and garbage before it skipped
+    pub fn from_stream(data: &[u8]) -> Option<Self> {
+        let first = FrameIterator::new(data).flatten().next()?;
+        Self::from_frame(&first)
+    }
+
+    // Byte offset, from the frame holding the header, of audio frame `frame`. Interpolates
+    // between the seek points, None without toc.
+    pub fn byte_offset(&self, frame: u64) -> Option<u64> {
+        let next = self
+            .seek_points
+            .iter()
+            .position(|(point, _)| *point > frame)
+            .unwrap_or(self.seek_points.len());
+        let (start_frame, start_byte) = *self.seek_points.get(next.checked_sub(1)?)?;
+
+        match self.seek_points.get(next) {
+            Some(&(end_frame, end_byte)) => Some(
+                start_byte
+                    + (end_byte - start_byte) * (frame - start_frame) / (end_frame - start_frame),
+            ),
+            None => Some(start_byte),
+        }
+    }
+}
+
+impl From<XingHeader> for VbrInfo {
+    // Toc entry i is the position, in 256ths of the stream size, reached after i% of the frames
+    fn from(xing: XingHeader) -> Self {
+        // A toc going backwards can't be interpolated
+        let toc = xing
+            .toc
+            .filter(|toc| toc.windows(2).all(|pair| pair[0] <= pair[1]));
+        let seek_points = match (toc, xing.frames, xing.bytes) {
+            (Some(toc), Some(frames), Some(bytes)) => toc
+                .iter()
+                .enumerate()
+                .map(|(i, entry)| {
+                    (
+                        frames as u64 * i as u64 / toc.len() as u64,
+                        bytes as u64 * *entry as u64 / 256,
+                    )
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Self {
+            frames: xing.frames,
+            bytes: xing.bytes,
+            seek_points,
+        }
+    }
+}
+
+impl From<VbriHeader> for VbrInfo {
+    // Toc entries are the sizes of consecutive runs of frames, starting after the VBRI frame. Its
+    // length isn't known here, offsets count from its end until `from_frame` adds it.
+    fn from(vbri: VbriHeader) -> Self {
+        let mut seek_points = Vec::with_capacity(vbri.toc.len() + 1);
+        let mut byte = 0;
+        seek_points.push((0, 0));
+
+        for (i, size) in vbri.toc.iter().enumerate() {
+            byte += *size as u64;
+            seek_points.push(((i as u64 + 1) * vbri.frames_per_entry as u64, byte));
+        }
+
+        Self {
+            frames: Some(vbri.frames),
+            bytes: Some(vbri.bytes),
+            seek_points,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::XING_TOC_SIZE;
+
+    fn xing(toc: [u8; XING_TOC_SIZE]) -> XingHeader {
+        XingHeader {
+            cbr: false,
+            frames: Some(1000),
+            bytes: Some(256_000),
+            toc: Some(toc),
+            quality: None,
+        }
+    }
+
+    #[test]
+    fn interpolates_the_toc() {
+        let toc = std::array::from_fn(|i| (i * 256 / XING_TOC_SIZE) as u8);
+        let info = VbrInfo::from(xing(toc));
+        assert_eq!(info.byte_offset(0), Some(0));
+        assert_eq!(info.byte_offset(15), Some(3500));
+        assert_eq!(info.byte_offset(500), Some(128_000));
+    }
+
+    #[test]
+    fn decreasing_toc_is_dropped() {
+        let toc = std::array::from_fn(|i| 255 - i as u8);
+        let info = VbrInfo::from(xing(toc));
+        assert!(info.seek_points.is_empty());
+        assert_eq!(info.byte_offset(500), None);
+    }
+}
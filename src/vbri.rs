@@ -0,0 +1,143 @@
+use crate::{constants::*, frame::Frame};
+
+// Fraunhofer's counterpart of the Xing header, written by its encoder
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VbriHeader {
+    pub version: u16,
+    // Encoder delay, in samples
+    pub delay: u16,
+    pub quality: u16,
+    // Size of the stream in bytes, the frame holding this header included
+    pub bytes: u32,
+    // Number of audio frames, the frame holding this header excluded
+    pub frames: u32,
+    // Size in bytes of each run of `frames_per_entry` frames, scale factor applied
+    pub toc: Vec<u32>,
+    pub frames_per_entry: u16,
+}
+
+impl VbriHeader {
+    // "VBRI" sits 32 bytes after the frame header, whatever the version and channel mode. Then
+    // come the version, delay and quality (16 bits each), byte and frame counts (32 bits), and
+    // the toc description (16 bits each): entry count, scale factor, entry size in bytes (1 to
+    // 4) and frames per entry. All fields are big endian.
+    pub fn from_frame(frame: &Frame) -> Option<Self> {
+        let bytes = frame.data.get(VBRI_OFFSET..)?;
+        if bytes.get(..4)? != b"VBRI" {
+            return None;
+        }
+
+        let field = |offset: usize, size: usize| {
+            let field = bytes.get(offset..offset + size)?;
+            Some(
+                field
+                    .iter()
+                    .fold(0u32, |value, byte| value << 8 | *byte as u32),
+            )
+        };
+
+        let entries = field(18, 2)? as usize;
+        let scale = field(20, 2)?;
+        let entry_size = field(22, 2)? as usize;
+        if !(1..=4).contains(&entry_size) {
+            return None;
+        }
+
+        let toc = (0..entries)
+            .map(|i| field(VBRI_HEADER_SIZE + i * entry_size, entry_size)?.checked_mul(scale))
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self {
+            version: field(4, 2)? as u16,
+            delay: field(6, 2)? as u16,
+            quality: field(8, 2)? as u16,
+            bytes: field(10, 4)?,
+            frames: field(14, 4)?,
+            toc,
+            frames_per_entry: field(24, 2)? as u16,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analysis::{duration, DurationMethod},
+        frame::FrameIterator,
+        header::Header,
+        vbr::VbrInfo,
+    };
+
+    // MPEG-1 Layer III 128 kbit/s 44.1 kHz frame carrying a VBRI header with `toc`
+    fn vbri_frame(scale: u16, entry_size: u16, toc: &[u32]) -> Vec<u8> {
+        let mut data = vec![0; 417];
+        data[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+
+        let mut vbri = b"VBRI".to_vec();
+        vbri.extend(1u16.to_be_bytes());
+        vbri.extend(576u16.to_be_bytes());
+        vbri.extend(75u16.to_be_bytes());
+        vbri.extend(100_000u32.to_be_bytes());
+        vbri.extend(200u32.to_be_bytes());
+        vbri.extend((toc.len() as u16).to_be_bytes());
+        vbri.extend(scale.to_be_bytes());
+        vbri.extend(entry_size.to_be_bytes());
+        vbri.extend(10u16.to_be_bytes());
+        for entry in toc {
+            vbri.extend(&entry.to_be_bytes()[4 - entry_size as usize..]);
+        }
+
+        data[VBRI_OFFSET..VBRI_OFFSET + vbri.len()].copy_from_slice(&vbri);
+        data
+    }
+
+    fn parse(data: &[u8]) -> Option<VbriHeader> {
+        VbriHeader::from_frame(&Frame {
+            offset: 0,
+            header: Header::from_bytes(data).unwrap(),
+            data,
+        })
+    }
+
+    #[test]
+    fn scaled_toc() {
+        let vbri = parse(&vbri_frame(2, 2, &[500, 600])).unwrap();
+        assert_eq!(vbri.toc, [1000, 1200]);
+        assert_eq!(vbri.frames_per_entry, 10);
+        assert_eq!((vbri.bytes, vbri.frames), (100_000, 200));
+    }
+
+    #[test]
+    fn overflowing_entry_is_rejected() {
+        assert_eq!(parse(&vbri_frame(0xFFFF, 4, &[0xFFFF_FFFF])), None);
+    }
+
+    #[test]
+    fn fhg_stream() {
+        let data = include_bytes!("../fixtures/vbri.mp3");
+        let frames: Vec<_> = FrameIterator::new(data).flatten().collect();
+        assert_eq!(frames.len(), 41);
+
+        let vbri = VbriHeader::from_frame(&frames[0]).unwrap();
+        assert_eq!((vbri.version, vbri.delay, vbri.quality), (1, 1105, 75));
+        assert_eq!(vbri.bytes as usize, data.len());
+        assert_eq!(vbri.frames, 40);
+        assert_eq!(vbri.frames_per_entry, 4);
+        assert_eq!(vbri.toc.len(), 10);
+        assert_eq!(vbri.toc[..3], [1356, 1878, 2400]);
+
+        // Seek points land on the frames, counted from the VBRI frame like Xing tocs
+        let info = VbrInfo::from_frame(&frames[0]).unwrap();
+        for entry in 0..10 {
+            let frame = 4 * entry as u64;
+            let offset = info.byte_offset(frame).unwrap();
+            assert_eq!(offset as usize, frames[1 + 4 * entry].offset);
+        }
+
+        // The duration comes from the frame count, the first frames' bitrate would mislead
+        let duration = duration(data).unwrap();
+        assert_eq!(duration.method, DurationMethod::VbrHeader);
+        assert_eq!(duration.duration, frames[0].header.duration() * 40);
+    }
+}
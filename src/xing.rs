@@ -0,0 +1,323 @@
+use crate::{
+    constants::*,
+    frame::{Frame, FrameIterator},
+    header::Header,
+};
+
+#[derive(Debug, Clone)]
+pub struct XingHeader {
+    // Encoders write "Info" instead of "Xing" when the stream is CBR
+    pub cbr: bool,
+    // Number of audio frames, the frame holding this header excluded
+    pub frames: Option<u32>,
+    // Size of the stream in bytes, the frame holding this header included
+    pub bytes: Option<u32>,
+    // Entry i is the position (scaled to 0..256 of `bytes`) reached after i% of the duration
+    pub toc: Option<[u8; XING_TOC_SIZE]>,
+    // 0 (worst) to 100 (best), see `QualityInfo`
+    pub quality: Option<u32>,
+}
+
+impl XingHeader {
+    // A header announcing no field, used to reserve room until the real values are known
+    pub fn empty() -> Self {
+        Self {
+            cbr: false,
+            frames: None,
+            bytes: None,
+            toc: None,
+            quality: None,
+        }
+    }
+
+    // The Xing header sits right after the side info of the first frame, so only Layer III frames
+    // hold one. The side info is shorter for MPEG-2 and 2.5 (17 bytes, 9 in mono) than for MPEG-1
+    // (32 bytes, 17 in mono).
+    pub fn from_frame(frame: &Frame) -> Option<Self> {
+        let bytes = frame.body().get(frame.side_info()?.len()..)?;
+
+        let cbr = match bytes.get(..4)? {
+            b"Xing" => false,
+            b"Info" => true,
+            _ => return None,
+        };
+        let flags = read_u32(bytes, 4)?;
+        let mut cursor = 8;
+
+        let mut field = |flag: u32, size: usize| -> Option<Option<&[u8]>> {
+            if flags & flag == 0 {
+                return Some(None);
+            }
+            let value = bytes.get(cursor..cursor + size)?;
+            cursor += size;
+            Some(Some(value))
+        };
+
+        let frames = field(XING_FRAMES_FLAG, 4)?.and_then(|b| read_u32(b, 0));
+        let total_bytes = field(XING_BYTES_FLAG, 4)?.and_then(|b| read_u32(b, 0));
+        let toc = field(XING_TOC_FLAG, XING_TOC_SIZE)?.map(|b| b.try_into().unwrap());
+        let quality = field(XING_QUALITY_FLAG, 4)?.and_then(|b| read_u32(b, 0));
+
+        Some(Self {
+            cbr,
+            frames,
+            bytes: total_bytes,
+            toc,
+            quality,
+        })
+    }
+
+    // Header of the first frame of the stream, tags and garbage before it skipped
+    pub fn from_stream(data: &[u8]) -> Option<Self> {
+        let first = FrameIterator::new(data).flatten().next()?;
+        Self::from_frame(&first)
+    }
+
+    // Size of the header as stored, only the fields announced by the flags are present
+    pub fn size(&self) -> usize {
+        8 + self.frames.map_or(0, |_| 4)
+            + self.bytes.map_or(0, |_| 4)
+            + self.toc.map_or(0, |_| XING_TOC_SIZE)
+            + self.quality.map_or(0, |_| 4)
+    }
+
+    // Builds a toc from the offset of each audio frame relative to the start of the stream
+    pub fn build_toc(frame_offsets: &[usize], total_bytes: usize) -> [u8; XING_TOC_SIZE] {
+        let mut toc = [0; XING_TOC_SIZE];
+
+        if frame_offsets.is_empty() || total_bytes == 0 {
+            return toc;
+        }
+
+        for (i, entry) in toc.iter_mut().enumerate() {
+            let offset = frame_offsets[i * frame_offsets.len() / XING_TOC_SIZE];
+            *entry = (offset * 256 / total_bytes).min(255) as u8;
+        }
+
+        toc
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut flags = 0;
+        let mut fields = Vec::new();
+
+        if let Some(frames) = self.frames {
+            flags |= XING_FRAMES_FLAG;
+            fields.extend_from_slice(&frames.to_be_bytes());
+        }
+        if let Some(bytes) = self.bytes {
+            flags |= XING_BYTES_FLAG;
+            fields.extend_from_slice(&bytes.to_be_bytes());
+        }
+        if let Some(toc) = &self.toc {
+            flags |= XING_TOC_FLAG;
+            fields.extend_from_slice(toc);
+        }
+        if let Some(quality) = self.quality {
+            flags |= XING_QUALITY_FLAG;
+            fields.extend_from_slice(&quality.to_be_bytes());
+        }
+
+        let mut out = Vec::with_capacity(8 + fields.len());
+        out.extend_from_slice(if self.cbr { b"Info" } else { b"Xing" });
+        out.extend_from_slice(&flags.to_be_bytes());
+        out.extend_from_slice(&fields);
+        out
+    }
+
+    // Derives, from the header of an audio frame, the header of a frame large enough to hold a
+    // complete Xing header. The stream's own bitrate is kept when possible so a CBR stream stays CBR.
+    pub fn frame_header(template: [u8; 4]) -> Option<[u8; 4]> {
+        let mut bytes = template;
+        // No CRC, no padding
+        bytes[1] |= 0x01;
+        bytes[2] &= !0x02;
+
+        let needed_for = |header: &Header| {
+            HEADER_SIZE / 8 + header.side_info_length() + 8 + 4 + 4 + XING_TOC_SIZE + 4
+        };
+
+        std::iter::once(template[2] >> 4)
+            .chain(1..15)
+            .find_map(|index| {
+                bytes[2] = (index << 4) | (bytes[2] & 0x0F);
+                let header = Header::from_bytes(&bytes).ok()?;
+                (header.frame_length() >= needed_for(&header)).then_some(bytes)
+            })
+    }
+
+    // Writes a whole (silent) frame carrying this header
+    pub fn to_frame(&self, header_bytes: [u8; 4]) -> Option<Vec<u8>> {
+        let header = Header::from_bytes(&header_bytes).ok()?;
+        let start = HEADER_SIZE / 8 + header.side_info_length();
+        let xing = self.to_bytes();
+
+        let mut frame = vec![0; header.frame_length()];
+        frame[..HEADER_SIZE / 8].copy_from_slice(&header_bytes);
+        frame
+            .get_mut(start..start + xing.len())?
+            .copy_from_slice(&xing);
+        Some(frame)
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analysis::{average_bitrate, duration, DurationMethod},
+        test_utils::frames,
+    };
+
+    #[test]
+    fn round_trip() {
+        let xing = XingHeader {
+            cbr: false,
+            frames: Some(1000),
+            bytes: Some(400_000),
+            toc: Some(std::array::from_fn(|i| (i * 256 / 100) as u8)),
+            quality: Some(78),
+        };
+
+        // MPEG-2 Layer III, 8 kbit/s, 22050 Hz, mono: 26 bytes frames, too short for the header
+        let header = XingHeader::frame_header([0xFF, 0xF3, 0x10, 0xC0]).unwrap();
+        assert_eq!(Header::from_bytes(&header).unwrap().bitrate(), 48);
+
+        let frame = xing.to_frame(header).unwrap();
+        assert_eq!(frame.len(), 156);
+        let parsed = FrameIterator::new(&frame).flatten().next().unwrap();
+        let parsed = XingHeader::from_frame(&parsed).unwrap();
+        assert_eq!(parsed.frames, xing.frames);
+        assert_eq!(parsed.bytes, xing.bytes);
+        assert_eq!(parsed.toc, xing.toc);
+        assert_eq!(parsed.quality, xing.quality);
+        assert_eq!(parsed.size(), xing.to_bytes().len());
+    }
+
+    #[test]
+    fn toc_of_evenly_spread_frames() {
+        let offsets: Vec<_> = (0..1000).map(|i| i * 400).collect();
+        let toc = XingHeader::build_toc(&offsets, 400_000);
+        assert_eq!(toc[0], 0);
+        assert_eq!(toc[50], 128);
+        assert_eq!(toc[99], 253);
+    }
+
+    #[test]
+    fn lame_quality() {
+        let data = include_bytes!("../fixtures/vbr.mp3");
+        let first = FrameIterator::new(data).flatten().next().unwrap();
+        let xing = XingHeader::from_frame(&first).unwrap();
+        assert!(!xing.cbr);
+        assert_eq!(xing.frames, Some(21));
+        assert_eq!(xing.bytes, Some(data.len() as u32));
+        // Encoded with -V 4: 100 - 10 * 4 - 0
+        assert_eq!(xing.quality, Some(60));
+    }
+
+    #[test]
+    fn mpeg2_side_info_offsets() {
+        // 9 bytes of side info in mono, 17 in stereo, the lengths of MPEG-1 stereo and mono
+        for (data, offset) in [
+            (&include_bytes!("../fixtures/mpeg2.mp3")[..], 4 + 9),
+            (include_bytes!("../fixtures/mpeg25.mp3"), 4 + 9),
+            (include_bytes!("../fixtures/mpeg2_stereo.mp3"), 4 + 17),
+        ] {
+            let first = FrameIterator::new(data).flatten().next().unwrap();
+            assert_eq!(&first.data[offset..offset + 4], b"Info");
+
+            let xing = XingHeader::from_frame(&first).unwrap();
+            assert!(xing.cbr);
+            let frames = FrameIterator::new(data).flatten().count() - 1;
+            assert_eq!(xing.frames, Some(frames as u32));
+        }
+
+        // A VBR MPEG-2 stereo frame, 64 kbit/s at 22050 Hz
+        let xing = XingHeader {
+            frames: Some(40),
+            ..XingHeader::empty()
+        };
+        let mut frame = xing.to_frame([0xFF, 0xF3, 0x80, 0x00]).unwrap();
+        assert_eq!(&frame[4 + 17..4 + 21], b"Xing");
+        let parsed = FrameIterator::new(&frame).flatten().next().unwrap();
+        assert_eq!(XingHeader::from_frame(&parsed).unwrap().frames, Some(40));
+
+        // Where an MPEG-1 stereo frame would have it, it isn't found
+        frame.copy_within(4 + 17..4 + 29, 4 + 32);
+        frame[4 + 17..4 + 32].fill(0);
+        let parsed = FrameIterator::new(&frame).flatten().next().unwrap();
+        assert!(XingHeader::from_frame(&parsed).is_none());
+    }
+
+    #[test]
+    fn four_layouts() {
+        let xing = XingHeader {
+            frames: Some(7),
+            ..XingHeader::empty()
+        };
+
+        // MPEG-1 then MPEG-2, mono then stereo, 128 and 64 kbit/s
+        for (header, offset) in [
+            ([0xFF, 0xFB, 0x90, 0xC0], 17),
+            ([0xFF, 0xFB, 0x90, 0x40], 32),
+            ([0xFF, 0xF3, 0x80, 0xC0], 9),
+            ([0xFF, 0xF3, 0x80, 0x40], 17),
+        ] {
+            let frame = xing.to_frame(header).unwrap();
+            assert_eq!(&frame[4 + offset..4 + offset + 4], b"Xing");
+            let parsed = FrameIterator::new(&frame).flatten().next().unwrap();
+            assert_eq!(XingHeader::from_frame(&parsed).unwrap().frames, Some(7));
+        }
+    }
+
+    #[test]
+    fn optional_fields() {
+        for flags in 0..16 {
+            let xing = XingHeader {
+                cbr: flags == 0,
+                frames: (flags & XING_FRAMES_FLAG != 0).then_some(500),
+                bytes: (flags & XING_BYTES_FLAG != 0).then_some(200_000),
+                toc: (flags & XING_TOC_FLAG != 0).then_some([7; XING_TOC_SIZE]),
+                quality: (flags & XING_QUALITY_FLAG != 0).then_some(57),
+            };
+            let frame = xing.to_frame([0xFF, 0xFB, 0x90, 0x40]).unwrap();
+            assert_eq!(frame[4 + 32 + 7] as u32, flags);
+
+            let parsed = FrameIterator::new(&frame).flatten().next().unwrap();
+            let parsed = XingHeader::from_frame(&parsed).unwrap();
+            assert_eq!(parsed.cbr, xing.cbr);
+            assert_eq!(parsed.frames, xing.frames);
+            assert_eq!(parsed.bytes, xing.bytes);
+            assert_eq!(parsed.toc, xing.toc);
+            assert_eq!(parsed.quality, xing.quality);
+            assert_eq!(parsed.size(), xing.to_bytes().len());
+        }
+    }
+
+    #[test]
+    fn duration_from_the_counts() {
+        // The header announces far more than the 3 frames present: they aren't scanned
+        let xing = XingHeader {
+            frames: Some(1000),
+            bytes: Some(1001 * 417),
+            ..XingHeader::empty()
+        };
+        let mut data = xing.to_frame([0xFF, 0xFB, 0x90, 0x40]).unwrap();
+        data.extend(frames(&[[0xFF, 0xFB, 0xB0, 0x40]; 3]));
+
+        let duration = duration(&data).unwrap();
+        assert_eq!(duration.method, DurationMethod::VbrHeader);
+        assert_eq!(
+            duration.duration,
+            Header::from_bytes(&data).unwrap().duration() * 1000
+        );
+        // 417 bytes every 1152 samples, 127.7 kbit/s
+        assert_eq!(average_bitrate(&data).unwrap(), 128);
+    }
+}